@@ -0,0 +1,43 @@
+//! # Derive
+//! Times the derivation of many passwords from one secret, as a long running process such as
+//! `zpass serve` does. Run with `cargo bench --bench derive`.
+
+use std::time::Instant;
+use zpass::crypto::{PasswordGenerator, PasswordParam, Secret, CURRENT_SCHEME, LEGACY_SCHEME};
+use zpass::rules::{self, Rules};
+
+/// Derivations timed for each case.
+const ROUNDS: usize = 10_000;
+
+/// Derives a password for each round and prints the average time of one derivation.
+fn bench(name: &str, secret: &Secret, length: usize, scheme: usize, rules: Option<&Rules>) {
+    let domains: Vec<String> = (0..ROUNDS).map(|i| format!("site{}.example.com", i)).collect();
+    let start = Instant::now();
+    for domain in &domains {
+        let params = PasswordParam {
+            domain,
+            username: "alice",
+            length,
+            version: 1,
+            scheme,
+            rules,
+            unambiguous: false,
+        };
+        secret.get("KEY", params).unwrap();
+    }
+    let elapsed = start.elapsed();
+    println!(
+        "{:<24} {:>8} ns/derivation",
+        name,
+        elapsed.as_nanos() / ROUNDS as u128
+    );
+}
+
+fn main() {
+    let secret = Secret::new("KEY", "IV", 32).unwrap();
+    let rules = rules::parse("required: upper; required: digit; required: [-!];").unwrap();
+    bench("legacy", &secret, 32, LEGACY_SCHEME, None);
+    bench("charset 20", &secret, 20, CURRENT_SCHEME, None);
+    bench("charset 64", &secret, 64, CURRENT_SCHEME, None);
+    bench("charset 20 with rules", &secret, 20, CURRENT_SCHEME, Some(&rules));
+}
@@ -0,0 +1,178 @@
+//! # API
+//! The vaults for programs that embed zpass, such as GUI frontends and bots, without the
+//! command line in between. Vaults are written back to their directory when the `ZPass`
+//! that opened them is dropped, as they are by the commands, and the decrypted secrets of
+//! the process are wiped then.
+//!
+//! ```no_run
+//! use zpass::api::{Options, ZPass};
+//!
+//! let mut zpass = ZPass::open("/home/alice/.zpass".as_ref())?;
+//! let password = zpass
+//!     .vault("work")?
+//!     .password("example.com", "key", &Options::default())?;
+//! # Ok::<(), zpass::vault::VaultError>(())
+//! ```
+
+use crate::safe::cache;
+use crate::safe::constants;
+use crate::safe::crypto::Secret;
+use crate::safe::preference::Preference;
+use crate::safe::rules;
+use crate::safe::vault::{Vault, VaultError, Vaults};
+use std::path::Path;
+
+/// # Options
+/// How a password is generated, what isn't given comes from the preference of the account.
+#[derive(Default, Debug, Clone)]
+pub struct Options {
+    // username picks the account, the default account of the domain otherwise
+    pub username: Option<String>,
+    // length overrides the length of the preference
+    pub length: Option<usize>,
+    // version picks an earlier version of the password
+    pub version: Option<usize>,
+}
+
+/// # ZPass
+/// The vaults of a directory.
+pub struct ZPass {
+    // vaults are read when opened and written when dropped
+    vaults: Vaults<Secret>,
+}
+
+impl ZPass {
+    /// Reads the vaults in the directory, a missing directory has no vaults.
+    pub fn open(root: &Path) -> Result<ZPass, VaultError> {
+        Ok(ZPass {
+            vaults: Vaults::open(root)?,
+        })
+    }
+
+    /// Returns the names of the vaults.
+    pub fn vault_names(&self) -> Vec<&str> {
+        self.vaults.iter().map(|v| v.name()).collect()
+    }
+
+    /// Returns the vault with the name.
+    pub fn vault(&mut self, name: &str) -> Result<VaultRef<'_>, VaultError> {
+        self.vaults
+            .get_mut(|v| v.name() == name)
+            .map(|vault| VaultRef { vault })
+            .ok_or_else(|| VaultError::NoMatchingVault(name.to_owned()))
+    }
+
+    /// Returns the default vault.
+    pub fn default_vault(&mut self) -> Result<VaultRef<'_>, VaultError> {
+        self.vaults
+            .get_default_mut()
+            .map(|vault| VaultRef { vault })
+            .ok_or_else(|| VaultError::NoMatchingVault("default".to_owned()))
+    }
+
+    /// Creates a vault whose secret is encrypted with the key, the first vault is the default.
+    pub fn create_vault(&mut self, name: &str, key: &str) -> Result<VaultRef<'_>, VaultError> {
+        let secret = Secret::new(key, name, constants::SECRET_LENGTH)?;
+        self.vaults.add(name, secret)?;
+        self.vault(name)
+    }
+}
+
+impl Drop for ZPass {
+    /// Wipes the decrypted secrets, an embedding program keeps running after it's done with
+    /// the vaults.
+    fn drop(&mut self) {
+        cache::clear();
+    }
+}
+
+/// # VaultRef
+/// A vault of an open ZPass.
+pub struct VaultRef<'a> {
+    // vault is owned by the ZPass
+    vault: &'a mut Vault<Secret>,
+}
+
+impl VaultRef<'_> {
+    /// Returns the name of the vault.
+    pub fn name(&self) -> &str {
+        self.vault.name()
+    }
+
+    /// Returns the accounts of the vault, most recently used first.
+    pub fn accounts(&self) -> Vec<&Preference> {
+        self.vault.preferences.by_recency()
+    }
+
+    /// Adds an account with passwords of the length, within the known rules of the domain.
+    pub fn add_account(
+        &mut self,
+        domain: &str,
+        username: &str,
+        length: usize,
+    ) -> Result<(), VaultError> {
+        let mut preference = Preference::new(domain, username, length);
+        if let Some(rules) = rules::for_domain(domain) {
+            preference = preference.with_rules(rules);
+        }
+        self.vault.preferences.add(preference)?;
+        Ok(())
+    }
+
+    /// Generates the password of an account of the domain, or decrypts it if it is stored.
+    pub fn password(
+        &mut self,
+        domain: &str,
+        key: &str,
+        options: &Options,
+    ) -> Result<String, VaultError> {
+        self.vault.get_password(
+            domain,
+            key,
+            options.username.as_deref(),
+            options.length,
+            options.version,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+    use std::process;
+
+    #[test]
+    fn opens_vaults_and_generates_passwords() {
+        let root = env::temp_dir().join(format!("zpass-api-{}", process::id()));
+        {
+            let mut zpass = ZPass::open(&root).unwrap();
+            assert!(zpass.vault_names().is_empty());
+            assert!(zpass.default_vault().is_err());
+
+            let mut work = zpass.create_vault("work", "key").unwrap();
+            work.add_account("example.com", "alice", 20).unwrap();
+            let options = Options::default();
+            let password = work.password("example.com", "key", &options).unwrap();
+            assert_eq!(password.chars().count(), 20);
+            assert_eq!(work.accounts()[0].username, "alice");
+
+            let mut work = zpass.default_vault().unwrap();
+            let options = Options {
+                username: Some("alice".to_owned()),
+                ..Options::default()
+            };
+            assert_eq!(
+                work.password("example.com", "key", &options).unwrap(),
+                password
+            );
+            assert!(matches!(
+                zpass.vault("personal"),
+                Err(VaultError::NoMatchingVault(_))
+            ));
+            assert_eq!(zpass.vault_names(), vec!["work"]);
+        }
+        fs::remove_dir_all(&root).ok();
+    }
+}
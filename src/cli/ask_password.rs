@@ -0,0 +1,69 @@
+//! # Ask password
+//! Asks for keys through systemd-ask-password on headless machines, where zpass runs from units
+//! or scripts without a terminal. systemd hands the question to its password agents, so it
+//! shows up on the console, through `wall` or with `systemd-tty-ask-password-agent`.
+
+use std::env;
+use std::error;
+use std::fmt;
+use std::io;
+use std::process::{Command, Stdio};
+
+/// Environment variable systemd sets for the processes of a unit.
+const INVOCATION_VARIABLE: &str = "INVOCATION_ID";
+/// Environment variables that point at a graphical session.
+const DISPLAY_VARIABLES: &[&str] = &["DISPLAY", "WAYLAND_DISPLAY"];
+/// Identifies zpass to the password agents.
+const ID: &str = "zpass";
+
+#[derive(Debug)]
+pub enum AskPasswordError {
+    IOError(io::Error),
+    Failed,
+}
+
+impl fmt::Display for AskPasswordError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::IOError(ref err) => write!(f, "Failed to run systemd-ask-password:\n{}", err),
+            Self::Failed => write!(f, "No key was entered, or the question timed out"),
+        }
+    }
+}
+
+impl error::Error for AskPasswordError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Self::IOError(ref err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for AskPasswordError {
+    fn from(err: io::Error) -> Self {
+        AskPasswordError::IOError(err)
+    }
+}
+
+/// Returns true if zpass runs under systemd or outside of a graphical session,
+/// where there is no one to answer a pinentry dialog.
+pub fn is_headless() -> bool {
+    env::var_os(INVOCATION_VARIABLE).is_some()
+        || DISPLAY_VARIABLES.iter().all(|v| env::var_os(v).is_none())
+}
+
+/// Asks the password agents the question and returns the answer.
+pub fn ask(message: &str) -> Result<String, AskPasswordError> {
+    let output = Command::new("systemd-ask-password")
+        .arg(format!("--id={}", ID))
+        .arg(message)
+        .stdin(Stdio::null())
+        .stderr(Stdio::inherit())
+        .output()?;
+    if !output.status.success() {
+        return Err(AskPasswordError::Failed);
+    }
+    let answer = String::from_utf8_lossy(&output.stdout);
+    Ok(answer.trim_end_matches(&['\r', '\n'][..]).to_owned())
+}
@@ -0,0 +1,112 @@
+//! # Complete
+//! Prints the values a shell can complete at the end of a command line: vault names after
+//! `-n`, domains after `-d` and usernames after `-u`. Domains and usernames come from the vault
+//! selected on the command line, or all the vaults, and usernames from the domain, if given.
+//! Shell completion scripts call the hidden `zpass __complete <words>` command, where the last
+//! word is the one being completed and may be empty.
+
+/// The hidden command that shells call.
+pub const COMMAND: &str = "__complete";
+
+/// A preference of a vault, as far as completion is concerned.
+pub struct Entry<'a> {
+    pub vault: &'a str,
+    pub domain: &'a str,
+    pub username: &'a str,
+}
+
+/// Returns the candidates for the last word, one per line when printed.
+pub fn candidates(words: &[String], vaults: &[&str], entries: &[Entry]) -> Vec<String> {
+    let (current, before) = match words.split_last() {
+        Some((current, before)) => (current.as_str(), before),
+        None => return Vec::new(),
+    };
+    let (option, prefix, current) = match (current.split_once('='), before.last()) {
+        (Some((option, value)), _) if option.starts_with('-') => {
+            (option, format!("{}=", option), value)
+        }
+        (_, Some(previous)) => (previous.as_str(), String::new(), current),
+        _ => return Vec::new(),
+    };
+    let vault = value_of(before, "-n", "--name").or_else(|| value_of(before, "-n", "--vault"));
+    let domain = value_of(before, "-d", "--domain");
+    let in_scope = |e: &&Entry| vault.is_none() || vault == Some(e.vault);
+    let mut values: Vec<&str> = match option {
+        "-n" | "--name" | "--vault" | "--from" | "--to" => vaults.to_vec(),
+        "-d" | "--domain" => entries.iter().filter(in_scope).map(|e| e.domain).collect(),
+        "-u" | "--username" => entries
+            .iter()
+            .filter(in_scope)
+            .filter(|e| domain.is_none() || domain == Some(e.domain))
+            .map(|e| e.username)
+            .filter(|u| !u.is_empty())
+            .collect(),
+        _ => Vec::new(),
+    };
+    values.sort_unstable();
+    values.dedup();
+    values
+        .into_iter()
+        .filter(|v| v.starts_with(current))
+        .map(|v| format!("{}{}", prefix, v))
+        .collect()
+}
+
+/// Returns the value of an option among the words, in any of its forms.
+fn value_of<'a>(words: &'a [String], short: &str, long: &str) -> Option<&'a str> {
+    let (short_equals, long_equals) = (format!("{}=", short), format!("{}=", long));
+    words.iter().enumerate().find_map(|(i, w)| {
+        if let Some(value) = w.strip_prefix(&long_equals) {
+            return Some(value);
+        }
+        if let Some(value) = w.strip_prefix(&short_equals) {
+            return Some(value);
+        }
+        if w == short || w == long {
+            return words.get(i + 1).map(|v| v.as_str());
+        }
+        None
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn completes_option_values() {
+        let words = |input: &str| -> Vec<String> { input.split(' ').map(String::from).collect() };
+        let entries = [
+            Entry {
+                vault: "work",
+                domain: "example.com",
+                username: "alice",
+            },
+            Entry {
+                vault: "work",
+                domain: "example.org",
+                username: "bob",
+            },
+            Entry {
+                vault: "home",
+                domain: "example.net",
+                username: "carol",
+            },
+        ];
+        let complete = |input: &str| candidates(&words(input), &["home", "work"], &entries);
+
+        assert_eq!(complete("get password -n "), vec!["home", "work"]);
+        assert_eq!(complete("get password -n w"), vec!["work"]);
+        assert_eq!(
+            complete("get password -n work -d "),
+            vec!["example.com", "example.org"]
+        );
+        assert_eq!(
+            complete("get password --domain=example.n"),
+            vec!["--domain=example.net"]
+        );
+        assert_eq!(complete("get password -d example.org -u "), vec!["bob"]);
+        assert_eq!(complete("get password -d=example.org -u=b"), vec!["-u=bob"]);
+        assert!(complete("get password ").is_empty());
+    }
+}
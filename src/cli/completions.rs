@@ -0,0 +1,223 @@
+//! # Completions
+//! Generates shell completion scripts from the tables of the parser, so they can't drift from
+//! the grammar. Operations, resources and params are completed from the tables, the values of
+//! the vault, domain and username params come from `zpass __complete`.
+//!
+//! - bash: `zpass completions bash > /etc/bash_completion.d/zpass`
+//! - zsh: `zpass completions zsh > "${fpath[1]}/_zpass"`
+//! - fish: `zpass completions fish > ~/.config/fish/completions/zpass.fish`
+
+use super::complete;
+use super::parser::{
+    ParamName, FLAGS, LONG_PARAMS, OPERATIONS, RESOURCES, SHORT_FLAGS, SHORT_PARAMS,
+};
+use std::env;
+use std::path::PathBuf;
+
+/// Shells there are scripts for.
+pub const SHELLS: &[&str] = &["bash", "zsh", "fish"];
+
+/// Params whose values are completed from the vaults.
+const DYNAMIC: &[ParamName] = &[
+    ParamName::VaultName,
+    ParamName::From,
+    ParamName::To,
+    ParamName::DomainName,
+    ParamName::UserName,
+];
+
+/// Returns the completion script of the shell, or None if the shell isn't supported.
+pub fn script(shell: &str) -> Option<String> {
+    match shell {
+        "bash" => Some(bash()),
+        "zsh" => Some(zsh()),
+        "fish" => Some(fish()),
+        _ => None,
+    }
+}
+
+/// Returns the file the shell loads the completions of zpass from, for `zpass init`.
+/// zsh only loads it if `~/.zfunc` is in its `fpath`.
+pub fn install_path(shell: &str) -> Option<PathBuf> {
+    let home = env::var_os("HOME").map(PathBuf::from);
+    let dir = |variable, fallback: &str| {
+        env::var_os(variable)
+            .map(PathBuf::from)
+            .or_else(|| home.as_ref().map(|home| home.join(fallback)))
+    };
+    match shell {
+        "bash" => dir("XDG_DATA_HOME", ".local/share")
+            .map(|dir| dir.join("bash-completion/completions/zpass")),
+        "zsh" => home.as_ref().map(|home| home.join(".zfunc/_zpass")),
+        "fish" => {
+            dir("XDG_CONFIG_HOME", ".config").map(|dir| dir.join("fish/completions/zpass.fish"))
+        }
+        _ => None,
+    }
+}
+
+fn bash() -> String {
+    format!(
+        r#"# bash completion for zpass
+_zpass() {{
+    local cur="${{COMP_WORDS[COMP_CWORD]}}"
+    local prev="${{COMP_WORDS[COMP_CWORD-1]}}"
+    case "$prev" in
+        {dynamic})
+            local IFS=$'\n'
+            COMPREPLY=($(zpass {complete} "${{COMP_WORDS[@]:1:COMP_CWORD}}" 2>/dev/null))
+            return ;;
+    esac
+    if [ "$COMP_CWORD" -eq 1 ]; then
+        COMPREPLY=($(compgen -W "{operations}" -- "$cur"))
+    elif [ "$COMP_CWORD" -eq 2 ] && [[ "$cur" != -* ]]; then
+        COMPREPLY=($(compgen -W "{resources}" -- "$cur"))
+    elif [[ "$cur" == -* ]]; then
+        COMPREPLY=($(compgen -W "{params}" -- "$cur"))
+    fi
+}}
+complete -o default -F _zpass zpass
+"#,
+        dynamic = dynamic_options().join("|"),
+        complete = complete::COMMAND,
+        operations = words(OPERATIONS).join(" "),
+        resources = words(RESOURCES).join(" "),
+        params = options().join(" "),
+    )
+}
+
+fn zsh() -> String {
+    format!(
+        r#"#compdef zpass
+_zpass() {{
+    local -a values
+    case "${{words[CURRENT-1]}}" in
+        {dynamic})
+            values=(${{(f)"$(zpass {complete} "${{(@)words[2,CURRENT]}}" 2>/dev/null)"}})
+            compadd -a values
+            return ;;
+    esac
+    if (( CURRENT == 2 )); then
+        compadd {operations}
+    elif (( CURRENT == 3 )) && [[ "${{words[CURRENT]}}" != -* ]]; then
+        compadd {resources}
+    elif [[ "${{words[CURRENT]}}" == -* ]]; then
+        compadd -- {params}
+    else
+        _files
+    fi
+}}
+compdef _zpass zpass
+"#,
+        dynamic = dynamic_options().join("|"),
+        complete = complete::COMMAND,
+        operations = words(OPERATIONS).join(" "),
+        resources = words(RESOURCES).join(" "),
+        params = options().join(" "),
+    )
+}
+
+fn fish() -> String {
+    let mut script = format!(
+        r#"# fish completion for zpass
+function __zpass_complete
+    zpass {complete} (commandline -opc)[2..-1] (commandline -ct) 2>/dev/null
+end
+complete -c zpass -n __fish_use_subcommand -f -a "{operations}"
+complete -c zpass -n "not __fish_use_subcommand; and test (count (commandline -opc)) -eq 2" -a "{resources}"
+"#,
+        complete = complete::COMMAND,
+        operations = words(OPERATIONS).join(" "),
+        resources = words(RESOURCES).join(" "),
+    );
+    for (long, param) in LONG_PARAMS {
+        let short = SHORT_PARAMS
+            .iter()
+            .find(|(_, p)| p == param)
+            .map_or(String::new(), |(s, _)| format!(" -s {}", s));
+        let values = if DYNAMIC.contains(param) {
+            " -x -a \"(__zpass_complete)\""
+        } else {
+            " -r"
+        };
+        script.push_str(&format!(
+            "complete -c zpass -l {}{}{}\n",
+            long, short, values
+        ));
+    }
+    for (flag, param) in FLAGS {
+        let short = SHORT_FLAGS
+            .iter()
+            .find(|(_, p)| p == param)
+            .map_or(String::new(), |(s, _)| format!(" -s {}", s));
+        script.push_str(&format!("complete -c zpass -l {}{}\n", flag, short));
+    }
+    for (flag, param) in SHORT_FLAGS {
+        if !FLAGS.iter().any(|(_, p)| p == param) {
+            script.push_str(&format!("complete -c zpass -o {}\n", flag));
+        }
+    }
+    script
+}
+
+/// Returns the words of a table of the grammar.
+fn words<T>(table: &[(&str, T)]) -> Vec<String> {
+    table.iter().map(|(w, _)| w.to_string()).collect()
+}
+
+/// Returns all the options as they are typed.
+fn options() -> Vec<String> {
+    let long = LONG_PARAMS
+        .iter()
+        .chain(FLAGS)
+        .map(|(n, _)| format!("--{}", n));
+    let short = SHORT_PARAMS
+        .iter()
+        .chain(SHORT_FLAGS)
+        .map(|(n, _)| format!("-{}", n));
+    long.chain(short).collect()
+}
+
+/// Returns the options whose values are completed from the vaults.
+fn dynamic_options() -> Vec<String> {
+    let long = LONG_PARAMS
+        .iter()
+        .filter(|(_, p)| DYNAMIC.contains(p))
+        .map(|(n, _)| format!("--{}", n));
+    let short = SHORT_PARAMS
+        .iter()
+        .filter(|(_, p)| DYNAMIC.contains(p))
+        .map(|(n, _)| format!("-{}", n));
+    long.chain(short).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scripts_cover_the_grammar() {
+        for shell in SHELLS {
+            let script = script(shell).unwrap();
+            for word in words(OPERATIONS).iter().chain(&words(FLAGS)) {
+                assert!(script.contains(word.as_str()), "{} lacks {}", shell, word);
+            }
+            assert!(script.contains(complete::COMMAND));
+        }
+        assert_eq!(
+            dynamic_options(),
+            vec![
+                "--name",
+                "--domain",
+                "--username",
+                "--from",
+                "--to",
+                "--vault",
+                "-n",
+                "-d",
+                "-u"
+            ]
+        );
+        assert!(script("powershell").is_none());
+    }
+}
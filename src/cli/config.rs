@@ -0,0 +1,373 @@
+//! # Config
+//! Defaults for the params, read at startup from `~/.config/zpass/config.toml` and then from
+//! `$ZPASS_HOME/config.toml`, the later file wins. Environment variables such as `ZPASS_VAULT`
+//! win over the files, and params given on the command line win over everything.
+//!
+//! ```toml
+//! root = "/home/alice/.zpass"
+//! vault = "personal"
+//! length = 24
+//! clipboard_timeout = 30
+//! output = "table"
+//! locale = "de"
+//! notify = false
+//! min_entropy = 80
+//! masked_email_service = "relay"
+//! masked_email_token = "..."
+//!
+//! [profiles]
+//! work = "/home/alice/work/.zpass"
+//! ```
+//!
+//! A profile, picked with `--profile` or ZPASS_PROFILE, is a root of its own, which wins over
+//! the root and ZPASS_HOME. Profiles that aren't configured live in the data directory, e.g.
+//! `~/.local/share/zpass/profiles/work`.
+//!
+//! Defaults that shouldn't be in plain text can be sealed with the key of the default vault
+//! by `zpass add config`, into `config.sealed` under the root. It is unsealed at startup, only
+//! if it exists and the command opens a vault, and it only fills in the defaults the other
+//! sources leave unset. The root and the profiles are picked before it is read, so they are
+//! ignored there.
+
+use super::i18n;
+use super::parser::{Command, Operation, ParamName, ParamValue, Resource};
+use super::plugin;
+use crate::safe::constants;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::env;
+use std::error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Name of the config file in each of the config directories.
+const FILE_NAME: &str = "config.toml";
+/// Name of the sealed config under the root, next to the vault that seals it.
+const SEALED_FILE_NAME: &str = "config.sealed";
+/// Environment variable that holds the default length, the vault is in `ZPASS_VAULT` as it is
+/// for plugins and the root in `ZPASS_HOME`.
+pub const LENGTH_VARIABLE: &str = "ZPASS_DOMAIN_DEFAULT_LENGTH";
+/// Environment variable that holds the clipboard timeout.
+pub const CLIPBOARD_TIMEOUT_VARIABLE: &str = "ZPASS_CLIPBOARD_TIMEOUT";
+/// Environment variable that holds the output format.
+pub const OUTPUT_VARIABLE: &str = "ZPASS_OUTPUT";
+/// Environment variable that holds the profile.
+pub const PROFILE_VARIABLE: &str = "ZPASS_PROFILE";
+
+#[derive(Debug)]
+pub enum ConfigError {
+    IOError(io::Error),
+    ParseError(PathBuf, toml::de::Error),
+    InvalidVariable(&'static str, String),
+    UnknownProfile(String),
+    UnknownLocale(String),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::IOError(ref err) => write!(f, "Failed to read the config:\n{}", err),
+            Self::ParseError(ref path, ref err) => {
+                write!(f, "Invalid config in {}:\n{}", path.display(), err)
+            }
+            Self::InvalidVariable(name, ref value) => {
+                write!(f, "Expected a number in {}, got:\n{}", name, value)
+            }
+            Self::UnknownProfile(ref name) => write!(f, "No root for the profile {}", name),
+            Self::UnknownLocale(ref code) => {
+                let codes: Vec<&str> = i18n::LOCALES.iter().map(|(c, _)| *c).collect();
+                write!(
+                    f,
+                    "Expected {} as the locale, got:\n{}",
+                    codes.join(" or "),
+                    code
+                )
+            }
+        }
+    }
+}
+
+impl error::Error for ConfigError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Self::IOError(ref err) => Some(err),
+            Self::ParseError(_, ref err) => Some(err),
+            Self::InvalidVariable(..) | Self::UnknownProfile(_) | Self::UnknownLocale(_) => None,
+        }
+    }
+}
+
+impl From<io::Error> for ConfigError {
+    fn from(err: io::Error) -> Self {
+        ConfigError::IOError(err)
+    }
+}
+
+/// # Config
+/// The defaults, each of them is optional.
+#[derive(Deserialize, Default, Debug, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    // root is the directory of the vaults, unless ZPASS_HOME is set
+    pub root: Option<PathBuf>,
+    // vault is used instead of the default vault
+    pub vault: Option<String>,
+    // length of the passwords that are added
+    pub length: Option<usize>,
+    // clipboard_timeout is the number of seconds after which a copied password is cleared
+    pub clipboard_timeout: Option<usize>,
+    // output is the format of the commands that take `--output`
+    pub output: Option<String>,
+    // profile picks the root among the profiles
+    pub profile: Option<String>,
+    // profiles are the roots of the profiles by name
+    pub profiles: HashMap<String, PathBuf>,
+    // locale is the language of the messages, e.g. `de`, instead of the one of LANG
+    pub locale: Option<String>,
+    // notify is false to clear the clipboard without a desktop notification
+    pub notify: Option<bool>,
+    // min_entropy is the fewest bits of a password `audit --entropy` doesn't report
+    pub min_entropy: Option<usize>,
+    // masked_email_service creates the aliases of --masked-email, simplelogin or relay
+    pub masked_email_service: Option<String>,
+    // masked_email_token is the API token of the service, best kept in the sealed config
+    pub masked_email_token: Option<String>,
+}
+
+/// Returns the config files, in the order they are read.
+pub fn paths() -> Vec<PathBuf> {
+    let mut paths = Vec::new();
+    let config_home = env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")));
+    if let Some(dir) = config_home {
+        paths.push(dir.join("zpass").join(FILE_NAME));
+    }
+    if let Some(home) = env::var_os(constants::HOME_VARIABLE) {
+        paths.push(PathBuf::from(home).join(FILE_NAME));
+    }
+    paths
+}
+
+/// Returns the sealed config of the root.
+pub fn sealed_path() -> PathBuf {
+    constants::root().join(SEALED_FILE_NAME)
+}
+
+/// Parses the text of a config, the path is only named in the error.
+pub fn from_text(path: &Path, text: &str) -> Result<Config, ConfigError> {
+    toml::from_str(text).map_err(|err| ConfigError::ParseError(path.to_owned(), err))
+}
+
+/// Reads the config files that exist, a missing file is the same as an empty one,
+/// and then the environment variables.
+pub fn load() -> Result<Config, ConfigError> {
+    let mut config = files()?;
+    config.merge(from_variables(|name| env::var(name).ok())?);
+    Ok(config)
+}
+
+/// Reads the config files that exist, the later file wins.
+fn files() -> Result<Config, ConfigError> {
+    let mut config = Config::default();
+    for path in paths() {
+        let text = match fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => continue,
+            Err(err) => return Err(err.into()),
+        };
+        config.merge(from_text(&path, &text)?);
+    }
+    Ok(config)
+}
+
+/// Reads the defaults from the environment variables that `var` returns a value for.
+fn from_variables<F>(var: F) -> Result<Config, ConfigError>
+where
+    F: Fn(&str) -> Option<String>,
+{
+    let number = |name: &'static str| -> Result<Option<usize>, ConfigError> {
+        var(name)
+            .map(|value| {
+                value
+                    .parse()
+                    .map_err(|_| ConfigError::InvalidVariable(name, value))
+            })
+            .transpose()
+    };
+    Ok(Config {
+        root: None,
+        vault: var(plugin::VAULT_VARIABLE),
+        length: number(LENGTH_VARIABLE)?,
+        clipboard_timeout: number(CLIPBOARD_TIMEOUT_VARIABLE)?,
+        output: var(OUTPUT_VARIABLE),
+        profile: var(PROFILE_VARIABLE),
+        profiles: HashMap::new(),
+        locale: None,
+        notify: None,
+        min_entropy: None,
+        masked_email_service: None,
+        masked_email_token: None,
+    })
+}
+
+impl Config {
+    /// Overwrites the defaults that are set in the other config.
+    pub fn merge(&mut self, other: Config) {
+        self.root = other.root.or_else(|| self.root.take());
+        self.vault = other.vault.or_else(|| self.vault.take());
+        self.length = other.length.or(self.length);
+        self.clipboard_timeout = other.clipboard_timeout.or(self.clipboard_timeout);
+        self.output = other.output.or_else(|| self.output.take());
+        self.profile = other.profile.or_else(|| self.profile.take());
+        self.profiles.extend(other.profiles);
+        self.locale = other.locale.or_else(|| self.locale.take());
+        self.notify = other.notify.or(self.notify);
+        self.min_entropy = other.min_entropy.or(self.min_entropy);
+        self.masked_email_service = other
+            .masked_email_service
+            .or_else(|| self.masked_email_service.take());
+        self.masked_email_token = other
+            .masked_email_token
+            .or_else(|| self.masked_email_token.take());
+    }
+
+    /// Returns the root of a profile, the configured one or one in the data directory.
+    pub fn profile_root(&self, name: &str) -> Result<PathBuf, ConfigError> {
+        if let Some(root) = self.profiles.get(name) {
+            return Ok(root.clone());
+        }
+        // the name becomes a directory, it must not lead out of the profiles
+        let valid = !name.is_empty() && !name.starts_with('.') && !name.contains(&['/', '\\'][..]);
+        constants::data_dir()
+            .filter(|_| valid)
+            .map(|dir| dir.join("profiles").join(name))
+            .ok_or_else(|| ConfigError::UnknownProfile(name.to_owned()))
+    }
+
+    /// Moves the root to the profile, or else to the configured root unless ZPASS_HOME is set.
+    /// The plugins and hooks see the root in ZPASS_HOME.
+    pub fn set_root(&self, profile: Option<&str>) -> Result<(), ConfigError> {
+        match profile.or(self.profile.as_deref()) {
+            Some(name) => env::set_var(constants::HOME_VARIABLE, self.profile_root(name)?),
+            None => {
+                if let (Some(root), None) = (&self.root, env::var_os(constants::HOME_VARIABLE)) {
+                    env::set_var(constants::HOME_VARIABLE, root);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Speaks the configured locale, the environment picks it otherwise.
+    pub fn set_locale(&self) -> Result<(), ConfigError> {
+        match &self.locale {
+            Some(code) if !i18n::choose(code) => Err(ConfigError::UnknownLocale(code.clone())),
+            _ => Ok(()),
+        }
+    }
+
+    /// Gives the command the params it takes a default for and that weren't given.
+    pub fn apply(&self, cmd: &mut Command) {
+        let (op, on, params) = (cmd.op, cmd.on, &mut cmd.params);
+        let mut default = |name, value: Option<ParamValue>| {
+            if let Some(value) = value.filter(|_| !params.has(name)) {
+                params.insert(name, value);
+            }
+        };
+        // the vault commands name the vault they work on, there is no default for them, and
+        // lock without a vault locks all of them
+        if on != Some(Resource::Vault) && op != Operation::Lock {
+            default(
+                ParamName::VaultName,
+                self.vault.clone().map(ParamValue::Text),
+            );
+        }
+        if matches!(op, Operation::Audit | Operation::List | Operation::Stats) {
+            default(ParamName::Output, self.output.clone().map(ParamValue::Text));
+        }
+        if matches!(op, Operation::Add | Operation::Remember | Operation::Forget) {
+            default(
+                ParamName::MaskedEmailService,
+                self.masked_email_service.clone().map(ParamValue::Text),
+            );
+        }
+        if op == Operation::Add && on == Some(Resource::Password) {
+            default(ParamName::Length, self.length.map(ParamValue::Number));
+            default(
+                ParamName::MaskedEmailToken,
+                self.masked_email_token.clone().map(ParamValue::Text),
+            );
+        }
+        if op == Operation::Audit {
+            default(
+                ParamName::MinEntropy,
+                self.min_entropy.map(ParamValue::Number),
+            );
+        }
+        default(
+            ParamName::ClearAfter,
+            self.clipboard_timeout.map(ParamValue::Number),
+        );
+        default(ParamName::Notify, self.notify.map(ParamValue::Flag));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::parser::parse;
+
+    #[test]
+    fn command_line_wins_over_variables_and_files() {
+        let mut config = Config {
+            vault: Some("personal".to_owned()),
+            length: Some(20),
+            ..Config::default()
+        };
+        config.merge(Config {
+            vault: Some("work".to_owned()),
+            clipboard_timeout: Some(30),
+            ..Config::default()
+        });
+        assert_eq!(config.vault.as_deref(), Some("work"));
+        assert_eq!(config.length, Some(20));
+
+        let words: Vec<String> = "add password -d example.com -l 32"
+            .split(' ')
+            .map(String::from)
+            .collect();
+        let mut cmd = parse(&words).unwrap();
+        config.apply(&mut cmd);
+        assert_eq!(cmd.params.text(ParamName::VaultName), Some("work"));
+        assert_eq!(cmd.params.number(ParamName::Length), Some(32));
+        assert_eq!(cmd.params.number(ParamName::ClearAfter), Some(30));
+
+        config.merge(
+            from_variables(|name| match name {
+                "ZPASS_VAULT" => Some("ci".to_owned()),
+                "ZPASS_DOMAIN_DEFAULT_LENGTH" => Some("40".to_owned()),
+                _ => None,
+            })
+            .unwrap(),
+        );
+        assert_eq!(config.vault.as_deref(), Some("ci"));
+        assert_eq!(config.length, Some(40));
+        assert_eq!(config.clipboard_timeout, Some(30));
+        assert!(from_variables(|_| Some("soon".to_owned())).is_err());
+
+        let words: Vec<String> = vec!["delete".into(), "vault".into()];
+        let mut cmd = parse(&words).unwrap();
+        config.apply(&mut cmd);
+        assert!(!cmd.params.has(ParamName::VaultName));
+
+        config
+            .profiles
+            .insert("work".to_owned(), PathBuf::from("/work"));
+        assert_eq!(config.profile_root("work").unwrap(), PathBuf::from("/work"));
+        assert!(config.profile_root("../work").is_err());
+        assert!(from_text(Path::new("sealed"), "vault = 1").is_err());
+    }
+}
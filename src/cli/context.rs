@@ -0,0 +1,252 @@
+//! # Context
+//! What the handlers take from the world outside: the vaults, the clipboard, the prompts, the
+//! notifications and the clock. `Context::system()` is the real thing, tests hand in the fakes of `fake`.
+
+use super::prompt::{self, Prompter};
+#[cfg(feature = "clipboard")]
+use super::sensitive;
+use crate::integration::notify;
+use crate::safe::crypto::Secret;
+use crate::safe::time;
+use crate::safe::vault::{VaultError, Vaults};
+#[cfg(feature = "clipboard")]
+use clipboard::{ClipboardContext, ClipboardProvider};
+use std::error;
+use tracing::debug;
+
+/// Where the vaults are read from and written to.
+pub trait Storage {
+    /// Reads the vaults, they are written back when they are dropped.
+    fn vaults(&self) -> Result<Vaults<Secret>, VaultError>;
+}
+
+/// Holds the copied passwords.
+pub trait Clipboard {
+    /// Returns what is on the clipboard.
+    fn contents(&mut self) -> Result<String, Box<dyn error::Error>>;
+    /// Puts the text on the clipboard.
+    fn set_contents(&mut self, text: String) -> Result<(), Box<dyn error::Error>>;
+    /// Puts a password on the clipboard, marked so clipboard histories don't record it where
+    /// the desktop supports that.
+    fn set_secret(&mut self, text: String) -> Result<(), Box<dyn error::Error>> {
+        self.set_contents(text)
+    }
+}
+
+/// Tells people what happened while they look elsewhere.
+pub trait Notifier {
+    /// Shows the message, a notification that can't be shown is only missed.
+    fn notify(&mut self, message: &str);
+}
+
+/// Tells the time.
+pub trait Clock {
+    /// Returns the current time as seconds since the unix epoch.
+    fn now(&self) -> u64;
+}
+
+/// # Context
+/// The outside world of the handlers.
+pub struct Context {
+    // storage holds the vaults
+    pub storage: Box<dyn Storage>,
+    // clipboard takes the copied passwords
+    pub clipboard: Box<dyn Clipboard>,
+    // prompter asks for keys, answers and choices
+    pub prompter: Box<dyn Prompter>,
+    // notifier shows the desktop notifications
+    pub notifier: Box<dyn Notifier>,
+    // clock timestamps the preferences and ages the passwords
+    pub clock: Box<dyn Clock>,
+}
+
+impl Context {
+    /// Returns the context of a command line run: the vaults under the root path, the system
+    /// clipboard, the prompter of the system, the desktop notifications and the system time.
+    pub fn system() -> Context {
+        Context {
+            storage: Box::new(Root),
+            clipboard: Box::new(SystemClipboard::default()),
+            prompter: prompt::system(),
+            notifier: Box::new(Desktop),
+            clock: Box::new(SystemClock),
+        }
+    }
+}
+
+/// The vaults under the root path, as it is when they are read.
+pub struct Root;
+
+impl Storage for Root {
+    fn vaults(&self) -> Result<Vaults<Secret>, VaultError> {
+        Vaults::new()
+    }
+}
+
+/// The clipboard of the desktop, connected to when it's first used.
+#[derive(Default)]
+pub struct SystemClipboard {
+    // context is None until the clipboard is used, there may be no desktop at all
+    #[cfg(feature = "clipboard")]
+    context: Option<ClipboardContext>,
+}
+
+#[cfg(feature = "clipboard")]
+impl SystemClipboard {
+    fn context(&mut self) -> Result<&mut ClipboardContext, Box<dyn error::Error>> {
+        if self.context.is_none() {
+            self.context = Some(ClipboardProvider::new()?);
+        }
+        Ok(self.context.as_mut().expect("connected above"))
+    }
+}
+
+#[cfg(feature = "clipboard")]
+impl Clipboard for SystemClipboard {
+    fn contents(&mut self) -> Result<String, Box<dyn error::Error>> {
+        self.context()?.get_contents()
+    }
+
+    fn set_contents(&mut self, text: String) -> Result<(), Box<dyn error::Error>> {
+        self.context()?.set_contents(text)
+    }
+
+    fn set_secret(&mut self, text: String) -> Result<(), Box<dyn error::Error>> {
+        if sensitive::copy(&text) {
+            return Ok(());
+        }
+        self.set_contents(text)
+    }
+}
+
+/// Without the feature there is no clipboard, passwords are printed with `--stdout`.
+#[cfg(not(feature = "clipboard"))]
+impl Clipboard for SystemClipboard {
+    fn contents(&mut self) -> Result<String, Box<dyn error::Error>> {
+        Err(NO_CLIPBOARD.into())
+    }
+
+    fn set_contents(&mut self, _text: String) -> Result<(), Box<dyn error::Error>> {
+        Err(NO_CLIPBOARD.into())
+    }
+}
+
+/// Explains why the clipboard can't be used.
+#[cfg(not(feature = "clipboard"))]
+const NO_CLIPBOARD: &str = "zpass was built without the `clipboard` feature, use --stdout";
+
+/// The notifications of the desktop.
+pub struct Desktop;
+
+impl Notifier for Desktop {
+    fn notify(&mut self, message: &str) {
+        if let Err(err) = notify::show(message) {
+            debug!("Failed to show a notification: {}", err);
+        }
+    }
+}
+
+/// The time of the system.
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> u64 {
+        time::now()
+    }
+}
+
+/// Stand-ins for the tests of the handlers.
+#[cfg(test)]
+pub mod fake {
+    use super::super::handler::HandlerError;
+    use super::*;
+    use std::cell::RefCell;
+    use std::path::PathBuf;
+    use std::rc::Rc;
+
+    /// The vaults in a directory of the test.
+    pub struct Directory(pub PathBuf);
+
+    impl Storage for Directory {
+        fn vaults(&self) -> Result<Vaults<Secret>, VaultError> {
+            Vaults::open(&self.0)
+        }
+    }
+
+    /// A clipboard the test can look at through the shared text.
+    pub struct SharedClipboard(pub Rc<RefCell<String>>);
+
+    impl Clipboard for SharedClipboard {
+        fn contents(&mut self) -> Result<String, Box<dyn error::Error>> {
+            Ok(self.0.borrow().clone())
+        }
+
+        fn set_contents(&mut self, text: String) -> Result<(), Box<dyn error::Error>> {
+            *self.0.borrow_mut() = text;
+            Ok(())
+        }
+    }
+
+    /// Gives the answers in order, and fails when they run out.
+    /// A choice is answered with the index of the item.
+    pub struct Answers(pub Vec<String>);
+
+    impl Answers {
+        fn next(&mut self) -> Result<String, HandlerError> {
+            if self.0.is_empty() {
+                return Err(HandlerError::MissingArgument("answer"));
+            }
+            Ok(self.0.remove(0))
+        }
+    }
+
+    impl Prompter for Answers {
+        fn read_secret(&mut self, _message: &str) -> Result<String, HandlerError> {
+            self.next()
+        }
+
+        fn read_line(&mut self, _message: &str) -> Result<String, HandlerError> {
+            self.next()
+        }
+
+        fn confirm(&mut self, _message: &str) -> Result<bool, HandlerError> {
+            Ok(self.next()? == "y")
+        }
+
+        fn select(
+            &mut self,
+            _message: &str,
+            _items: &[String],
+        ) -> Result<Option<usize>, HandlerError> {
+            Ok(self.next()?.parse().ok())
+        }
+    }
+
+    /// Keeps the notifications to itself.
+    pub struct Silent;
+
+    impl Notifier for Silent {
+        fn notify(&mut self, _message: &str) {}
+    }
+
+    /// A clock that stands still.
+    pub struct FixedClock(pub u64);
+
+    impl Clock for FixedClock {
+        fn now(&self) -> u64 {
+            self.0
+        }
+    }
+
+    /// Returns a context of the vaults in the directory, the answers and a clipboard the test
+    /// can look at.
+    pub fn context(root: PathBuf, answers: &[&str], clipboard: Rc<RefCell<String>>) -> Context {
+        Context {
+            storage: Box::new(Directory(root)),
+            clipboard: Box::new(SharedClipboard(clipboard)),
+            prompter: Box::new(Answers(answers.iter().map(|a| a.to_string()).collect())),
+            notifier: Box::new(Silent),
+            clock: Box::new(FixedClock(0)),
+        }
+    }
+}
@@ -0,0 +1,199 @@
+//! # Doctor
+//! Checks of the environment zpass runs in: the root of the vaults and its permissions, the
+//! vault files, the clipboard, the ssh-agent and the crypto. Every failed check comes with what
+//! to do about it.
+
+use crate::safe::crypto;
+use crate::safe::schema;
+#[cfg(feature = "clipboard")]
+use clipboard::{ClipboardContext, ClipboardProvider};
+use std::fs;
+use std::path::Path;
+
+/// File written and removed again to find out if the root is writable.
+const PROBE_FILE: &str = ".doctor";
+
+/// # Check
+/// What was checked, and the problem and its fix if it failed.
+pub struct Check {
+    // name says what was checked
+    pub name: String,
+    // failure holds the problem and the fix, None if the check passed
+    pub failure: Option<(String, String)>,
+}
+
+impl Check {
+    fn passed(name: String) -> Check {
+        Check {
+            name,
+            failure: None,
+        }
+    }
+
+    fn failed(name: String, problem: String, fix: String) -> Check {
+        Check {
+            name,
+            failure: Some((problem, fix)),
+        }
+    }
+}
+
+/// Runs all the checks on the vaults in the root.
+pub fn run(root: &Path) -> Vec<Check> {
+    let mut checks = root_checks(root);
+    #[cfg(feature = "clipboard")]
+    checks.push(clipboard());
+    #[cfg(feature = "ssh")]
+    checks.push(agent());
+    checks.push(self_test());
+    checks
+}
+
+/// Checks that the root exists and is writable, and the permissions and contents of the
+/// vault files.
+pub fn root_checks(root: &Path) -> Vec<Check> {
+    let name = format!("{} exists", root.display());
+    if !root.is_dir() {
+        let fix = "Run `zpass init`, or set ZPASS_HOME to the directory of the vaults";
+        return vec![Check::failed(
+            name,
+            "The directory of the vaults is missing".to_owned(),
+            fix.to_owned(),
+        )];
+    }
+    let mut checks = vec![Check::passed(name)];
+
+    let name = format!("{} is writable", root.display());
+    let probe = root.join(PROBE_FILE);
+    checks.push(
+        match fs::write(&probe, "").and_then(|_| fs::remove_file(&probe)) {
+            Ok(()) => Check::passed(name),
+            Err(err) => Check::failed(
+                name,
+                err.to_string(),
+                format!("Run `chmod u+w {}` as its owner", root.display()),
+            ),
+        },
+    );
+
+    checks.push(permissions(root, 0o700));
+    let mut files: Vec<_> = fs::read_dir(root)
+        .map(|entries| entries.filter_map(|e| e.ok()).map(|e| e.path()).collect())
+        .unwrap_or_default();
+    files.retain(|path| path.is_file() && path.extension() == Some("json".as_ref()));
+    files.sort();
+    for path in files {
+        checks.push(permissions(&path, 0o600));
+        let name = format!("{} is a valid vault", path.display());
+        let problem = match fs::read_to_string(&path) {
+            Ok(contents) => schema::validate(&contents).err().map(|err| err.to_string()),
+            Err(err) => Some(err.to_string()),
+        };
+        checks.push(match problem {
+            None => Check::passed(name),
+            Some(problem) => Check::failed(
+                name,
+                problem,
+                "Run `zpass undo` if the last command broke it, or restore it from a backup"
+                    .to_owned(),
+            ),
+        });
+    }
+    checks
+}
+
+/// Checks that only the owner can read and write the file or directory.
+#[cfg(unix)]
+fn permissions(path: &Path, mode: u32) -> Check {
+    use std::os::unix::fs::PermissionsExt;
+    let name = format!("{} is private", path.display());
+    match fs::metadata(path).map(|m| m.permissions().mode() & 0o777) {
+        Ok(actual) if actual & 0o077 == 0 => Check::passed(name),
+        Ok(actual) => Check::failed(
+            name,
+            format!("The permissions are {:o}, others can read it", actual),
+            format!("Run `chmod {:o} {}`", mode, path.display()),
+        ),
+        Err(err) => Check::failed(
+            name,
+            err.to_string(),
+            "Check that the file is readable".to_owned(),
+        ),
+    }
+}
+
+/// Permissions are left to the ACLs of the platform.
+#[cfg(not(unix))]
+fn permissions(path: &Path, _mode: u32) -> Check {
+    Check::passed(format!("{} is private", path.display()))
+}
+
+/// Checks that passwords can be copied to the clipboard.
+#[cfg(feature = "clipboard")]
+fn clipboard() -> Check {
+    let name = "The clipboard is available".to_owned();
+    let context: Result<ClipboardContext, _> = ClipboardProvider::new();
+    match context {
+        Ok(_) => Check::passed(name),
+        Err(err) => Check::failed(
+            name,
+            err.to_string(),
+            "Run zpass in a graphical session, or print passwords with --stdout".to_owned(),
+        ),
+    }
+}
+
+/// Checks that the ssh-agent answers, for `zpass ssh add`.
+#[cfg(feature = "ssh")]
+fn agent() -> Check {
+    let name = "The ssh-agent is reachable".to_owned();
+    match crate::integration::ssh::reach_agent() {
+        Ok(()) => Check::passed(name),
+        Err(err) => Check::failed(
+            name,
+            err.to_string(),
+            "Start one with `eval $(ssh-agent)`".to_owned(),
+        ),
+    }
+}
+
+/// Checks that encryption, key changes and password generation work on this platform.
+fn self_test() -> Check {
+    let name = "The crypto self-test passes".to_owned();
+    match crypto::self_test() {
+        Ok(()) => Check::passed(name),
+        Err(err) => Check::failed(
+            name,
+            err.to_string(),
+            "Don't trust this build with your vaults, reinstall zpass".to_owned(),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_missing_roots_and_broken_vaults() {
+        let root = std::env::temp_dir().join(format!("zpass-doctor-{}", std::process::id()));
+        let checks = root_checks(&root);
+        assert_eq!(checks.len(), 1);
+        assert!(checks[0].failure.is_some());
+
+        fs::create_dir_all(&root).unwrap();
+        fs::write(root.join("broken.json"), "{").unwrap();
+        let failed: Vec<String> = root_checks(&root)
+            .into_iter()
+            .filter(|c| c.failure.is_some())
+            .map(|c| c.name)
+            .collect();
+        assert!(failed.contains(&format!(
+            "{} is a valid vault",
+            root.join("broken.json").display()
+        )));
+        assert!(!root.join(PROBE_FILE).exists());
+        assert!(self_test().failure.is_none());
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
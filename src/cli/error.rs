@@ -0,0 +1,231 @@
+//! # Error
+//! The failures of the commands as one type. Each failure has a kind with a stable code, so
+//! wrappers can react to it without reading the message. With `--output json` the failure is
+//! printed as `{"error":{"code":"not_found","message":"..."}}`.
+
+use super::handler::HandlerError;
+use super::parser::ParseError;
+use crate::safe::crypto::CryptoError;
+use crate::safe::preference::PreferenceError;
+use crate::safe::vault::VaultError;
+use crate::safe::wordlist::WordlistError;
+use serde::Serialize;
+use std::error;
+use std::fmt;
+use std::io;
+
+/// The kinds of failures, their codes don't change between versions.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Kind {
+    Usage,
+    NotFound,
+    AlreadyExists,
+    KeyMismatch,
+    WrongKey,
+    NotInteractive,
+    Unsupported,
+    InvalidVault,
+    Io,
+    Failed,
+}
+
+impl Kind {
+    /// Returns the code of the kind.
+    pub fn code(self) -> &'static str {
+        match self {
+            Self::Usage => "usage",
+            Self::NotFound => "not_found",
+            Self::AlreadyExists => "already_exists",
+            Self::KeyMismatch => "key_mismatch",
+            Self::WrongKey => "wrong_key",
+            Self::NotInteractive => "not_interactive",
+            Self::Unsupported => "unsupported",
+            Self::InvalidVault => "invalid_vault",
+            Self::Io => "io",
+            Self::Failed => "failed",
+        }
+    }
+}
+
+/// # ZPassError
+/// A failure of a command and its kind.
+#[derive(Debug)]
+pub struct ZPassError {
+    // kind of the failure
+    kind: Kind,
+    // message explains the failure to people
+    message: String,
+}
+
+/// The JSON form of an error.
+#[derive(Serialize)]
+struct Report<'a> {
+    // error has the code and the message
+    error: Body<'a>,
+}
+
+#[derive(Serialize)]
+struct Body<'a> {
+    // code of the kind
+    code: &'static str,
+    // message of the error
+    message: &'a str,
+}
+
+impl ZPassError {
+    /// Creates an error of the kind.
+    pub fn new(kind: Kind, message: impl Into<String>) -> ZPassError {
+        ZPassError {
+            kind,
+            message: message.into(),
+        }
+    }
+
+    /// Returns the kind of the failure.
+    pub fn kind(&self) -> Kind {
+        self.kind
+    }
+
+    /// Puts the prefix before the message, the kind stays.
+    pub fn context(self, prefix: &str) -> ZPassError {
+        ZPassError {
+            message: format!("{}{}", prefix, self.message),
+            ..self
+        }
+    }
+
+    /// Returns the error as JSON.
+    pub fn to_json(&self) -> String {
+        let report = Report {
+            error: Body {
+                code: self.kind.code(),
+                message: &self.message,
+            },
+        };
+        serde_json::to_string(&report).unwrap_or_default()
+    }
+}
+
+impl fmt::Display for ZPassError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl error::Error for ZPassError {}
+
+impl From<HandlerError> for ZPassError {
+    fn from(err: HandlerError) -> Self {
+        let kind = match &err {
+            HandlerError::MissingParam(_)
+            | HandlerError::MissingArgument(_)
+            | HandlerError::InvalidPeriod(_)
+            | HandlerError::UnknownEncoding(_)
+            | HandlerError::UnknownPreset(_)
+            | HandlerError::UnknownSort(_)
+            | HandlerError::RecipeError(_)
+            | HandlerError::ConfigError(_) => Kind::Usage,
+            HandlerError::MissingVault
+            | HandlerError::UnknownDomain(..)
+            | HandlerError::Archived(_)
+            | HandlerError::NotAMember(_) => Kind::NotFound,
+            HandlerError::KeyMismatch => Kind::KeyMismatch,
+            HandlerError::NotInteractive(_) => Kind::NotInteractive,
+            HandlerError::UnsupportedOutput(_) | HandlerError::FeatureDisabled(_) => {
+                Kind::Unsupported
+            }
+            HandlerError::InvalidVault(_) => Kind::InvalidVault,
+            HandlerError::IOError(_) => Kind::Io,
+            HandlerError::VaultError(err) => vault_kind(err),
+            HandlerError::SecretError(err) => crypto_kind(err),
+            HandlerError::PreferenceError(err) => preference_kind(err),
+            _ => Kind::Failed,
+        };
+        ZPassError::new(kind, err.to_string())
+    }
+}
+
+impl From<ParseError> for ZPassError {
+    fn from(err: ParseError) -> Self {
+        ZPassError::new(Kind::Usage, err.to_string())
+    }
+}
+
+impl From<io::Error> for ZPassError {
+    fn from(err: io::Error) -> Self {
+        ZPassError::new(Kind::Io, err.to_string())
+    }
+}
+
+fn vault_kind(err: &VaultError) -> Kind {
+    match err {
+        VaultError::NoMatchingPreference
+        | VaultError::NoMatchingVault(_)
+        | VaultError::NoMatchingMember(_) => Kind::NotFound,
+        VaultError::VaultAlreadyExists | VaultError::MemberAlreadyExists(_) => Kind::AlreadyExists,
+        VaultError::InvalidVaultName(_)
+        | VaultError::VersionOutOfRange { .. }
+        | VaultError::LegacyVersion(_) => Kind::Usage,
+        VaultError::SecretError(err) => crypto_kind(err),
+        VaultError::PreferenceError(err) => preference_kind(err),
+        VaultError::IOError(_) => Kind::Io,
+        VaultError::SerializationError(_) => Kind::InvalidVault,
+        VaultError::MigrationFailed(_) => Kind::Failed,
+        VaultError::WordlistError(err) => wordlist_kind(err),
+    }
+}
+
+fn wordlist_kind(err: &WordlistError) -> Kind {
+    match err {
+        WordlistError::NoSuchList(_) => Kind::NotFound,
+        WordlistError::AlreadyExists(_) => Kind::AlreadyExists,
+        WordlistError::InvalidName(_) => Kind::Usage,
+        WordlistError::IOError(_) => Kind::Io,
+        WordlistError::TooShort { .. } | WordlistError::Changed(_) => Kind::Failed,
+    }
+}
+
+fn crypto_kind(err: &CryptoError) -> Kind {
+    match err {
+        // a wrong key leaves invalid padding or invalid text behind
+        CryptoError::FailedToDecrypt(_) | CryptoError::InvalidUtf8(_) | CryptoError::WrongKey => {
+            Kind::WrongKey
+        }
+        _ => Kind::Failed,
+    }
+}
+
+fn preference_kind(err: &PreferenceError) -> Kind {
+    match err {
+        PreferenceError::PreferenceExists => Kind::AlreadyExists,
+        PreferenceError::NoMatchingPreferenceFound | PreferenceError::NoSuchChange(_) => {
+            Kind::NotFound
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn kinds_follow_the_cause() {
+        let err = ZPassError::from(HandlerError::VaultError(VaultError::NoMatchingVault(
+            "work".to_owned(),
+        )));
+        assert_eq!(err.kind(), Kind::NotFound);
+        assert_eq!(err.kind().code(), "not_found");
+        assert!(err.to_string().contains("No vault named work"));
+
+        let err = ZPassError::from(HandlerError::PreferenceError(
+            PreferenceError::PreferenceExists,
+        ))
+        .context("Line 2:\n");
+        assert_eq!(err.kind(), Kind::AlreadyExists);
+        assert!(err.to_string().starts_with("Line 2:\n"));
+        assert_eq!(
+            ZPassError::from(HandlerError::KeyMismatch).kind(),
+            Kind::KeyMismatch
+        );
+    }
+}
@@ -1,37 +1,248 @@
-use super::parser::ParamName;
+use super::ask_password::AskPasswordError;
+use super::complete::{self, Entry};
+use super::completions;
+use super::config;
+use super::context::Context;
+use super::doctor;
+use super::i18n::{tr, Message};
+use super::interrupt;
+use super::menu;
+use super::parser::{ParamName, Params};
+use super::pinentry::PinentryError;
+use super::plugin;
+use super::progress::{Bar, Status};
+use super::spell;
+use super::suggest;
+use super::table::Table;
+#[cfg(feature = "tui")]
+use super::tui::{self, Action};
+use crate::integration::agent::{self, Agent, AgentError, Client};
+#[cfg(feature = "autotype")]
+use crate::integration::autotype;
+#[cfg(feature = "hibp")]
+use crate::integration::hibp;
+#[cfg(feature = "keychain")]
+use crate::integration::keychain;
+#[cfg(feature = "masked-email")]
+use crate::integration::masked_email::{self, MaskedEmailError};
+#[cfg(feature = "secret-service")]
+use crate::integration::secret_service;
+#[cfg(feature = "ssh")]
+use crate::integration::ssh;
+#[cfg(feature = "import")]
+use crate::interop::archive::{self, Archive};
+#[cfg(feature = "import")]
+use crate::interop::paper::{self, Backup};
+use crate::interop::template::{self, TemplateError};
+#[cfg(feature = "import")]
+use crate::interop::transfer::{self, Receiver, TransferError};
+#[cfg(feature = "import")]
+use crate::interop::{self, age, csv, keepass, onepassword, pass, zvault};
+use crate::safe::alias;
+use crate::safe::audit::{self, Finding};
 use crate::safe::constants;
-use crate::safe::crypto::{CryptoError, Secret};
-use crate::safe::preference::{Preference, PreferenceError};
-use crate::safe::vault::{VaultError, Vaults};
-use clipboard::{ClipboardContext, ClipboardProvider};
-use rpassword;
-use std::collections::HashMap;
-use std::{error, fmt, io, num};
+use crate::safe::crypto::{self, CryptoError, Secret};
+use crate::safe::history::{self, Entry as HistoryEntry};
+use crate::safe::hooks::{self, Event};
+use crate::safe::idn;
+use crate::safe::journal;
+#[cfg(feature = "import")]
+use crate::safe::memory::Locked;
+use crate::safe::plan;
+use crate::safe::preference::{Preference, PreferenceError, Preferences, Sort};
+use crate::safe::preset::{self, Preset};
+use crate::safe::recipe::{self, Recipe, RecipeError};
+use crate::safe::rules::{self, Rules};
+use crate::safe::schema::{self, Invalid};
+use crate::safe::stats::{self, Stats};
+use crate::safe::strength;
+use crate::safe::throttle;
+use crate::safe::time;
+use crate::safe::token::{self, Encoding, Token};
+#[cfg(feature = "import")]
+use crate::safe::vault::{self, Member};
+use crate::safe::vault::{Vault, VaultError, Vaults};
+use crate::safe::wordlist::Wordlist;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, UNIX_EPOCH};
+use std::{env, error, fmt, fs, io, mem, num, process, thread};
+use tracing::debug;
+
+/// Environment variable that holds a command printing the key of the vault in $ZPASS_VAULT,
+/// e.g. a password manager or keychain lookup.
+pub const KEY_COMMAND_VARIABLE: &str = "ZPASS_KEY_CMD";
+/// Minutes a vault stays unlocked when `unlock` has no --lock-after.
+const DEFAULT_LOCK_AFTER: usize = 15;
+/// Reminds that a hint is readable by anyone who can read the vault file.
+const HINT_WARNING: &str = "The hint is stored in plain text, it must not give the key away";
 
 #[derive(Debug)]
 pub enum HandlerError {
     MissingVault,
+    KeyMismatch,
+    InvalidPeriod(String),
+    UnknownEncoding(String),
+    UnknownPreset(String),
+    UnknownSort(String),
+    NotAMember(String),
+    KeyCommandFailed(String),
+    ChecksFailed(usize),
+    NotInteractive(String),
+    UnknownDomain(String, Vec<String>),
+    Archived(String),
     IOError(io::Error),
+    PinentryError(PinentryError),
+    TemplateError(TemplateError),
+    AskPasswordError(AskPasswordError),
     MissingParam(ParamName),
+    MissingArgument(&'static str),
     VaultError(VaultError),
     SecretError(CryptoError),
     PreferenceError(PreferenceError),
+    RecipeError(RecipeError),
     ClipboardError(Box<dyn error::Error>),
     ConversionError(num::ParseIntError),
+    SerializationError(serde_json::Error),
+    UnsupportedOutput(String),
+    ConfigError(config::ConfigError),
+    #[cfg(feature = "import")]
+    CsvError(csv::CsvError),
+    #[cfg(feature = "import")]
+    PassError(pass::PassError),
+    #[cfg(feature = "import")]
+    OnePasswordError(onepassword::OnePasswordError),
+    #[cfg(feature = "import")]
+    AgeError(age::AgeError),
+    #[cfg(feature = "import")]
+    ArchiveError(archive::ArchiveError),
+    #[cfg(feature = "import")]
+    PaperError(paper::PaperError),
+    #[cfg(feature = "import")]
+    ZVaultError(zvault::ZVaultError),
+    #[cfg(feature = "import")]
+    TransferError(TransferError),
+    InvalidVault(Invalid),
+    FeatureDisabled(&'static str),
+    #[cfg(feature = "hibp")]
+    BreachError(hibp::BreachError),
+    #[cfg(feature = "keychain")]
+    KeychainError(keychain::KeychainError),
+    #[cfg(feature = "masked-email")]
+    MaskedEmailError(MaskedEmailError),
+    #[cfg(feature = "ssh")]
+    SshError(ssh::SshError),
+    #[cfg(feature = "secret-service")]
+    SecretServiceError(secret_service::SecretServiceError),
+    AgentError(AgentError),
 }
 
 impl fmt::Display for HandlerError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
-            Self::MissingParam(name) => write!(f, "Expected param:\n{:?}", name),
-            Self::VaultError(ref err) => write!(f, "Vault error:\n{}", err),
-            Self::SecretError(ref err) => write!(f, "Secret error:\n{}", err),
-            Self::PreferenceError(ref err) => write!(f, "Preference error:\n{}", err),
-            Self::IOError(ref err) => write!(f, "IO error:\n{}", err),
-            Self::ClipboardError(ref err) => write!(f, "Clipboard Error:\n{}", err),
-            Self::ConversionError(ref err) => write!(f, "Conversion Error:\n{}", err),
-            Self::MissingVault => write!(f, "Failed to find the vault"),
+            Self::MissingParam(name) => {
+                write!(f, "{}\n{:?}", tr(Message::MissingParam, &[]), name)
+            }
+            Self::MissingArgument(name) => caused(f, Message::MissingArgument, name),
+            Self::VaultError(ref err) => caused(f, Message::VaultFailed, &vault_message(err)),
+            Self::SecretError(ref err) => caused(f, Message::SecretFailed, err),
+            Self::PreferenceError(ref err) => caused(f, Message::PreferenceFailed, err),
+            Self::RecipeError(ref err) => caused(f, Message::InvalidRecipe, err),
+            Self::IOError(ref err) => caused(f, Message::IoFailed, err),
+            Self::PinentryError(ref err) => caused(f, Message::PromptFailed, err),
+            Self::TemplateError(ref err) => caused(f, Message::TemplateFailed, err),
+            Self::AskPasswordError(ref err) => caused(f, Message::PromptFailed, err),
+            Self::ClipboardError(ref err) => caused(f, Message::ClipboardFailed, err),
+            Self::ConversionError(ref err) => caused(f, Message::ConversionFailed, err),
+            Self::SerializationError(ref err) => caused(f, Message::SerializationFailed, err),
+            Self::UnsupportedOutput(ref output) => {
+                write!(f, "{}", tr(Message::UnsupportedOutput, &[output]))
+            }
+            Self::ConfigError(ref err) => caused(f, Message::ConfigFailed, err),
+            #[cfg(feature = "import")]
+            Self::CsvError(ref err) => caused(f, Message::CsvFailed, err),
+            #[cfg(feature = "import")]
+            Self::PassError(ref err) => caused(f, Message::PassFailed, err),
+            #[cfg(feature = "import")]
+            Self::OnePasswordError(ref err) => caused(f, Message::OnePasswordFailed, err),
+            #[cfg(feature = "import")]
+            Self::AgeError(ref err) => caused(f, Message::EncryptionFailed, err),
+            #[cfg(feature = "import")]
+            Self::ArchiveError(ref err) => caused(f, Message::ArchiveFailed, err),
+            #[cfg(feature = "import")]
+            Self::PaperError(ref err) => caused(f, Message::PaperFailed, err),
+            #[cfg(feature = "import")]
+            Self::ZVaultError(ref err) => caused(f, Message::VaultFileFailed, err),
+            #[cfg(feature = "import")]
+            Self::TransferError(ref err) => caused(f, Message::TransferFailed, err),
+            Self::InvalidVault(ref invalid) => caused(f, Message::InvalidVault, invalid),
+            Self::FeatureDisabled(feature) => {
+                write!(f, "{}", tr(Message::FeatureDisabled, &[feature]))
+            }
+            #[cfg(feature = "hibp")]
+            Self::BreachError(ref err) => caused(f, Message::BreachFailed, err),
+            #[cfg(feature = "keychain")]
+            Self::KeychainError(ref err) => caused(f, Message::KeychainFailed, err),
+            #[cfg(feature = "masked-email")]
+            Self::MaskedEmailError(ref err) => caused(f, Message::MaskedEmailFailed, err),
+            #[cfg(feature = "ssh")]
+            Self::SshError(ref err) => caused(f, Message::SshFailed, err),
+            #[cfg(feature = "secret-service")]
+            Self::SecretServiceError(ref err) => caused(f, Message::SecretServiceFailed, err),
+            Self::AgentError(ref err) => caused(f, Message::AgentFailed, err),
+            Self::MissingVault => write!(f, "{}", tr(Message::MissingVault, &[])),
+            Self::KeyMismatch => write!(f, "{}", tr(Message::KeyMismatch, &[])),
+            Self::UnknownEncoding(ref name) => caused(f, Message::UnknownEncoding, name),
+            Self::UnknownPreset(ref name) => caused(f, Message::UnknownPreset, name),
+            Self::UnknownSort(ref name) => caused(f, Message::UnknownSort, name),
+            Self::InvalidPeriod(ref text) => caused(f, Message::InvalidPeriod, text),
+            Self::NotAMember(ref identity) => {
+                write!(f, "{}", tr(Message::NotAMember, &[identity]))
+            }
+            Self::KeyCommandFailed(ref command) => caused(f, Message::KeyCommandFailed, command),
+            Self::ChecksFailed(count) => write!(f, "{}", tr(Message::ChecksFailed, &[count])),
+            Self::NotInteractive(ref message) => {
+                write!(f, "{}", tr(Message::NotInteractive, &[message]))
+            }
+            Self::Archived(ref account) => write!(f, "{}", tr(Message::Archived, &[account])),
+            Self::UnknownDomain(ref domain, ref domains) => write!(
+                f,
+                "{}{}",
+                tr(Message::UnknownDomain, &[domain]),
+                suggest::did_you_mean(domain, domains.iter().map(|d| d.as_str()))
+            ),
+        }
+    }
+}
+
+/// Writes a translated heading and the error below it.
+fn caused(f: &mut fmt::Formatter, heading: Message, err: &dyn fmt::Display) -> fmt::Result {
+    write!(f, "{}\n{}", tr(heading, &[]), err)
+}
+
+/// Returns the vault error in the current locale. The library says its errors in English,
+/// the errors it wraps keep their own text.
+fn vault_message(err: &VaultError) -> String {
+    let heading =
+        |message: Message, err: &dyn fmt::Display| format!("{}\n{}", tr(message, &[]), err);
+    match err {
+        VaultError::SecretError(ref err) => heading(Message::DecryptFailed, err),
+        VaultError::PreferenceError(ref err) => heading(Message::InvalidKeyLength, err),
+        VaultError::SerializationError(ref err) => heading(Message::VaultSerializationFailed, err),
+        VaultError::IOError(ref err) => heading(Message::IoFailed, err),
+        VaultError::NoMatchingPreference => tr(Message::NoMatchingPreference, &[]),
+        VaultError::NoMatchingVault(ref name) => tr(Message::NoMatchingVault, &[name]),
+        VaultError::VaultAlreadyExists => tr(Message::VaultAlreadyExists, &[]),
+        VaultError::InvalidVaultName(ref name) => tr(Message::InvalidVaultName, &[name]),
+        VaultError::MemberAlreadyExists(ref recipient) => {
+            tr(Message::MemberAlreadyExists, &[recipient])
+        }
+        VaultError::NoMatchingMember(ref recipient) => tr(Message::NoMatchingMember, &[recipient]),
+        VaultError::VersionOutOfRange { requested, highest } => {
+            tr(Message::VersionOutOfRange, &[requested, highest])
         }
+        VaultError::LegacyVersion(version) => tr(Message::LegacyVersion, &[version]),
+        VaultError::MigrationFailed(ref name) => tr(Message::MigrationFailed, &[name]),
+        VaultError::WordlistError(ref err) => err.to_string(),
     }
 }
 
@@ -41,9 +252,42 @@ impl error::Error for HandlerError {
             Self::VaultError(ref err) => Some(err),
             Self::SecretError(ref err) => Some(err),
             Self::PreferenceError(ref err) => Some(err),
+            Self::RecipeError(ref err) => Some(err),
             Self::IOError(ref err) => Some(err),
+            Self::PinentryError(ref err) => Some(err),
+            Self::TemplateError(ref err) => Some(err),
+            Self::AskPasswordError(ref err) => Some(err),
             Self::ClipboardError(ref err) => Some(err.as_ref()),
             Self::ConversionError(ref err) => Some(err),
+            Self::SerializationError(ref err) => Some(err),
+            Self::ConfigError(ref err) => Some(err),
+            #[cfg(feature = "import")]
+            Self::CsvError(ref err) => Some(err),
+            #[cfg(feature = "import")]
+            Self::PassError(ref err) => Some(err),
+            #[cfg(feature = "import")]
+            Self::OnePasswordError(ref err) => Some(err),
+            #[cfg(feature = "import")]
+            Self::AgeError(ref err) => Some(err),
+            #[cfg(feature = "import")]
+            Self::ArchiveError(ref err) => Some(err),
+            #[cfg(feature = "import")]
+            Self::PaperError(ref err) => Some(err),
+            #[cfg(feature = "import")]
+            Self::ZVaultError(ref err) => Some(err),
+            #[cfg(feature = "import")]
+            Self::TransferError(ref err) => Some(err),
+            #[cfg(feature = "hibp")]
+            Self::BreachError(ref err) => Some(err),
+            #[cfg(feature = "keychain")]
+            Self::KeychainError(ref err) => Some(err),
+            #[cfg(feature = "masked-email")]
+            Self::MaskedEmailError(ref err) => Some(err),
+            #[cfg(feature = "ssh")]
+            Self::SshError(ref err) => Some(err),
+            #[cfg(feature = "secret-service")]
+            Self::SecretServiceError(ref err) => Some(err),
+            Self::AgentError(ref err) => Some(err),
             _ => None,
         }
     }
@@ -61,18 +305,48 @@ impl From<CryptoError> for HandlerError {
     }
 }
 
+impl From<RecipeError> for HandlerError {
+    fn from(err: RecipeError) -> Self {
+        HandlerError::RecipeError(err)
+    }
+}
+
 impl From<PreferenceError> for HandlerError {
     fn from(err: PreferenceError) -> Self {
         HandlerError::PreferenceError(err)
     }
 }
 
+impl From<TemplateError> for HandlerError {
+    fn from(err: TemplateError) -> Self {
+        HandlerError::TemplateError(err)
+    }
+}
+
+impl From<PinentryError> for HandlerError {
+    fn from(err: PinentryError) -> Self {
+        HandlerError::PinentryError(err)
+    }
+}
+
+impl From<AskPasswordError> for HandlerError {
+    fn from(err: AskPasswordError) -> Self {
+        HandlerError::AskPasswordError(err)
+    }
+}
+
 impl From<io::Error> for HandlerError {
     fn from(err: io::Error) -> Self {
         HandlerError::IOError(err)
     }
 }
 
+impl From<config::ConfigError> for HandlerError {
+    fn from(err: config::ConfigError) -> Self {
+        HandlerError::ConfigError(err)
+    }
+}
+
 impl From<Box<dyn error::Error>> for HandlerError {
     fn from(err: Box<dyn error::Error>) -> Self {
         HandlerError::ClipboardError(err)
@@ -85,69 +359,3294 @@ impl From<num::ParseIntError> for HandlerError {
     }
 }
 
-/// Creates a new vault
-pub fn add_vault(params: &HashMap<ParamName, String>) -> Result<(), HandlerError> {
-    let mut vs: Vaults<Secret> = Vaults::new()?;
-    let key = read_key_from_std_in("Key:")?;
-    let name = params
-        .get(&ParamName::VaultName)
-        .ok_or(HandlerError::MissingParam(ParamName::VaultName))?;
-    let secret = Secret::new(&key, &name, constants::SECRET_LENGTH)?;
-    vs.add(&name, secret)?;
+#[cfg(feature = "import")]
+impl From<csv::CsvError> for HandlerError {
+    fn from(err: csv::CsvError) -> Self {
+        HandlerError::CsvError(err)
+    }
+}
+
+#[cfg(feature = "import")]
+impl From<pass::PassError> for HandlerError {
+    fn from(err: pass::PassError) -> Self {
+        HandlerError::PassError(err)
+    }
+}
+
+#[cfg(feature = "import")]
+impl From<onepassword::OnePasswordError> for HandlerError {
+    fn from(err: onepassword::OnePasswordError) -> Self {
+        HandlerError::OnePasswordError(err)
+    }
+}
+
+#[cfg(feature = "import")]
+impl From<age::AgeError> for HandlerError {
+    fn from(err: age::AgeError) -> Self {
+        HandlerError::AgeError(err)
+    }
+}
+
+#[cfg(feature = "import")]
+impl From<archive::ArchiveError> for HandlerError {
+    fn from(err: archive::ArchiveError) -> Self {
+        HandlerError::ArchiveError(err)
+    }
+}
+
+#[cfg(feature = "import")]
+impl From<paper::PaperError> for HandlerError {
+    fn from(err: paper::PaperError) -> Self {
+        HandlerError::PaperError(err)
+    }
+}
+
+#[cfg(feature = "import")]
+impl From<zvault::ZVaultError> for HandlerError {
+    fn from(err: zvault::ZVaultError) -> Self {
+        HandlerError::ZVaultError(err)
+    }
+}
+
+#[cfg(feature = "import")]
+impl From<TransferError> for HandlerError {
+    fn from(err: TransferError) -> Self {
+        HandlerError::TransferError(err)
+    }
+}
+
+impl From<serde_json::Error> for HandlerError {
+    fn from(err: serde_json::Error) -> Self {
+        HandlerError::SerializationError(err)
+    }
+}
+
+#[cfg(feature = "hibp")]
+impl From<hibp::BreachError> for HandlerError {
+    fn from(err: hibp::BreachError) -> Self {
+        HandlerError::BreachError(err)
+    }
+}
+
+#[cfg(feature = "keychain")]
+impl From<keychain::KeychainError> for HandlerError {
+    fn from(err: keychain::KeychainError) -> Self {
+        HandlerError::KeychainError(err)
+    }
+}
+
+#[cfg(feature = "masked-email")]
+impl From<MaskedEmailError> for HandlerError {
+    fn from(err: MaskedEmailError) -> Self {
+        HandlerError::MaskedEmailError(err)
+    }
+}
+
+#[cfg(feature = "ssh")]
+impl From<ssh::SshError> for HandlerError {
+    fn from(err: ssh::SshError) -> Self {
+        HandlerError::SshError(err)
+    }
+}
+
+#[cfg(feature = "secret-service")]
+impl From<secret_service::SecretServiceError> for HandlerError {
+    fn from(err: secret_service::SecretServiceError) -> Self {
+        HandlerError::SecretServiceError(err)
+    }
+}
+
+impl From<AgentError> for HandlerError {
+    fn from(err: AgentError) -> Self {
+        HandlerError::AgentError(err)
+    }
+}
+
+/// Creates a new vault, the key is asked for twice when it is typed
+pub fn add_vault(ctx: &mut Context, params: &Params) -> Result<(), HandlerError> {
+    let mut vs = ctx.storage.vaults()?;
+    let name = required(params, ParamName::VaultName)?;
+    let key = read_key(ctx, params, "Key:")?;
+    // a typo would give passwords that can't be generated again
+    if is_terminal_key(params) && read_key(ctx, params, "Repeat the key:")? != key {
+        return Err(HandlerError::KeyMismatch);
+    }
+    let secret = Secret::new(&key, name, constants::SECRET_LENGTH)?;
+    vs.add(name, secret)?;
+    if let Some(v) = vs.get_mut(|v| v.name() == name) {
+        if let Some(hint) = params.text(ParamName::Hint) {
+            v.set_hint(Some(hint.to_owned()));
+            inform(params, HINT_WARNING.to_owned());
+        }
+        v.set_local_only(params.flag(ParamName::LocalOnly));
+    }
+    Ok(())
+}
+
+/// Sets the hint of the key of a vault, `--hint none` removes it, and whether the vault stays
+/// on this machine
+pub fn update_vault(ctx: &mut Context, params: &Params) -> Result<(), HandlerError> {
+    let mut m = ctx.storage.vaults()?;
+    let v = select_vault(&mut m, params)?;
+    if !params.has(ParamName::Hint) && !params.has(ParamName::LocalOnly) {
+        return Err(HandlerError::MissingParam(ParamName::Hint));
+    }
+    match params.text(ParamName::Hint) {
+        Some("none") => v.set_hint(None),
+        Some(hint) => {
+            v.set_hint(Some(hint.to_owned()));
+            inform(params, HINT_WARNING.to_owned());
+        }
+        None => {}
+    }
+    if params.has(ParamName::LocalOnly) {
+        v.set_local_only(params.flag(ParamName::LocalOnly));
+    }
     Ok(())
 }
 
 /// Stores the defaults for a password
-pub fn add_password(params: &HashMap<ParamName, String>) -> Result<(), HandlerError> {
-    let mut m: Vaults<Secret> = Vaults::new()?;
-    let v = m.get_default_mut().ok_or(HandlerError::MissingVault)?;
-    let domain = params
-        .get(&ParamName::DomainName)
-        .ok_or(HandlerError::MissingParam(ParamName::DomainName))?;
-    let username = params
-        .get(&ParamName::UserName)
-        .ok_or(HandlerError::MissingParam(ParamName::UserName))?;
+pub fn add_password(ctx: &mut Context, params: &Params) -> Result<(), HandlerError> {
+    let mut m = ctx.storage.vaults()?;
+    let v = select_vault(&mut m, params)?;
+    let domain = idn::to_ascii(required(params, ParamName::DomainName)?);
+    let domain = domain.as_str();
+    let unicode = idn::to_unicode(domain);
+    if unicode != domain && v.preferences.has_domain(&unicode) {
+        eprintln!(
+            "Warning: {} is stored as Unicode and keeps its passwords, this one is added as {}",
+            unicode, domain
+        );
+    }
     let length = params
-        .get(&ParamName::Length)
-        .ok_or(HandlerError::MissingParam(ParamName::Length))?
-        .parse::<usize>()?;
-    let p = Preference::new(domain, username, length);
+        .number(ParamName::Length)
+        .or_else(|| v.default_length())
+        .ok_or(HandlerError::MissingParam(ParamName::Length))?;
+    let alias = masked_email(params, domain)?;
+    let default_username = v.default_username().map(String::from);
+    let username = match (&alias, params.text(ParamName::UserName), &default_username) {
+        (Some(alias), ..) => alias.as_str(),
+        (None, Some(username), _) => username,
+        (None, None, Some(username)) => username.as_str(),
+        (None, None, None) => return Err(HandlerError::MissingParam(ParamName::UserName)),
+    };
+    let mut p = Preference::new(domain, username, length);
+    p.unambiguous = params.flag(ParamName::Unambiguous);
+    p.match_subdomains = params.flag(ParamName::MatchSubdomains);
+    if let Some(every) = rotation_period(params)? {
+        p.rotate_every = every;
+    }
+    if let Some(recipe) = recipe(params)? {
+        p.recipe = recipe;
+    }
+    if let Some(list) = params.text(ParamName::Wordlist) {
+        let root = v.dir();
+        let list = if Path::new(list).is_file() {
+            Wordlist::import(&root, Path::new(list))
+        } else {
+            Wordlist::find(&root, list)
+        }
+        .map_err(VaultError::from)?;
+        p.wordlist = Some(list);
+        inform(params, format!("Strength: {}", strength::of(&p)));
+        v.preferences.add(p)?;
+        hooks::run(&v.dir(), Event::PreferenceAdded, v.name(), domain, username);
+        return Ok(());
+    }
+    if let Some(name) = params.text(ParamName::Preset) {
+        let preset =
+            Preset::parse(name).ok_or_else(|| HandlerError::UnknownPreset(name.to_owned()))?;
+        p = p.with_rules(preset.rules());
+        p.preset = Some(preset);
+    } else if !params.flag(ParamName::IgnoreRules) {
+        if let Some(rules) = rules::for_domain(domain) {
+            p = p.with_rules(rules);
+            inform(
+                params,
+                format!(
+                    "Applied the known password rules of {}, the length is {} (skip with --ignore-rules)",
+                    domain, p.length
+                ),
+            );
+        }
+    }
+    p.length = fit_length(params, domain, length, p.rules.as_ref());
+    inform(params, format!("Strength: {}", strength::of(&p)));
+    let allowed_length = p
+        .rules
+        .as_ref()
+        .map_or(constants::MIN_PASSWORD_LENGTH, |r| {
+            r.clamp_length(constants::MIN_PASSWORD_LENGTH)
+        });
+    if p.length < constants::MIN_PASSWORD_LENGTH && allowed_length > p.length {
+        inform(
+            params,
+            format!(
+                "Consider -l {} if the site allows it ({})",
+                allowed_length,
+                strength::estimate(allowed_length, crypto::CHARSET_SIZE)
+            ),
+        );
+    }
     v.preferences.add(p)?;
+    hooks::run(&v.dir(), Event::PreferenceAdded, v.name(), domain, username);
     Ok(())
 }
 
 /// Generates a password
-pub fn get_password(params: &HashMap<ParamName, String>) -> Result<(), HandlerError> {
-    let mut m: Vaults<Secret> = Vaults::new()?;
-    let v = m.get_default_mut().ok_or(HandlerError::MissingVault)?;
-    let key = read_key_from_std_in("Key:")?;
-    let domain = params
-        .get(&ParamName::DomainName)
-        .ok_or(HandlerError::MissingParam(ParamName::DomainName))?;
-    let username = params.get(&ParamName::UserName).map(|v| &v[..]);
-    let length = match params.get(&ParamName::Length) {
-        Some(l) => match l.parse::<usize>() {
-            Ok(l) => Some(l),
-            Err(err) => return Err(HandlerError::ConversionError(err)),
-        },
-        None => None,
-    };
-    let password = v.get_password(domain, &key, username, length, None)?;
-    copy_password_to_clipboard(password)?;
+pub fn get_password(ctx: &mut Context, params: &Params) -> Result<(), HandlerError> {
+    let mut m = ctx.storage.vaults()?;
+    let v = select_vault(&mut m, params)?;
+    let (domain, username) = match params.text(ParamName::DomainName) {
+        Some(domain) => (
+            stored_domain(v, domain),
+            params.text(ParamName::UserName).map(String::from),
+        ),
+        // most recently used first
+        None => {
+            let preferences = unarchived(v.preferences.by_recency(), params);
+            let labels: Vec<String> = preferences.iter().map(|p| menu::label(p)).collect();
+            match ctx.prompter.select(&tr(Message::Account, &[]), &labels)? {
+                Some(i) => (
+                    preferences[i].domain.clone(),
+                    Some(preferences[i].username.clone()),
+                ),
+                None => return Ok(()),
+            }
+        }
+    };
+    let inherited = if v.preferences.has_domain(&domain) {
+        None
+    } else {
+        v.preferences.inherited(&domain, username.as_deref())
+    };
+    let (domain, username) = match inherited {
+        Some(p) => {
+            if !params.flag(ParamName::Quiet) {
+                eprintln!(
+                    "Using the password of {} {} for {}",
+                    p.domain, p.username, domain
+                );
+            }
+            (p.domain.clone(), Some(p.username.clone()))
+        }
+        None => (domain, username),
+    };
+    known_domain(v, &domain)?;
+    let archived: Vec<bool> = v
+        .preferences
+        .of_domain(&domain)
+        .iter()
+        .filter(|p| username.is_none() || username.as_deref() == Some(p.username.as_str()))
+        .map(|p| p.archived)
+        .collect();
+    if !archived.is_empty()
+        && archived.iter().all(|&a| a)
+        && !params.flag(ParamName::IncludeArchived)
+    {
+        return Err(HandlerError::Archived(domain));
+    }
+    let username = match username {
+        None if v.preferences.default_of(&domain).is_none() => {
+            match choose_account(ctx, params, v, &domain)? {
+                Some(username) => Some(username),
+                None => return Ok(()),
+            }
+        }
+        username => username,
+    };
+    let (domain, username) = (&domain, username.as_deref());
+    let key = vault_key(ctx, params, v)?;
+    let length = params.number(ParamName::Length);
+    let version = params.number(ParamName::Version);
+    let password = v.get_password(domain, &key, username, length, version)?;
+    let preference = match username {
+        Some(username) => v.preferences.account(domain, username),
+        None => v.preferences.default_of(domain),
+    };
+    if let Some(p) = preference.filter(|p| p.is_due(ctx.clock.now())) {
+        eprintln!(
+            "The password of {} {} is due for rotation, run zpass rotate --due",
+            p.domain, p.username
+        );
+    }
+    if params.flag(ParamName::Spell) {
+        // each group is spelled on a line of its own
+        let size = params.number(ParamName::Group).unwrap_or(0);
+        let spelled: Vec<String> = spell::groups(&password, size)
+            .iter()
+            .map(|g| spell::spell(g))
+            .collect();
+        println!("{}", spelled.join("\n"));
+        return Ok(());
+    }
+    if params.text(ParamName::Output) == Some("qr") {
+        // a phone scanning the code of a Wi-Fi passphrase joins the network named by the domain
+        if matches!(preference, Some(p) if p.preset == Some(Preset::Wifi)) {
+            return print_qr(&preset::wifi_qr_text(domain, &password));
+        }
+        return print_qr(&password);
+    }
+    if params.flag(ParamName::Stdout) {
+        println!("{}", grouped(params, &password));
+        return Ok(());
+    }
+    if params.flag(ParamName::Type) {
+        let username = match username {
+            Some(username) => username.to_owned(),
+            None => v
+                .preferences
+                .default_of(domain)
+                .map(|p| p.username.clone())
+                .unwrap_or_default(),
+        };
+        return type_login(&username, &password);
+    }
+    copy_password_to_clipboard(ctx, params, password)?;
     Ok(())
 }
 
-// --------------------------------- Helpers ----------------------------------
+/// Asks which account is meant when a domain has several and none of them is the default,
+/// unless one of them has the default username of the vault.
+/// The choice can become the default, so it isn't asked again. None if it was cancelled.
+fn choose_account(
+    ctx: &mut Context,
+    params: &Params,
+    v: &mut Vault<Secret>,
+    domain: &str,
+) -> Result<Option<String>, HandlerError> {
+    let usernames: Vec<String> = unarchived(v.preferences.of_domain(domain), params)
+        .iter()
+        .map(|p| p.username.clone())
+        .collect();
+    if usernames.len() == 1 {
+        return Ok(usernames.into_iter().next());
+    }
+    if let Some(username) = v
+        .default_username()
+        .filter(|u| usernames.iter().any(|n| n == u))
+    {
+        return Ok(Some(username.to_owned()));
+    }
+    let message = tr(Message::AccountOf, &[&domain]);
+    let username = match ctx.prompter.select(&message, &usernames)? {
+        Some(i) => usernames[i].clone(),
+        None => return Ok(None),
+    };
+    let message = tr(Message::MakeDefault, &[&username, &domain]);
+    if confirm(ctx, params, &message)? {
+        v.preferences.set_default(domain, &username)?;
+    }
+    Ok(Some(username))
+}
 
-/// Reads a line from stdin while concealing what's being typed.
-fn read_key_from_std_in(message: &str) -> Result<String, HandlerError> {
-    let key = rpassword::read_password_from_tty(Some(message))?;
-    Ok(key)
+/// Lists the stored password preferences, most recently used first or in the --sort order
+pub fn list_passwords(ctx: &mut Context, params: &Params) -> Result<(), HandlerError> {
+    let mut m = ctx.storage.vaults()?;
+    let v = select_vault(&mut m, params)?;
+    let now = ctx.clock.now();
+    let sort = match params.text(ParamName::Sort) {
+        Some(name) => {
+            Sort::parse(name).ok_or_else(|| HandlerError::UnknownSort(name.to_owned()))?
+        }
+        None => Sort::LastUsed,
+    };
+    let sorted = v.preferences.sorted(sort, params.flag(ParamName::Reverse));
+    let preferences = page(unarchived(sorted, params), params);
+    match params.text(ParamName::Output) {
+        Some("alfred") => {
+            let filter = menu::ScriptFilter::new(&preferences, |p| {
+                format!("{}, {}", p.username, describe_last_used(p.last_used, now))
+            });
+            println!("{}", serde_json::to_string(&filter)?);
+        }
+        Some("table") | None => {
+            let mut table = Table::new(&["DOMAIN", "USERNAME", "LAST USED", "USES"]);
+            for p in preferences {
+                let cells = vec![
+                    p.domain.clone(),
+                    p.username.clone(),
+                    describe_last_used(p.last_used, now),
+                    p.uses.to_string(),
+                ];
+                table.push(cells, p.is_default(), p.is_expired(now));
+            }
+            table.print();
+        }
+        Some(output) => return Err(HandlerError::UnsupportedOutput(output.to_owned())),
+    }
+    Ok(())
+}
+
+/// Lists the domains of the selected vault that match the --filter glob, e.g. `*.google.*`,
+/// each with its usernames if --usernames is given
+pub fn list_domains(ctx: &mut Context, params: &Params) -> Result<(), HandlerError> {
+    let mut m = ctx.storage.vaults()?;
+    let v = select_vault(&mut m, params)?;
+    let pattern = params.text(ParamName::Filter).unwrap_or("*");
+    for domain in v.preferences.domains_matching(pattern) {
+        let accounts = unarchived(v.preferences.of_domain(domain), params);
+        if accounts.is_empty() {
+            continue;
+        }
+        if !params.flag(ParamName::Usernames) {
+            println!("{}", domain);
+            continue;
+        }
+        for p in accounts {
+            println!("{}\t{}", domain, p.username);
+        }
+    }
+    Ok(())
+}
+
+/// Deletes the preferences of a password, a stored password is deleted with them
+pub fn delete_password(ctx: &mut Context, params: &Params) -> Result<(), HandlerError> {
+    let mut m = ctx.storage.vaults()?;
+    let v = select_vault(&mut m, params)?;
+    let domain = &stored_domain(v, required(params, ParamName::DomainName)?);
+    let username = required(params, ParamName::UserName)?;
+    known_domain(v, domain)?;
+    let message = tr(Message::DeletePassword, &[&domain, &username]);
+    if !confirm(ctx, params, &message)? {
+        return Ok(());
+    }
+    v.move_to_trash(domain, username, ctx.clock.now())?;
+    inform(
+        params,
+        format!(
+            "Moved {} {} to the trash of {}, trash restore brings it back",
+            domain,
+            username,
+            v.name()
+        ),
+    );
+    Ok(())
+}
+
+/// Leaves an account out of the listings and the suggestions, or lists it again
+pub fn archive_password(
+    ctx: &mut Context,
+    params: &Params,
+    archived: bool,
+) -> Result<(), HandlerError> {
+    let mut m = ctx.storage.vaults()?;
+    let v = select_vault(&mut m, params)?;
+    let domain = &stored_domain(v, required(params, ParamName::DomainName)?);
+    known_domain(v, domain)?;
+    let username = username_of(v, params, domain)?;
+    v.get_preference_mut(domain, &username)?.archived = archived;
+    let message = if archived {
+        format!(
+            "Archived {} {}, --include-archived lists it",
+            domain, username
+        )
+    } else {
+        format!("Listing {} {} again", domain, username)
+    };
+    inform(params, message);
+    Ok(())
+}
+
+/// Deletes a vault, its secret can't be recovered without a backup
+pub fn delete_vault(ctx: &mut Context, params: &Params) -> Result<(), HandlerError> {
+    let mut m = ctx.storage.vaults()?;
+    let name = required(params, ParamName::VaultName)?;
+    let message = tr(Message::DeleteVault, &[&name]);
+    if !confirm(ctx, params, &message)? {
+        return Ok(());
+    }
+    m.remove(name)?;
+    inform(params, format!("Deleted the vault {}", name));
+    Ok(())
+}
+
+/// Changes the length or the version of a password, without either it moves to a new version
+pub fn update_password(ctx: &mut Context, params: &Params) -> Result<(), HandlerError> {
+    let mut m = ctx.storage.vaults()?;
+    let v = select_vault(&mut m, params)?;
+    let domain = &stored_domain(v, required(params, ParamName::DomainName)?);
+    let username = required(params, ParamName::UserName)?;
+    known_domain(v, domain)?;
+    let length = params.number(ParamName::Length);
+    let version = params.number(ParamName::Version);
+    let period = rotation_period(params)?;
+    let recipe = recipe(params)?;
+    // a period, a recipe or subdomain matching alone is no new version
+    let settings_only =
+        period.is_some() || recipe.is_some() || params.has(ParamName::MatchSubdomains);
+    let p = v.get_preference_mut(domain, username)?;
+    if length.is_some() || version.is_some() || !settings_only {
+        p.record_change(ctx.clock.now());
+    }
+    if let Some(length) = length {
+        p.length = fit_length(params, domain, length, p.rules.as_ref());
+    }
+    if let Some(every) = period {
+        p.rotate_every = every;
+    }
+    if let Some(recipe) = recipe {
+        p.recipe = recipe;
+    }
+    if params.has(ParamName::MatchSubdomains) {
+        p.match_subdomains = params.flag(ParamName::MatchSubdomains);
+    }
+    match version {
+        Some(version) => p.set_version(version),
+        None if length.is_none() && !settings_only => p.set_version(p.highest_version() + 1),
+        None => {}
+    }
+    inform(
+        params,
+        format!(
+            "{} {} is at version {} with length {}",
+            domain, username, p.version, p.length
+        ),
+    );
+    Ok(())
+}
+
+/// Moves the passwords of the selected vault, or the default one, that are due for rotation to
+/// a new version, and prints them so the accounts can be updated
+pub fn rotate(ctx: &mut Context, params: &Params) -> Result<(), HandlerError> {
+    if !params.flag(ParamName::Due) {
+        return Err(HandlerError::MissingParam(ParamName::Due));
+    }
+    let mut m = ctx.storage.vaults()?;
+    let v = select_vault(&mut m, params)?;
+    let now = ctx.clock.now();
+    let mut rotated = 0;
+    for p in v.preferences.iter_mut().filter(|p| p.is_due(now)) {
+        p.record_change(now);
+        p.set_version(p.highest_version() + 1);
+        println!("{}\t{}\tversion {}", p.domain, p.username, p.version);
+        rotated += 1;
+    }
+    if rotated == 0 {
+        inform(params, "No password is due for rotation".to_owned());
+    }
+    Ok(())
+}
+
+/// Makes an account the default of its domain
+pub fn set_password(ctx: &mut Context, params: &Params) -> Result<(), HandlerError> {
+    let mut m = ctx.storage.vaults()?;
+    let v = select_vault(&mut m, params)?;
+    let domain = required(params, ParamName::DomainName)?;
+    let username = required(params, ParamName::UserName)?;
+    v.preferences.set_default(domain, username)?;
+    Ok(())
+}
+
+/// Makes a vault the default vault, or sets the length and the username of the passwords added
+/// to it without -l or -u: `--default-length 0` and `--default-username none` remove them
+pub fn set_vault(ctx: &mut Context, params: &Params) -> Result<(), HandlerError> {
+    let mut m = ctx.storage.vaults()?;
+    if !params.has(ParamName::DefaultLength) && !params.has(ParamName::DefaultUsername) {
+        let name = required(params, ParamName::VaultName)?;
+        m.set_default(name)?;
+        return Ok(());
+    }
+    let v = select_vault(&mut m, params)?;
+    if let Some(length) = params.number(ParamName::DefaultLength) {
+        v.set_default_length(Some(length).filter(|&length| length > 0));
+        let message = match v.default_length() {
+            Some(length) => format!(
+                "Passwords added to {} without -l are {} characters long",
+                v.name(),
+                length
+            ),
+            None => format!("Passwords added to {} need -l again", v.name()),
+        };
+        inform(params, message);
+    }
+    match params.text(ParamName::DefaultUsername) {
+        Some("none") => {
+            v.set_default_username(None);
+            inform(
+                params,
+                format!("Passwords added to {} need -u again", v.name()),
+            );
+        }
+        Some(username) => {
+            v.set_default_username(Some(username.to_owned()));
+            inform(
+                params,
+                format!(
+                    "Passwords added to {} without -u are for {}",
+                    v.name(),
+                    username
+                ),
+            );
+        }
+        None => {}
+    }
+    Ok(())
+}
+
+/// Renames a vault, a key kept in the keychain has to be remembered again
+pub fn rename_vault(
+    ctx: &mut Context,
+    params: &Params,
+    args: &[String],
+) -> Result<(), HandlerError> {
+    let mut m = ctx.storage.vaults()?;
+    let name = required(params, ParamName::VaultName)?;
+    let new_name = args
+        .first()
+        .ok_or(HandlerError::MissingArgument("new name"))?;
+    let message = tr(Message::RenameVault, &[&name, &new_name]);
+    if !confirm(ctx, params, &message)? {
+        return Ok(());
+    }
+    m.rename(name, new_name)?;
+    inform(
+        params,
+        format!("Renamed the vault {} to {}", name, new_name),
+    );
+    Ok(())
+}
+
+/// Copies a vault under a new name, it generates the same passwords with the same key
+pub fn clone_vault(ctx: &mut Context, params: &Params) -> Result<(), HandlerError> {
+    let mut m = ctx.storage.vaults()?;
+    let name = required(params, ParamName::VaultName)?;
+    let new_name = required(params, ParamName::To)?;
+    m.duplicate(name, new_name)?;
+    inform(params, format!("Cloned the vault {} to {}", name, new_name));
+    Ok(())
+}
+
+/// Copies the settings of the accounts of a domain, or one of them, from a vault to another.
+/// The other vault generates passwords of its own, stored passwords, notes and totp stay behind
+pub fn copy_password(ctx: &mut Context, params: &Params) -> Result<(), HandlerError> {
+    let mut m = ctx.storage.vaults()?;
+    let domain = required(params, ParamName::DomainName)?;
+    let username = params.text(ParamName::UserName);
+    let (from, to) = (
+        required(params, ParamName::From)?,
+        required(params, ParamName::To)?,
+    );
+    let source = m
+        .get_mut(|v| v.name() == from)
+        .ok_or(HandlerError::MissingVault)?;
+    let domain = &stored_domain(source, domain);
+    known_domain(source, domain)?;
+    let copies: Vec<Preference> = source
+        .preferences
+        .of_domain(domain)
+        .into_iter()
+        .filter(|p| username.is_none() || username == Some(p.username.as_str()))
+        .map(|p| {
+            if p.is_stored() || p.notes.is_some() || p.totp.is_some() {
+                eprintln!(
+                    "The stored password, note and totp of {} {} are not copied",
+                    p.domain, p.username
+                );
+            }
+            p.copy_settings()
+        })
+        .collect();
+    if copies.is_empty() {
+        return Err(PreferenceError::NoMatchingPreferenceFound.into());
+    }
+    let target = m
+        .get_mut(|v| v.name() == to)
+        .ok_or(HandlerError::MissingVault)?;
+    for p in copies {
+        let account = format!("{} {}", p.domain, p.username);
+        target.preferences.add(p)?;
+        inform(
+            params,
+            format!("Copied {} from {} to {}", account, from, to),
+        );
+    }
+    Ok(())
+}
+
+/// Lists the vaults, with --long also their accounts, files and schemes
+pub fn list_vaults(ctx: &mut Context, params: &Params) -> Result<(), HandlerError> {
+    let m = ctx.storage.vaults()?;
+    if !params.flag(ParamName::Long) {
+        let mut table = Table::new(&["VAULT"]);
+        for v in m.iter() {
+            table.push(vec![v.name().to_owned()], v.is_default(), false);
+        }
+        table.print();
+        return Ok(());
+    }
+    let now = ctx.clock.now();
+    let mut table = Table::new(&[
+        "VAULT", "DEFAULT", "SYNC", "ACCOUNTS", "TRASHED", "SIZE", "FORMAT", "SCHEMES", "MODIFIED",
+    ]);
+    for v in m.iter() {
+        let metadata = fs::metadata(v.path()).ok();
+        let modified = metadata
+            .as_ref()
+            .and_then(|m| m.modified().ok())
+            .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+            .map(|d| d.as_secs());
+        let mut schemes: Vec<usize> = v.preferences.iter().map(|p| p.scheme).collect();
+        schemes.sort_unstable();
+        schemes.dedup();
+        let schemes: Vec<String> = schemes.iter().map(|s| s.to_string()).collect();
+        let format = if v.is_outdated()? {
+            "outdated"
+        } else {
+            "current"
+        };
+        let cells = vec![
+            v.name().to_owned(),
+            if v.is_default() { "yes" } else { "no" }.to_owned(),
+            if v.is_local_only() { "local" } else { "remote" }.to_owned(),
+            v.preferences.len().to_string(),
+            v.trash().len().to_string(),
+            describe_size(metadata.map(|m| m.len()).unwrap_or(0)),
+            format.to_owned(),
+            schemes.join(","),
+            match modified {
+                Some(_) => describe_last_used(modified, now),
+                None => "-".to_owned(),
+            },
+        ];
+        table.push(cells, v.is_default(), false);
+    }
+    table.print();
+    Ok(())
+}
+
+/// Lists the settings of every password in the selected vault, or the default one
+pub fn list_preferences(ctx: &mut Context, params: &Params) -> Result<(), HandlerError> {
+    let mut m = ctx.storage.vaults()?;
+    let v = select_vault(&mut m, params)?;
+    let now = ctx.clock.now();
+    let mut table = Table::new(&["DOMAIN", "USERNAME", "SETTINGS"]);
+    for p in page(unarchived(v.preferences.by_recency(), params), params) {
+        let mut settings = vec![
+            format!("length {}", p.length),
+            format!("version {}", p.version),
+            format!("scheme {}", p.scheme),
+        ];
+        if p.is_stored() {
+            settings.push("stored".to_owned());
+        }
+        if p.unambiguous {
+            settings.push("unambiguous".to_owned());
+        }
+        if p.rules.is_some() {
+            settings.push("rules".to_owned());
+        }
+        if p.notes.is_some() {
+            settings.push("note".to_owned());
+        }
+        if p.archived {
+            settings.push("archived".to_owned());
+        }
+        if !p.tags.is_empty() {
+            settings.push(format!("tags {}", p.tags.join(",")));
+        }
+        if let Some(every) = p.rotate_every {
+            settings.push(format!("rotate every {}", time::describe_period(every)));
+        }
+        settings.push(format!("used {} times", p.uses));
+        let cells = vec![p.domain.clone(), p.username.clone(), settings.join(", ")];
+        table.push(cells, p.is_default(), p.is_expired(now));
+    }
+    table.print();
+    Ok(())
+}
+
+/// Prints everything known about the accounts of a domain, or one of them, in every vault or
+/// the one named, without generating a password
+pub fn get_preference(ctx: &mut Context, params: &Params) -> Result<(), HandlerError> {
+    let m = ctx.storage.vaults()?;
+    let domain = required(params, ParamName::DomainName)?;
+    let username = params.text(ParamName::UserName);
+    let vault = params.text(ParamName::VaultName);
+    let now = ctx.clock.now();
+    let vaults: Vec<&Vault<Secret>> = m
+        .iter()
+        .filter(|v| vault.is_none() || vault == Some(v.name()))
+        .collect();
+    let mut found = 0;
+    for v in &vaults {
+        for p in v.preferences.of_domain(&stored_domain(v, domain)) {
+            if username.is_some() && username != Some(p.username.as_str()) {
+                continue;
+            }
+            if found > 0 {
+                println!();
+            }
+            found += 1;
+            for (name, value) in describe_preference(v, p, now) {
+                println!("{:<14}{}", name, value);
+            }
+        }
+    }
+    if found == 0 {
+        let domains = vaults
+            .iter()
+            .flat_map(|v| v.preferences.by_recency())
+            .filter(|p| !p.archived)
+            .map(|p| p.domain.clone())
+            .collect();
+        return Err(HandlerError::UnknownDomain(domain.to_owned(), domains));
+    }
+    Ok(())
+}
+
+/// Returns the length a password of the domain gets, cut to what its rules allow, or to the
+/// known maximum of the site with --truncate. Warns when the length is more than the site
+/// accepts: a site that cuts a password silently locks its user out.
+fn fit_length(params: &Params, domain: &str, length: usize, rules: Option<&Rules>) -> usize {
+    let known = rules::for_domain(domain);
+    let max = rules.or(known.as_ref()).and_then(|r| r.max_length);
+    let fitted = match (rules, max) {
+        (Some(rules), _) => rules.clamp_length(length),
+        (None, Some(max)) if params.flag(ParamName::Truncate) => length.min(max),
+        (None, _) => length,
+    };
+    match max {
+        Some(max) if length > max && fitted <= max => eprintln!(
+            "Warning: {} accepts at most {} characters, the length is cut to {}",
+            domain, max, fitted
+        ),
+        Some(max) if length > max => eprintln!(
+            "Warning: {} accepts at most {} characters and may cut a longer password, \
+             which locks you out. Use -l {} or --truncate",
+            domain, max, max
+        ),
+        _ => {}
+    }
+    fitted
+}
+
+/// Returns the settings of a preference as names and values, the length with what bounds it.
+fn describe_preference(v: &Vault<Secret>, p: &Preference, now: u64) -> Vec<(&'static str, String)> {
+    let yes_no = |on: bool| if on { "yes" } else { "no" }.to_owned();
+    let mut length = p.length.to_string();
+    if let Some(rules) = &p.rules {
+        let clamped = rules.clamp_length(p.length);
+        if clamped != p.length {
+            length = format!("{} (the rules of the domain make it {})", p.length, clamped);
+        }
+    }
+    let charset = match (&p.token, &p.rules, p.unambiguous) {
+        (Some(token), _, _) => format!(
+            "token: prefix {:?}, encoding {:?}",
+            token.prefix, token.encoding
+        ),
+        (None, Some(rules), _) => format!(
+            "rules: {} allowed characters, {} required groups",
+            rules.allowed.chars().count(),
+            rules.required.len()
+        ),
+        (None, None, true) => "default, without ambiguous characters".to_owned(),
+        (None, None, false) => "default".to_owned(),
+    };
+    let charset = match &p.wordlist {
+        Some(list) => format!("wordlist {}, the length is in words", list.name),
+        None => charset,
+    };
+    let mut settings = vec![
+        (
+            "vault",
+            format!(
+                "{}{}",
+                v.name(),
+                if v.is_default() { " (default)" } else { "" }
+            ),
+        ),
+        ("domain", p.domain.clone()),
+        ("username", p.username.clone()),
+        ("default", yes_no(p.is_default())),
+        ("length", length),
+        (
+            "version",
+            format!("{} (highest {})", p.version, p.highest_version()),
+        ),
+        ("scheme", p.scheme.to_string()),
+        ("charset", charset),
+        ("stored", yes_no(p.is_stored())),
+        ("tags", p.tags.join(", ")),
+        ("note", yes_no(p.notes.is_some())),
+        ("totp", yes_no(p.totp.is_some())),
+        (
+            "rotate every",
+            p.rotate_every
+                .map_or_else(|| "never".to_owned(), time::describe_period),
+        ),
+        (
+            "changed",
+            p.changed.map_or_else(|| "unknown".to_owned(), time::format),
+        ),
+        ("last used", describe_last_used(p.last_used, now)),
+        ("uses", p.uses.to_string()),
+    ];
+    if p.is_due(now) {
+        settings.push(("due", "yes, run zpass rotate --due".to_owned()));
+    }
+    settings
+}
+
+/// Keeps a note with a password, the note is read from stdin up to an empty line
+pub fn add_note(ctx: &mut Context, params: &Params) -> Result<(), HandlerError> {
+    let mut m = ctx.storage.vaults()?;
+    let v = select_vault(&mut m, params)?;
+    let domain = &stored_domain(v, required(params, ParamName::DomainName)?);
+    let username = required(params, ParamName::UserName)?;
+    known_domain(v, domain)?;
+    let key = vault_key(ctx, params, v)?;
+    eprintln!("Note, end with an empty line:");
+    let note = read_lines_from_std_in()?;
+    let sealed = v.seal(&key, note.trim_end())?;
+    v.get_preference_mut(domain, username)?.notes = Some(sealed);
+    Ok(())
+}
+
+/// Prints the note of a password
+pub fn get_note(ctx: &mut Context, params: &Params) -> Result<(), HandlerError> {
+    let mut m = ctx.storage.vaults()?;
+    let v = select_vault(&mut m, params)?;
+    let domain = &stored_domain(v, required(params, ParamName::DomainName)?);
+    let username = required(params, ParamName::UserName)?;
+    known_domain(v, domain)?;
+    let sealed = match &v.get_preference_mut(domain, username)?.notes {
+        Some(sealed) => sealed.clone(),
+        None => return Ok(()),
+    };
+    let key = vault_key(ctx, params, v)?;
+    println!("{}", v.unseal(&key, &sealed)?);
+    Ok(())
+}
+
+/// Deletes the note of a password
+pub fn delete_note(ctx: &mut Context, params: &Params) -> Result<(), HandlerError> {
+    let mut m = ctx.storage.vaults()?;
+    let v = select_vault(&mut m, params)?;
+    let domain = &stored_domain(v, required(params, ParamName::DomainName)?);
+    let username = required(params, ParamName::UserName)?;
+    known_domain(v, domain)?;
+    let message = tr(Message::DeleteNote, &[&domain, &username]);
+    if !confirm(ctx, params, &message)? {
+        return Ok(());
+    }
+    v.get_preference_mut(domain, username)?.notes = None;
+    Ok(())
+}
+
+/// Changes the key of the selected vault, or the default one.
+/// The passwords stay the same, a key kept in the keychain is replaced.
+pub fn update_key(ctx: &mut Context, params: &Params) -> Result<(), HandlerError> {
+    let mut m = ctx.storage.vaults()?;
+    let v = select_vault(&mut m, params)?;
+    let message = tr(Message::ChangeKey, &[&v.name()]);
+    if !confirm(ctx, params, &message)? {
+        return Ok(());
+    }
+    let key = vault_key(ctx, params, v)?;
+    let new_key = read_key(ctx, params, "New key:")?;
+    if is_terminal_key(params) && read_key(ctx, params, "Repeat the new key:")? != new_key {
+        return Err(HandlerError::KeyMismatch);
+    }
+    v.secret_mut().change_key(&key, &new_key)?;
+    #[cfg(feature = "keychain")]
+    {
+        if !constants::is_dry_run() && keychain::load(v.name())?.is_some() {
+            keychain::store(v.name(), &new_key)?;
+        }
+    }
+    inform(params, format!("Changed the key of {}", v.name()));
+    Ok(())
+}
+
+/// Sets up zpass on the first run: the directory of the vaults, the first vault and the
+/// completions of the shell. The key is hashed rather than stretched, so there is no key
+/// derivation whose cost could be calibrated to the machine.
+pub fn init(ctx: &mut Context, params: &Params) -> Result<(), HandlerError> {
+    let root = init_root(ctx, params)?;
+    let mut vs = ctx.storage.vaults()?;
+    if vs.iter().next().is_some() {
+        inform(params, format!("{} already holds vaults", root.display()));
+    } else {
+        let name = match params.text(ParamName::VaultName) {
+            Some(name) => name.to_owned(),
+            None => ask(ctx, params, "Name of the first vault", "personal")?,
+        };
+        let key = read_key(ctx, params, "Key:")?;
+        if is_terminal_key(params) && read_key(ctx, params, "Repeat the key:")? != key {
+            return Err(HandlerError::KeyMismatch);
+        }
+        let secret = Secret::new(&key, &name, constants::SECRET_LENGTH)?;
+        vs.add(&name, secret)?;
+        inform(params, format!("Created the vault {}", name));
+    }
+    init_completions(ctx, params)
+}
+
+/// Creates the directory of the vaults. Unless ZPASS_HOME names it, it's asked for, with the
+/// data directory of the platform as the default, and kept as the root of the config.
+fn init_root(ctx: &mut Context, params: &Params) -> Result<PathBuf, HandlerError> {
+    if env::var_os(constants::HOME_VARIABLE).is_some() {
+        let root = constants::root();
+        if !constants::is_dry_run() {
+            fs::create_dir_all(&root)?;
+        }
+        return Ok(root);
+    }
+    let default = constants::data_dir().unwrap_or_else(constants::root);
+    let answer = ask(
+        ctx,
+        params,
+        "Where should the vaults be kept?",
+        &default.to_string_lossy(),
+    )?;
+    let root = env::current_dir()?.join(answer);
+    let config_path = config::paths()
+        .into_iter()
+        .next()
+        .ok_or(HandlerError::MissingArgument("HOME or XDG_CONFIG_HOME"))?;
+    // ZPASS_HOME would be set from the root of the config, so the config has none yet
+    let mut text = match fs::read_to_string(&config_path) {
+        Ok(text) => text,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(err) => return Err(err.into()),
+    };
+    if !text.is_empty() && !text.ends_with('\n') {
+        text.push('\n');
+    }
+    text.push_str(&format!("root = {:?}\n", root.to_string_lossy()));
+    if !constants::is_dry_run() {
+        fs::create_dir_all(&root)?;
+        if let Some(dir) = config_path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+    }
+    write_file(&config_path.to_string_lossy(), text)?;
+    inform(params, format!("Keeping the vaults in {}", root.display()));
+    env::set_var(constants::HOME_VARIABLE, &root);
+    Ok(root)
+}
+
+/// Offers to install the completions of the shell in $SHELL.
+fn init_completions(ctx: &mut Context, params: &Params) -> Result<(), HandlerError> {
+    let shell = env::var("SHELL").unwrap_or_default();
+    let shell = Path::new(&shell)
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let (path, script) = match (
+        completions::install_path(&shell),
+        completions::script(&shell),
+    ) {
+        (Some(path), Some(script)) => (path, script),
+        _ => return Ok(()),
+    };
+    let message = format!("Install the {} completions to {}?", shell, path.display());
+    if !confirm(ctx, params, &message)? {
+        return Ok(());
+    }
+    if let Some(dir) = path.parent().filter(|_| !constants::is_dry_run()) {
+        fs::create_dir_all(dir)?;
+    }
+    write_file(&path.to_string_lossy(), script)?;
+    inform(
+        params,
+        format!("Installed the completions to {}", path.display()),
+    );
+    if shell == "zsh" {
+        inform(
+            params,
+            "Add `fpath+=~/.zfunc` before `compinit` in ~/.zshrc to load them".to_owned(),
+        );
+    }
+    Ok(())
+}
+
+/// Checks the environment and prints how to fix what fails
+pub fn doctor() -> Result<(), HandlerError> {
+    let checks = doctor::run(&constants::root());
+    let mut failed = 0;
+    for check in checks {
+        match check.failure {
+            None => println!("ok    {}", check.name),
+            Some((problem, fix)) => {
+                failed += 1;
+                println!(
+                    "FAIL  {}\n      {}\n      Fix: {}",
+                    check.name, problem, fix
+                );
+            }
+        }
+    }
+    if failed > 0 {
+        return Err(HandlerError::ChecksFailed(failed));
+    }
+    Ok(())
 }
 
-/// Copeis a string to the clipboard
-fn copy_password_to_clipboard(password: String) -> Result<(), HandlerError> {
-    let mut ctx: ClipboardContext = ClipboardProvider::new()?;
-    ctx.set_contents(password)?;
+/// Puts the vault files back as they were before the last command that changed them
+pub fn undo(params: &Params) -> Result<(), HandlerError> {
+    let root = constants::root();
+    if constants::is_dry_run() {
+        println!("Would undo the last change of {}", root.display());
+        return Ok(());
+    }
+    let restored = journal::undo(&root)?;
+    if restored.is_empty() {
+        inform(params, "There is nothing to undo".to_owned());
+    }
+    for path in restored {
+        inform(params, format!("Restored {}", path.display()));
+        if let Some(vault) = path.file_stem().and_then(|s| s.to_str()) {
+            history::record(&root, vault, &[HistoryEntry::new("undo", "", "")])?;
+        }
+    }
     Ok(())
 }
+
+/// Rewrites every vault in the newest format and reports what was done to each
+pub fn migrate(ctx: &mut Context, params: &Params) -> Result<(), HandlerError> {
+    let migrations = ctx.storage.vaults()?.migrate()?;
+    let mut table = Table::new(&["VAULT", "FILE", "BACKUP", "LEGACY ACCOUNTS"]);
+    for migration in &migrations {
+        let file = match (migration.rewritten, constants::is_dry_run()) {
+            (false, _) => "up to date",
+            (true, false) => "rewritten",
+            (true, true) => "would be rewritten",
+        };
+        let backup = migration
+            .backup
+            .as_ref()
+            .map(|path| path.display().to_string())
+            .unwrap_or_default();
+        let cells = vec![
+            migration.name.clone(),
+            file.to_owned(),
+            backup,
+            migration.legacy.to_string(),
+        ];
+        table.push(cells, false, migration.legacy > 0);
+    }
+    if !params.flag(ParamName::Quiet) {
+        table.print();
+    }
+    if migrations.iter().any(|m| m.legacy > 0) {
+        inform(
+            params,
+            "Accounts on the legacy scheme keep their passwords, zpass audit lists them".to_owned(),
+        );
+    }
+    Ok(())
+}
+
+/// Shows the history of the selected vault, or the default one, or of one of its domains
+pub fn show_log(ctx: &mut Context, params: &Params) -> Result<(), HandlerError> {
+    let mut m = ctx.storage.vaults()?;
+    let v = select_vault(&mut m, params)?;
+    let key = vault_key(ctx, params, v)?;
+    let domain = params.text(ParamName::DomainName);
+    let entries: Vec<HistoryEntry> = v
+        .history(&key)?
+        .into_iter()
+        .filter(|e| domain.is_none() || domain == Some(e.domain.as_str()))
+        .collect();
+    let mut table = Table::new(&["WHEN", "WHO", "DOMAIN", "USERNAME", "WHAT"]);
+    for e in page(entries, params) {
+        let cells = vec![time::format(e.time), e.user, e.domain, e.username, e.action];
+        table.push(cells, false, false);
+    }
+    table.print();
+    Ok(())
+}
+
+/// Lists the earlier settings of a password, most recent first, or goes back to the settings
+/// before the --rollback nth last change
+pub fn show_history(ctx: &mut Context, params: &Params) -> Result<(), HandlerError> {
+    let mut m = ctx.storage.vaults()?;
+    let v = select_vault(&mut m, params)?;
+    let domain = required(params, ParamName::DomainName)?;
+    let username = username_of(v, params, domain)?;
+    if let Some(n) = params.number(ParamName::Rollback) {
+        let change = v
+            .preferences
+            .account(domain, &username)
+            .and_then(|p| p.change(n))
+            .cloned();
+        v.preferences
+            .rollback(domain, &username, n, ctx.clock.now())?;
+        if let Some(c) = change {
+            inform(
+                params,
+                format!(
+                    "{} {} is back at version {} with length {}",
+                    domain, c.username, c.version, c.length
+                ),
+            );
+        }
+        return Ok(());
+    }
+    let p = v.get_preference_mut(domain, &username)?;
+    let mut table = Table::new(&["N", "WHEN", "USERNAME", "VERSION", "LENGTH", "CHARACTERS"]);
+    for (i, c) in p.changes.iter().rev().enumerate() {
+        let characters = crypto::charset(c.rules.as_ref(), c.unambiguous).len();
+        let cells = vec![
+            (i + 1).to_string(),
+            time::format(c.time),
+            c.username.clone(),
+            c.version.to_string(),
+            c.length.to_string(),
+            characters.to_string(),
+        ];
+        table.push(cells, false, false);
+    }
+    table.print();
+    Ok(())
+}
+
+/// Prints where zpass keeps its files
+pub fn get_config() -> Result<(), HandlerError> {
+    for path in config::paths() {
+        let state = if path.exists() { "" } else { " (missing)" };
+        println!("config\t{}{}", path.display(), state);
+    }
+    let root = constants::root();
+    println!("root\t{}", root.display());
+    println!("hooks\t{}", root.join(constants::HOOKS_DIR).display());
+    let sealed = config::sealed_path();
+    let state = if sealed.exists() { "" } else { " (missing)" };
+    println!("sealed\t{}{}", sealed.display(), state);
+    Ok(())
+}
+
+/// Seals a config file with the key of the default vault
+pub fn add_config(ctx: &mut Context, params: &Params, args: &[String]) -> Result<(), HandlerError> {
+    let path = args
+        .first()
+        .ok_or(HandlerError::MissingArgument("config file"))?;
+    let text = fs::read_to_string(path)?;
+    config::from_text(Path::new(path), &text)?;
+    let sealed = {
+        let mut m = ctx.storage.vaults()?;
+        let v = m.get_default_mut().ok_or(HandlerError::MissingVault)?;
+        let key = vault_key(ctx, params, v)?;
+        v.seal(&key, &text)?
+    };
+    let target = config::sealed_path();
+    write_file(&target.to_string_lossy(), serde_json::to_string(&sealed)?)?;
+    inform(
+        params,
+        format!(
+            "Sealed {} into {}, delete the plain file",
+            path,
+            target.display()
+        ),
+    );
+    Ok(())
+}
+
+/// Deletes the sealed config
+pub fn delete_config(params: &Params) -> Result<(), HandlerError> {
+    let sealed = config::sealed_path();
+    // a missing file fails in a dry run too
+    fs::metadata(&sealed)?;
+    if !constants::is_dry_run() {
+        fs::remove_file(&sealed)?;
+    }
+    inform(params, format!("Deleted {}", sealed.display()));
+    Ok(())
+}
+
+/// Returns the sealed config, unsealed with the key of the default vault, or None if there is
+/// none. The key is asked for like any other, so scripts give it first.
+pub fn unseal_config(
+    ctx: &mut Context,
+    params: &Params,
+) -> Result<Option<config::Config>, HandlerError> {
+    let path = config::sealed_path();
+    let text = match fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(err.into()),
+    };
+    let sealed: crypto::Sealed = serde_json::from_str(&text)?;
+    let plaintext = {
+        let mut m = ctx.storage.vaults()?;
+        let v = m.get_default_mut().ok_or(HandlerError::MissingVault)?;
+        let key = vault_key(ctx, params, v)?;
+        v.unseal(&key, &sealed)?
+    };
+    Ok(Some(config::from_text(&path, &plaintext)?))
+}
+
+/// Runs a command with the password in an environment variable of the command only,
+/// then exits with the status of the command
+pub fn exec(ctx: &mut Context, params: &Params, command: &[String]) -> Result<(), HandlerError> {
+    let (program, args) = command
+        .split_first()
+        .ok_or(HandlerError::MissingArgument("command"))?;
+    let variable = required(params, ParamName::Env)?;
+    let password = {
+        let mut m = ctx.storage.vaults()?;
+        let v = select_vault(&mut m, params)?;
+        let key = vault_key(ctx, params, v)?;
+        let domain = required(params, ParamName::DomainName)?;
+        let username = params.text(ParamName::UserName);
+        v.get_password(domain, &key, username, None, None)?
+    };
+    let status = process::Command::new(program)
+        .args(args)
+        .env(variable, password)
+        .status()?;
+    process::exit(status.code().unwrap_or(1));
+}
+
+/// Replaces the placeholders of a template with the passwords of the selected vault,
+/// or the default one, and writes the result to a file or stdout
+pub fn render(ctx: &mut Context, params: &Params, args: &[String]) -> Result<(), HandlerError> {
+    let path = args
+        .first()
+        .ok_or(HandlerError::MissingArgument("template"))?;
+    let contents = fs::read_to_string(path)?;
+    let mut m = ctx.storage.vaults()?;
+    let v = select_vault(&mut m, params)?;
+    let key = vault_key(ctx, params, v)?;
+    let rendered = template::render(&contents, |domain, username| {
+        v.get_password(domain, &key, username, None, None)
+    })?;
+    match params.text(ParamName::OutFile) {
+        Some(out) => {
+            write_file(out, rendered)?;
+            if !params.flag(ParamName::Quiet) {
+                eprintln!("Rendered {} to {}", path, out);
+            }
+        }
+        None => print!("{}", rendered),
+    }
+    Ok(())
+}
+
+/// Prints the completion script of a shell
+pub fn completions(args: &[String]) -> Result<(), HandlerError> {
+    let shell = args
+        .first()
+        .ok_or(HandlerError::MissingArgument("bash, zsh or fish"))?;
+    let script =
+        completions::script(shell).ok_or_else(|| HandlerError::UnsupportedOutput(shell.clone()))?;
+    print!("{}", script);
+    Ok(())
+}
+
+/// Prints the completions of the last word, for shell completion scripts
+pub fn complete(ctx: &mut Context, words: &[String]) -> Result<(), HandlerError> {
+    let m = ctx.storage.vaults()?;
+    let vaults: Vec<&str> = m.iter().map(|v| v.name()).collect();
+    let entries: Vec<Entry> = m
+        .iter()
+        .flat_map(|v| {
+            v.preferences
+                .iter()
+                .filter(|p| !p.archived)
+                .map(move |p| Entry {
+                    vault: v.name(),
+                    domain: &p.domain,
+                    username: &p.username,
+                })
+        })
+        .collect();
+    for candidate in complete::candidates(words, &vaults, &entries) {
+        println!("{}", candidate);
+    }
+    Ok(())
+}
+
+/// Lets a picker such as dmenu choose an account and copies its password
+pub fn menu(ctx: &mut Context, params: &Params) -> Result<(), HandlerError> {
+    let mut m = ctx.storage.vaults()?;
+    let v = select_vault(&mut m, params)?;
+    let (domain, username) = {
+        let preferences = unarchived(v.preferences.by_recency(), params);
+        let selection = match menu::pick(&preferences)? {
+            Some(selection) => selection,
+            None => return Ok(()),
+        };
+        let p = menu::find(&preferences, &selection).ok_or(VaultError::NoMatchingPreference)?;
+        (p.domain.clone(), p.username.clone())
+    };
+    let key = vault_key(ctx, params, v)?;
+    let password = v.get_password(&domain, &key, Some(&username), None, None)?;
+    copy_password_to_clipboard(ctx, params, password)?;
+    Ok(())
+}
+
+/// Reports weak spots in the preferences of all the vaults
+pub fn audit(ctx: &mut Context, params: &Params) -> Result<(), HandlerError> {
+    let m = ctx.storage.vaults()?;
+    let now = ctx.clock.now();
+    if params.flag(ParamName::Entropy) {
+        return audit_entropy(&m, params);
+    }
+    let mut findings: Vec<Finding> = m
+        .iter()
+        .flat_map(|v| audit::audit(v.name(), &v.preferences, now))
+        .collect();
+    let vaults: Vec<(&str, &Preferences)> = m.iter().map(|v| (v.name(), &v.preferences)).collect();
+    findings.extend(audit::duplicates(&vaults));
+    if params.flag(ParamName::Hibp) {
+        findings.extend(check_breaches(ctx, params, &m)?);
+    }
+
+    match params.text(ParamName::Output) {
+        Some("json") => println!("{}", serde_json::to_string_pretty(&findings)?),
+        Some("table") | None => {
+            if findings.is_empty() {
+                println!("No issues found");
+            }
+            for f in findings {
+                println!(
+                    "{:<16} {:<32} {:<24} {}",
+                    f.vault, f.domain, f.username, f.issue
+                );
+            }
+        }
+        Some(output) => return Err(HandlerError::UnsupportedOutput(output.to_owned())),
+    }
+    Ok(())
+}
+
+/// Reports the effective entropy of every generated password, the ones below --min-entropy
+/// are weak
+fn audit_entropy(m: &Vaults<Secret>, params: &Params) -> Result<(), HandlerError> {
+    let minimum = params
+        .number(ParamName::MinEntropy)
+        .unwrap_or(constants::MIN_ENTROPY);
+    let report: Vec<_> = m
+        .iter()
+        .flat_map(|v| audit::entropy(v.name(), &v.preferences, minimum))
+        .collect();
+    match params.text(ParamName::Output) {
+        Some("json") => println!("{}", serde_json::to_string_pretty(&report)?),
+        Some("table") | None => {
+            let mut table = Table::new(&["VAULT", "DOMAIN", "USERNAME", "BITS", "WEAK"]);
+            for e in &report {
+                let cells = vec![
+                    e.vault.clone(),
+                    e.domain.clone(),
+                    e.username.clone(),
+                    format!("{:.0}", e.bits),
+                    if e.weak { "yes" } else { "no" }.to_owned(),
+                ];
+                table.push(cells, false, e.weak);
+            }
+            table.print();
+            let weak = report.iter().filter(|e| e.weak).count();
+            inform(
+                params,
+                format!(
+                    "{} of {} passwords have less than {} bits",
+                    weak,
+                    report.len(),
+                    minimum
+                ),
+            );
+        }
+        Some(output) => return Err(HandlerError::UnsupportedOutput(output.to_owned())),
+    }
+    Ok(())
+}
+
+/// Sums up every vault: its accounts, stored passwords, tags, lengths, the oldest
+/// password that was never rotated, and the size of its file
+pub fn stats(ctx: &mut Context, params: &Params) -> Result<(), HandlerError> {
+    let m = ctx.storage.vaults()?;
+    let now = ctx.clock.now();
+    let mut summaries = Vec::new();
+    for v in m.iter() {
+        summaries.push(Stats {
+            bytes: fs::metadata(v.path()).map(|m| m.len()).unwrap_or(0),
+            ..stats::stats(v.name(), &v.preferences, now)
+        });
+    }
+    let total: u64 = summaries.iter().map(|s| s.bytes).sum();
+
+    match params.text(ParamName::Output) {
+        Some("json") => println!("{}", serde_json::to_string_pretty(&summaries)?),
+        Some("table") | None => {
+            let mut table = Table::new(&[
+                "VAULT",
+                "ACCOUNTS",
+                "STORED",
+                "TAGS",
+                "AVG LENGTH",
+                "USES",
+                "UNUSED",
+                "OLDEST NEVER ROTATED",
+                "SIZE",
+            ]);
+            for s in summaries {
+                let oldest = match s.oldest_never_rotated {
+                    Some(o) => format!("{} {} ({} days)", o.domain, o.username, o.days),
+                    None => "-".to_owned(),
+                };
+                let cells = vec![
+                    s.vault,
+                    s.preferences.to_string(),
+                    s.stored.to_string(),
+                    s.tags.to_string(),
+                    format!("{:.1}", s.average_length),
+                    s.uses.to_string(),
+                    s.unused.to_string(),
+                    oldest,
+                    describe_size(s.bytes),
+                ];
+                table.push(cells, false, false);
+            }
+            table.print();
+            inform(params, format!("Total: {}", describe_size(total)));
+        }
+        Some(output) => return Err(HandlerError::UnsupportedOutput(output.to_owned())),
+    }
+    Ok(())
+}
+
+/// Imports the accounts of a CSV export into the selected vault, or the default one
+#[cfg(feature = "import")]
+pub fn import_csv(ctx: &mut Context, params: &Params, args: &[String]) -> Result<(), HandlerError> {
+    let path = args.first().ok_or(HandlerError::MissingArgument("file"))?;
+    let format = match params.text(ParamName::Format) {
+        Some(f) => f.parse::<csv::Format>()?,
+        None => csv::Format::Generic,
+    };
+    let rows = csv::read_accounts(&fs::read_to_string(path)?, format)?;
+    let mut m = ctx.storage.vaults()?;
+    let v = select_vault(&mut m, params)?;
+    let key = vault_key(ctx, params, v)?;
+    let summary = import_accounts(params, v, &key, rows.accounts)?;
+    inform(
+        params,
+        format!(
+            "Imported {} accounts into {}, skipped {} duplicates and {} invalid rows, {} failed",
+            summary.added,
+            v.name(),
+            summary.duplicates,
+            rows.skipped,
+            summary.failed.len()
+        ),
+    );
+    Ok(())
+}
+
+/// Imports the entries of a pass(1) store into the selected vault, or the default one
+#[cfg(feature = "import")]
+pub fn import_pass(
+    ctx: &mut Context,
+    params: &Params,
+    args: &[String],
+) -> Result<(), HandlerError> {
+    let store = match args.first() {
+        Some(path) => PathBuf::from(path),
+        None => pass::default_store(),
+    };
+    inform(
+        params,
+        format!("Decrypting the entries of {}", store.display()),
+    );
+    let accounts = pass::read_store(&store)?;
+    let mut m = ctx.storage.vaults()?;
+    let v = select_vault(&mut m, params)?;
+    let key = vault_key(ctx, params, v)?;
+    let summary = import_accounts(params, v, &key, accounts)?;
+    inform(
+        params,
+        format!(
+            "Imported {} accounts into {}, skipped {} duplicates, {} failed",
+            summary.added,
+            v.name(),
+            summary.duplicates,
+            summary.failed.len()
+        ),
+    );
+    Ok(())
+}
+
+/// Imports the logins, passwords and notes of a 1PUX export into the selected vault, or the default one
+#[cfg(feature = "import")]
+pub fn import_1pux(
+    ctx: &mut Context,
+    params: &Params,
+    args: &[String],
+) -> Result<(), HandlerError> {
+    let path = args.first().ok_or(HandlerError::MissingArgument("file"))?;
+    let rows = onepassword::read_export(Path::new(path))?;
+    let mut m = ctx.storage.vaults()?;
+    let v = select_vault(&mut m, params)?;
+    let key = vault_key(ctx, params, v)?;
+    let summary = import_accounts(params, v, &key, rows.accounts)?;
+    inform(
+        params,
+        format!(
+            "Imported {} accounts into {}, skipped {} duplicates and {} unsupported items, {} failed",
+            summary.added,
+            v.name(),
+            summary.duplicates,
+            rows.skipped,
+            summary.failed.len()
+        ),
+    );
+    Ok(())
+}
+
+/// Imports the accounts with a progress bar and lists the ones that failed on stderr.
+#[cfg(feature = "import")]
+fn import_accounts(
+    params: &Params,
+    v: &mut Vault<Secret>,
+    key: &str,
+    accounts: Vec<interop::Account>,
+) -> Result<interop::Summary, HandlerError> {
+    let bar = if params.flag(ParamName::Quiet) {
+        Bar::hidden()
+    } else {
+        Bar::start("Importing", accounts.len())
+    };
+    let summary = interop::import(v, key, accounts, bar.count())?;
+    drop(bar);
+    for failure in &summary.failed {
+        eprintln!(
+            "Failed to import {} {}: {}",
+            failure.domain, failure.username, failure.reason
+        );
+    }
+    Ok(summary)
+}
+
+/// Writes the accounts of the selected vault, or the default one, to a KeePass XML file
+#[cfg(feature = "import")]
+pub fn export_keepass(ctx: &mut Context, params: &Params) -> Result<(), HandlerError> {
+    let path = required(params, ParamName::OutFile)?;
+    if path.ends_with(".kdbx") {
+        return Err(HandlerError::UnsupportedOutput(
+            "kdbx, write a KeePass XML file and import it with KeePassXC instead".to_owned(),
+        ));
+    }
+    let mut m = ctx.storage.vaults()?;
+    let v = select_vault(&mut m, params)?;
+    let message = format!(
+        "{} contains every password of {} in plaintext, continue?",
+        path,
+        v.name()
+    );
+    if !confirm(ctx, params, &message)? {
+        return Ok(());
+    }
+    let key = vault_key(ctx, params, v)?;
+    let accounts = interop::export(v, &key, |p| selected(params, p))?;
+    write_file(path, keepass::to_xml(v.name(), &accounts))?;
+    inform(
+        params,
+        format!("Exported {} accounts to {}", accounts.len(), path),
+    );
+    Ok(())
+}
+
+/// Writes the accounts of the selected vault as plaintext CSV to a file or to stdout
+#[cfg(feature = "import")]
+pub fn export_csv(ctx: &mut Context, params: &Params) -> Result<(), HandlerError> {
+    let mut m = ctx.storage.vaults()?;
+    let v = select_vault(&mut m, params)?;
+    let destination = params.text(ParamName::OutFile).unwrap_or("stdout");
+    eprintln!("WARNING: the export is NOT encrypted, anyone who can read it has your passwords.");
+    eprintln!("Delete it as soon as it is no longer needed.");
+    let message = format!(
+        "Write the passwords of {} in plaintext to {}?",
+        v.name(),
+        destination
+    );
+    if !confirm(ctx, params, &message)? {
+        return Ok(());
+    }
+    let key = vault_key(ctx, params, v)?;
+    let accounts = interop::export(v, &key, |p| selected(params, p))?;
+    let output = csv::write_accounts(&accounts);
+    match params.text(ParamName::OutFile) {
+        Some(path) => {
+            write_file(path, output)?;
+            if !params.flag(ParamName::Quiet) {
+                eprintln!("Exported {} accounts to {}", accounts.len(), path);
+            }
+        }
+        None => print!("{}", output),
+    }
+    Ok(())
+}
+
+/// Writes the accounts of the selected vault, or the default one, to a pass(1) store
+#[cfg(feature = "import")]
+pub fn export_pass(
+    ctx: &mut Context,
+    params: &Params,
+    args: &[String],
+) -> Result<(), HandlerError> {
+    let gpg_id = required(params, ParamName::GpgId)?;
+    let store = match args.first() {
+        Some(path) => PathBuf::from(path),
+        None => pass::default_store(),
+    };
+    let mut m = ctx.storage.vaults()?;
+    let v = select_vault(&mut m, params)?;
+    let key = vault_key(ctx, params, v)?;
+    let accounts = interop::export(v, &key, |p| selected(params, p))?;
+    if constants::is_dry_run() {
+        println!(
+            "Would write {} accounts to {}",
+            accounts.len(),
+            store.display()
+        );
+        return Ok(());
+    }
+    let written = pass::write_store(&store, gpg_id, &accounts)?;
+    inform(
+        params,
+        format!(
+            "Exported {} accounts to {}, skipped {} existing entries",
+            written,
+            store.display(),
+            accounts.len() - written
+        ),
+    );
+    Ok(())
+}
+
+/// Writes the selected vaults, or all of them, to an archive encrypted with age
+#[cfg(feature = "import")]
+pub fn export_age(ctx: &mut Context, params: &Params) -> Result<(), HandlerError> {
+    let recipient = required(params, ParamName::Age)?;
+    let path = required(params, ParamName::OutFile)?;
+    whole_vaults(params)?;
+    let m = ctx.storage.vaults()?;
+    let names: Vec<&str> = match params.text(ParamName::VaultName) {
+        Some(names) => names.split(',').collect(),
+        None => m.iter().map(|v| v.name()).collect(),
+    };
+    let mut vaults = Vec::new();
+    for name in &names {
+        let v = m
+            .get(|v| v.name() == *name)
+            .ok_or(HandlerError::MissingVault)?;
+        vaults.push(v.serialize()?);
+    }
+    let archive = Archive::new(vaults).pack()?;
+    write_file(path, age::encrypt(recipient, &archive)?)?;
+    inform(params, format!("Exported {} to {}", names.join(", "), path));
+    Ok(())
+}
+
+/// Adds the vaults of an archive encrypted with age, vaults whose name is taken are skipped
+#[cfg(feature = "import")]
+pub fn import_age(ctx: &mut Context, params: &Params, args: &[String]) -> Result<(), HandlerError> {
+    let identity = required(params, ParamName::Age)?;
+    let path = args.first().ok_or(HandlerError::MissingArgument("file"))?;
+    let archive = Archive::unpack(&age::decrypt(Path::new(identity), &fs::read(path)?)?)?;
+    let mut m = ctx.storage.vaults()?;
+    for serialized in archive.vaults {
+        match m.import(serialized) {
+            Ok(name) => inform(params, format!("Imported {}", name)),
+            Err(VaultError::VaultAlreadyExists) => {
+                inform(params, "Skipped a vault that already exists".to_owned())
+            }
+            Err(err) => return Err(err.into()),
+        }
+    }
+    Ok(())
+}
+
+/// Writes the selected vault, or the default one, to a file encrypted with its key
+#[cfg(feature = "import")]
+pub fn export_vault(ctx: &mut Context, params: &Params) -> Result<(), HandlerError> {
+    let path = required(params, ParamName::OutFile)?;
+    whole_vaults(params)?;
+    let mut m = ctx.storage.vaults()?;
+    let v = select_vault(&mut m, params)?;
+    let key = vault_key(ctx, params, v)?;
+    write_file(path, zvault::pack(&key, v.serialize()?)?)?;
+    inform(params, format!("Exported {} to {}", v.name(), path));
+    Ok(())
+}
+
+/// Adds the vault of a file written by export vault
+#[cfg(feature = "import")]
+pub fn import_vault(
+    ctx: &mut Context,
+    params: &Params,
+    args: &[String],
+) -> Result<(), HandlerError> {
+    let path = args.first().ok_or(HandlerError::MissingArgument("file"))?;
+    let file = fs::read(path)?;
+    let key = read_key(ctx, params, "Key:")?;
+    let serialized = zvault::unpack(&key, &file)?;
+    let mut m = ctx.storage.vaults()?;
+    let name = m.import(serialized)?;
+    inform(params, format!("Imported {}", name));
+    Ok(())
+}
+
+/// Prints the encrypted secret of the selected vault, or the default one, for a paper backup
+#[cfg(feature = "import")]
+pub fn backup_paper(ctx: &mut Context, params: &Params) -> Result<(), HandlerError> {
+    let mut m = ctx.storage.vaults()?;
+    let v = select_vault(&mut m, params)?;
+    let (encrypted_secret, iv) = v.secret().parts();
+    let code = Backup {
+        name: v.name().to_owned(),
+        scheme: crypto::CURRENT_SCHEME as u8,
+        iv: iv.to_owned(),
+        encrypted_secret: encrypted_secret.to_vec(),
+    }
+    .encode()?;
+    println!("zpass paper backup of {}", v.name());
+    println!("{}", paper::qr(&code)?);
+    println!("{}", paper::grouped(&code));
+    println!("Restore with: zpass restore paper, the key of the vault is needed too.");
+    Ok(())
+}
+
+/// Decrypts a backup in memory and checks that restoring it would succeed: every vault is
+/// checked against the schema and the vaults of the root, and with the key of an exported
+/// vault, its secret and sealed values are decrypted. Nothing is written.
+#[cfg(feature = "import")]
+pub fn backup_verify(
+    ctx: &mut Context,
+    params: &Params,
+    args: &[String],
+) -> Result<(), HandlerError> {
+    let path = args.first().ok_or(HandlerError::MissingArgument("file"))?;
+    let file = fs::read(path)?;
+    let (vaults, key) = if zvault::is_vault(&file) {
+        let key = read_key(ctx, params, "Key:")?;
+        (vec![zvault::unpack(&key, &file)?], Some(key))
+    } else {
+        let identity = required(params, ParamName::Age)?;
+        let archive = Archive::unpack(&age::decrypt(Path::new(identity), &file)?)?;
+        (archive.vaults, None)
+    };
+    let m = ctx.storage.vaults()?;
+    let mut failed = 0;
+    for serialized in vaults {
+        let name = serde_json::from_str::<serde_json::Value>(&serialized)
+            .ok()
+            .and_then(|v| v.get("name")?.as_str().map(String::from))
+            .unwrap_or_else(|| "(unnamed)".to_owned());
+        match verify_vault(&m, serialized, key.as_deref()) {
+            Ok(passwords) => println!("ok    {} ({} passwords)", name, passwords),
+            Err(problem) => {
+                failed += 1;
+                println!("FAIL  {}\n      {}", name, problem);
+            }
+        }
+    }
+    if failed > 0 {
+        return Err(HandlerError::ChecksFailed(failed));
+    }
+    inform(params, format!("{} can be restored", path));
+    Ok(())
+}
+
+/// Returns the number of passwords of a vault of a backup, or why it can't be restored.
+#[cfg(feature = "import")]
+fn verify_vault(
+    m: &Vaults<Secret>,
+    serialized: String,
+    key: Option<&str>,
+) -> Result<usize, String> {
+    schema::validate(&serialized).map_err(|err| err.to_string())?;
+    let v = match m.check_import(serialized) {
+        Err(VaultError::VaultAlreadyExists) => {
+            return Err("A vault of the same name exists, delete or rename it first".to_owned())
+        }
+        result => result.map_err(|err| err.to_string())?,
+    };
+    if let Some(key) = key {
+        v.secret().check_key(key).map_err(|err| err.to_string())?;
+        for p in v.preferences.iter() {
+            for sealed in [&p.stored, &p.notes, &p.totp].iter().copied().flatten() {
+                v.unseal(key, sealed).map_err(|err| {
+                    format!(
+                        "The values of {} {} are damaged:\n{}",
+                        p.domain, p.username, err
+                    )
+                })?;
+            }
+        }
+    }
+    Ok(v.preferences.iter().count())
+}
+
+/// Restores a vault from the code of a paper backup, given as arguments or typed in
+#[cfg(feature = "import")]
+pub fn restore_paper(
+    ctx: &mut Context,
+    params: &Params,
+    args: &[String],
+) -> Result<(), HandlerError> {
+    let code = if args.is_empty() {
+        println!("Type the code, finish with an empty line:");
+        read_lines_from_std_in()?
+    } else {
+        args.concat()
+    };
+    let backup = Backup::decode(&code)?;
+    if backup.scheme as usize > crypto::CURRENT_SCHEME {
+        return Err(paper::PaperError::UnsupportedScheme(backup.scheme).into());
+    }
+    let name = params.text(ParamName::VaultName).unwrap_or(&backup.name);
+    let secret = Secret::from_parts(backup.encrypted_secret, backup.iv);
+    let key = read_key(ctx, params, "Key:")?;
+    {
+        let _status = progress(params, &format!("Unlocking {}", name));
+        secret.check_key(&key)?;
+    }
+    let mut vs = ctx.storage.vaults()?;
+    vs.add(name, secret)?;
+    inform(
+        params,
+        format!(
+            "Restored {}, add its passwords again with add password to list them",
+            name
+        ),
+    );
+    Ok(())
+}
+
+/// Prints the JSON schema of the vault files
+pub fn schema() -> Result<(), HandlerError> {
+    println!("{}", schema::vault_schema()?);
+    Ok(())
+}
+
+/// Checks that a file is a valid vault
+pub fn validate(args: &[String]) -> Result<(), HandlerError> {
+    let path = args.first().ok_or(HandlerError::MissingArgument("file"))?;
+    let contents = fs::read_to_string(path)?;
+    schema::validate(&contents).map_err(HandlerError::InvalidVault)?;
+    println!("{} is a valid vault", path);
+    Ok(())
+}
+
+/// Stores the key of the selected vault, or the default one, in the OS keychain
+#[cfg(feature = "keychain")]
+pub fn remember(ctx: &mut Context, params: &Params) -> Result<(), HandlerError> {
+    if params.flag(ParamName::MaskedEmail) {
+        return remember_masked_email_token(ctx, params);
+    }
+    let mut m = ctx.storage.vaults()?;
+    let v = select_vault(&mut m, params)?;
+    wait_for_attempt(ctx, v);
+    let key = read_key(ctx, params, "Key:")?;
+    check_key(params, v, &key)?;
+    if !constants::is_dry_run() {
+        keychain::store(v.name(), &key)?;
+    }
+    inform(
+        params,
+        format!(
+            "The key of {} is in the keychain, it won't be asked for",
+            v.name()
+        ),
+    );
+    Ok(())
+}
+
+/// Removes the key of the selected vault, or the default one, from the OS keychain
+#[cfg(feature = "keychain")]
+pub fn forget(ctx: &mut Context, params: &Params) -> Result<(), HandlerError> {
+    if params.flag(ParamName::MaskedEmail) {
+        return forget_masked_email_token(params);
+    }
+    let mut m = ctx.storage.vaults()?;
+    let v = select_vault(&mut m, params)?;
+    if !constants::is_dry_run() {
+        keychain::forget(v.name())?;
+    }
+    inform(
+        params,
+        format!("The key of {} will be asked for again", v.name()),
+    );
+    Ok(())
+}
+
+/// Keeps the API token of the masked email service in the OS keychain
+#[cfg(all(feature = "keychain", feature = "masked-email"))]
+fn remember_masked_email_token(ctx: &mut Context, params: &Params) -> Result<(), HandlerError> {
+    let service = masked_email_service(params)?;
+    let token = read_key(ctx, params, &format!("API token of {}:", service))?;
+    if !constants::is_dry_run() {
+        keychain::store(service.keychain_name(), &token)?;
+    }
+    inform(
+        params,
+        format!("The API token of {} is in the keychain", service),
+    );
+    Ok(())
+}
+
+/// Removes the API token of the masked email service from the OS keychain
+#[cfg(all(feature = "keychain", feature = "masked-email"))]
+fn forget_masked_email_token(params: &Params) -> Result<(), HandlerError> {
+    let service = masked_email_service(params)?;
+    if !constants::is_dry_run() {
+        keychain::forget(service.keychain_name())?;
+    }
+    inform(
+        params,
+        format!("The API token of {} is no longer in the keychain", service),
+    );
+    Ok(())
+}
+
+/// Masked emails are only compiled in with the `masked-email` feature.
+#[cfg(all(feature = "keychain", not(feature = "masked-email")))]
+fn remember_masked_email_token(_ctx: &mut Context, _params: &Params) -> Result<(), HandlerError> {
+    Err(HandlerError::FeatureDisabled("masked-email"))
+}
+
+/// Masked emails are only compiled in with the `masked-email` feature.
+#[cfg(all(feature = "keychain", not(feature = "masked-email")))]
+fn forget_masked_email_token(_params: &Params) -> Result<(), HandlerError> {
+    Err(HandlerError::FeatureDisabled("masked-email"))
+}
+
+/// The keychain is only compiled in with the `keychain` feature.
+#[cfg(not(feature = "keychain"))]
+pub fn remember(_ctx: &mut Context, _params: &Params) -> Result<(), HandlerError> {
+    Err(HandlerError::FeatureDisabled("keychain"))
+}
+
+/// The keychain is only compiled in with the `keychain` feature.
+#[cfg(not(feature = "keychain"))]
+pub fn forget(_ctx: &mut Context, _params: &Params) -> Result<(), HandlerError> {
+    Err(HandlerError::FeatureDisabled("keychain"))
+}
+
+/// Runs a share subcommand: `add-member` and `remove-member` share the selected vault, or the
+/// default one, with a member or stop it, `join` adds a vault that was shared with you.
+pub fn share(ctx: &mut Context, params: &Params, args: &[String]) -> Result<(), HandlerError> {
+    match args.first().map(|a| a.as_str()) {
+        Some("add-member") => share_add_member(ctx, params),
+        Some("remove-member") => share_remove_member(ctx, params),
+        Some("join") => share_join(ctx, params, &args[1..]),
+        _ => Err(HandlerError::MissingArgument(
+            "add-member, remove-member or join",
+        )),
+    }
+}
+
+/// Lists, restores or empties the deleted passwords of the selected vault, or the default one
+pub fn trash(ctx: &mut Context, params: &Params, args: &[String]) -> Result<(), HandlerError> {
+    match args.first().map(|a| a.as_str()) {
+        Some("list") => trash_list(ctx, params),
+        Some("restore") => trash_restore(ctx, params),
+        Some("empty") => trash_empty(ctx, params),
+        _ => Err(HandlerError::MissingArgument("list, restore or empty")),
+    }
+}
+
+/// Lists the deleted passwords, oldest first.
+fn trash_list(ctx: &mut Context, params: &Params) -> Result<(), HandlerError> {
+    let mut m = ctx.storage.vaults()?;
+    let v = select_vault(&mut m, params)?;
+    let now = ctx.clock.now();
+    let mut table = Table::new(&["DOMAIN", "USERNAME", "DELETED"]);
+    for t in v.trash() {
+        let cells = vec![
+            t.preference.domain.clone(),
+            t.preference.username.clone(),
+            describe_last_used(Some(t.deleted), now),
+        ];
+        table.push(cells, false, false);
+    }
+    table.print();
+    Ok(())
+}
+
+/// Puts a deleted password back.
+fn trash_restore(ctx: &mut Context, params: &Params) -> Result<(), HandlerError> {
+    let mut m = ctx.storage.vaults()?;
+    let v = select_vault(&mut m, params)?;
+    let domain = required(params, ParamName::DomainName)?;
+    let username = required(params, ParamName::UserName)?;
+    v.restore(domain, username)?;
+    inform(
+        params,
+        format!("Restored {} {} to {}", domain, username, v.name()),
+    );
+    Ok(())
+}
+
+/// Deletes the passwords in the trash for good.
+fn trash_empty(ctx: &mut Context, params: &Params) -> Result<(), HandlerError> {
+    let mut m = ctx.storage.vaults()?;
+    let v = select_vault(&mut m, params)?;
+    let message = format!(
+        "Delete the {} passwords in the trash of {} for good?",
+        v.trash().len(),
+        v.name()
+    );
+    if !confirm(ctx, params, &message)? {
+        return Ok(());
+    }
+    let count = v.empty_trash();
+    inform(params, format!("Deleted {} passwords for good", count));
+    Ok(())
+}
+
+/// Wraps the secret of the vault to the age recipient of a new member.
+#[cfg(feature = "import")]
+fn share_add_member(ctx: &mut Context, params: &Params) -> Result<(), HandlerError> {
+    let recipient = required(params, ParamName::Age)?;
+    let mut m = ctx.storage.vaults()?;
+    let v = select_vault(&mut m, params)?;
+    let key = vault_key(ctx, params, v)?;
+    let wrapped = age::encrypt(recipient, &v.secret().decrypt(&key)?)?;
+    v.add_member(Member {
+        recipient: recipient.to_owned(),
+        wrapped,
+    })?;
+    inform(
+        params,
+        format!(
+            "Shared {} with {}, send them {}",
+            v.name(),
+            recipient,
+            v.path().display()
+        ),
+    );
+    Ok(())
+}
+
+/// Removes a member, whose copy of the vault keeps working: rotate the passwords they knew.
+#[cfg(feature = "import")]
+fn share_remove_member(ctx: &mut Context, params: &Params) -> Result<(), HandlerError> {
+    let recipient = required(params, ParamName::Age)?;
+    let mut m = ctx.storage.vaults()?;
+    let v = select_vault(&mut m, params)?;
+    v.remove_member(recipient)?;
+    inform(
+        params,
+        format!(
+            "Stopped sharing {} with {}, rotate the passwords they knew",
+            v.name(),
+            recipient
+        ),
+    );
+    Ok(())
+}
+
+/// Adds a shared vault file, its secret unwrapped with the age identity of a member and kept
+/// under a key of your own.
+#[cfg(feature = "import")]
+fn share_join(ctx: &mut Context, params: &Params, args: &[String]) -> Result<(), HandlerError> {
+    let path = args.first().ok_or(HandlerError::MissingArgument("file"))?;
+    let identity = required(params, ParamName::Age)?;
+    let serialized = fs::read_to_string(path)?;
+    let (name, members) = vault::members_of(&serialized)?;
+    // age fails for the members that aren't wrapped to the identity
+    let revealed = members
+        .iter()
+        .find_map(|m| age::decrypt(Path::new(identity), &m.wrapped).ok())
+        .map(Locked::new)
+        .ok_or_else(|| HandlerError::NotAMember(identity.to_owned()))?;
+    let key = read_key(ctx, params, &format!("Your key for {}:", name))?;
+    if is_terminal_key(params) && read_key(ctx, params, "Repeat the key:")? != key {
+        return Err(HandlerError::KeyMismatch);
+    }
+    let secret = Secret::from_revealed(&revealed, &key, &name)?;
+    let mut m = ctx.storage.vaults()?;
+    m.import(serialized)?;
+    if let Some(v) = m.get_mut(|v| v.name() == name) {
+        *v.secret_mut() = secret;
+    }
+    inform(params, format!("Joined {}", name));
+    Ok(())
+}
+
+/// Sharing uses age, which is only compiled in with the `import` feature.
+#[cfg(not(feature = "import"))]
+fn share_add_member(_ctx: &mut Context, _params: &Params) -> Result<(), HandlerError> {
+    Err(HandlerError::FeatureDisabled("import"))
+}
+
+/// Sharing uses age, which is only compiled in with the `import` feature.
+#[cfg(not(feature = "import"))]
+fn share_remove_member(_ctx: &mut Context, _params: &Params) -> Result<(), HandlerError> {
+    Err(HandlerError::FeatureDisabled("import"))
+}
+
+/// Sharing uses age, which is only compiled in with the `import` feature.
+#[cfg(not(feature = "import"))]
+fn share_join(_ctx: &mut Context, _params: &Params, _args: &[String]) -> Result<(), HandlerError> {
+    Err(HandlerError::FeatureDisabled("import"))
+}
+
+/// Runs a transfer subcommand: `send` shows the selected vault, or the default one, as QR codes
+/// encrypted with a one-time code, `receive` adds the vault of the scanned codes.
+pub fn transfer(ctx: &mut Context, params: &Params, args: &[String]) -> Result<(), HandlerError> {
+    match args.first().map(|a| a.as_str()) {
+        Some("send") => transfer_send(ctx, params),
+        Some("receive") => transfer_receive(ctx, params),
+        _ => Err(HandlerError::MissingArgument("send or receive")),
+    }
+}
+
+#[cfg(feature = "import")]
+fn transfer_send(ctx: &mut Context, params: &Params) -> Result<(), HandlerError> {
+    whole_vaults(params)?;
+    let mut m = ctx.storage.vaults()?;
+    let v = select_vault(&mut m, params)?;
+    let code = transfer::code();
+    let frames = transfer::frames(&code, v.serialize()?)?;
+    println!(
+        "Run `zpass transfer receive` on the other machine and type the code {}",
+        code
+    );
+    for (i, frame) in frames.iter().enumerate() {
+        ctx.prompter.read_line(&format!(
+            "Press Enter to show frame {} of {}",
+            i + 1,
+            frames.len()
+        ))?;
+        println!("{}\n{}", paper::qr(frame)?, frame);
+    }
+    inform(
+        params,
+        format!("Sent {} in {} frames", v.name(), frames.len()),
+    );
+    Ok(())
+}
+
+/// Reads the frames until all of them are scanned, an empty line gives up.
+#[cfg(feature = "import")]
+fn transfer_receive(ctx: &mut Context, params: &Params) -> Result<(), HandlerError> {
+    let code = read_key(ctx, params, "Code of the transfer:")?;
+    let mut receiver = Receiver::new();
+    while !receiver.is_complete() {
+        let message = match receiver.missing() {
+            missing if missing.is_empty() => "Scan a frame:".to_owned(),
+            missing => format!("Scan one of the frames {:?}:", missing),
+        };
+        let frame = ctx.prompter.read_line(&message)?;
+        if frame.trim().is_empty() {
+            break;
+        }
+        match receiver.add(&frame) {
+            Ok(true) => {}
+            Ok(false) => eprintln!("The frame was scanned before"),
+            Err(err) => eprintln!("{}", err),
+        }
+    }
+    let serialized = receiver.finish(&code)?;
+    let mut m = ctx.storage.vaults()?;
+    let name = m.import(serialized)?;
+    inform(params, format!("Received {}", name));
+    Ok(())
+}
+
+/// Transfers render QR codes, which are only compiled in with the `import` feature.
+#[cfg(not(feature = "import"))]
+fn transfer_send(_ctx: &mut Context, _params: &Params) -> Result<(), HandlerError> {
+    Err(HandlerError::FeatureDisabled("import"))
+}
+
+/// Transfers render QR codes, which are only compiled in with the `import` feature.
+#[cfg(not(feature = "import"))]
+fn transfer_receive(_ctx: &mut Context, _params: &Params) -> Result<(), HandlerError> {
+    Err(HandlerError::FeatureDisabled("import"))
+}
+
+/// Generates an API token, the account is added with the --prefix, --encoding and length the
+/// first time, later they come from its preference. Copies it to the clipboard or prints it
+pub fn get_token(ctx: &mut Context, params: &Params) -> Result<(), HandlerError> {
+    let mut m = ctx.storage.vaults()?;
+    let v = select_vault(&mut m, params)?;
+    let domain = required(params, ParamName::DomainName)?;
+    let username = params.text(ParamName::UserName).unwrap_or(token::USERNAME);
+    if v.preferences.account(domain, username).is_none() {
+        let name = params.text(ParamName::Encoding).unwrap_or("base62");
+        let encoding =
+            Encoding::parse(name).ok_or_else(|| HandlerError::UnknownEncoding(name.to_owned()))?;
+        let length = params.number(ParamName::Length).unwrap_or(token::LENGTH);
+        let mut p = Preference::new(domain, username, length);
+        p.token = Some(Token {
+            prefix: params.text(ParamName::Prefix).unwrap_or("").to_owned(),
+            encoding,
+        });
+        v.preferences.add(p)?;
+        hooks::run(&v.dir(), Event::PreferenceAdded, v.name(), domain, username);
+    }
+    let key = vault_key(ctx, params, v)?;
+    let version = params.number(ParamName::Version);
+    let token = v.get_password(domain, &key, Some(username), None, version)?;
+    if params.flag(ParamName::Stdout) {
+        println!("{}", grouped(params, &token));
+        return Ok(());
+    }
+    copy_password_to_clipboard(ctx, params, token)
+}
+
+/// Returns a printed password in the groups of --group, joined by --group-sep or a dash.
+/// What is copied or typed stays as it is.
+fn grouped(params: &Params, password: &str) -> String {
+    match params.number(ParamName::Group) {
+        Some(size) => spell::group(
+            password,
+            size,
+            params.text(ParamName::GroupSep).unwrap_or("-"),
+        ),
+        None => password.to_owned(),
+    }
+}
+
+/// Prints the QR code of a password, e.g. for a phone to join a Wi-Fi network.
+#[cfg(feature = "import")]
+fn print_qr(text: &str) -> Result<(), HandlerError> {
+    println!("{}", paper::qr(text)?);
+    Ok(())
+}
+
+/// QR codes are only compiled in with the `import` feature.
+#[cfg(not(feature = "import"))]
+fn print_qr(_text: &str) -> Result<(), HandlerError> {
+    Err(HandlerError::FeatureDisabled("import"))
+}
+
+/// Prints the login identity of a domain derived from the vault secret: a username, or a plus
+/// address of the email address given with -u. A new --version derives a new one
+pub fn get_alias(ctx: &mut Context, params: &Params) -> Result<(), HandlerError> {
+    let domain = required(params, ParamName::DomainName)?;
+    let mut m = ctx.storage.vaults()?;
+    let v = select_vault(&mut m, params)?;
+    let key = vault_key(ctx, params, v)?;
+    let version = params.number(ParamName::Version).unwrap_or(0);
+    let bytes = v
+        .secret()
+        .derive_bytes(&key, &alias::context(domain, version))?;
+    println!("{}", alias::alias(&bytes, params.text(ParamName::UserName)));
+    Ok(())
+}
+
+/// Prints the username and the password of an account as one JSON object, e.g. for login
+/// tests. The password ends up in the output, so it takes --show.
+pub fn get_login(ctx: &mut Context, params: &Params) -> Result<(), HandlerError> {
+    match params.text(ParamName::Output) {
+        Some("json") => {}
+        Some(output) => return Err(HandlerError::UnsupportedOutput(output.to_owned())),
+        None => return Err(HandlerError::MissingParam(ParamName::Output)),
+    }
+    if !params.flag(ParamName::Show) {
+        return Err(HandlerError::MissingParam(ParamName::Show));
+    }
+    let domain = required(params, ParamName::DomainName)?;
+    let mut m = ctx.storage.vaults()?;
+    let v = select_vault(&mut m, params)?;
+    let domain = &stored_domain(v, domain);
+    known_domain(v, domain)?;
+    let username = match params.text(ParamName::UserName) {
+        Some(username) => username.to_owned(),
+        None => v
+            .preferences
+            .default_of(domain)
+            .map(|p| p.username.clone())
+            .ok_or(HandlerError::MissingParam(ParamName::UserName))?,
+    };
+    let key = vault_key(ctx, params, v)?;
+    let version = params.number(ParamName::Version);
+    let password = v.get_password(domain, &key, Some(&username), None, version)?;
+    println!(
+        "{{\"domain\":{},\"username\":{},\"password\":{}}}",
+        serde_json::to_string(domain)?,
+        serde_json::to_string(&username)?,
+        serde_json::to_string(&password)?
+    );
+    Ok(())
+}
+
+/// Runs an ssh subcommand, `add` is the only one.
+pub fn ssh(ctx: &mut Context, params: &Params, args: &[String]) -> Result<(), HandlerError> {
+    match args.first().map(|a| a.as_str()) {
+        Some("add") => ssh_add(ctx, params),
+        _ => Err(HandlerError::MissingArgument("add")),
+    }
+}
+
+/// Derives the Ed25519 key of a host and loads it into the running ssh-agent.
+#[cfg(feature = "ssh")]
+fn ssh_add(ctx: &mut Context, params: &Params) -> Result<(), HandlerError> {
+    let domain = required(params, ParamName::DomainName)?;
+    let mut m = ctx.storage.vaults()?;
+    let v = select_vault(&mut m, params)?;
+    let key = vault_key(ctx, params, v)?;
+    let seed = v
+        .secret()
+        .derive_bytes(&key, &format!("ssh-ed25519:{}", domain))?;
+    let pair = ssh::KeyPair::from_seed(&seed)?;
+    let comment = format!("zpass:{}", domain);
+    ssh::add(&pair, &comment)?;
+    inform(
+        params,
+        format!(
+            "The key of {} is in the ssh-agent, its public key is:",
+            domain
+        ),
+    );
+    println!("{}", pair.authorized_key(&comment));
+    Ok(())
+}
+
+/// Derived ssh keys are only compiled in with the `ssh` feature.
+#[cfg(not(feature = "ssh"))]
+fn ssh_add(_ctx: &mut Context, _params: &Params) -> Result<(), HandlerError> {
+    Err(HandlerError::FeatureDisabled("ssh"))
+}
+
+/// Serves the selected vault, or the default one, to other applications as the Secret Service
+#[cfg(feature = "secret-service")]
+pub fn serve(ctx: &mut Context, params: &Params) -> Result<(), HandlerError> {
+    let mut m = ctx.storage.vaults()?;
+    let v = select_vault(&mut m, params)?;
+    let key = vault_key(ctx, params, v)?;
+    let accounts = interop::export(v, &key, |_| true)?;
+    if !params.flag(ParamName::Quiet) {
+        eprintln!("Serving {} accounts of {}", accounts.len(), v.name());
+    }
+    secret_service::serve(v.name(), accounts)?;
+    Ok(())
+}
+
+/// The Secret Service is only compiled in with the `secret-service` feature.
+#[cfg(not(feature = "secret-service"))]
+pub fn serve(_ctx: &mut Context, _params: &Params) -> Result<(), HandlerError> {
+    Err(HandlerError::FeatureDisabled("secret-service"))
+}
+
+/// Keeps the keys of the vaults that are unlocked through it, until the process ends
+pub fn agent(params: &Params) -> Result<(), HandlerError> {
+    let path = agent::socket_path();
+    if !params.flag(ParamName::Quiet) {
+        eprintln!("The agent listens on {}", path.display());
+    }
+    agent::serve(&mut Agent::new(constants::root()), &path)?;
+    Ok(())
+}
+
+/// Asks for the key of the selected vault, or the default one, and has the agent keep it for
+/// --lock-after minutes, 0 keeps it until `zpass lock`
+pub fn unlock(ctx: &mut Context, params: &Params) -> Result<(), HandlerError> {
+    let mut m = ctx.storage.vaults()?;
+    m.detach();
+    let v = select_vault(&mut m, params)?;
+    let mut agent = Client::connect()?;
+    let minutes = params
+        .number(ParamName::LockAfter)
+        .unwrap_or(DEFAULT_LOCK_AFTER);
+    let lock_after = Some(minutes as u64 * 60).filter(|seconds| *seconds > 0);
+    wait_for_attempt(ctx, v);
+    let key = find_key(ctx, params, v.name())?;
+    check_key(params, v, &key)?;
+    agent.unlock_for(v.name(), &key, lock_after)?;
+    match lock_after {
+        Some(seconds) => inform(
+            params,
+            format!(
+                "Unlocked {} until {} UTC",
+                v.name(),
+                time::format(time::now() + seconds)
+            ),
+        ),
+        None => inform(params, format!("Unlocked {} until `zpass lock`", v.name())),
+    }
+    Ok(())
+}
+
+/// Has the agent forget the key of the vault of --name, or of every vault
+pub fn lock(params: &Params) -> Result<(), HandlerError> {
+    let vault = params.text(ParamName::VaultName);
+    Client::connect()?.lock(vault)?;
+    match vault {
+        Some(vault) => inform(params, format!("Locked {}", vault)),
+        None => inform(params, "Locked all vaults".to_owned()),
+    }
+    Ok(())
+}
+
+/// Shows the vaults the agent keeps unlocked and when it locks them
+pub fn status(params: &Params) -> Result<(), HandlerError> {
+    let sessions = match Client::connect() {
+        Ok(mut agent) => agent.sessions()?,
+        Err(AgentError::NotRunning(_)) => {
+            inform(
+                params,
+                "The agent isn't running, start it with `zpass agent`".to_owned(),
+            );
+            return Ok(());
+        }
+        Err(err) => return Err(err.into()),
+    };
+    if sessions.is_empty() {
+        inform(params, "No vault is unlocked".to_owned());
+        return Ok(());
+    }
+    let now = time::now();
+    let mut table = Table::new(&["VAULT", "EXPIRES"]);
+    for session in sessions {
+        let expires = match session.expires {
+            Some(at) => format!(
+                "{} UTC (in {}m)",
+                time::format(at),
+                (at.saturating_sub(now) + 59) / 60
+            ),
+            None => "on lock".to_owned(),
+        };
+        table.push(vec![session.vault, expires], false, false);
+    }
+    table.print();
+    Ok(())
+}
+
+/// Shows the selected vault, or the default one, full-screen
+#[cfg(feature = "tui")]
+pub fn tui(ctx: &mut Context, params: &Params) -> Result<(), HandlerError> {
+    let mut m = ctx.storage.vaults()?;
+    let v = select_vault(&mut m, params)?;
+    let key = vault_key(ctx, params, v)?;
+    let mut app = tui::App::new(tui::rows(&unarchived(v.preferences.by_recency(), params)));
+    tui::run(&mut app, |app, action| {
+        app.status = match tui_action(ctx, v, &key, app, action) {
+            Ok(status) => status,
+            Err(err) => err.to_string(),
+        };
+        app.set_rows(tui::rows(&unarchived(v.preferences.by_recency(), params)));
+    })?;
+    Ok(())
+}
+
+/// The full-screen view is only compiled in with the `tui` feature.
+#[cfg(not(feature = "tui"))]
+pub fn tui(_ctx: &mut Context, _params: &Params) -> Result<(), HandlerError> {
+    Err(HandlerError::FeatureDisabled("tui"))
+}
+
+/// Applies an action of the full-screen view to the vault and describes the outcome.
+#[cfg(feature = "tui")]
+fn tui_action(
+    ctx: &mut Context,
+    v: &mut Vault<Secret>,
+    key: &str,
+    app: &tui::App,
+    action: Action,
+) -> Result<String, HandlerError> {
+    match action {
+        Action::Copy(i) => {
+            let row = app.row(i);
+            let password = v.get_password(&row.domain, key, Some(&row.username), None, None)?;
+            copy_password_to_clipboard(ctx, &Params::new(), password)?;
+            Ok(format!(
+                "Copied the password of {} {}",
+                row.domain, row.username
+            ))
+        }
+        Action::Rotate(i) => {
+            let row = app.row(i);
+            let p = v.get_preference_mut(&row.domain, &row.username)?;
+            p.record_change(ctx.clock.now());
+            p.set_version(p.highest_version() + 1);
+            Ok(format!(
+                "{} {} is at version {}",
+                row.domain, row.username, p.version
+            ))
+        }
+        Action::SetLength(i, length) => {
+            let row = app.row(i);
+            let p = v.get_preference_mut(&row.domain, &row.username)?;
+            p.record_change(ctx.clock.now());
+            p.length = p.rules.as_ref().map_or(length, |r| r.clamp_length(length));
+            Ok(format!(
+                "{} {} has length {}",
+                row.domain, row.username, p.length
+            ))
+        }
+        Action::None | Action::Quit => Ok(String::new()),
+    }
+}
+
+// --------------------------------- Helpers ----------------------------------
+
+/// Returns the rotation period of `--rotate-every`, `never` turns rotation off.
+/// Returns None if the param isn't given.
+fn rotation_period(params: &Params) -> Result<Option<Option<u64>>, HandlerError> {
+    match params.text(ParamName::RotateEvery) {
+        None => Ok(None),
+        Some("never") => Ok(Some(None)),
+        Some(text) => time::parse_period(text)
+            .map(|every| Some(Some(every)))
+            .ok_or_else(|| HandlerError::InvalidPeriod(text.to_owned())),
+    }
+}
+
+/// Returns the recipe of `--recipe`, `none` removes it. Returns None if the param isn't given.
+fn recipe(params: &Params) -> Result<Option<Option<Recipe>>, HandlerError> {
+    match params.text(ParamName::Recipe) {
+        None => Ok(None),
+        Some("none") => Ok(Some(None)),
+        Some(text) => Ok(Some(Some(recipe::parse(text)?))),
+    }
+}
+
+/// Returns the key of a vault once it is checked, so a mistyped key can't derive wrong
+/// passwords or seal values nobody can read. Wrong keys slow down the next attempts. Vaults
+/// from before key check values only check the padding, see `Secret::check_key`.
+fn vault_key(
+    ctx: &mut Context,
+    params: &Params,
+    v: &Vault<Secret>,
+) -> Result<String, HandlerError> {
+    if let Some(key) = session_key(params, v) {
+        return Ok(key);
+    }
+    wait_for_attempt(ctx, v);
+    let key = find_key(ctx, params, v.name())?;
+    check_key(params, v, &key)?;
+    Ok(key)
+}
+
+/// Returns the key of a vault the agent keeps unlocked, unless a script gives the key. A key
+/// that no longer opens the vault, e.g. after `update key`, is asked for again.
+fn session_key(params: &Params, v: &Vault<Secret>) -> Option<String> {
+    if !is_terminal_key(params) {
+        return None;
+    }
+    let key = Client::connect()
+        .and_then(|mut agent| agent.key(v.name()))
+        .ok()?;
+    v.secret().check_key(&key).ok()?;
+    debug!("Read the key of {} from the agent", v.name());
+    Some(key)
+}
+
+/// Waits until the key of a vault may be tried again, after too many wrong keys.
+fn wait_for_attempt(ctx: &Context, v: &Vault<Secret>) {
+    let wait = throttle::remaining(&v.dir(), v.name(), ctx.clock.now());
+    if wait > 0 {
+        eprintln!("Too many wrong keys for {}, waiting {}s", v.name(), wait);
+        thread::sleep(Duration::from_secs(wait));
+    }
+}
+
+/// Checks the key of a vault and counts it if it's wrong.
+fn check_key(params: &Params, v: &Vault<Secret>, key: &str) -> Result<(), HandlerError> {
+    let status = progress(params, &format!("Unlocking {}", v.name()));
+    let checked = v.secret().check_key(key);
+    drop(status);
+    match checked {
+        Ok(()) if v.secret().is_checked() => Ok(throttle::succeeded(&v.dir(), v.name())?),
+        // only the padding passed, which about one in 256 wrong keys do, so the count stays
+        Ok(()) => Ok(()),
+        Err(err) => {
+            throttle::failed(&v.dir(), v.name())?;
+            if let Some(hint) = v.hint() {
+                eprintln!("{}", tr(Message::Hint, &[&hint]));
+            }
+            Err(err.into())
+        }
+    }
+}
+
+/// Returns the key of a vault from the OS keychain if the vault opted in, or asks for it.
+#[cfg(feature = "keychain")]
+fn find_key(ctx: &mut Context, params: &Params, vault: &str) -> Result<String, HandlerError> {
+    match keychain::load(vault)? {
+        Some(key) => {
+            debug!("Read the key of {} from the keychain", vault);
+            Ok(key)
+        }
+        None => key_of(ctx, params, vault),
+    }
+}
+
+/// Asks for the key of a vault.
+#[cfg(not(feature = "keychain"))]
+fn find_key(ctx: &mut Context, params: &Params, vault: &str) -> Result<String, HandlerError> {
+    key_of(ctx, params, vault)
+}
+
+/// Returns the key of a vault from $ZPASS_KEY_CMD if it is set, or asks for it.
+/// The command gets the name of the vault in $ZPASS_VAULT.
+fn key_of(ctx: &mut Context, params: &Params, vault: &str) -> Result<String, HandlerError> {
+    let command = match env::var(KEY_COMMAND_VARIABLE) {
+        Ok(command) if is_terminal_key(params) => command,
+        _ => return read_key(ctx, params, &tr(Message::KeyFor, &[&vault])),
+    };
+    debug!("Running {} for the key of {}", KEY_COMMAND_VARIABLE, vault);
+    let output = shell(&command)
+        .env(plugin::VAULT_VARIABLE, vault)
+        .stderr(process::Stdio::inherit())
+        .output()?;
+    if !output.status.success() {
+        return Err(HandlerError::KeyCommandFailed(command));
+    }
+    read_line(&output.stdout[..])
+}
+
+/// Reads a key from stdin with `--key-stdin`, from a file descriptor with `--key-fd`,
+/// or from the terminal.
+fn read_key(ctx: &mut Context, params: &Params, message: &str) -> Result<String, HandlerError> {
+    if params.flag(ParamName::KeyStdin) {
+        debug!("Reading the key from stdin");
+        return read_line(io::stdin());
+    }
+    match params.number(ParamName::KeyFd) {
+        Some(fd) => read_line_from_fd(fd),
+        None => ctx.prompter.read_secret(message),
+    }
+}
+
+/// Returns true if the keys are typed in, rather than given by a script.
+fn is_terminal_key(params: &Params) -> bool {
+    !params.flag(ParamName::KeyStdin) && !params.has(ParamName::KeyFd)
+}
+
+/// Reads up to the end of the line, byte by byte so the next line is left for the next key.
+fn read_line(mut reader: impl io::Read) -> Result<String, HandlerError> {
+    let (mut line, mut byte) = (Vec::new(), [0]);
+    while reader.read(&mut byte)? == 1 && byte[0] != b'\n' {
+        line.push(byte[0]);
+    }
+    if line.last() == Some(&b'\r') {
+        line.pop();
+    }
+    String::from_utf8(line).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err).into())
+}
+
+/// Reads a line from a file descriptor the calling program opened, e.g. with `3<<<"$KEY"`.
+#[cfg(unix)]
+fn read_line_from_fd(fd: usize) -> Result<String, HandlerError> {
+    use std::os::unix::io::FromRawFd;
+    // the descriptor belongs to the caller and may hold more keys, so it's not closed
+    let file = mem::ManuallyDrop::new(unsafe { fs::File::from_raw_fd(fd as i32) });
+    read_line(&*file)
+}
+
+/// File descriptors can only be passed on unix.
+#[cfg(not(unix))]
+fn read_line_from_fd(_fd: usize) -> Result<String, HandlerError> {
+    Err(io::Error::new(io::ErrorKind::Other, "--key-fd needs a unix system").into())
+}
+
+/// Returns a command that runs the command line in the shell.
+#[cfg(unix)]
+fn shell(command: &str) -> process::Command {
+    let mut shell = process::Command::new("sh");
+    shell.args(["-c", command]);
+    shell
+}
+
+/// Returns a command that runs the command line in the shell.
+#[cfg(not(unix))]
+fn shell(command: &str) -> process::Command {
+    let mut shell = process::Command::new("cmd");
+    shell.args(["/C", command]);
+    shell
+}
+
+/// Reads lines from stdin until an empty line or the end of the input.
+fn read_lines_from_std_in() -> Result<String, HandlerError> {
+    let mut text = String::new();
+    loop {
+        let mut line = String::new();
+        if io::stdin().read_line(&mut line)? == 0 || line.trim().is_empty() {
+            return Ok(text);
+        }
+        text.push_str(&line);
+    }
+}
+
+/// Shows a spinner with the message while slow work is done, unless it's --quiet or the output
+/// is JSON.
+fn progress(params: &Params, message: &str) -> Status {
+    if params.flag(ParamName::Quiet) || params.text(ParamName::Output) == Some("json") {
+        return Status::hidden();
+    }
+    Status::start(message)
+}
+
+/// Prints what a command did, unless it's --quiet.
+fn inform(params: &Params, message: String) {
+    if params.flag(ParamName::Quiet) {
+        return;
+    }
+    if constants::is_dry_run() {
+        println!("(dry run) {}", message);
+    } else {
+        println!("{}", message);
+    }
+}
+
+/// Prints the changes the library skipped in a dry run, such as the vault files it would write.
+pub fn report_plan(params: &Params) {
+    for change in plan::take() {
+        inform(params, change);
+    }
+}
+
+/// Writes a file, or only says so in a dry run.
+fn write_file(path: &str, contents: impl AsRef<[u8]>) -> Result<(), HandlerError> {
+    if constants::is_dry_run() {
+        println!("Would write {}", path);
+        return Ok(());
+    }
+    fs::write(path, contents)?;
+    Ok(())
+}
+
+/// Returns a param the command can't do without.
+fn required(params: &Params, name: ParamName) -> Result<&str, HandlerError> {
+    params.text(name).ok_or(HandlerError::MissingParam(name))
+}
+
+/// Creates an alias for the domain with --masked-email, None without it. The token is the one
+/// of the config, or else the one in the keychain.
+#[cfg(feature = "masked-email")]
+fn masked_email(params: &Params, domain: &str) -> Result<Option<String>, HandlerError> {
+    if !params.flag(ParamName::MaskedEmail) {
+        return Ok(None);
+    }
+    let service = masked_email_service(params)?;
+    let token = match params.text(ParamName::MaskedEmailToken) {
+        Some(token) => token.to_owned(),
+        None => {
+            masked_email_keychain_token(service)?.ok_or(MaskedEmailError::MissingToken(service))?
+        }
+    };
+    if constants::is_dry_run() {
+        inform(params, format!("Would create an alias with {}", service));
+        return Ok(Some(format!("alias@{}", domain)));
+    }
+    let alias = masked_email::create(service, &token, domain)?;
+    inform(
+        params,
+        format!("Created the alias {} with {}", alias, service),
+    );
+    Ok(Some(alias))
+}
+
+/// Masked emails are only compiled in with the `masked-email` feature.
+#[cfg(not(feature = "masked-email"))]
+fn masked_email(params: &Params, _domain: &str) -> Result<Option<String>, HandlerError> {
+    if params.flag(ParamName::MaskedEmail) {
+        return Err(HandlerError::FeatureDisabled("masked-email"));
+    }
+    Ok(None)
+}
+
+/// Returns the service of --masked-email-service, SimpleLogin by default.
+#[cfg(feature = "masked-email")]
+fn masked_email_service(params: &Params) -> Result<masked_email::Service, HandlerError> {
+    let name = params
+        .text(ParamName::MaskedEmailService)
+        .unwrap_or(masked_email::DEFAULT_SERVICE);
+    Ok(masked_email::Service::parse(name)?)
+}
+
+#[cfg(all(feature = "masked-email", feature = "keychain"))]
+fn masked_email_keychain_token(
+    service: masked_email::Service,
+) -> Result<Option<String>, HandlerError> {
+    Ok(keychain::load(service.keychain_name())?)
+}
+
+#[cfg(all(feature = "masked-email", not(feature = "keychain")))]
+fn masked_email_keychain_token(
+    _service: masked_email::Service,
+) -> Result<Option<String>, HandlerError> {
+    Ok(None)
+}
+
+/// Returns true if an account passes the --domain, --filter and --tag params, everything passes
+/// without them. Each of them is a comma separated list, a domain passes with its subdomains.
+#[cfg(feature = "import")]
+fn selected(params: &Params, p: &Preference) -> bool {
+    let domains = [
+        params.text(ParamName::DomainName),
+        params.text(ParamName::Filter),
+    ];
+    let tagged = match params.text(ParamName::Tag) {
+        Some(tags) => tags
+            .split(',')
+            .any(|t| p.tags.iter().any(|tag| tag == t.trim())),
+        None => true,
+    };
+    tagged
+        && domains
+            .iter()
+            .flatten()
+            .all(|filter| interop::matches_filter(&p.domain, filter))
+}
+
+/// Fails if an export of whole vaults is asked to leave accounts out. The secret of a vault
+/// derives all of its passwords, so the accounts can't be taken from it.
+#[cfg(feature = "import")]
+fn whole_vaults(params: &Params) -> Result<(), HandlerError> {
+    let partial = [ParamName::DomainName, ParamName::Filter, ParamName::Tag]
+        .iter()
+        .any(|name| params.has(*name));
+    if partial {
+        return Err(HandlerError::UnsupportedOutput(
+            "a partial vault, its secret derives every password. Export csv, keepass or pass \
+             with --domain or --tag instead"
+                .to_owned(),
+        ));
+    }
+    Ok(())
+}
+
+/// Asks a yes or no question, --yes answers it for scripts.
+fn confirm(ctx: &mut Context, params: &Params, message: &str) -> Result<bool, HandlerError> {
+    if params.flag(ParamName::Yes) {
+        return Ok(true);
+    }
+    ctx.prompter.confirm(message)
+}
+
+/// Asks for a line of text, an empty answer or --yes takes the default.
+fn ask(
+    ctx: &mut Context,
+    params: &Params,
+    message: &str,
+    default: &str,
+) -> Result<String, HandlerError> {
+    if params.flag(ParamName::Yes) {
+        return Ok(default.to_owned());
+    }
+    match ctx
+        .prompter
+        .read_line(&format!("{} [{}]", message, default))?
+    {
+        answer if answer.is_empty() => Ok(default.to_owned()),
+        answer => Ok(answer),
+    }
+}
+
+/// Returns the items of a listing that `--offset` and `--limit` ask for, all of them by default.
+fn page<T>(items: Vec<T>, params: &Params) -> Vec<T> {
+    let offset = params.number(ParamName::Offset).unwrap_or(0);
+    let limit = params.number(ParamName::Limit).unwrap_or(usize::MAX);
+    items.into_iter().skip(offset).take(limit).collect()
+}
+
+/// Fails with the domains of the vault that are close to the domain, unless the vault has
+/// a password for it.
+fn known_domain(v: &Vault<Secret>, domain: &str) -> Result<(), HandlerError> {
+    if v.preferences.has_domain(domain) {
+        return Ok(());
+    }
+    let domains = v
+        .preferences
+        .by_recency()
+        .into_iter()
+        .filter(|p| !p.archived)
+        .map(|p| p.domain.clone())
+        .collect();
+    Err(HandlerError::UnknownDomain(domain.to_owned(), domains))
+}
+
+/// Returns the domain in punycode, or in Unicode if the vault only has it that way, its
+/// passwords are derived from the form they were added with.
+fn stored_domain(v: &Vault<Secret>, domain: &str) -> String {
+    let canonical = idn::to_ascii(domain);
+    if !idn::is_international(&canonical) {
+        return canonical;
+    }
+    let unicode = idn::to_unicode(&canonical);
+    match (
+        v.preferences.has_domain(&canonical),
+        v.preferences.has_domain(&unicode),
+    ) {
+        (true, true) => {
+            eprintln!(
+                "Warning: {} is also stored as {}, the two have different passwords",
+                canonical, unicode
+            );
+            canonical
+        }
+        (false, true) => {
+            eprintln!(
+                "Warning: {} is stored as Unicode, new passwords use {}",
+                unicode, canonical
+            );
+            unicode
+        }
+        _ => canonical,
+    }
+}
+
+/// Leaves out the archived accounts, unless --include-archived is given.
+fn unarchived<'a>(preferences: Vec<&'a Preference>, params: &Params) -> Vec<&'a Preference> {
+    if params.flag(ParamName::IncludeArchived) {
+        return preferences;
+    }
+    preferences.into_iter().filter(|p| !p.archived).collect()
+}
+
+/// Returns the username of the params, or else the one of the default account of the domain.
+fn username_of(v: &Vault<Secret>, params: &Params, domain: &str) -> Result<String, HandlerError> {
+    match params.text(ParamName::UserName) {
+        Some(username) => Ok(username.to_owned()),
+        None => v
+            .preferences
+            .default_of(domain)
+            .map(|p| p.username.clone())
+            .ok_or(HandlerError::MissingParam(ParamName::UserName)),
+    }
+}
+
+/// Returns the vault named by the params or the default vault.
+fn select_vault<'a>(
+    m: &'a mut Vaults<Secret>,
+    params: &Params,
+) -> Result<&'a mut Vault<Secret>, HandlerError> {
+    let v = match params.text(ParamName::VaultName) {
+        Some(name) => m.get_mut(|v| v.name() == name),
+        None => m.get_default_mut(),
+    }
+    .ok_or(HandlerError::MissingVault)?;
+    debug!("Selected the vault {}", v.name());
+    Ok(v)
+}
+
+/// Generates the passwords of every vault and checks them against Have I Been Pwned.
+#[cfg(feature = "hibp")]
+fn check_breaches(
+    ctx: &mut Context,
+    params: &Params,
+    m: &Vaults<Secret>,
+) -> Result<Vec<Finding>, HandlerError> {
+    let mut findings = Vec::new();
+    for v in m.iter() {
+        if v.preferences.is_empty() {
+            continue;
+        }
+        let key = vault_key(ctx, params, v)?;
+        inform(
+            params,
+            format!(
+                "Checking the passwords of {} against Have I Been Pwned",
+                v.name()
+            ),
+        );
+        for p in v.preferences.iter() {
+            let password = v.derive_password(&key, p)?;
+            let count = hibp::times_pwned(&password)?;
+            if count > 0 {
+                findings.push(Finding {
+                    vault: v.name().to_owned(),
+                    domain: p.domain.clone(),
+                    username: p.username.clone(),
+                    issue: audit::Issue::Breached { count },
+                });
+            }
+        }
+    }
+    Ok(findings)
+}
+
+/// Breach checks need network access which is only compiled in with the `hibp` feature.
+#[cfg(not(feature = "hibp"))]
+fn check_breaches(
+    _ctx: &mut Context,
+    _params: &Params,
+    _m: &Vaults<Secret>,
+) -> Result<Vec<Finding>, HandlerError> {
+    Err(HandlerError::FeatureDisabled("hibp"))
+}
+
+/// Describes how long ago a timestamp was, relative to now.
+fn describe_last_used(last_used: Option<u64>, now: u64) -> String {
+    let elapsed = match last_used {
+        None => return "never used".to_owned(),
+        Some(t) => now.saturating_sub(t),
+    };
+    match elapsed {
+        0..=59 => "just now".to_owned(),
+        60..=3599 => format!("{} minutes ago", elapsed / 60),
+        3600..=86399 => format!("{} hours ago", elapsed / 3600),
+        _ => format!("{} days ago", elapsed / 86400),
+    }
+}
+
+/// Describes a number of bytes in the largest unit that keeps it above 1.
+fn describe_size(bytes: u64) -> String {
+    match bytes {
+        0..=1023 => format!("{} B", bytes),
+        1024..=1048575 => format!("{:.1} KiB", bytes as f64 / 1024.0),
+        _ => format!("{:.1} MiB", bytes as f64 / 1048576.0),
+    }
+}
+
+/// Types the username and password into the focused window.
+#[cfg(feature = "autotype")]
+fn type_login(username: &str, password: &str) -> Result<(), HandlerError> {
+    autotype::type_login(username, password)?;
+    Ok(())
+}
+
+/// Autotype is only compiled in with the `autotype` feature.
+#[cfg(not(feature = "autotype"))]
+fn type_login(_username: &str, _password: &str) -> Result<(), HandlerError> {
+    Err(HandlerError::FeatureDisabled("autotype"))
+}
+
+/// Copeis a string to the clipboard, and clears it after the --clear-after seconds
+/// unless something else was copied in the meantime, which a notification tells unless
+/// --no-notify
+fn copy_password_to_clipboard(
+    ctx: &mut Context,
+    params: &Params,
+    password: String,
+) -> Result<(), HandlerError> {
+    ctx.clipboard.set_secret(password.clone())?;
+    debug!("Copied the password to the clipboard");
+    if let Some(seconds) = params.number(ParamName::ClearAfter) {
+        inform(
+            params,
+            format!("Clearing the clipboard in {} seconds", seconds),
+        );
+        interrupt::clear_on_exit(&password);
+        thread::sleep(Duration::from_secs(seconds as u64));
+        interrupt::forget();
+        if ctx.clipboard.contents()? == password {
+            ctx.clipboard.set_contents(String::new())?;
+            if params.flag_or(ParamName::Notify, true) {
+                ctx.notifier.notify(&tr(Message::ClipboardCleared, &[]));
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::context::fake;
+    use super::super::parser::ParamValue;
+    use super::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    #[test]
+    fn handlers_use_the_context() {
+        let root = env::temp_dir().join(format!("zpass-handler-{}", process::id()));
+        let clipboard = Rc::new(RefCell::new(String::new()));
+        let mut ctx = fake::context(
+            root.clone(),
+            &["KEY", "KYE", "KEY", "KEY", "y", ""],
+            clipboard.clone(),
+        );
+
+        let mut params = Params::new();
+        params.insert(
+            ParamName::VaultName,
+            ParamValue::Text("personal".to_owned()),
+        );
+        assert!(matches!(
+            add_vault(&mut ctx, &params),
+            Err(HandlerError::KeyMismatch)
+        ));
+        assert!(!root.join("personal.json").exists());
+        add_vault(&mut ctx, &params).unwrap();
+        assert!(root.join("personal.json").exists());
+        assert!(confirm(&mut ctx, &params, "Sure?").unwrap());
+        assert_eq!(ask(&mut ctx, &params, "Name", "work").unwrap(), "work");
+
+        copy_password_to_clipboard(&mut ctx, &params, "secret".to_owned()).unwrap();
+        assert_eq!(*clipboard.borrow(), "secret");
+        params.insert(ParamName::ClearAfter, ParamValue::Number(0));
+        copy_password_to_clipboard(&mut ctx, &params, "secret".to_owned()).unwrap();
+        assert_eq!(*clipboard.borrow(), "");
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[cfg(feature = "import")]
+    #[test]
+    fn verifies_backups_without_writing() {
+        let root = env::temp_dir().join(format!("zpass-verify-{}", process::id()));
+        let backup = env::temp_dir().join(format!("zpass-verify-{}.zvault", process::id()));
+        let secret = Secret::new("KEY", "personal", 40).unwrap();
+        let mut v = Vault::new("personal", secret, true);
+        v.detach();
+        fs::write(
+            &backup,
+            zvault::pack("KEY", v.serialize().unwrap()).unwrap(),
+        )
+        .unwrap();
+        let clipboard = Rc::new(RefCell::new(String::new()));
+        let mut ctx = fake::context(root.clone(), &["KEY"], clipboard);
+
+        let args = [backup.display().to_string()];
+        backup_verify(&mut ctx, &Params::new(), &args).unwrap();
+        assert!(!root.join("personal.json").exists());
+        fs::remove_file(&backup).unwrap();
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn leaves_out_archived_accounts() {
+        let mut archived = Preference::new("old-forum.example", "a", 20);
+        archived.archived = true;
+        let active = Preference::new("example.com", "a", 20);
+        let mut params = Params::new();
+        assert_eq!(unarchived(vec![&archived, &active], &params), vec![&active]);
+        params.insert(ParamName::IncludeArchived, ParamValue::Flag(true));
+        assert_eq!(unarchived(vec![&archived, &active], &params).len(), 2);
+    }
+
+    #[test]
+    fn pages_listings() {
+        let mut params = Params::new();
+        assert_eq!(page(vec![1, 2, 3, 4], &params), vec![1, 2, 3, 4]);
+        params.insert(ParamName::Offset, ParamValue::Number(1));
+        params.insert(ParamName::Limit, ParamValue::Number(2));
+        assert_eq!(page(vec![1, 2, 3, 4], &params), vec![2, 3]);
+        params.insert(ParamName::Offset, ParamValue::Number(9));
+        assert!(page(vec![1, 2, 3, 4], &params).is_empty());
+    }
+
+    #[test]
+    fn fits_lengths_to_the_site() {
+        let mut params = Params::new();
+        let rules = rules::parse("maxlength: 20;").unwrap();
+        assert_eq!(fit_length(&params, "example.com", 32, Some(&rules)), 20);
+        assert_eq!(fit_length(&params, "example.com", 32, None), 32);
+        // paypal.com is known to accept 20 characters at most
+        assert_eq!(fit_length(&params, "www.paypal.com", 32, None), 32);
+        params.insert(ParamName::Truncate, ParamValue::Flag(true));
+        assert_eq!(fit_length(&params, "www.paypal.com", 32, None), 20);
+        assert_eq!(fit_length(&params, "www.paypal.com", 16, None), 16);
+    }
+
+    #[cfg(feature = "import")]
+    #[test]
+    fn selects_accounts_to_export() {
+        let mut shared = Preference::new("ci.example.com", "bot", 20);
+        shared.tags = vec!["shared".to_owned()];
+        let private = Preference::new("example.org", "alice", 20);
+        let mut params = Params::new();
+        assert!(selected(&params, &shared) && selected(&params, &private));
+        assert!(whole_vaults(&params).is_ok());
+
+        params.insert(
+            ParamName::DomainName,
+            ParamValue::Text("example.com,example.org".to_owned()),
+        );
+        params.insert(ParamName::Tag, ParamValue::Text("shared".to_owned()));
+        assert!(selected(&params, &shared));
+        assert!(!selected(&params, &private));
+        assert!(whole_vaults(&params).is_err());
+    }
+}
@@ -0,0 +1,567 @@
+//! # Help
+//! Prints usage text that is derived from the tables of the parser, so it lists exactly the
+//! operations, resources and params the parser accepts. Each operation has a summary and
+//! examples, the examples are parsed to find the operation they belong to.
+
+use super::i18n::{self, tr, Locale, Message};
+use super::parser::{
+    parse, Operation, FLAGS, LONG_PARAMS, OPERATIONS, RESOURCES, SHORT_FLAGS, SHORT_PARAMS,
+};
+
+/// What each operation does.
+const SUMMARIES: &[(Operation, &str)] = &[
+    (
+        Operation::Add,
+        "Adds a vault, a password to the default vault, a note to a password, or a sealed config",
+    ),
+    (
+        Operation::Get,
+        "Copies a password or an API token to the clipboard, or spells or types it, or prints its settings, a note, an alias, a login or the config",
+    ),
+    (
+        Operation::List,
+        "Lists the passwords, preferences or matching domains of the default vault",
+    ),
+    (
+        Operation::Delete,
+        "Deletes a password or a note from a vault, a vault with its secret, or the sealed config",
+    ),
+    (
+        Operation::Update,
+        "Changes the length of a password, moves it to a new version, or changes a vault key or hint",
+    ),
+    (
+        Operation::Set,
+        "Makes an account the default of its domain, or a vault the default vault, or sets the default length and username of a vault",
+    ),
+    (Operation::Rename, "Renames a vault"),
+    (
+        Operation::Clone,
+        "Copies a vault with its secret and preferences under a new name",
+    ),
+    (
+        Operation::Copy,
+        "Copies the settings of a password to another vault, which generates its own password",
+    ),
+    (
+        Operation::Audit,
+        "Reports weak, old, reused and duplicate passwords",
+    ),
+    (
+        Operation::Stats,
+        "Sums up the accounts, lengths, rotation and size of each vault",
+    ),
+    (
+        Operation::Import,
+        "Imports accounts from other password managers, or vaults from a backup",
+    ),
+    (
+        Operation::Export,
+        "Exports accounts to other password managers, or vaults to a backup",
+    ),
+    (
+        Operation::Backup,
+        "Prints a paper backup of a vault secret, or checks that a backup can be restored",
+    ),
+    (
+        Operation::Restore,
+        "Restores a vault secret from a paper backup",
+    ),
+    (
+        Operation::Schema,
+        "Prints the JSON schema of the vault files",
+    ),
+    (
+        Operation::Validate,
+        "Checks a vault file against the schema",
+    ),
+    (
+        Operation::Menu,
+        "Picks an account with dmenu, rofi or fzf and copies its password",
+    ),
+    (
+        Operation::Tui,
+        "Browses a vault full-screen, copies, rotates and edits passwords",
+    ),
+    (
+        Operation::Rotate,
+        "Moves every password that is due for rotation to a new version",
+    ),
+    (
+        Operation::Undo,
+        "Puts the vaults back as they were before the last command that changed them",
+    ),
+    (
+        Operation::Migrate,
+        "Rewrites every vault in the newest format, after a backup of each file",
+    ),
+    (
+        Operation::Log,
+        "Shows who changed a vault and when, e.g. when a password was rotated",
+    ),
+    (
+        Operation::History,
+        "Shows the earlier settings of a password and goes back to them",
+    ),
+    (
+        Operation::Batch,
+        "Executes the commands of a file or stdin, one per line",
+    ),
+    (
+        Operation::Init,
+        "Sets up the vaults directory, the first vault and the shell completions",
+    ),
+    (
+        Operation::Doctor,
+        "Checks the vaults directory, the files, the clipboard and the crypto",
+    ),
+    (
+        Operation::Remember,
+        "Keeps the key of a vault in the OS keychain",
+    ),
+    (
+        Operation::Forget,
+        "Removes the key of a vault from the OS keychain",
+    ),
+    (
+        Operation::Ssh,
+        "Loads the derived ssh key of a host into the ssh-agent",
+    ),
+    (
+        Operation::Share,
+        "Shares a vault with a team, each member keeps it under a key of their own",
+    ),
+    (
+        Operation::Transfer,
+        "Moves a vault to another machine as QR codes, encrypted with a one-time code",
+    ),
+    (
+        Operation::Trash,
+        "Lists, restores or empties the deleted passwords of a vault",
+    ),
+    (
+        Operation::Archive,
+        "Leaves a password out of the listings, --include-archived shows it",
+    ),
+    (Operation::Unarchive, "Lists an archived password again"),
+    (
+        Operation::Serve,
+        "Serves a vault to other applications as the Secret Service",
+    ),
+    (
+        Operation::Agent,
+        "Keeps the keys of unlocked vaults for other commands and applications",
+    ),
+    (
+        Operation::Unlock,
+        "Asks for the key of a vault once and has the agent keep it, --lock-after minutes",
+    ),
+    (Operation::Lock, "Has the agent forget the keys at once"),
+    (
+        Operation::Status,
+        "Shows the vaults the agent keeps unlocked and when they are locked",
+    ),
+    (
+        Operation::Exec,
+        "Runs a command with a password in its environment",
+    ),
+    (Operation::Render, "Fills the passwords into a template"),
+    (
+        Operation::Completions,
+        "Prints the completion script of bash, zsh or fish",
+    ),
+    (Operation::Help, "Explains the commands"),
+];
+
+/// What each operation does, in German.
+const SUMMARIES_DE: &[(Operation, &str)] = &[
+    (
+        Operation::Add,
+        "Legt einen Tresor, ein Passwort im Standard-Tresor, eine Notiz zu einem Passwort oder eine versiegelte Konfiguration an",
+    ),
+    (
+        Operation::Get,
+        "Kopiert ein Passwort oder einen API-Token in die Zwischenablage, buchstabiert oder tippt es, oder zeigt seine Einstellungen, eine Notiz, einen Alias, eine Anmeldung oder die Konfiguration",
+    ),
+    (
+        Operation::List,
+        "Listet die Passwörter, Einstellungen oder passenden Domains des Standard-Tresors",
+    ),
+    (
+        Operation::Delete,
+        "Löscht ein Passwort oder eine Notiz aus einem Tresor, einen Tresor mit seinem Geheimnis oder die versiegelte Konfiguration",
+    ),
+    (
+        Operation::Update,
+        "Ändert die Länge eines Passworts, setzt es auf eine neue Version, oder ändert den Schlüssel oder Hinweis eines Tresors",
+    ),
+    (
+        Operation::Set,
+        "Macht ein Konto zum Standard seiner Domain, oder einen Tresor zum Standard-Tresor, oder legt die Standardlänge und den Standard-Benutzernamen eines Tresors fest",
+    ),
+    (Operation::Rename, "Benennt einen Tresor um"),
+    (
+        Operation::Clone,
+        "Kopiert einen Tresor mit Geheimnis und Einstellungen unter einem neuen Namen",
+    ),
+    (
+        Operation::Copy,
+        "Kopiert die Einstellungen eines Passworts in einen anderen Tresor, der sein eigenes Passwort erzeugt",
+    ),
+    (
+        Operation::Audit,
+        "Meldet schwache, alte, mehrfach genutzte und doppelte Passwörter",
+    ),
+    (
+        Operation::Stats,
+        "Fasst Konten, Längen, Rotation und Größe jedes Tresors zusammen",
+    ),
+    (
+        Operation::Import,
+        "Importiert Konten aus anderen Passwort-Managern, oder Tresore aus einer Sicherung",
+    ),
+    (
+        Operation::Export,
+        "Exportiert Konten für andere Passwort-Manager, oder Tresore in eine Sicherung",
+    ),
+    (
+        Operation::Backup,
+        "Druckt eine Papiersicherung des Geheimnisses eines Tresors, oder prüft, ob sich eine Sicherung wiederherstellen lässt",
+    ),
+    (
+        Operation::Restore,
+        "Stellt das Geheimnis eines Tresors aus einer Papiersicherung wieder her",
+    ),
+    (
+        Operation::Schema,
+        "Zeigt das JSON-Schema der Tresor-Dateien",
+    ),
+    (
+        Operation::Validate,
+        "Prüft eine Tresor-Datei gegen das Schema",
+    ),
+    (
+        Operation::Menu,
+        "Wählt ein Konto mit dmenu, rofi oder fzf und kopiert sein Passwort",
+    ),
+    (
+        Operation::Tui,
+        "Zeigt einen Tresor im Vollbild, kopiert, rotiert und bearbeitet Passwörter",
+    ),
+    (
+        Operation::Rotate,
+        "Setzt jedes fällige Passwort auf eine neue Version",
+    ),
+    (
+        Operation::Undo,
+        "Stellt die Tresore wieder her, wie sie vor dem letzten ändernden Befehl waren",
+    ),
+    (
+        Operation::Migrate,
+        "Schreibt jeden Tresor im neuesten Format, nach einer Sicherung jeder Datei",
+    ),
+    (
+        Operation::Log,
+        "Zeigt, wer einen Tresor wann geändert hat, z.B. wann ein Passwort rotiert wurde",
+    ),
+    (
+        Operation::History,
+        "Zeigt die früheren Einstellungen eines Passworts und kehrt zu ihnen zurück",
+    ),
+    (
+        Operation::Batch,
+        "Führt die Befehle einer Datei oder von stdin aus, einen pro Zeile",
+    ),
+    (
+        Operation::Init,
+        "Richtet das Tresor-Verzeichnis, den ersten Tresor und die Shell-Vervollständigung ein",
+    ),
+    (
+        Operation::Doctor,
+        "Prüft das Tresor-Verzeichnis, die Dateien, die Zwischenablage und die Kryptografie",
+    ),
+    (
+        Operation::Remember,
+        "Legt den Schlüssel eines Tresors im Schlüsselbund des Systems ab",
+    ),
+    (
+        Operation::Forget,
+        "Entfernt den Schlüssel eines Tresors aus dem Schlüsselbund des Systems",
+    ),
+    (
+        Operation::Ssh,
+        "Lädt den abgeleiteten ssh-Schlüssel eines Hosts in den ssh-agent",
+    ),
+    (
+        Operation::Share,
+        "Teilt einen Tresor mit einem Team, jedes Mitglied hat seinen eigenen Schlüssel",
+    ),
+    (
+        Operation::Transfer,
+        "Bringt einen Tresor als QR-Codes auf einen anderen Rechner, verschlüsselt mit einem Einmalcode",
+    ),
+    (
+        Operation::Trash,
+        "Listet die gelöschten Passwörter eines Tresors, stellt sie wieder her oder leert den Papierkorb",
+    ),
+    (
+        Operation::Archive,
+        "Blendet ein Passwort in den Listen aus, --include-archived zeigt es",
+    ),
+    (Operation::Unarchive, "Zeigt ein archiviertes Passwort wieder in den Listen"),
+    (
+        Operation::Serve,
+        "Stellt einen Tresor anderen Anwendungen als Secret Service bereit",
+    ),
+    (
+        Operation::Agent,
+        "Behält die Schlüssel entsperrter Tresore für andere Befehle und Anwendungen",
+    ),
+    (
+        Operation::Unlock,
+        "Fragt einmal nach dem Schlüssel eines Tresors und lässt ihn den Agenten behalten, --lock-after Minuten",
+    ),
+    (Operation::Lock, "Lässt den Agenten die Schlüssel sofort vergessen"),
+    (
+        Operation::Status,
+        "Zeigt die Tresore, die der Agent entsperrt hält, und wann sie gesperrt werden",
+    ),
+    (
+        Operation::Exec,
+        "Führt einen Befehl mit einem Passwort in seiner Umgebung aus",
+    ),
+    (Operation::Render, "Füllt die Passwörter in eine Vorlage ein"),
+    (
+        Operation::Completions,
+        "Zeigt das Vervollständigungs-Skript für bash, zsh oder fish",
+    ),
+    (Operation::Help, "Erklärt die Befehle"),
+];
+
+/// Example commands, without the leading `zpass`.
+const EXAMPLES: &[&str] = &[
+    "add vault -n example",
+    "add password -d example.com -u example -l 40",
+    "add password -d example.com -u example -l 40 --unambiguous",
+    "add password -d example.com -u example -l 40 --match-subdomains",
+    "add password -d paypal.com -u example -l 32 --ignore-rules --truncate",
+    "add password -d HomeNetwork -u wifi -l 20 --preset wifi",
+    "add password -d example.com -u example -l 6 --wordlist eff_large",
+    "add password -d shop.example -l 32 --masked-email",
+    "get password -d HomeNetwork --output qr",
+    "get password",
+    "get password -d example.com",
+    "get password example.com example",
+    "get password -d example.com -u example --version 2",
+    "get password -d example.com --spell",
+    "get password -d example.com --type",
+    "get password -d example.com --clear-after 30",
+    "get password -d example.com --clear-after 30 --no-notify",
+    "get password -d example.com --key-stdin",
+    "get password -d example.com --stdout -q",
+    "get password -d example.com --spell --group 4",
+    "get password -d example.com -vv",
+    "list password",
+    "list password --output alfred",
+    "list password --limit 20 --offset 40",
+    "list password --profile work",
+    "list password --sort length --reverse",
+    "list preference",
+    "get preference example.com example",
+    "get token -d internal-service --prefix zp_ --encoding base62 -l 40",
+    "get alias -d shop.example",
+    "get login -d example.com --output json --show",
+    "get alias -d shop.example -u me@example.com",
+    "list domain --filter '*.google.*' --usernames",
+    "list vault --long",
+    "update vault -n example --local-only",
+    "add note -d example.com -u example",
+    "get note -d example.com -u example",
+    "delete note -d example.com -u example",
+    "update key -n example",
+    "update vault -n example --hint none",
+    "get config",
+    "add config secrets.toml",
+    "delete config",
+    "delete password -d example.com -u example",
+    "delete vault -n example",
+    "delete vault -n example -y",
+    "delete password -d example.com -u example --dry-run",
+    "update password -d example.com -u example",
+    "update password -d example.com -u example -l 24",
+    "update password -d example.com -u example --recipe prefix:PRJ-",
+    "add password -d example.com -u example -l 24 --rotate-every 90d",
+    "rotate --due",
+    "set password -d example.com -u example",
+    "set vault -n example",
+    "set vault -n example --default-length 32",
+    "set vault -n example --default-username alice@example.com",
+    "rename vault -n example personal",
+    "clone vault -n work --to work-backup",
+    "copy password -d example.com --from personal --to work",
+    "audit",
+    "audit --output=json --hibp",
+    "audit --entropy --min-entropy 80",
+    "stats --output=json",
+    "import csv passwords.csv --format lastpass -n example",
+    "import 1pux export.1pux -n example",
+    "import pass ~/.password-store -n example",
+    "import vault example.zvault",
+    "import backup.age --age ~/.config/age/key.txt",
+    "export csv -o example.csv --filter example.com,example.org",
+    "export keepass -n example -o example.xml",
+    "export csv --vault work --tag shared -o shared.csv",
+    "export pass --gpg-id alice@example.com",
+    "export vault -n example -o example.zvault",
+    "export --age age1example -n personal,work -o backup.age",
+    "backup paper -n example",
+    "backup verify backup.age --age ~/.config/age/key.txt",
+    "restore paper",
+    "schema",
+    "validate .zpass/example.json",
+    "menu -n example",
+    "tui -n example",
+    "undo",
+    "migrate",
+    "log -n example -d example.com",
+    "history -d example.com --rollback 1",
+    "batch commands.txt",
+    "batch - --yes",
+    "init",
+    "doctor",
+    "remember -n example",
+    "forget -n example",
+    "ssh add -d host.example",
+    "share add-member -n example --age age1example",
+    "share join example.json --age ~/.config/age/key.txt",
+    "transfer send -n example",
+    "transfer receive",
+    "trash restore -d example.com -u example",
+    "archive password -d old-forum.example",
+    "unarchive password -d old-forum.example",
+    "list password --include-archived",
+    "serve -n example",
+    "agent",
+    "unlock -n example --lock-after 30",
+    "lock",
+    "status",
+    "exec -d api.example --env API_TOKEN -- mycommand --verbose",
+    "render template.env.tpl -o .env",
+    "completions bash",
+    "help get",
+];
+
+/// Returns the overview of all the commands.
+pub fn usage() -> String {
+    let mut text = tr(Message::Usage, &[]);
+    text.push_str(&format!("\n{}\n", tr(Message::Operations, &[])));
+    for (word, op) in OPERATIONS {
+        text.push_str(&format!("  {:<14}{}\n", word, summary(*op)));
+    }
+    text.push_str(&format!("\n{}\n  ", tr(Message::Resources, &[])));
+    text.push_str(
+        &RESOURCES
+            .iter()
+            .map(|(w, _)| *w)
+            .collect::<Vec<_>>()
+            .join(", "),
+    );
+    text.push_str(&format!("\n\n{}\n", tr(Message::Params, &[])));
+    for (long, param) in LONG_PARAMS {
+        let short = SHORT_PARAMS.iter().find(|(_, p)| p == param);
+        let short = short.map_or(String::new(), |(s, _)| format!("-{}, ", s));
+        text.push_str(&format!("  {}--{} <value>\n", short, long));
+    }
+    for (flag, param) in FLAGS {
+        let short = SHORT_FLAGS.iter().find(|(_, p)| p == param);
+        let short = short.map_or(String::new(), |(s, _)| format!("-{}, ", s));
+        text.push_str(&format!("  {}--[no-]{}\n", short, flag));
+    }
+    for (flag, param) in SHORT_FLAGS {
+        if !FLAGS.iter().any(|(_, p)| p == param) {
+            text.push_str(&format!("  -{}\n", flag));
+        }
+    }
+    text.push_str("  --version\n");
+    for message in &[Message::HelpConfig, Message::HelpKeys, Message::HelpMore] {
+        text.push_str(&format!("\n{}", tr(*message, &[])));
+    }
+    text
+}
+
+/// Returns the help of an operation, or None if the word isn't an operation.
+pub fn operation(word: &str) -> Option<String> {
+    let (word, op) = OPERATIONS.iter().find(|(w, _)| *w == word)?;
+    Some(help(word, *op))
+}
+
+/// Returns the help of a parsed operation.
+pub fn of(op: Operation) -> String {
+    let word = OPERATIONS
+        .iter()
+        .find(|(_, o)| *o == op)
+        .map_or("", |(w, _)| w);
+    help(word, op)
+}
+
+fn help(word: &str, op: Operation) -> String {
+    let mut text = format!(
+        "{}\n\n{}\n",
+        tr(Message::OperationUsage, &[&word]),
+        summary(op)
+    );
+    let examples: Vec<&&str> = EXAMPLES
+        .iter()
+        .filter(|e| example_of(e) == Some(op))
+        .collect();
+    if !examples.is_empty() {
+        text.push_str(&format!("\n{}\n", tr(Message::Examples, &[])));
+        for example in examples {
+            text.push_str(&format!("  zpass {}\n", example));
+        }
+    }
+    text
+}
+
+/// Returns the summary of an operation in the current locale.
+fn summary(op: Operation) -> &'static str {
+    let summaries = match i18n::current() {
+        Locale::English => SUMMARIES,
+        Locale::German => SUMMARIES_DE,
+    };
+    summaries
+        .iter()
+        .find(|(o, _)| *o == op)
+        .map_or("", |(_, s)| s)
+}
+
+/// Returns the operation of an example.
+fn example_of(example: &str) -> Option<Operation> {
+    let words: Vec<String> = example.split(' ').map(String::from).collect();
+    parse(&words).ok().map(|cmd| cmd.op)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_operation_is_explained() {
+        for (word, op) in OPERATIONS {
+            assert!(!summary(*op).is_empty(), "{} has no summary", word);
+            assert!(
+                SUMMARIES_DE.iter().any(|(o, _)| o == op),
+                "{} has no German summary",
+                word
+            );
+            assert!(
+                EXAMPLES.iter().any(|e| example_of(e) == Some(*op)),
+                "{} has no example",
+                word
+            );
+        }
+        assert!(EXAMPLES.iter().all(|e| example_of(e).is_some()));
+        assert!(usage().contains("-n, --name <value>"));
+        assert!(operation("nope").is_none());
+    }
+}
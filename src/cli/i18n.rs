@@ -0,0 +1,428 @@
+//! # I18n
+//! Translations of what zpass says to people: the help, the questions and the errors around a
+//! command. The locale is the `locale` of the config, or else the language of LC_ALL,
+//! LC_MESSAGES or LANG, e.g. `de_DE.UTF-8`. What isn't translated yet is said in English.
+//!
+//! Each message is a template of the English text, its `{}` are filled in order. A locale is
+//! a match over all the messages, so the compiler names the ones a new locale has to cover.
+
+use std::env;
+use std::fmt;
+use std::sync::Mutex;
+
+/// The locales by the language code they are picked with.
+pub const LOCALES: &[(&str, Locale)] = &[("en", Locale::English), ("de", Locale::German)];
+
+/// The locale picked by the config, None until then.
+static CHOSEN: Mutex<Option<Locale>> = Mutex::new(None);
+
+/// # Locale
+/// A language zpass speaks.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Locale {
+    English,
+    German,
+}
+
+/// # Message
+/// A text that is shown to people.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Message {
+    Usage,
+    OperationUsage,
+    Operations,
+    Resources,
+    Params,
+    Examples,
+    HelpConfig,
+    HelpKeys,
+    HelpMore,
+    ParseFailed,
+    UnknownCommand,
+    RunHelp,
+    ExecuteFailed,
+    SealedConfigFailed,
+    UndoFailed,
+    KeyFor,
+    Hint,
+    YesNo,
+    Account,
+    AccountOf,
+    MakeDefault,
+    DeletePassword,
+    DeleteNote,
+    DeleteVault,
+    RenameVault,
+    ChangeKey,
+    MissingVault,
+    MissingParam,
+    MissingArgument,
+    ClipboardCleared,
+    VaultFailed,
+    SecretFailed,
+    PreferenceFailed,
+    InvalidRecipe,
+    IoFailed,
+    PromptFailed,
+    TemplateFailed,
+    ClipboardFailed,
+    ConversionFailed,
+    SerializationFailed,
+    UnsupportedOutput,
+    ConfigFailed,
+    CsvFailed,
+    PassFailed,
+    OnePasswordFailed,
+    EncryptionFailed,
+    ArchiveFailed,
+    PaperFailed,
+    VaultFileFailed,
+    TransferFailed,
+    InvalidVault,
+    FeatureDisabled,
+    BreachFailed,
+    KeychainFailed,
+    MaskedEmailFailed,
+    SshFailed,
+    SecretServiceFailed,
+    AgentFailed,
+    KeyMismatch,
+    UnknownEncoding,
+    UnknownPreset,
+    UnknownSort,
+    InvalidPeriod,
+    NotAMember,
+    KeyCommandFailed,
+    ChecksFailed,
+    NotInteractive,
+    Archived,
+    UnknownDomain,
+    DidYouMean,
+    Or,
+    DecryptFailed,
+    InvalidKeyLength,
+    VaultSerializationFailed,
+    NoMatchingPreference,
+    NoMatchingVault,
+    VaultAlreadyExists,
+    InvalidVaultName,
+    MemberAlreadyExists,
+    NoMatchingMember,
+    VersionOutOfRange,
+    LegacyVersion,
+    MigrationFailed,
+}
+
+/// Picks the locale by its language code, e.g. from the config. Returns false if zpass
+/// doesn't speak the language.
+pub fn choose(code: &str) -> bool {
+    match find(code) {
+        Some(locale) => {
+            if let Ok(mut chosen) = CHOSEN.lock() {
+                *chosen = Some(locale);
+            }
+            true
+        }
+        None => false,
+    }
+}
+
+/// Returns the locale picked by the config, or else the one of the environment.
+pub fn current() -> Locale {
+    let chosen = CHOSEN.lock().ok().and_then(|chosen| *chosen);
+    chosen.unwrap_or_else(|| from_env(|name| env::var(name).ok()))
+}
+
+/// Returns the locale of the first of LC_ALL, LC_MESSAGES and LANG that is set, as the C
+/// library picks it. Languages zpass doesn't speak are English.
+fn from_env<F>(var: F) -> Locale
+where
+    F: Fn(&str) -> Option<String>,
+{
+    ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .iter()
+        .find_map(|name| var(name).filter(|value| !value.is_empty()))
+        .and_then(|value| find(&value))
+        .unwrap_or(Locale::English)
+}
+
+/// Returns the locale of a code such as `de`, `de_DE` or `de_DE.UTF-8`.
+fn find(code: &str) -> Option<Locale> {
+    let language = code.split(&['_', '-', '.'][..]).next()?;
+    LOCALES
+        .iter()
+        .find(|(l, _)| l.eq_ignore_ascii_case(language))
+        .map(|(_, locale)| *locale)
+}
+
+/// Returns the message in the current locale, its `{}` filled with the args in order.
+pub fn tr(message: Message, args: &[&dyn fmt::Display]) -> String {
+    fill(text(current(), message), args)
+}
+
+/// Returns the template of a message in a locale.
+pub fn text(locale: Locale, message: Message) -> &'static str {
+    match locale {
+        Locale::English => english(message),
+        Locale::German => german(message),
+    }
+}
+
+/// Returns true if the answer to a yes or no question is a yes, in English or the locale.
+pub fn is_yes(answer: &str) -> bool {
+    let answer = answer.to_lowercase();
+    matches!(answer.as_str(), "y" | "yes")
+        || (current() == Locale::German && matches!(answer.as_str(), "j" | "ja"))
+}
+
+/// Replaces the `{}` of the template with the args, missing args leave them empty.
+fn fill(template: &str, args: &[&dyn fmt::Display]) -> String {
+    let mut parts = template.split("{}");
+    let mut text = parts.next().unwrap_or_default().to_owned();
+    for (i, part) in parts.enumerate() {
+        if let Some(arg) = args.get(i) {
+            text.push_str(&arg.to_string());
+        }
+        text.push_str(part);
+    }
+    text
+}
+
+fn english(message: Message) -> &'static str {
+    match message {
+        Message::Usage => {
+            "Usage: zpass <operation> [<resource>] [<param>...] [<arg>...]\n       zpass <domain> [<username>], short for zpass get password\n"
+        }
+        Message::OperationUsage => "Usage: zpass {} ...",
+        Message::Operations => "Operations:",
+        Message::Resources => "Resources:",
+        Message::Params => "Params:",
+        Message::Examples => "Examples:",
+        Message::HelpConfig => {
+            "Defaults for the params are read from ~/.config/zpass/config.toml and\n$ZPASS_HOME/config.toml, `zpass get config` shows where they are. The\nvariables ZPASS_VAULT, ZPASS_DOMAIN_DEFAULT_LENGTH, ZPASS_CLIPBOARD_TIMEOUT and\nZPASS_OUTPUT win over the files. `zpass add config` seals a config with the\nkey of the default vault, it fills in what the others leave unset.\n"
+        }
+        Message::HelpKeys => {
+            "Keys are typed in, unless they come from --key-stdin, --key-fd or the\ncommand in ZPASS_KEY_CMD, which gets the name of the vault in ZPASS_VAULT.\n"
+        }
+        Message::HelpMore => {
+            "Run `zpass help <operation>` or `zpass <operation> --help` for examples.\n"
+        }
+        Message::ParseFailed => "Failed to parse the command:",
+        Message::UnknownCommand => "Unknown command {}",
+        Message::RunHelp => "Run `zpass help` for usage",
+        Message::ExecuteFailed => "Failed to execute the command:",
+        Message::SealedConfigFailed => "Failed to read the sealed config:",
+        Message::UndoFailed => "Failed to keep the vaults for undo:",
+        Message::KeyFor => "Key for {}:",
+        Message::Hint => "Hint: {}",
+        Message::YesNo => "[y/N]",
+        Message::Account => "Account",
+        Message::AccountOf => "Account of {}",
+        Message::MakeDefault => "Make {} the default account of {}?",
+        Message::DeletePassword => "Delete the password of {} {}?",
+        Message::DeleteNote => "Delete the note of {} {}?",
+        Message::DeleteVault => {
+            "Delete the vault {}? Its passwords can't be generated again without a backup."
+        }
+        Message::RenameVault => "Rename the vault {} to {}?",
+        Message::ChangeKey => "Change the key of {}?",
+        Message::MissingVault => "Failed to find the vault",
+        Message::MissingParam => "Expected param:",
+        Message::MissingArgument => "Expected argument:",
+        Message::ClipboardCleared => "The clipboard was cleared",
+        Message::VaultFailed => "Vault error:",
+        Message::SecretFailed => "Secret error:",
+        Message::PreferenceFailed => "Preference error:",
+        Message::InvalidRecipe => "Invalid recipe:",
+        Message::IoFailed => "IO error:",
+        Message::PromptFailed => "Prompt error:",
+        Message::TemplateFailed => "Template error:",
+        Message::ClipboardFailed => "Clipboard Error:",
+        Message::ConversionFailed => "Conversion Error:",
+        Message::SerializationFailed => "Serialization Error:",
+        Message::UnsupportedOutput => "Unsupported output: {}",
+        Message::ConfigFailed => "Config error:",
+        Message::CsvFailed => "CSV error:",
+        Message::PassFailed => "Password store error:",
+        Message::OnePasswordFailed => "1Password export error:",
+        Message::EncryptionFailed => "Encryption error:",
+        Message::ArchiveFailed => "Archive error:",
+        Message::PaperFailed => "Paper backup error:",
+        Message::VaultFileFailed => "Vault file error:",
+        Message::TransferFailed => "Transfer error:",
+        Message::InvalidVault => "Invalid vault:",
+        Message::FeatureDisabled => "zpass was built without the `{}` feature",
+        Message::BreachFailed => "Breach check error:",
+        Message::KeychainFailed => "Keychain error:",
+        Message::MaskedEmailFailed => "Masked email error:",
+        Message::SshFailed => "SSH error:",
+        Message::SecretServiceFailed => "Secret Service error:",
+        Message::AgentFailed => "Agent error:",
+        Message::KeyMismatch => "The keys don't match",
+        Message::UnknownEncoding => "Expected base62, base32 or hex as the encoding, got:",
+        Message::UnknownPreset => "Expected wifi as the preset, got:",
+        Message::UnknownSort => "Expected domain, username, last-used or length as the order, got:",
+        Message::InvalidPeriod => "Expected a period such as 90d or 12w, or never, got:",
+        Message::NotAMember => "The vault isn't shared with the identity in {}",
+        Message::KeyCommandFailed => "The key command failed:",
+        Message::ChecksFailed => "{} checks failed",
+        Message::NotInteractive => "Can't ask for {} without a terminal",
+        Message::Archived => "{} is archived, --include-archived gets its password",
+        Message::UnknownDomain => "No password for {}",
+        Message::DidYouMean => "Did you mean {}?",
+        Message::Or => "{} or {}",
+        Message::DecryptFailed => "Failed to decrypt:",
+        Message::InvalidKeyLength => "Invalid Key or IV length:",
+        Message::VaultSerializationFailed => "de/serialization error:",
+        Message::NoMatchingPreference => "No matching preference found",
+        Message::NoMatchingVault => "No vault named {}",
+        Message::VaultAlreadyExists => "Vault already exists",
+        Message::InvalidVaultName => "Invalid vault name: {}",
+        Message::MemberAlreadyExists => "{} is already a member of the vault",
+        Message::NoMatchingMember => "{} is not a member of the vault",
+        Message::VersionOutOfRange => "Version {} was never used, the highest version is {}",
+        Message::LegacyVersion => {
+            "The password uses the legacy scheme, which has no versions, version {} would be the same password"
+        }
+        Message::MigrationFailed => {
+            "{} didn't read back as it was written, the vaults were restored"
+        }
+    }
+}
+
+fn german(message: Message) -> &'static str {
+    match message {
+        Message::Usage => {
+            "Aufruf: zpass <Operation> [<Ressource>] [<Parameter>...] [<Argument>...]\n        zpass <Domain> [<Benutzername>], kurz für zpass get password\n"
+        }
+        Message::OperationUsage => "Aufruf: zpass {} ...",
+        Message::Operations => "Operationen:",
+        Message::Resources => "Ressourcen:",
+        Message::Params => "Parameter:",
+        Message::Examples => "Beispiele:",
+        Message::HelpConfig => {
+            "Vorgaben für die Parameter stehen in ~/.config/zpass/config.toml und\n$ZPASS_HOME/config.toml, `zpass get config` zeigt, wo sie liegen. Die\nVariablen ZPASS_VAULT, ZPASS_DOMAIN_DEFAULT_LENGTH, ZPASS_CLIPBOARD_TIMEOUT und\nZPASS_OUTPUT haben Vorrang vor den Dateien. `zpass add config` versiegelt eine\nKonfiguration mit dem Schlüssel des Standard-Tresors, sie ergänzt, was die\nanderen offen lassen.\n"
+        }
+        Message::HelpKeys => {
+            "Schlüssel werden eingetippt, außer sie kommen von --key-stdin, --key-fd oder\ndem Befehl in ZPASS_KEY_CMD, der den Namen des Tresors in ZPASS_VAULT erhält.\n"
+        }
+        Message::HelpMore => {
+            "`zpass help <Operation>` oder `zpass <Operation> --help` zeigt Beispiele.\n"
+        }
+        Message::ParseFailed => "Der Befehl ist ungültig:",
+        Message::UnknownCommand => "Unbekannter Befehl {}",
+        Message::RunHelp => "`zpass help` erklärt den Aufruf",
+        Message::ExecuteFailed => "Der Befehl ist fehlgeschlagen:",
+        Message::SealedConfigFailed => "Die versiegelte Konfiguration ist nicht lesbar:",
+        Message::UndoFailed => "Die Tresore konnten nicht für undo gesichert werden:",
+        Message::KeyFor => "Schlüssel für {}:",
+        Message::Hint => "Hinweis: {}",
+        Message::YesNo => "[j/N]",
+        Message::Account => "Konto",
+        Message::AccountOf => "Konto für {}",
+        Message::MakeDefault => "{} zum Standardkonto für {} machen?",
+        Message::DeletePassword => "Das Passwort von {} {} löschen?",
+        Message::DeleteNote => "Die Notiz von {} {} löschen?",
+        Message::DeleteVault => {
+            "Den Tresor {} löschen? Ohne Sicherung lassen sich seine Passwörter nicht wieder erzeugen."
+        }
+        Message::RenameVault => "Den Tresor {} in {} umbenennen?",
+        Message::ChangeKey => "Den Schlüssel von {} ändern?",
+        Message::MissingVault => "Der Tresor wurde nicht gefunden",
+        Message::MissingParam => "Parameter fehlt:",
+        Message::MissingArgument => "Argument fehlt:",
+        Message::ClipboardCleared => "Die Zwischenablage wurde geleert",
+        Message::VaultFailed => "Tresorfehler:",
+        Message::SecretFailed => "Fehler im Geheimnis:",
+        Message::PreferenceFailed => "Fehler in der Einstellung:",
+        Message::InvalidRecipe => "Ungültiges Rezept:",
+        Message::IoFailed => "Ein-/Ausgabefehler:",
+        Message::PromptFailed => "Fehler bei der Eingabe:",
+        Message::TemplateFailed => "Fehler in der Vorlage:",
+        Message::ClipboardFailed => "Fehler in der Zwischenablage:",
+        Message::ConversionFailed => "Fehler bei der Umwandlung:",
+        Message::SerializationFailed => "Fehler bei der Serialisierung:",
+        Message::UnsupportedOutput => "Nicht unterstützte Ausgabe: {}",
+        Message::ConfigFailed => "Fehler in der Konfiguration:",
+        Message::CsvFailed => "CSV-Fehler:",
+        Message::PassFailed => "Fehler im Password Store:",
+        Message::OnePasswordFailed => "Fehler im 1Password-Export:",
+        Message::EncryptionFailed => "Fehler bei der Verschlüsselung:",
+        Message::ArchiveFailed => "Fehler im Archiv:",
+        Message::PaperFailed => "Fehler in der Papiersicherung:",
+        Message::VaultFileFailed => "Fehler in der Tresordatei:",
+        Message::TransferFailed => "Fehler bei der Übertragung:",
+        Message::InvalidVault => "Ungültiger Tresor:",
+        Message::FeatureDisabled => "zpass wurde ohne das Feature `{}` gebaut",
+        Message::BreachFailed => "Fehler bei der Prüfung auf Datenlecks:",
+        Message::KeychainFailed => "Fehler im Schlüsselbund:",
+        Message::MaskedEmailFailed => "Fehler bei der maskierten E-Mail:",
+        Message::SshFailed => "SSH-Fehler:",
+        Message::SecretServiceFailed => "Fehler im Secret Service:",
+        Message::AgentFailed => "Fehler im Agenten:",
+        Message::KeyMismatch => "Die Schlüssel stimmen nicht überein",
+        Message::UnknownEncoding => "Erwartet base62, base32 oder hex als Kodierung, erhalten:",
+        Message::UnknownPreset => "Erwartet wifi als Vorgabe, erhalten:",
+        Message::UnknownSort => {
+            "Erwartet domain, username, last-used oder length als Reihenfolge, erhalten:"
+        }
+        Message::InvalidPeriod => "Erwartet einen Zeitraum wie 90d oder 12w, oder never, erhalten:",
+        Message::NotAMember => "Der Tresor ist nicht mit der Identität in {} geteilt",
+        Message::KeyCommandFailed => "Der Schlüsselbefehl ist fehlgeschlagen:",
+        Message::ChecksFailed => "{} Prüfungen sind fehlgeschlagen",
+        Message::NotInteractive => "Ohne Terminal kann nicht nach {} gefragt werden",
+        Message::Archived => "{} ist archiviert, --include-archived liefert sein Passwort",
+        Message::UnknownDomain => "Kein Passwort für {}",
+        Message::DidYouMean => "Meinten Sie {}?",
+        Message::Or => "{} oder {}",
+        Message::DecryptFailed => "Entschlüsseln fehlgeschlagen:",
+        Message::InvalidKeyLength => "Ungültige Länge von Schlüssel oder IV:",
+        Message::VaultSerializationFailed => "Fehler bei der De-/Serialisierung:",
+        Message::NoMatchingPreference => "Keine passende Einstellung gefunden",
+        Message::NoMatchingVault => "Kein Tresor namens {}",
+        Message::VaultAlreadyExists => "Der Tresor existiert bereits",
+        Message::InvalidVaultName => "Ungültiger Tresorname: {}",
+        Message::MemberAlreadyExists => "{} ist bereits Mitglied des Tresors",
+        Message::NoMatchingMember => "{} ist kein Mitglied des Tresors",
+        Message::VersionOutOfRange => "Version {} wurde nie benutzt, die höchste Version ist {}",
+        Message::LegacyVersion => {
+            "Das Passwort nutzt das alte Verfahren ohne Versionen, Version {} wäre dasselbe Passwort"
+        }
+        Message::MigrationFailed => {
+            "{} ließ sich nicht so lesen, wie es geschrieben wurde, die Tresore wurden wiederhergestellt"
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn picks_the_locale_and_fills_the_message() {
+        let env = |lang: &'static str| {
+            move |name: &str| match name {
+                "LC_ALL" => Some(String::new()),
+                "LANG" => Some(lang.to_owned()),
+                _ => None,
+            }
+        };
+        assert_eq!(from_env(env("de_DE.UTF-8")), Locale::German);
+        assert_eq!(from_env(env("fr_FR.UTF-8")), Locale::English);
+        assert_eq!(from_env(env("C")), Locale::English);
+        assert_eq!(find("DE"), Some(Locale::German));
+
+        assert_eq!(
+            fill(text(Locale::German, Message::RenameVault), &[&"a", &"b"]),
+            "Den Tresor a in b umbenennen?"
+        );
+        assert_eq!(
+            fill(text(Locale::German, Message::NoMatchingVault), &[&"work"]),
+            "Kein Tresor namens work"
+        );
+        assert_eq!(
+            fill(text(Locale::English, Message::KeyFor), &[]),
+            "Key for :"
+        );
+    }
+}
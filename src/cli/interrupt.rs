@@ -0,0 +1,66 @@
+//! # Interrupt
+//! Ctrl+C, SIGTERM and SIGHUP end zpass right away, the handler only makes sure a password
+//! that is waiting to be cleared doesn't stay on the clipboard.
+
+use crate::safe::cache;
+#[cfg(feature = "clipboard")]
+use clipboard::{ClipboardContext, ClipboardProvider};
+use std::process;
+use std::sync::Mutex;
+#[cfg(feature = "clipboard")]
+use tracing::debug;
+use tracing::warn;
+
+/// Exit code of a process that was interrupted, as the shells report SIGINT.
+const INTERRUPTED: i32 = 130;
+
+/// The password on the clipboard that has to be cleared when zpass is interrupted.
+static PENDING: Mutex<Option<String>> = Mutex::new(None);
+
+/// Installs the handler of the interrupt and termination signals.
+pub fn install() {
+    if let Err(err) = ctrlc::set_handler(|| {
+        clear_pending();
+        cache::clear();
+        process::exit(INTERRUPTED);
+    }) {
+        warn!(
+            "Failed to handle Ctrl+C, the clipboard isn't cleared then: {}",
+            err
+        );
+    }
+}
+
+/// Clears the password from the clipboard if zpass is interrupted before `forget` is called.
+pub fn clear_on_exit(password: &str) {
+    if let Ok(mut pending) = PENDING.lock() {
+        *pending = Some(password.to_owned());
+    }
+}
+
+/// Leaves the clipboard alone on an interrupt again.
+pub fn forget() {
+    if let Ok(mut pending) = PENDING.lock() {
+        *pending = None;
+    }
+}
+
+/// Clears the clipboard unless something else was copied since the password.
+#[cfg(feature = "clipboard")]
+fn clear_pending() {
+    let password = match PENDING.lock().ok().and_then(|mut p| p.take()) {
+        Some(password) => password,
+        None => return,
+    };
+    let ctx: Result<ClipboardContext, _> = ClipboardProvider::new();
+    if let Ok(mut ctx) = ctx {
+        if ctx.get_contents().ok().as_deref() == Some(&password[..]) {
+            debug!("Clearing the clipboard after an interrupt");
+            let _ = ctx.set_contents(String::new());
+        }
+    }
+}
+
+/// Nothing is copied without the clipboard.
+#[cfg(not(feature = "clipboard"))]
+fn clear_pending() {}
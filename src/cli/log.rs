@@ -0,0 +1,29 @@
+//! # Log
+//! Diagnostics on stderr, such as which files are read and written and which vault and
+//! preference were picked. Only warnings by default, `-v` or `--verbose` adds the debug
+//! messages, `-vv` traces everything, and ZPASS_LOG takes filter directives instead, e.g.
+//! `ZPASS_LOG=zpass::safe::vault=trace`. Keys, secrets and passwords are never logged.
+
+use super::parser::{ParamName, Params};
+use std::env;
+use std::io;
+use tracing_subscriber::EnvFilter;
+
+/// Environment variable that holds the filter directives.
+pub const LOG_VARIABLE: &str = "ZPASS_LOG";
+
+/// Starts logging at the level the params or ZPASS_LOG ask for.
+pub fn init(params: &Params) {
+    let directives = match env::var(LOG_VARIABLE) {
+        Ok(directives) => directives,
+        Err(_) if params.flag(ParamName::Trace) => "zpass=trace".to_owned(),
+        Err(_) if params.flag(ParamName::Verbose) => "zpass=debug".to_owned(),
+        Err(_) => "warn".to_owned(),
+    };
+    // only the first subscriber counts, a second one is left out
+    let _ = tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::new(directives))
+        .with_writer(io::stderr)
+        .without_time()
+        .try_init();
+}
@@ -0,0 +1,140 @@
+//! # Menu
+//! Lets a picker such as dmenu, rofi or fzf choose the account to copy the password of.
+//! The accounts are written to the picker one per line, as `domain (username)`,
+//! and the line it prints is mapped back to the account.
+//!
+//! Without a picker, `get password` falls back to a fuzzy finder in the terminal, see `prompt`.
+//!
+//! Launchers such as Alfred and Raycast get the accounts as script filter items instead,
+//! the workflow receives the label as `arg` and the account as `domain` and `username` variables.
+
+use crate::safe::preference::Preference;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::env;
+use std::io::{self, Write};
+use std::process::{Command, Stdio};
+
+/// Environment variable that holds the picker command, e.g. `rofi -dmenu -i`.
+pub const PICKER_VARIABLE: &str = "ZPASS_MENU";
+/// Picker used when the variable isn't set.
+const DEFAULT_PICKER: &str = "dmenu";
+
+/// # ScriptFilter
+/// The JSON document Alfred and Raycast script filters print.
+#[derive(Serialize, Debug)]
+pub struct ScriptFilter {
+    // items are the rows the launcher shows
+    pub items: Vec<Item>,
+}
+
+/// # Item
+/// One row of a script filter.
+#[derive(Serialize, Debug, PartialEq)]
+pub struct Item {
+    // uid lets the launcher learn which rows are picked most
+    pub uid: String,
+    // title is the domain
+    pub title: String,
+    // subtitle describes the account
+    pub subtitle: String,
+    // arg is passed to the next action of the workflow
+    pub arg: String,
+    // autocomplete is what tab completes the query to
+    pub autocomplete: String,
+    // variables are set in the environment of the next action
+    pub variables: HashMap<String, String>,
+}
+
+impl ScriptFilter {
+    /// Creates one item per preference, the subtitle is described by the caller.
+    pub fn new<F>(preferences: &[&Preference], subtitle: F) -> ScriptFilter
+    where
+        F: Fn(&Preference) -> String,
+    {
+        let items = preferences
+            .iter()
+            .map(|p| {
+                let mut variables = HashMap::new();
+                variables.insert("domain".to_owned(), p.domain.clone());
+                variables.insert("username".to_owned(), p.username.clone());
+                Item {
+                    uid: label(p),
+                    title: p.domain.clone(),
+                    subtitle: subtitle(p),
+                    arg: label(p),
+                    autocomplete: p.domain.clone(),
+                    variables,
+                }
+            })
+            .collect();
+        ScriptFilter { items }
+    }
+}
+
+/// Returns the line that represents a preference.
+pub fn label(p: &Preference) -> String {
+    if p.username.is_empty() {
+        p.domain.clone()
+    } else {
+        format!("{} ({})", p.domain, p.username)
+    }
+}
+
+/// Returns the preference of the selected line, if any.
+pub fn find<'a>(preferences: &[&'a Preference], selection: &str) -> Option<&'a Preference> {
+    let selection = selection.trim();
+    preferences.iter().copied().find(|p| label(p) == selection)
+}
+
+/// Runs the picker with one line per preference and returns the line that was picked.
+/// Returns None when the picker was dismissed.
+pub fn pick(preferences: &[&Preference]) -> io::Result<Option<String>> {
+    let picker = env::var(PICKER_VARIABLE).unwrap_or_else(|_| DEFAULT_PICKER.to_owned());
+    let mut child = Command::new("sh")
+        .arg("-c")
+        .arg(&picker)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        for p in preferences {
+            writeln!(stdin, "{}", label(p))?;
+        }
+    }
+    let output = child.wait_with_output()?;
+    let selection = String::from_utf8_lossy(&output.stdout).trim().to_owned();
+    if !output.status.success() || selection.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(selection))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_selected_lines() {
+        let alice = Preference::new("example.com", "alice", 16);
+        let anonymous = Preference::new("example.org", "", 16);
+        let preferences = vec![&alice, &anonymous];
+
+        assert_eq!(label(&alice), "example.com (alice)");
+        assert_eq!(find(&preferences, "example.com (alice)\n"), Some(&alice));
+        assert_eq!(find(&preferences, "example.org"), Some(&anonymous));
+        assert_eq!(find(&preferences, "example.net"), None);
+    }
+
+    #[test]
+    fn script_filter_items() {
+        let alice = Preference::new("example.com", "alice", 16);
+        let filter = ScriptFilter::new(&[&alice], |p| p.username.clone());
+        let item = &filter.items[0];
+
+        assert_eq!(item.title, "example.com");
+        assert_eq!(item.subtitle, "alice");
+        assert_eq!(item.arg, "example.com (alice)");
+        assert_eq!(item.variables["username"], "alice");
+    }
+}
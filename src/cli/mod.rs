@@ -1,3 +1,27 @@
+pub mod ask_password;
+pub mod complete;
+pub mod completions;
+pub mod config;
+pub mod context;
+pub mod doctor;
+pub mod error;
 pub mod handler;
+pub mod help;
+pub mod i18n;
+pub mod interrupt;
+pub mod log;
+pub mod menu;
 pub mod parser;
+pub mod pinentry;
+pub mod plugin;
+pub mod progress;
+pub mod prompt;
 pub mod run;
+#[cfg(feature = "clipboard")]
+pub mod sensitive;
+pub mod spell;
+pub mod suggest;
+pub mod table;
+#[cfg(feature = "tui")]
+pub mod tui;
+pub mod version;
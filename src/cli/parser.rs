@@ -1,105 +1,1089 @@
 //! # Parser
 //! - add vault -n example
 //! - add vault --name=example
+//! - add vault -n example --hint "the usual + year"
+//! - update vault -n example --hint none
+//! - update vault -n example --local-only
 //! - add password -d example.com -u example -l 40
 //! - add password --domain=example.com --username=example --length=40
+//! - add password -d example.com -u example -l 40 --ignore-rules
+//! - add password -d example.com -u example -l 40 --unambiguous
+//! - add password -d example.com -u example -l 40 --match-subdomains
+//! - add password -d paypal.com -u example -l 32 --ignore-rules --truncate
+//! - add password -d HomeNetwork -u wifi -l 20 --preset wifi
+//! - add password -d example.com -u example -l 6 --wordlist ~/eff_large_wordlist.txt
+//! - add password -d shop.example -l 32 --masked-email
+//! - get password -d HomeNetwork --output qr
+//! - get password
 //! - get password -d example.com
 //! - get password -d example.com -u example
 //! - get password -d example.com -u example -l 40
 //! - get password --domain=example.com --username=example --length=40
+//! - get password -d example.com --version 2
+//! - get password --domain example.com --version=2
+//! - get password -d example.com --spell
+//! - get password -d example.com --type
+//! - get password -d example.com --clear-after 30
+//! - get password -d example.com --clear-after 30 --no-notify
+//! - get password -d example.com --key-stdin
+//! - get password -d example.com --key-fd 3
+//! - get password -d example.com --stdout -q
+//! - get password -d example.com --stdout --group 4 --group-sep ' '
+//! - get password -d example.com -vv
+//! - get password -d=example.com -u=example -l=40
+//! - get password -- -example.com
+//! - add password -d example.com -u example -l 40 --no-unambiguous
+//! - get password -d example.com -u "first last"
+//! - get password example.com
+//! - get password example.com example
+//! - get password -u example example.com
+//! - get password --domain=example.com --username='first last'
+//! - remember -n example
+//! - forget -n example
+//! - get alias -d shop.example
+//! - get alias -d shop.example -u me@example.com --version 1
+//! - get token -d internal-service --prefix zp_ --encoding base62 -l 40
+//! - get token -d internal-service --stdout
+//! - get login -d example.com --output json --show
+//! - ssh add -d host.example
+//! - ssh add -d host.example -n example
+//! - share add-member -n example --age age1example
+//! - share remove-member -n example --age age1example
+//! - share join example.json --age ~/.config/age/key.txt
+//! - transfer send -n example
+//! - transfer receive
+//! - trash list -n example
+//! - trash restore -d example.com -u example
+//! - trash empty -n example
+//! - serve
+//! - serve -n example
+//! - agent
+//! - unlock -n example
+//! - unlock -n example --lock-after 30
+//! - lock
+//! - lock -n example
+//! - status
+//! - exec -d api.example --env API_TOKEN -- mycommand --verbose
+//! - render template.env.tpl -o .env
+//! - render template.env.tpl -n example
+//! - completions bash
+//! - help
+//! - help get
+//! - get password --help
+//! - delete password -d example.com -u example
+//! - archive password -d old-forum.example
+//! - unarchive password -d old-forum.example -u example
+//! - list password --include-archived
+//! - delete vault -n example
+//! - delete vault -n example --yes
+//! - delete password -d example.com -u example --dry-run
+//! - update password -d example.com -u example
+//! - update password -d example.com -u example -l 24 --version 3
+//! - update password -d example.com -u example --rotate-every 90d
+//! - update password -d example.com -u example --no-match-subdomains
+//! - update password -d example.com -u example --recipe 'prefix: PRJ-; position -1: digit'
+//! - rotate --due
+//! - rotate --due -n example
+//! - set password -d example.com -u example
+//! - set vault -n example
+//! - set vault -n example --default-length 32
+//! - set vault -n example --default-username alice@example.com
+//! - set vault -n example --default-username none
+//! - rename vault -n example personal
+//! - clone vault -n work --to work-backup
+//! - migrate
+//! - copy password -d example.com --from personal --to work
+//! - copy password -d example.com -u example --from personal --to work
+//! - list preference
+//! - get preference -d example.com
+//! - get preference example.com example
+//! - list domain --filter '*.google.*'
+//! - list domain --filter '*.google.*' --usernames
+//! - list vault
+//! - list vault --long
+//! - update key -n example
+//! - get config
+//! - add config secrets.toml
+//! - delete config
+//! - add note -d example.com -u example
+//! - get note -d example.com -u example
+//! - delete note -d example.com -u example
+//! - list password
+//! - list password --output alfred
+//! - list password --limit 20 --offset 40
+//! - tui
+//! - tui -n example
+//! - list password --profile work
+//! - list password --sort length --reverse
+//! - undo
+//! - log
+//! - log -n example -d example.com
+//! - history -d example.com
+//! - history -d example.com -u example --rollback 1
+//! - batch commands.txt
+//! - batch - --yes
+//! - init
+//! - doctor
+//! - menu
+//! - menu -n example
+//! - audit
+//! - audit --output=json
+//! - audit --hibp
+//! - audit --entropy --min-entropy 80
+//! - stats
+//! - stats --output=json
+//! - import csv passwords.csv
+//! - import csv passwords.csv --format lastpass -n example
+//! - import 1pux export.1pux -n example
+//! - import pass
+//! - import pass ~/.password-store -n example
+//! - export --age age1example -o backup.age
+//! - export --age age1example -n personal,work -o backup.age
+//! - import backup.age --age ~/.config/age/key.txt
+//! - backup paper -n example
+//! - restore paper
+//! - restore paper MZXW 6YTB OI -n example
+//! - backup verify backup.age --age ~/.config/age/key.txt
+//! - backup verify example.zvault
+//! - export vault -n example -o example.zvault
+//! - import vault example.zvault
+//! - export keepass -n example -o example.xml
+//! - schema
+//! - validate .zpass/example.json
+//! - export pass --gpg-id alice@example.com
+//! - export pass ~/.password-store --gpg-id alice@example.com -n example
+//! - export csv -o example.csv --filter example.com,example.org
 
+use super::suggest;
 use std::collections::HashMap;
+use std::error;
+use std::fmt;
 
-/// Users specify a command: <Operation> <Resource> [<Param>]
-/// where param is either: `-key vaule` or `--key=value`
+/// Users specify a command: <Operation> [<Resource>] [<Param> | <Arg>]
+/// where param is either: `-key vaule`, `--key=value` or `--key value`
+/// and arg is a positional argument such as a file name.
+/// Values and args can be quoted with `"` or `'`, inside the quotes a backslash escapes the
+/// next character.
+/// Everything after `--` is kept as it is: the command line of a child process for `exec`,
+/// positional args for the other operations, even if they start with a dash.
 pub struct Command {
     pub op: Operation,
-    pub on: Resource,
-    pub params: HashMap<ParamName, String>,
+    pub on: Option<Resource>,
+    pub params: Params,
+    pub args: Vec<String>,
+    pub rest: Vec<String>,
 }
 
 /// The action we want to perform on a resource.
+#[derive(Clone, Copy, PartialEq, Debug)]
 pub enum Operation {
     Add,
     Get,
+    List,
+    Delete,
+    Update,
+    Set,
+    Rename,
+    Clone,
+    Copy,
+    Audit,
+    Stats,
+    Import,
+    Export,
+    Backup,
+    Restore,
+    Schema,
+    Validate,
+    Menu,
+    Tui,
+    Rotate,
+    Undo,
+    Migrate,
+    Log,
+    History,
+    Batch,
+    Init,
+    Doctor,
+    Remember,
+    Forget,
+    Ssh,
+    Share,
+    Transfer,
+    Trash,
+    Archive,
+    Unarchive,
+    Serve,
+    Agent,
+    Unlock,
+    Lock,
+    Status,
+    Exec,
+    Render,
+    Completions,
+    Help,
 }
 
 /// The objects are can interact with.
+#[derive(Clone, Copy, PartialEq, Debug)]
 pub enum Resource {
     Password,
     Vault,
+    Csv,
+    Keepass,
+    Pass,
+    OnePux,
+    Paper,
+    Preference,
+    Key,
+    Config,
+    Note,
+    Domain,
+    Alias,
+    Token,
+    Login,
+    Verify,
 }
 
-/// Options are specified as `-key vaule` or `--key=value`
-#[derive(Hash, Eq, PartialEq, Debug)]
+/// Options are specified as `-key vaule`, `-key=value`, `--key=value`, `--key value`, or as a
+/// `--flag` that
+/// is turned off with `--no-flag`
+#[derive(Hash, Eq, PartialEq, Debug, Clone, Copy)]
 pub enum ParamName {
     VaultName,
     DomainName,
     UserName,
     Length,
+    Output,
+    Hibp,
+    Version,
+    IgnoreRules,
+    Unambiguous,
+    Spell,
+    Type,
+    Env,
+    Help,
+    Format,
+    OutFile,
+    Filter,
+    GpgId,
+    Age,
+    ClearAfter,
+    KeyStdin,
+    KeyFd,
+    Stdout,
+    Quiet,
+    Verbose,
+    Trace,
+    Yes,
+    DryRun,
+    Limit,
+    Offset,
+    Profile,
+    RotateEvery,
+    Due,
+    Usernames,
+    From,
+    To,
+    Prefix,
+    Encoding,
+    Preset,
+    Recipe,
+    Hint,
+    Tag,
+    Long,
+    LocalOnly,
+    Group,
+    GroupSep,
+    Truncate,
+    Notify,
+    Rollback,
+    IncludeArchived,
+    Show,
+    Wordlist,
+    Entropy,
+    MinEntropy,
+    MaskedEmail,
+    MaskedEmailService,
+    // the token is only read from the config, on the command line it would be kept in the
+    // history of the shell
+    MaskedEmailToken,
+    Sort,
+    Reverse,
+    DefaultLength,
+    DefaultUsername,
+    MatchSubdomains,
+    LockAfter,
+}
+
+/// The value of a param, converted to the type the param takes.
+#[derive(Clone, PartialEq, Debug)]
+pub enum ParamValue {
+    Text(String),
+    Number(usize),
+    Flag(bool),
+}
+
+/// The params of a command, with accessors for each type of value.
+#[derive(Default, Debug, Clone)]
+pub struct Params {
+    values: HashMap<ParamName, ParamValue>,
+}
+
+impl Params {
+    /// Creates an empty set of params.
+    pub fn new() -> Params {
+        Params::default()
+    }
+
+    /// Sets the value of a param.
+    pub fn insert(&mut self, name: ParamName, value: ParamValue) {
+        self.values.insert(name, value);
+    }
+
+    /// Adds the params of the other set that aren't in this one.
+    pub fn merge(&mut self, other: &Params) {
+        for (name, value) in &other.values {
+            self.values.entry(*name).or_insert_with(|| value.clone());
+        }
+    }
+
+    /// Returns true if the param was given.
+    pub fn has(&self, name: ParamName) -> bool {
+        self.values.contains_key(&name)
+    }
+
+    /// Returns the value of a param that takes text.
+    pub fn text(&self, name: ParamName) -> Option<&str> {
+        match self.values.get(&name) {
+            Some(ParamValue::Text(text)) => Some(text),
+            _ => None,
+        }
+    }
+
+    /// Returns the value of a param that takes a number.
+    pub fn number(&self, name: ParamName) -> Option<usize> {
+        match self.values.get(&name) {
+            Some(ParamValue::Number(number)) => Some(*number),
+            _ => None,
+        }
+    }
+
+    /// Returns true if the flag was given, and not as `--no-flag`.
+    pub fn flag(&self, name: ParamName) -> bool {
+        self.flag_or(name, false)
+    }
+
+    /// Returns the flag if it was given either way, otherwise the default.
+    pub fn flag_or(&self, name: ParamName, default: bool) -> bool {
+        match self.values.get(&name) {
+            Some(ParamValue::Flag(on)) => *on,
+            _ => default,
+        }
+    }
+}
+
+/// The words of the operations.
+pub const OPERATIONS: &[(&str, Operation)] = &[
+    ("add", Operation::Add),
+    ("get", Operation::Get),
+    ("list", Operation::List),
+    ("delete", Operation::Delete),
+    ("update", Operation::Update),
+    ("set", Operation::Set),
+    ("rename", Operation::Rename),
+    ("clone", Operation::Clone),
+    ("copy", Operation::Copy),
+    ("audit", Operation::Audit),
+    ("stats", Operation::Stats),
+    ("import", Operation::Import),
+    ("export", Operation::Export),
+    ("backup", Operation::Backup),
+    ("restore", Operation::Restore),
+    ("schema", Operation::Schema),
+    ("validate", Operation::Validate),
+    ("menu", Operation::Menu),
+    ("tui", Operation::Tui),
+    ("rotate", Operation::Rotate),
+    ("undo", Operation::Undo),
+    ("migrate", Operation::Migrate),
+    ("log", Operation::Log),
+    ("history", Operation::History),
+    ("batch", Operation::Batch),
+    ("init", Operation::Init),
+    ("doctor", Operation::Doctor),
+    ("remember", Operation::Remember),
+    ("forget", Operation::Forget),
+    ("ssh", Operation::Ssh),
+    ("share", Operation::Share),
+    ("transfer", Operation::Transfer),
+    ("trash", Operation::Trash),
+    ("archive", Operation::Archive),
+    ("unarchive", Operation::Unarchive),
+    ("serve", Operation::Serve),
+    ("agent", Operation::Agent),
+    ("unlock", Operation::Unlock),
+    ("lock", Operation::Lock),
+    ("status", Operation::Status),
+    ("exec", Operation::Exec),
+    ("render", Operation::Render),
+    ("completions", Operation::Completions),
+    ("help", Operation::Help),
+];
+
+/// The words of the resources.
+pub const RESOURCES: &[(&str, Resource)] = &[
+    ("password", Resource::Password),
+    ("vault", Resource::Vault),
+    ("csv", Resource::Csv),
+    ("keepass", Resource::Keepass),
+    ("pass", Resource::Pass),
+    ("1pux", Resource::OnePux),
+    ("paper", Resource::Paper),
+    ("preference", Resource::Preference),
+    ("key", Resource::Key),
+    ("config", Resource::Config),
+    ("note", Resource::Note),
+    ("domain", Resource::Domain),
+    ("alias", Resource::Alias),
+    ("token", Resource::Token),
+    ("login", Resource::Login),
+    ("verify", Resource::Verify),
+];
+
+/// Params that take a value, as `--key=value` or `--key value`.
+/// Names that are prefixes of others come after them.
+pub const LONG_PARAMS: &[(&str, ParamName)] = &[
+    ("name", ParamName::VaultName),
+    ("domain", ParamName::DomainName),
+    ("username", ParamName::UserName),
+    ("length", ParamName::Length),
+    ("default-length", ParamName::DefaultLength),
+    ("default-username", ParamName::DefaultUsername),
+    ("output", ParamName::Output),
+    ("version", ParamName::Version),
+    ("format", ParamName::Format),
+    ("out", ParamName::OutFile),
+    ("filter", ParamName::Filter),
+    ("gpg-id", ParamName::GpgId),
+    ("age", ParamName::Age),
+    ("env", ParamName::Env),
+    ("clear-after", ParamName::ClearAfter),
+    ("lock-after", ParamName::LockAfter),
+    ("key-fd", ParamName::KeyFd),
+    ("limit", ParamName::Limit),
+    ("offset", ParamName::Offset),
+    ("profile", ParamName::Profile),
+    ("rotate-every", ParamName::RotateEvery),
+    ("from", ParamName::From),
+    ("to", ParamName::To),
+    ("prefix", ParamName::Prefix),
+    ("encoding", ParamName::Encoding),
+    ("preset", ParamName::Preset),
+    ("wordlist", ParamName::Wordlist),
+    ("min-entropy", ParamName::MinEntropy),
+    ("masked-email-service", ParamName::MaskedEmailService),
+    ("recipe", ParamName::Recipe),
+    ("sort", ParamName::Sort),
+    ("hint", ParamName::Hint),
+    ("tag", ParamName::Tag),
+    ("vault", ParamName::VaultName),
+    ("group-sep", ParamName::GroupSep),
+    ("group", ParamName::Group),
+    ("rollback", ParamName::Rollback),
+];
+
+/// Params that take a value, as `-key value` or `-key=value`.
+pub const SHORT_PARAMS: &[(&str, ParamName)] = &[
+    ("n", ParamName::VaultName),
+    ("d", ParamName::DomainName),
+    ("u", ParamName::UserName),
+    ("l", ParamName::Length),
+    ("o", ParamName::OutFile),
+];
+
+/// Params that take a number, the others take text.
+pub const NUMBER_PARAMS: &[ParamName] = &[
+    ParamName::Length,
+    ParamName::Version,
+    ParamName::ClearAfter,
+    ParamName::Limit,
+    ParamName::Offset,
+    ParamName::Group,
+    ParamName::Rollback,
+    ParamName::MinEntropy,
+    ParamName::DefaultLength,
+    ParamName::LockAfter,
+];
+
+/// Params without a value, as `--flag` or `--no-flag`.
+pub const FLAGS: &[(&str, ParamName)] = &[
+    ("hibp", ParamName::Hibp),
+    ("entropy", ParamName::Entropy),
+    ("masked-email", ParamName::MaskedEmail),
+    ("ignore-rules", ParamName::IgnoreRules),
+    ("unambiguous", ParamName::Unambiguous),
+    ("spell", ParamName::Spell),
+    ("type", ParamName::Type),
+    ("help", ParamName::Help),
+    ("key-stdin", ParamName::KeyStdin),
+    ("stdout", ParamName::Stdout),
+    ("quiet", ParamName::Quiet),
+    ("verbose", ParamName::Verbose),
+    ("yes", ParamName::Yes),
+    ("dry-run", ParamName::DryRun),
+    ("due", ParamName::Due),
+    ("usernames", ParamName::Usernames),
+    ("long", ParamName::Long),
+    ("local-only", ParamName::LocalOnly),
+    ("truncate", ParamName::Truncate),
+    ("show", ParamName::Show),
+    ("notify", ParamName::Notify),
+    ("include-archived", ParamName::IncludeArchived),
+    ("reverse", ParamName::Reverse),
+    ("match-subdomains", ParamName::MatchSubdomains),
+];
+
+/// Flags that are also given as `-flag`, they can't be negated that way.
+pub const SHORT_FLAGS: &[(&str, ParamName)] = &[
+    ("q", ParamName::Quiet),
+    ("v", ParamName::Verbose),
+    ("vv", ParamName::Trace),
+    ("y", ParamName::Yes),
+];
+
+/// Where and why a command line could not be parsed.
+#[derive(Debug, PartialEq)]
+pub struct ParseError {
+    // input is the command line as it was parsed
+    pub input: String,
+    // position is the byte offset of the first argument that could not be parsed
+    pub position: usize,
+    // expected are the tokens that would have been accepted at the position
+    pub expected: Vec<String>,
+}
+
+impl ParseError {
+    /// Returns the argument that could not be parsed, empty if the input ended early.
+    pub fn argument(&self) -> &str {
+        self.input[self.position..].split(' ').next().unwrap_or("")
+    }
+
+    /// Returns the command line with the offending argument underlined.
+    pub fn highlight(&self) -> String {
+        let indent = self.input[..self.position].chars().count();
+        let width = self.argument().chars().count().max(1);
+        format!(
+            "  {}\n  {}{}",
+            self.input,
+            " ".repeat(indent),
+            "^".repeat(width)
+        )
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let expected = match &self.expected[..] {
+            [one] => one.clone(),
+            many => format!("one of: {}", many.join(", ")),
+        };
+        match self.argument() {
+            "" => write!(f, "Expected {}", expected),
+            argument => write!(
+                f,
+                "Unexpected `{}`, expected {}{}",
+                argument,
+                expected,
+                suggest::did_you_mean(argument, self.expected.iter().map(|e| e.as_str()))
+            ),
+        }
+    }
+}
+
+impl error::Error for ParseError {}
+
+/// Params that can be given as positional args, in the order of the args.
+/// A param given as a flag is skipped, so the next arg fills the next param.
+/// The params take text.
+pub const POSITIONAL_PARAMS: &[(Operation, Resource, &[ParamName])] = &[
+    (
+        Operation::Get,
+        Resource::Password,
+        &[ParamName::DomainName, ParamName::UserName],
+    ),
+    (
+        Operation::Get,
+        Resource::Alias,
+        &[ParamName::DomainName, ParamName::UserName],
+    ),
+    (
+        Operation::Get,
+        Resource::Login,
+        &[ParamName::DomainName, ParamName::UserName],
+    ),
+    (
+        Operation::Get,
+        Resource::Preference,
+        &[ParamName::DomainName, ParamName::UserName],
+    ),
+    (
+        Operation::Delete,
+        Resource::Password,
+        &[ParamName::DomainName, ParamName::UserName],
+    ),
+    (
+        Operation::Update,
+        Resource::Password,
+        &[ParamName::DomainName, ParamName::UserName],
+    ),
+    (
+        Operation::Set,
+        Resource::Password,
+        &[ParamName::DomainName, ParamName::UserName],
+    ),
+    (Operation::Add, Resource::Vault, &[ParamName::VaultName]),
+];
+
+/// Returns true if the word is a built-in operation, other words may be plugins.
+pub fn is_operation(word: &str) -> bool {
+    (operation() - end()).parse(word.as_bytes()).is_ok()
 }
 
 /// Parses a slice of strings into a Command
-pub fn parse(input: &[String]) -> Result<Command, String> {
-    let input = input.join(" ");
-    command(&input)
+pub fn parse(input: &[String]) -> Result<Command, ParseError> {
+    let (input, rest) = match input.iter().position(|a| a == "--") {
+        Some(i) => (&input[..i], input[i + 1..].to_vec()),
+        None => (input, Vec::new()),
+    };
+    let input: Vec<String> = input.iter().map(|a| quote(a)).collect();
+    command(&input.join(" "), rest)
+}
+
+/// Parses a line such as `add password -d example.com`, e.g. of a batch file.
+/// Values are quoted as they are in the shell, everything after ` -- ` is split at spaces.
+pub fn parse_line(line: &str) -> Result<Command, ParseError> {
+    match line.split_once(" -- ") {
+        Some((input, rest)) => command(
+            input.trim(),
+            rest.split_whitespace().map(String::from).collect(),
+        ),
+        None => command(line.trim(), Vec::new()),
+    }
+}
+
+/// Quotes an argument the shell already split, so its spaces survive the join.
+/// The value of `--key=value` or `-key=value` is quoted on its own.
+fn quote(arg: &str) -> String {
+    if !arg.is_empty() && !arg.contains(' ') && !arg.starts_with(&['"', '\''][..]) {
+        return arg.to_owned();
+    }
+    let quoted = |s: &str| format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""));
+    match arg.find('=') {
+        Some(i) if arg.starts_with('-') => format!("{}={}", &arg[..i], quoted(&arg[i + 1..])),
+        _ => quoted(arg),
+    }
 }
 
 use pom::parser::Parser;
 use pom::parser::*;
 
-fn param_long<'a>(name: &'a str) -> Parser<'a, u8, String> {
-    let key = seq(b"--") + seq(name.as_bytes()) + sym(b'=');
-    let value = none_of(b" ").repeat(1..).convert(|s| String::from_utf8(s));
-    key * value
+/// Returns the value and where it starts.
+fn param_long<'a>(name: &'a str) -> Parser<'a, u8, (usize, String)> {
+    let key = seq(b"--") + seq(name.as_bytes());
+    let separator = sym(b'=').discard() | sym(b' ').repeat(1..).discard();
+    key * separator * (empty().pos() + value())
 }
 
-fn param_short<'a>(name: &'a str) -> Parser<'a, u8, String> {
+/// Returns the value and where it starts.
+fn param_short<'a>(name: &'a str) -> Parser<'a, u8, (usize, String)> {
     let key = seq(b"-") + seq(name.as_bytes());
-    let space = sym(b' ').repeat(1..);
-    let value = none_of(b" ").repeat(1..).convert(|s| String::from_utf8(s));
-    key * space * value
+    let separator = sym(b'=').discard() | sym(b' ').repeat(1..).discard();
+    key * separator * (empty().pos() + value())
+}
+
+/// Returns false if the flag is negated.
+fn flag<'a>(name: &'a str) -> Parser<'a, u8, bool> {
+    let key = seq(b"--") * seq(b"no-").opt() - seq(name.as_bytes());
+    (key - boundary()).map(|negated| negated.is_none())
+}
+
+fn short_flag<'a>(name: &'a str) -> Parser<'a, u8, ()> {
+    (seq(b"-") + seq(name.as_bytes()) - boundary()).discard()
+}
+
+/// A value up to the next space, or a quoted value that may contain spaces.
+fn value<'a>() -> Parser<'a, u8, String> {
+    let bare = none_of(b" \"'") + none_of(b" ").repeat(0..);
+    let bare = bare.collect().map(|s| s.to_vec());
+    (quoted(b'"') | quoted(b'\'') | bare).convert(String::from_utf8)
+}
+
+/// A value between quotes, a backslash escapes the next character.
+fn quoted<'a>(quote: u8) -> Parser<'a, u8, Vec<u8>> {
+    let escaped = sym(b'\\') * any();
+    let plain = is_a(move |c| c != quote && c != b'\\');
+    sym(quote) * (escaped | plain).repeat(0..) - sym(quote) - boundary()
+}
+
+fn positional<'a>() -> Parser<'a, u8, String> {
+    let bare = none_of(b" -\"'") + none_of(b" ").repeat(0..);
+    let bare = bare.collect().map(|s| s.to_vec());
+    // a lone `-` stands for stdin
+    let dash = (sym(b'-') - boundary()).map(|c| vec![c]);
+    let value = (quoted(b'"') | quoted(b'\'') | dash | bare).convert(String::from_utf8);
+    space() * value - space()
+}
+
+fn word<'a>(w: &'a [u8]) -> Parser<'a, u8, &'a [u8]> {
+    seq(w) - boundary()
+}
+
+fn boundary<'a>() -> Parser<'a, u8, bool> {
+    -(sym(b' ').discard() | end())
 }
 
 fn space<'a>() -> Parser<'a, u8, ()> {
     sym(b' ').repeat(0..).discard()
 }
 
-fn param<'a>() -> Parser<'a, u8, (ParamName, String)> {
-    space() * {
-        param_long("name").map(|v| (ParamName::VaultName, v))
-            | param_long("domain").map(|v| (ParamName::DomainName, v))
-            | param_long("username").map(|v| (ParamName::UserName, v))
-            | param_long("length").map(|v| (ParamName::Length, v))
-            | param_short("n").map(|v| (ParamName::VaultName, v))
-            | param_short("d").map(|v| (ParamName::DomainName, v))
-            | param_short("u").map(|v| (ParamName::UserName, v))
-            | param_short("l").map(|v| (ParamName::Length, v))
-    } - space()
+fn param<'a>() -> Parser<'a, u8, Arg> {
+    let long = LONG_PARAMS
+        .iter()
+        .map(|&(n, p)| param_long(n).map(move |(at, v)| Arg::Param(p, at, v)));
+    let flags = FLAGS
+        .iter()
+        .map(|&(n, p)| flag(n).map(move |on| Arg::Flag(p, on)));
+    let short = SHORT_PARAMS
+        .iter()
+        .map(|&(n, p)| param_short(n).map(move |(at, v)| Arg::Param(p, at, v)));
+    let short_flags = SHORT_FLAGS
+        .iter()
+        .map(|&(n, p)| short_flag(n).map(move |_| Arg::Flag(p, true)));
+    space() * one_of(long.chain(flags).chain(short).chain(short_flags)) - space()
 }
 
 fn operation<'a>() -> Parser<'a, u8, Operation> {
-    let op = seq(b"add").map(|_| Operation::Add) | seq(b"get").map(|_| Operation::Get);
-    space() * op - space()
+    let op = OPERATIONS
+        .iter()
+        .map(|&(w, o)| word(w.as_bytes()).map(move |_| o));
+    space() * one_of(op) - space()
 }
 
 fn resource<'a>() -> Parser<'a, u8, Resource> {
-    let re = seq(b"password").map(|_| Resource::Password) | seq(b"vault").map(|_| Resource::Vault);
-    space() * re - space()
+    let re = RESOURCES
+        .iter()
+        .map(|&(w, r)| word(w.as_bytes()).map(move |_| r));
+    space() * one_of(re) - space()
+}
+
+/// Tries the parsers in order, the tables they come from are never empty.
+fn one_of<'a, O: 'a>(parsers: impl Iterator<Item = Parser<'a, u8, O>>) -> Parser<'a, u8, O> {
+    parsers
+        .reduce(|a, b| a | b)
+        .expect("a table of the grammar is empty")
+}
+
+/// A param with the position of its value, a flag or a positional argument.
+enum Arg {
+    Param(ParamName, usize, String),
+    Flag(ParamName, bool),
+    Positional(String),
 }
 
-fn params<'a>() -> Parser<'a, u8, Vec<(ParamName, String)>> {
-    param().repeat(0..)
+fn args<'a>() -> Parser<'a, u8, Vec<Arg>> {
+    let arg = param() | positional().map(Arg::Positional);
+    arg.repeat(0..)
 }
 
-fn command(input: &str) -> Result<Command, String> {
-    let ((op, on), ps) = { operation() + resource() + params() }
+/// Converts the value to the type the param takes, or returns what was expected instead.
+fn typed(name: ParamName, value: String) -> Result<ParamValue, String> {
+    if !NUMBER_PARAMS.contains(&name) {
+        return Ok(ParamValue::Text(value));
+    }
+    value.parse().map(ParamValue::Number).map_err(|_| {
+        let word = LONG_PARAMS
+            .iter()
+            .find(|(_, p)| *p == name)
+            .map_or("", |(w, _)| w);
+        format!("a number for `--{}`", word)
+    })
+}
+
+/// Returns the params as they are written on the command line.
+fn param_words() -> Vec<String> {
+    let long = LONG_PARAMS.iter().map(|(n, _)| format!("--{}", n));
+    let flags = FLAGS
+        .iter()
+        .flat_map(|(n, _)| vec![format!("--{}", n), format!("--no-{}", n)]);
+    let short = SHORT_PARAMS
+        .iter()
+        .chain(SHORT_FLAGS)
+        .map(|(n, _)| format!("-{}", n));
+    long.chain(flags).chain(short).collect()
+}
+
+/// Parses the command line, the words after `--` are taken literally.
+fn command(input: &str, literal: Vec<String>) -> Result<Command, ParseError> {
+    let error = |position, expected| ParseError {
+        input: input.to_owned(),
+        position,
+        expected,
+    };
+    let start = space().pos().parse(input.as_bytes()).unwrap_or(0);
+    let (((op, on), parsed), end) = { operation() + resource().opt() + args() + empty().pos() }
         .parse(input.as_bytes())
+        .map_err(|_| {
+            error(
+                start,
+                OPERATIONS.iter().map(|(w, _)| w.to_string()).collect(),
+            )
+        })?;
+    if end < input.len() {
+        let words = param_words();
+        let argument = input[end..].split(' ').next().unwrap_or("");
+        return Err(match words.iter().find(|w| *w == argument) {
+            // a param that takes a value, given as the last argument
+            Some(word) => error(input.len(), vec![format!("a value for `{}`", word)]),
+            None => error(end, words),
+        });
+    }
+    let mut params = Params::new();
+    let mut args = Vec::new();
+    for arg in parsed {
+        match arg {
+            Arg::Param(name, at, value) => {
+                let value = typed(name, value).map_err(|expected| error(at, vec![expected]))?;
+                params.insert(name, value);
+            }
+            Arg::Flag(name, on) => params.insert(name, ParamValue::Flag(on)),
+            Arg::Positional(value) => args.push(value),
+        }
+    }
+    let rest = match op {
+        Operation::Exec => literal,
+        _ => {
+            args.extend(literal);
+            Vec::new()
+        }
+    };
+    // positional args fill, in order, the params that weren't given as flags
+    if let Some((_, _, names)) = POSITIONAL_PARAMS
+        .iter()
+        .find(|(o, r, _)| *o == op && Some(*r) == on)
+    {
+        for name in names.iter() {
+            if !params.has(*name) && !args.is_empty() {
+                params.insert(*name, ParamValue::Text(args.remove(0)));
+            }
+        }
+    }
+
+    Ok(Command {
+        op,
+        on,
+        params,
+        args,
+        rest,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(input: &str) -> Vec<String> {
+        input.split(' ').map(|s| s.to_owned()).collect()
+    }
+
+    #[test]
+    fn long_params_with_or_without_equals() {
+        for input in &[
+            "get password --domain=example.com --version=2",
+            "get password --domain example.com --version 2",
+            "get password -d example.com --version 2",
+        ] {
+            let cmd = parse(&args(input)).unwrap();
+            assert_eq!(cmd.params.text(ParamName::DomainName), Some("example.com"));
+            assert_eq!(cmd.params.number(ParamName::Version), Some(2));
+        }
+    }
+
+    #[test]
+    fn positional_args() {
+        let cmd = parse(&args("import csv passwords.csv --format lastpass")).unwrap();
+
+        assert!(matches!(cmd.op, Operation::Import));
+        assert!(matches!(cmd.on, Some(Resource::Csv)));
+        assert_eq!(cmd.args, vec!["passwords.csv"]);
+        assert_eq!(cmd.params.text(ParamName::Format), Some("lastpass"));
+    }
+
+    #[test]
+    fn batch_lines() {
+        let cmd = parse(&args("batch - --yes")).unwrap();
+        assert_eq!(cmd.args, vec!["-"]);
+        assert!(cmd.params.flag(ParamName::Yes));
+
+        let cmd = parse_line("  add password -d 'my site' -u alice -- x y").unwrap();
+        assert_eq!(cmd.params.text(ParamName::DomainName), Some("my site"));
+        assert_eq!(cmd.params.text(ParamName::UserName), Some("alice"));
+        assert_eq!(cmd.args, vec!["x", "y"]);
+
+        let mut params = Params::new();
+        params.insert(ParamName::Yes, ParamValue::Flag(false));
+        let mut batch = Params::new();
+        batch.insert(ParamName::Yes, ParamValue::Flag(true));
+        batch.insert(ParamName::Quiet, ParamValue::Flag(true));
+        params.merge(&batch);
+        assert!(!params.flag(ParamName::Yes));
+        assert!(params.flag(ParamName::Quiet));
+    }
+
+    #[test]
+    fn short_flags() {
+        let cmd = parse(&args("get password -d example.com --stdout -q")).unwrap();
+        assert!(cmd.params.flag(ParamName::Stdout));
+        assert!(cmd.params.flag(ParamName::Quiet));
+        assert!(cmd.args.is_empty());
+
+        let cmd = parse(&args("get password -vv example.com")).unwrap();
+        assert!(cmd.params.flag(ParamName::Trace));
+        assert!(!cmd.params.flag(ParamName::Verbose));
+    }
+
+    #[test]
+    fn built_in_operations() {
+        assert!(is_operation("get"));
+        assert!(is_operation("validate"));
+        assert!(!is_operation("getter"));
+        assert!(!is_operation("sync"));
+    }
+
+    #[test]
+    fn rest_after_double_dash() {
+        let cmd = parse(&args(
+            "exec -d api.example --env API_TOKEN -- curl -d x --env",
+        ))
         .unwrap();
-    let mut params = HashMap::new();
-    for (k, v) in ps {
-        params.insert(k, v);
+
+        assert!(matches!(cmd.op, Operation::Exec));
+        assert_eq!(cmd.params.text(ParamName::DomainName), Some("api.example"));
+        assert_eq!(cmd.params.text(ParamName::Env), Some("API_TOKEN"));
+        assert_eq!(cmd.rest, vec!["curl", "-d", "x", "--env"]);
+    }
+
+    #[test]
+    fn short_params_with_equals() {
+        let cmd = parse(&args("get password -d=example.com -u=alice -l=40")).unwrap();
+
+        assert_eq!(cmd.params.text(ParamName::DomainName), Some("example.com"));
+        assert_eq!(cmd.params.text(ParamName::UserName), Some("alice"));
+        assert_eq!(cmd.params.number(ParamName::Length), Some(40));
+    }
+
+    #[test]
+    fn literal_args_after_double_dash() {
+        let cmd = parse(&args("get password -u alice -- -example.com")).unwrap();
+        assert_eq!(cmd.params.text(ParamName::DomainName), Some("-example.com"));
+        assert!(cmd.rest.is_empty());
+
+        let cmd = parse(&args("import csv -- --passwords.csv")).unwrap();
+        assert_eq!(cmd.args, vec!["--passwords.csv"]);
+    }
+
+    #[test]
+    fn negated_flags() {
+        let cmd = parse(&args("get password -d example.com --no-spell --type")).unwrap();
+
+        assert!(cmd.params.has(ParamName::Spell));
+        assert!(!cmd.params.flag(ParamName::Spell));
+        assert!(!cmd.params.flag_or(ParamName::Spell, true));
+        assert!(cmd.params.flag(ParamName::Type));
+        assert!(cmd.params.flag_or(ParamName::Unambiguous, true));
+        assert!(parse(&args("get password --no-domain example.com")).is_err());
     }
 
-    Ok(Command { op, on, params })
+    #[test]
+    fn positional_params() {
+        for input in &[
+            "get password example.com alice",
+            "get password -d example.com alice",
+            "get password -u alice example.com",
+            "get password -u alice -d example.com",
+        ] {
+            let cmd = parse(&args(input)).unwrap();
+            assert_eq!(cmd.params.text(ParamName::DomainName), Some("example.com"));
+            assert_eq!(cmd.params.text(ParamName::UserName), Some("alice"));
+            assert!(cmd.args.is_empty(), "{}", input);
+        }
+
+        let cmd = parse(&args("get password -d example.com -u alice extra")).unwrap();
+        assert_eq!(cmd.args, vec!["extra"]);
+    }
+
+    #[test]
+    fn quoted_values() {
+        for (input, username) in &[
+            (r#"get password -u "first last""#, "first last"),
+            (r#"get password --username='first last'"#, "first last"),
+            (r#"get password --username "say \"hi\"""#, r#"say "hi""#),
+            (r#"get password -u 'it\'s'"#, "it's"),
+            (r#"get password -u "back\\slash""#, r"back\slash"),
+            (r#"get password -u it's"#, "it's"),
+            (r#"get password -u """#, ""),
+        ] {
+            let cmd = command(input, Vec::new()).unwrap();
+            assert_eq!(
+                cmd.params.text(ParamName::UserName),
+                Some(*username),
+                "{}",
+                input
+            );
+        }
+
+        let cmd = command(r#"import csv "my passwords.csv" -n 'a b'"#, Vec::new()).unwrap();
+        assert_eq!(cmd.args, vec!["my passwords.csv"]);
+        assert_eq!(cmd.params.text(ParamName::VaultName), Some("a b"));
+
+        assert!(command(r#"get password -u "unclosed"#, Vec::new()).is_err());
+        assert!(command(r#"get password -u "a"b"#, Vec::new()).is_err());
+    }
+
+    #[test]
+    fn shell_split_args_keep_their_spaces() {
+        let input: Vec<String> = vec!["get", "password", "--username=first last", "-d", "\"x\\"]
+            .into_iter()
+            .map(String::from)
+            .collect();
+        let cmd = parse(&input).unwrap();
+
+        assert_eq!(cmd.params.text(ParamName::UserName), Some("first last"));
+        assert_eq!(cmd.params.text(ParamName::DomainName), Some("\"x\\"));
+    }
+
+    #[test]
+    fn errors_point_at_the_argument() {
+        let err = parse(&args("get password --lenght 3")).err().unwrap();
+        assert_eq!(err.position, 13);
+        assert_eq!(err.argument(), "--lenght");
+        assert!(err.expected.contains(&"--length".to_owned()));
+        assert!(err.highlight().ends_with("\n               ^^^^^^^^"));
+
+        let err = parse(&args("get password -d")).err().unwrap();
+        assert_eq!(err.argument(), "");
+        assert_eq!(err.to_string(), "Expected a value for `-d`");
+
+        let err = parse(&args("get password -d example.com --length=abc"))
+            .err()
+            .unwrap();
+        assert_eq!(err.argument(), "abc");
+        assert_eq!(
+            err.to_string(),
+            "Unexpected `abc`, expected a number for `--length`"
+        );
+
+        let err = parse(&args("fetch password")).err().unwrap();
+        assert_eq!(err.position, 0);
+        assert!(err.expected.contains(&"get".to_owned()));
+    }
 }
@@ -0,0 +1,154 @@
+//! # Pinentry
+//! Asks for keys through pinentry when there is no terminal to read them from, e.g. when zpass
+//! is started by rofi or a browser. pinentry speaks the Assuan protocol on its stdin and stdout:
+//! each request is a line, answered by data lines (`D`) and a final `OK` or `ERR`.
+
+use std::env;
+use std::error;
+use std::fmt;
+use std::io::{self, BufRead, BufReader, Write};
+use std::process::{Command, Stdio};
+
+/// Environment variable that holds the pinentry program, e.g. `pinentry-gnome3`.
+pub const PINENTRY_VARIABLE: &str = "ZPASS_PINENTRY";
+/// Program used when the variable isn't set.
+const DEFAULT_PINENTRY: &str = "pinentry";
+/// Title of the pinentry window.
+const TITLE: &str = "zpass";
+
+#[derive(Debug)]
+pub enum PinentryError {
+    IOError(io::Error),
+    Cancelled,
+    Failed(String),
+}
+
+impl fmt::Display for PinentryError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::IOError(ref err) => write!(f, "Failed to run pinentry:\n{}", err),
+            Self::Cancelled => write!(f, "The key prompt was cancelled"),
+            Self::Failed(ref reason) => write!(f, "pinentry failed:\n{}", reason),
+        }
+    }
+}
+
+impl error::Error for PinentryError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Self::IOError(ref err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for PinentryError {
+    fn from(err: io::Error) -> Self {
+        PinentryError::IOError(err)
+    }
+}
+
+/// Shows a pinentry dialog with the prompt and returns what was entered.
+pub fn get_pin(prompt: &str) -> Result<String, PinentryError> {
+    let program = env::var(PINENTRY_VARIABLE).unwrap_or_else(|_| DEFAULT_PINENTRY.to_owned());
+    let mut child = Command::new(program)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()?;
+    let (mut stdin, stdout) = match (child.stdin.take(), child.stdout.take()) {
+        (Some(stdin), Some(stdout)) => (stdin, stdout),
+        _ => return Err(PinentryError::Failed("no pipes to pinentry".to_owned())),
+    };
+    let mut replies = BufReader::new(stdout);
+
+    let result = (|| {
+        read_reply(&mut replies)?;
+        for request in &[
+            format!("SETTITLE {}", encode(TITLE)),
+            format!("SETPROMPT {}", encode(prompt)),
+        ] {
+            writeln!(stdin, "{}", request)?;
+            read_reply(&mut replies)?;
+        }
+        writeln!(stdin, "GETPIN")?;
+        read_reply(&mut replies)
+    })();
+    let _ = writeln!(stdin, "BYE");
+    drop(stdin);
+    child.wait()?;
+    result
+}
+
+/// Reads the reply to a request and returns its data.
+fn read_reply<R: BufRead>(replies: &mut R) -> Result<String, PinentryError> {
+    let mut data = String::new();
+    loop {
+        let mut line = String::new();
+        if replies.read_line(&mut line)? == 0 {
+            return Err(PinentryError::Failed("pinentry closed the pipe".to_owned()));
+        }
+        let line = line.trim_end_matches(&['\r', '\n'][..]);
+        if line == "OK" || line.starts_with("OK ") {
+            return Ok(data);
+        } else if let Some(d) = line.strip_prefix("D ") {
+            data.push_str(&decode(d));
+        } else if let Some(err) = line.strip_prefix("ERR ") {
+            if err.to_lowercase().contains("cancel") {
+                return Err(PinentryError::Cancelled);
+            }
+            return Err(PinentryError::Failed(err.to_owned()));
+        }
+        // status (S) and comment (#) lines are ignored
+    }
+}
+
+/// Percent-escapes the characters that would end an Assuan line.
+fn encode(text: &str) -> String {
+    text.replace('%', "%25")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+}
+
+/// Undoes the percent-escapes of a data line.
+fn decode(data: &str) -> String {
+    let bytes = data.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = match (bytes[i], data.get(i + 1..i + 3)) {
+            (b'%', Some(hex)) => u8::from_str_radix(hex, 16).ok(),
+            _ => None,
+        };
+        match escaped {
+            Some(b) => {
+                decoded.push(b);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_replies() {
+        let mut replies = "OK Pleased to meet you\nS PASSWORD_FROM_CACHE\nD p%25ss%0Aword\nOK\n\
+                           ERR 83886179 Operation cancelled <Pinentry>\n"
+            .as_bytes();
+
+        assert_eq!(read_reply(&mut replies).unwrap(), "");
+        assert_eq!(read_reply(&mut replies).unwrap(), "p%ss\nword");
+        assert!(matches!(
+            read_reply(&mut replies),
+            Err(PinentryError::Cancelled)
+        ));
+        assert_eq!(encode("50%\n"), "50%25%0A");
+    }
+}
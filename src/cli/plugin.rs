@@ -0,0 +1,85 @@
+//! # Plugin
+//! Commands zpass doesn't know are looked up as executables on PATH, as git and cargo do:
+//! `zpass foo --bar` runs `zpass-foo --bar`. Plugins get the location of the vaults in
+//! `ZPASS_ROOT` and the vault selected with `-n` or `--name`, if any, in `ZPASS_VAULT`.
+
+use crate::safe::constants;
+use std::env;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Prefix of the executables that implement plugin commands.
+const PREFIX: &str = "zpass-";
+/// Environment variable that holds the directory of the vaults.
+pub const ROOT_VARIABLE: &str = "ZPASS_ROOT";
+/// Environment variable that holds the selected vault.
+pub const VAULT_VARIABLE: &str = "ZPASS_VAULT";
+
+/// Returns the executable that implements the command, if there is one on PATH.
+pub fn find(command: &str) -> Option<PathBuf> {
+    if command.is_empty() || command.starts_with('-') || command.contains(&['/', '\\'][..]) {
+        return None;
+    }
+    let name = format!("{}{}", PREFIX, command);
+    env::split_paths(&env::var_os("PATH")?)
+        .map(|dir| dir.join(&name))
+        .find(|path| is_executable(path))
+}
+
+/// Runs the plugin with the arguments and returns its exit code.
+pub fn run(plugin: &Path, args: &[String]) -> io::Result<i32> {
+    let mut command = Command::new(plugin);
+    command
+        .args(args)
+        .env(ROOT_VARIABLE, env::current_dir()?.join(constants::root()));
+    if let Some(vault) = selected_vault(args) {
+        command.env(VAULT_VARIABLE, vault);
+    }
+    Ok(command.status()?.code().unwrap_or(1))
+}
+
+/// Returns the value of `-n`, `--name` or `--name=`, the way the built-in commands select a vault.
+fn selected_vault(args: &[String]) -> Option<&str> {
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if let Some(name) = arg.strip_prefix("--name=") {
+            return Some(name);
+        }
+        if arg == "-n" || arg == "--name" {
+            return args.next().map(|n| &n[..]);
+        }
+    }
+    None
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    path.metadata()
+        .map(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+        .unwrap_or(false)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn forwards_the_selected_vault() {
+        let args = |input: &str| -> Vec<String> { input.split(' ').map(String::from).collect() };
+
+        assert_eq!(selected_vault(&args("sync -n work")), Some("work"));
+        assert_eq!(
+            selected_vault(&args("sync --name=work --dry-run")),
+            Some("work")
+        );
+        assert_eq!(selected_vault(&args("sync --dry-run")), None);
+        assert_eq!(find("../zpass"), None);
+    }
+}
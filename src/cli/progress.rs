@@ -0,0 +1,163 @@
+//! # Progress
+//! A spinner on stderr while zpass unlocks a vault, so a slow key derivation doesn't look like a
+//! hang, and a bar for work with a known number of steps such as an import. They only show up
+//! when the work takes longer than a moment and stderr is a terminal, and the line is cleared
+//! again when the work is done.
+
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// Work that is done sooner shows no spinner.
+const DELAY: Duration = Duration::from_millis(200);
+/// Time between the frames of the spinner.
+const TICK: Duration = Duration::from_millis(80);
+/// The frames of the spinner.
+const FRAMES: &[char] = &['|', '/', '-', '\\'];
+/// Characters of a full bar.
+const BAR_WIDTH: usize = 30;
+
+/// # Status
+/// A spinner with a message that runs until the status is dropped.
+pub struct Status {
+    // done tells the spinner to stop
+    done: Arc<AtomicBool>,
+    // spinner is the thread that draws the spinner, None if nothing is shown
+    spinner: Option<JoinHandle<()>>,
+}
+
+impl Status {
+    /// Starts a spinner with the message, unless stderr isn't a terminal.
+    pub fn start(message: &str) -> Status {
+        if !atty::is(atty::Stream::Stderr) {
+            return Status::hidden();
+        }
+        let done = Arc::new(AtomicBool::new(false));
+        let stop = done.clone();
+        let message = message.to_owned();
+        let spinner = thread::spawn(move || spin(&message, &stop));
+        Status {
+            done,
+            spinner: Some(spinner),
+        }
+    }
+
+    /// Returns a status that shows nothing, e.g. for --quiet.
+    pub fn hidden() -> Status {
+        Status {
+            done: Arc::new(AtomicBool::new(true)),
+            spinner: None,
+        }
+    }
+}
+
+impl Drop for Status {
+    fn drop(&mut self) {
+        self.done.store(true, Ordering::SeqCst);
+        if let Some(spinner) = self.spinner.take() {
+            let _ = spinner.join();
+        }
+    }
+}
+
+/// # Bar
+/// A bar of the finished steps of some work that runs until the bar is dropped.
+pub struct Bar {
+    // done tells the bar to stop
+    done: Arc<AtomicBool>,
+    // count is the number of finished steps, the work adds to it
+    count: Arc<AtomicUsize>,
+    // drawer is the thread that draws the bar, None if nothing is shown
+    drawer: Option<JoinHandle<()>>,
+}
+
+impl Bar {
+    /// Starts a bar of the steps with the message, unless stderr isn't a terminal.
+    pub fn start(message: &str, total: usize) -> Bar {
+        if !atty::is(atty::Stream::Stderr) {
+            return Bar::hidden();
+        }
+        let done = Arc::new(AtomicBool::new(false));
+        let count = Arc::new(AtomicUsize::new(0));
+        let (stop, finished) = (done.clone(), count.clone());
+        let message = message.to_owned();
+        let drawer = thread::spawn(move || draw(&message, total, &finished, &stop));
+        Bar {
+            done,
+            count,
+            drawer: Some(drawer),
+        }
+    }
+
+    /// Returns a bar that shows nothing, e.g. for --quiet.
+    pub fn hidden() -> Bar {
+        Bar {
+            done: Arc::new(AtomicBool::new(true)),
+            count: Arc::new(AtomicUsize::new(0)),
+            drawer: None,
+        }
+    }
+
+    /// Returns the counter the work adds its finished steps to.
+    pub fn count(&self) -> &AtomicUsize {
+        &self.count
+    }
+}
+
+impl Drop for Bar {
+    fn drop(&mut self) {
+        self.done.store(true, Ordering::SeqCst);
+        if let Some(drawer) = self.drawer.take() {
+            let _ = drawer.join();
+        }
+    }
+}
+
+/// Draws the bar after the delay until it is told to stop, then clears the line.
+fn draw(message: &str, total: usize, count: &AtomicUsize, done: &AtomicBool) {
+    let start = Instant::now();
+    let mut drawn = false;
+    while !done.load(Ordering::SeqCst) {
+        if start.elapsed() >= DELAY {
+            let finished = count.load(Ordering::Relaxed).min(total);
+            let filled = (finished * BAR_WIDTH)
+                .checked_div(total)
+                .unwrap_or(BAR_WIDTH);
+            eprint!(
+                "\r{} [{}{}] {}/{}",
+                message,
+                "#".repeat(filled),
+                " ".repeat(BAR_WIDTH - filled),
+                finished,
+                total
+            );
+            let _ = io::stderr().flush();
+            drawn = true;
+        }
+        thread::sleep(TICK);
+    }
+    if drawn {
+        eprint!("\r\x1b[K");
+        let _ = io::stderr().flush();
+    }
+}
+
+/// Draws the spinner after the delay until it is told to stop, then clears the line.
+fn spin(message: &str, done: &AtomicBool) {
+    let start = Instant::now();
+    let mut frame = 0;
+    while !done.load(Ordering::SeqCst) {
+        if start.elapsed() >= DELAY {
+            eprint!("\r{} {}", FRAMES[frame % FRAMES.len()], message);
+            let _ = io::stderr().flush();
+            frame += 1;
+        }
+        thread::sleep(TICK);
+    }
+    if frame > 0 {
+        eprint!("\r\x1b[K");
+        let _ = io::stderr().flush();
+    }
+}
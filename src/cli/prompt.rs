@@ -0,0 +1,143 @@
+//! # Prompt
+//! Asks the user for keys, answers and choices. The terminal asks for all of them; without a
+//! terminal, e.g. when zpass is started by rofi or a browser, keys come from a pinentry dialog,
+//! and on headless machines from the systemd password agents. Tests answer with fakes.
+
+use super::ask_password;
+use super::handler::HandlerError;
+use super::i18n::{self, tr, Message};
+use super::pinentry;
+use dialoguer::FuzzySelect;
+use std::collections::HashMap;
+use std::io;
+
+/// Asks the user for input.
+pub trait Prompter {
+    /// Asks for a secret such as a key, without showing what's typed.
+    fn read_secret(&mut self, message: &str) -> Result<String, HandlerError>;
+
+    /// Asks for a line of text.
+    fn read_line(&mut self, message: &str) -> Result<String, HandlerError> {
+        eprintln!("{}", message);
+        stdin_line()
+    }
+
+    /// Asks a yes or no question, anything but yes is a no.
+    fn confirm(&mut self, message: &str) -> Result<bool, HandlerError> {
+        eprintln!("{} {}", message, tr(Message::YesNo, &[]));
+        Ok(i18n::is_yes(&stdin_line()?))
+    }
+
+    /// Lets the user pick one of the items, None if the choice was cancelled.
+    fn select(&mut self, message: &str, _items: &[String]) -> Result<Option<usize>, HandlerError> {
+        Err(HandlerError::NotInteractive(message.to_owned()))
+    }
+}
+
+/// Returns the prompter for where zpass runs: the terminal, the password agents on a headless
+/// machine, or pinentry.
+pub fn system() -> Box<dyn Prompter> {
+    if atty::is(atty::Stream::Stdin) {
+        Box::new(Terminal)
+    } else if ask_password::is_headless() {
+        Box::new(AskPassword)
+    } else {
+        Box::new(Pinentry)
+    }
+}
+
+/// Asks for each secret once and gives the same answer when it's asked for again, so a batch
+/// asks for the key of a vault once. Everything else is asked every time.
+#[derive(Default)]
+pub struct Remembering {
+    // prompter asks the questions, None while it is being replaced
+    prompter: Option<Box<dyn Prompter>>,
+    // secrets are the answers by question
+    secrets: HashMap<String, String>,
+}
+
+impl Remembering {
+    /// Remembers the secrets the prompter is asked for.
+    pub fn new(prompter: Box<dyn Prompter>) -> Remembering {
+        Remembering {
+            prompter: Some(prompter),
+            secrets: HashMap::new(),
+        }
+    }
+
+    fn prompter(&mut self) -> Result<&mut Box<dyn Prompter>, HandlerError> {
+        self.prompter
+            .as_mut()
+            .ok_or_else(|| HandlerError::NotInteractive("anything".to_owned()))
+    }
+}
+
+impl Prompter for Remembering {
+    fn read_secret(&mut self, message: &str) -> Result<String, HandlerError> {
+        if let Some(secret) = self.secrets.get(message) {
+            return Ok(secret.clone());
+        }
+        let secret = self.prompter()?.read_secret(message)?;
+        self.secrets.insert(message.to_owned(), secret.clone());
+        Ok(secret)
+    }
+
+    fn read_line(&mut self, message: &str) -> Result<String, HandlerError> {
+        self.prompter()?.read_line(message)
+    }
+
+    fn confirm(&mut self, message: &str) -> Result<bool, HandlerError> {
+        self.prompter()?.confirm(message)
+    }
+
+    fn select(&mut self, message: &str, items: &[String]) -> Result<Option<usize>, HandlerError> {
+        self.prompter()?.select(message, items)
+    }
+}
+
+/// Reads a line from stdin, without the line break. Scripts answer questions this way too.
+/// The questions go to stderr so they don't end up in redirected output.
+fn stdin_line() -> Result<String, HandlerError> {
+    let mut line = String::new();
+    io::stdin().read_line(&mut line)?;
+    Ok(line.trim().to_owned())
+}
+
+/// Asks in the terminal, keys are read without echo and choices are fuzzy searched.
+pub struct Terminal;
+
+impl Prompter for Terminal {
+    fn read_secret(&mut self, message: &str) -> Result<String, HandlerError> {
+        Ok(rpassword::read_password_from_tty(Some(message))?)
+    }
+
+    fn select(&mut self, message: &str, items: &[String]) -> Result<Option<usize>, HandlerError> {
+        if items.is_empty() {
+            return Ok(None);
+        }
+        let selection = FuzzySelect::new()
+            .with_prompt(message)
+            .items(items)
+            .default(0)
+            .interact_opt()?;
+        Ok(selection)
+    }
+}
+
+/// Asks for keys with a pinentry dialog.
+pub struct Pinentry;
+
+impl Prompter for Pinentry {
+    fn read_secret(&mut self, message: &str) -> Result<String, HandlerError> {
+        Ok(pinentry::get_pin(message)?)
+    }
+}
+
+/// Asks for keys through systemd-ask-password.
+pub struct AskPassword;
+
+impl Prompter for AskPassword {
+    fn read_secret(&mut self, message: &str) -> Result<String, HandlerError> {
+        Ok(ask_password::ask(message)?)
+    }
+}
@@ -1,38 +1,714 @@
+use super::complete;
+use super::config::{self, Config};
+use super::context::Context;
+use super::error::{Kind, ZPassError};
 use super::handler;
-use super::parser::{parse, Command, Operation, Resource};
+use super::help;
+use super::i18n::{tr, Message};
+use super::interrupt;
+use super::log;
+use super::parser::{
+    is_operation, parse, parse_line, Command, Operation, ParamName, Resource, OPERATIONS, RESOURCES,
+};
+use super::plugin;
+use super::prompt::Remembering;
+use super::suggest;
+use super::version;
+use crate::safe::cache;
+use crate::safe::constants;
+use crate::safe::journal;
 use std::env;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::mem;
 
-/// Reads a the arguments that were used to start the app and executes them as a command.
-pub fn start() {
-    let args: Vec<String> = env::args().collect();
-    match parse(&args[1..]) {
-        Err(msg) => println!("Failed to parse the command:\n{}", msg),
-        Ok(cmd) => {
-            if let Err(msg) = execute_command(cmd) {
-                println!("Failed to execute the command:\n{}", msg)
+/// Exit status of a command that succeeded.
+pub const SUCCESS: i32 = 0;
+/// Exit status of a command that failed.
+pub const FAILURE: i32 = 1;
+/// Exit status of a command line that couldn't be parsed.
+pub const USAGE: i32 = 2;
+
+/// Executes the arguments the app was started with, the first one is the name of the app.
+/// Usage, parse errors and failures are written to `out`, the handlers still print their
+/// results to stdout. Returns the exit status.
+pub fn run(args: &[String], out: &mut impl Write) -> i32 {
+    // a closed pipe leaves nothing to report to
+    let code = start(args, out).unwrap_or(FAILURE);
+    cache::clear();
+    code
+}
+
+fn start(args: &[String], out: &mut impl Write) -> io::Result<i32> {
+    match args.get(1).map(|a| &a[..]) {
+        Some(complete::COMMAND) => {
+            // errors would show up as candidates, there is nothing to complete then
+            let _ = handler::complete(&mut Context::system(), &args[2..]);
+            return Ok(SUCCESS);
+        }
+        None | Some("--help") | Some("-h") => {
+            write!(out, "{}", help::usage())?;
+            return Ok(SUCCESS);
+        }
+        Some("--version") | Some("-V") => {
+            write!(out, "{}", version::describe())?;
+            return Ok(SUCCESS);
+        }
+        _ => {}
+    }
+    let config = match config::load() {
+        Ok(config) => config,
+        Err(err) => {
+            writeln!(out, "{}", err)?;
+            return Ok(FAILURE);
+        }
+    };
+    if let Err(err) = config.set_root(None).and_then(|_| config.set_locale()) {
+        writeln!(out, "{}", err)?;
+        return Ok(FAILURE);
+    }
+    match constants::migrate_root() {
+        Ok(Some(dir)) => eprintln!(
+            "Moved the vaults from {} to {}",
+            constants::ROOT_PATH,
+            dir.display()
+        ),
+        Ok(None) => {}
+        Err(err) => eprintln!(
+            "Failed to move the vaults from {}:\n{}",
+            constants::ROOT_PATH,
+            err
+        ),
+    }
+    if let Some(command) = args.get(1).filter(|c| !is_operation(c)) {
+        return match plugin::find(command) {
+            Some(path) => match plugin::run(&path, &args[2..]) {
+                Ok(code) => Ok(code),
+                Err(err) => {
+                    writeln!(out, "Failed to run {}:\n{}", path.display(), err)?;
+                    Ok(FAILURE)
+                }
+            },
+            // `zpass example.com` is short for `zpass get password example.com`
+            None if command.contains('.') => {
+                let mut words = vec!["get".to_owned(), "password".to_owned()];
+                words.extend_from_slice(&args[1..]);
+                run_command(&words, &config, out)
+            }
+            None => {
+                writeln!(
+                    out,
+                    "{}\n{}\n{}",
+                    tr(Message::ParseFailed, &[]),
+                    tr(Message::UnknownCommand, &[command]),
+                    tr(Message::RunHelp, &[])
+                )?;
+                Ok(USAGE)
+            }
+        };
+    }
+    run_command(&args[1..], &config, out)
+}
+
+/// Operations that change the vaults, the last one of them can be undone.
+const CHANGING: &[Operation] = &[
+    Operation::Add,
+    Operation::Delete,
+    Operation::Update,
+    Operation::Set,
+    Operation::Rename,
+    Operation::Clone,
+    Operation::Copy,
+    Operation::Import,
+    Operation::Restore,
+    Operation::Tui,
+    Operation::Rotate,
+    Operation::Migrate,
+    Operation::History,
+    Operation::Batch,
+    Operation::Share,
+    Operation::Transfer,
+    Operation::Trash,
+    Operation::Archive,
+    Operation::Unarchive,
+];
+
+/// Operations that don't open a vault, or repair the root, so they never unseal the config.
+const WITHOUT_SEALED_CONFIG: &[Operation] = &[
+    Operation::Schema,
+    Operation::Validate,
+    Operation::Undo,
+    Operation::Init,
+    Operation::Doctor,
+    Operation::Agent,
+    Operation::Lock,
+    Operation::Status,
+    Operation::Completions,
+    Operation::Help,
+];
+
+/// Parses the words into a command, fills in the defaults of the config and executes it.
+fn run_command(words: &[String], config: &Config, out: &mut impl Write) -> io::Result<i32> {
+    match parse(words) {
+        Err(err) => {
+            writeln!(
+                out,
+                "{}\n{}\n{}\n{}",
+                tr(Message::ParseFailed, &[]),
+                err.highlight(),
+                err,
+                tr(Message::RunHelp, &[])
+            )?;
+            Ok(USAGE)
+        }
+        Ok(mut cmd) => {
+            config.apply(&mut cmd);
+            if let Err(err) = config.set_root(cmd.params.text(ParamName::Profile)) {
+                writeln!(out, "{}", err)?;
+                return Ok(FAILURE);
+            }
+            log::init(&cmd.params);
+            interrupt::install();
+            // the vaults are written when they are dropped, deep below the handlers
+            if cmd.params.flag(ParamName::DryRun) {
+                env::set_var(constants::DRY_RUN_VARIABLE, "1");
+            }
+            let mut ctx = Context::system();
+            if uses_sealed_config(&cmd) && config::sealed_path().exists() {
+                // the key of the default vault is asked for once, here and in the command
+                ctx.prompter = Box::new(Remembering::new(mem::replace(
+                    &mut ctx.prompter,
+                    Box::new(Remembering::default()),
+                )));
+                match handler::unseal_config(&mut ctx, &cmd.params) {
+                    Ok(sealed) => sealed.unwrap_or_default().apply(&mut cmd),
+                    Err(err) => {
+                        writeln!(out, "{}\n{}", tr(Message::SealedConfigFailed, &[]), err)?;
+                        return Ok(FAILURE);
+                    }
+                }
+            }
+            if CHANGING.contains(&cmd.op) && !constants::is_dry_run() {
+                if let Err(err) = journal::begin(&constants::root()) {
+                    writeln!(out, "{}\n{}", tr(Message::UndoFailed, &[]), err)?;
+                    return Ok(FAILURE);
+                }
+            }
+            let json = cmd.params.text(ParamName::Output) == Some("json");
+            let params = cmd.params.clone();
+            let executed = execute_command(cmd, config, &mut ctx, out);
+            // the vaults of the command are dropped by now, so their files are planned too
+            if constants::is_dry_run() {
+                handler::report_plan(&params);
+            }
+            match executed {
+                Ok(()) => Ok(SUCCESS),
+                Err(err) => {
+                    if json {
+                        writeln!(out, "{}", err.to_json())?;
+                    } else {
+                        writeln!(out, "{}\n{}", tr(Message::ExecuteFailed, &[]), err)?;
+                    }
+                    Ok(FAILURE)
+                }
             }
         }
     }
 }
 
-/// Calls the handler associated with the Command.
-fn execute_command(cmd: Command) -> Result<(), String> {
+/// Returns true if the command may need the defaults of the sealed config. The config and
+/// vault commands manage what seals it, so they work without it.
+fn uses_sealed_config(cmd: &Command) -> bool {
+    !WITHOUT_SEALED_CONFIG.contains(&cmd.op)
+        && !matches!(cmd.on, Some(Resource::Config) | Some(Resource::Vault))
+        && !cmd.params.flag(ParamName::Help)
+}
+
+/// Calls the handler associated with the Command, help goes to `out`.
+fn execute_command(
+    cmd: Command,
+    config: &Config,
+    ctx: &mut Context,
+    out: &mut impl Write,
+) -> Result<(), ZPassError> {
+    if cmd.params.flag(ParamName::Help) {
+        return write!(out, "{}", help::of(cmd.op)).map_err(ZPassError::from);
+    }
     match cmd {
         Command {
             op: Operation::Add,
-            on: Resource::Vault,
+            on: Some(Resource::Vault),
             ..
-        } => handler::add_vault(&cmd.params).map_err(|e| format!("{}", e)),
+        } => handler::add_vault(ctx, &cmd.params).map_err(ZPassError::from),
         Command {
             op: Operation::Add,
-            on: Resource::Password,
+            on: Some(Resource::Password),
+            ..
+        } => handler::add_password(ctx, &cmd.params).map_err(ZPassError::from),
+        Command {
+            op: Operation::Get,
+            on: Some(Resource::Password),
+            ..
+        } => handler::get_password(ctx, &cmd.params).map_err(ZPassError::from),
+        Command {
+            op: Operation::List,
+            on: Some(Resource::Password),
+            ..
+        } => handler::list_passwords(ctx, &cmd.params).map_err(ZPassError::from),
+        Command {
+            op: Operation::List,
+            on: Some(Resource::Preference),
+            ..
+        } => handler::list_preferences(ctx, &cmd.params).map_err(ZPassError::from),
+        Command {
+            op: Operation::Get,
+            on: Some(Resource::Token),
             ..
-        } => handler::add_password(&cmd.params).map_err(|e| format!("{}", e)),
+        } => handler::get_token(ctx, &cmd.params).map_err(ZPassError::from),
         Command {
             op: Operation::Get,
-            on: Resource::Password,
+            on: Some(Resource::Login),
+            ..
+        } => handler::get_login(ctx, &cmd.params).map_err(ZPassError::from),
+        Command {
+            op: Operation::Get,
+            on: Some(Resource::Alias),
+            ..
+        } => handler::get_alias(ctx, &cmd.params).map_err(ZPassError::from),
+        Command {
+            op: Operation::Get,
+            on: Some(Resource::Preference),
+            ..
+        } => handler::get_preference(ctx, &cmd.params).map_err(ZPassError::from),
+        Command {
+            op: Operation::List,
+            on: Some(Resource::Domain),
+            ..
+        } => handler::list_domains(ctx, &cmd.params).map_err(ZPassError::from),
+        Command {
+            op: Operation::List,
+            on: Some(Resource::Vault),
+            ..
+        } => handler::list_vaults(ctx, &cmd.params).map_err(ZPassError::from),
+        Command {
+            op: Operation::Add,
+            on: Some(Resource::Note),
+            ..
+        } => handler::add_note(ctx, &cmd.params).map_err(ZPassError::from),
+        Command {
+            op: Operation::Get,
+            on: Some(Resource::Note),
+            ..
+        } => handler::get_note(ctx, &cmd.params).map_err(ZPassError::from),
+        Command {
+            op: Operation::Delete,
+            on: Some(Resource::Note),
+            ..
+        } => handler::delete_note(ctx, &cmd.params).map_err(ZPassError::from),
+        Command {
+            op: Operation::Archive,
+            on: Some(Resource::Password),
+            ..
+        } => handler::archive_password(ctx, &cmd.params, true).map_err(ZPassError::from),
+        Command {
+            op: Operation::Unarchive,
+            on: Some(Resource::Password),
+            ..
+        } => handler::archive_password(ctx, &cmd.params, false).map_err(ZPassError::from),
+        Command {
+            op: Operation::Update,
+            on: Some(Resource::Vault),
+            ..
+        } => handler::update_vault(ctx, &cmd.params).map_err(ZPassError::from),
+        Command {
+            op: Operation::Update,
+            on: Some(Resource::Key),
+            ..
+        } => handler::update_key(ctx, &cmd.params).map_err(ZPassError::from),
+        Command {
+            op: Operation::Get,
+            on: Some(Resource::Config),
+            ..
+        } => handler::get_config().map_err(ZPassError::from),
+        Command {
+            op: Operation::Add,
+            on: Some(Resource::Config),
+            ..
+        } => handler::add_config(ctx, &cmd.params, &cmd.args).map_err(ZPassError::from),
+        Command {
+            op: Operation::Delete,
+            on: Some(Resource::Config),
+            ..
+        } => handler::delete_config(&cmd.params).map_err(ZPassError::from),
+        Command {
+            op: Operation::Delete,
+            on: Some(Resource::Password),
+            ..
+        } => handler::delete_password(ctx, &cmd.params).map_err(ZPassError::from),
+        Command {
+            op: Operation::Delete,
+            on: Some(Resource::Vault),
+            ..
+        } => handler::delete_vault(ctx, &cmd.params).map_err(ZPassError::from),
+        Command {
+            op: Operation::Update,
+            on: Some(Resource::Password),
+            ..
+        } => handler::update_password(ctx, &cmd.params).map_err(ZPassError::from),
+        Command {
+            op: Operation::Set,
+            on: Some(Resource::Password),
+            ..
+        } => handler::set_password(ctx, &cmd.params).map_err(ZPassError::from),
+        Command {
+            op: Operation::Set,
+            on: Some(Resource::Vault),
+            ..
+        } => handler::set_vault(ctx, &cmd.params).map_err(ZPassError::from),
+        Command {
+            op: Operation::Rename,
+            on: Some(Resource::Vault),
+            ..
+        } => handler::rename_vault(ctx, &cmd.params, &cmd.args).map_err(ZPassError::from),
+        Command {
+            op: Operation::Clone,
+            on: Some(Resource::Vault),
+            ..
+        } => handler::clone_vault(ctx, &cmd.params).map_err(ZPassError::from),
+        Command {
+            op: Operation::Copy,
+            on: Some(Resource::Password),
+            ..
+        } => handler::copy_password(ctx, &cmd.params).map_err(ZPassError::from),
+        Command {
+            op: Operation::Audit,
+            on: None,
+            ..
+        } => handler::audit(ctx, &cmd.params).map_err(ZPassError::from),
+        Command {
+            op: Operation::Stats,
+            on: None,
+            ..
+        } => handler::stats(ctx, &cmd.params).map_err(ZPassError::from),
+        #[cfg(feature = "import")]
+        Command {
+            op: Operation::Import,
+            on: Some(Resource::Csv),
+            ..
+        } => handler::import_csv(ctx, &cmd.params, &cmd.args).map_err(ZPassError::from),
+        #[cfg(feature = "import")]
+        Command {
+            op: Operation::Import,
+            on: Some(Resource::Pass),
+            ..
+        } => handler::import_pass(ctx, &cmd.params, &cmd.args).map_err(ZPassError::from),
+        #[cfg(feature = "import")]
+        Command {
+            op: Operation::Import,
+            on: Some(Resource::OnePux),
+            ..
+        } => handler::import_1pux(ctx, &cmd.params, &cmd.args).map_err(ZPassError::from),
+        #[cfg(feature = "import")]
+        Command {
+            op: Operation::Export,
+            on: Some(Resource::Keepass),
+            ..
+        } => handler::export_keepass(ctx, &cmd.params).map_err(ZPassError::from),
+        #[cfg(feature = "import")]
+        Command {
+            op: Operation::Export,
+            on: Some(Resource::Csv),
+            ..
+        } => handler::export_csv(ctx, &cmd.params).map_err(ZPassError::from),
+        #[cfg(feature = "import")]
+        Command {
+            op: Operation::Export,
+            on: Some(Resource::Pass),
+            ..
+        } => handler::export_pass(ctx, &cmd.params, &cmd.args).map_err(ZPassError::from),
+        #[cfg(feature = "import")]
+        Command {
+            op: Operation::Export,
+            on: Some(Resource::Vault),
+            ..
+        } => handler::export_vault(ctx, &cmd.params).map_err(ZPassError::from),
+        #[cfg(feature = "import")]
+        Command {
+            op: Operation::Import,
+            on: Some(Resource::Vault),
+            ..
+        } => handler::import_vault(ctx, &cmd.params, &cmd.args).map_err(ZPassError::from),
+        #[cfg(feature = "import")]
+        Command {
+            op: Operation::Backup,
+            on: Some(Resource::Paper),
+            ..
+        } => handler::backup_paper(ctx, &cmd.params).map_err(ZPassError::from),
+        #[cfg(feature = "import")]
+        Command {
+            op: Operation::Backup,
+            on: Some(Resource::Verify),
+            ..
+        } => handler::backup_verify(ctx, &cmd.params, &cmd.args).map_err(ZPassError::from),
+        #[cfg(feature = "import")]
+        Command {
+            op: Operation::Restore,
+            on: Some(Resource::Paper),
+            ..
+        } => handler::restore_paper(ctx, &cmd.params, &cmd.args).map_err(ZPassError::from),
+        #[cfg(feature = "import")]
+        Command {
+            op: Operation::Export,
+            on: None,
+            ..
+        } => handler::export_age(ctx, &cmd.params).map_err(ZPassError::from),
+        #[cfg(feature = "import")]
+        Command {
+            op: Operation::Import,
+            on: None,
+            ..
+        } => handler::import_age(ctx, &cmd.params, &cmd.args).map_err(ZPassError::from),
+        Command {
+            op: Operation::Schema,
+            on: None,
+            ..
+        } => handler::schema().map_err(ZPassError::from),
+        Command {
+            op: Operation::Validate,
+            on: None,
+            ..
+        } => handler::validate(&cmd.args).map_err(ZPassError::from),
+        Command {
+            op: Operation::Menu,
+            on: None,
+            ..
+        } => handler::menu(ctx, &cmd.params).map_err(ZPassError::from),
+        Command {
+            op: Operation::Tui,
+            on: None,
+            ..
+        } => handler::tui(ctx, &cmd.params).map_err(ZPassError::from),
+        Command {
+            op: Operation::Rotate,
+            on: None,
+            ..
+        } => handler::rotate(ctx, &cmd.params).map_err(ZPassError::from),
+        Command {
+            op: Operation::Undo,
+            on: None,
+            ..
+        } => handler::undo(&cmd.params).map_err(ZPassError::from),
+        Command {
+            op: Operation::Migrate,
+            on: None,
+            ..
+        } => handler::migrate(ctx, &cmd.params).map_err(ZPassError::from),
+        Command {
+            op: Operation::Log,
+            on: None,
+            ..
+        } => handler::show_log(ctx, &cmd.params).map_err(ZPassError::from),
+        Command {
+            op: Operation::History,
+            on: None,
+            ..
+        } => handler::show_history(ctx, &cmd.params).map_err(ZPassError::from),
+        Command {
+            op: Operation::Init,
+            on: None,
+            ..
+        } => handler::init(ctx, &cmd.params).map_err(ZPassError::from),
+        Command {
+            op: Operation::Doctor,
+            on: None,
+            ..
+        } => handler::doctor().map_err(ZPassError::from),
+        Command {
+            op: Operation::Remember,
+            on: None,
             ..
-        } => handler::get_password(&cmd.params).map_err(|e| format!("{}", e)),
-        _ => Err("Unexpected command".to_owned()),
+        } => handler::remember(ctx, &cmd.params).map_err(ZPassError::from),
+        Command {
+            op: Operation::Forget,
+            on: None,
+            ..
+        } => handler::forget(ctx, &cmd.params).map_err(ZPassError::from),
+        Command {
+            op: Operation::Ssh,
+            on: None,
+            ..
+        } => handler::ssh(ctx, &cmd.params, &cmd.args).map_err(ZPassError::from),
+        Command {
+            op: Operation::Share,
+            on: None,
+            ..
+        } => handler::share(ctx, &cmd.params, &cmd.args).map_err(ZPassError::from),
+        Command {
+            op: Operation::Transfer,
+            on: None,
+            ..
+        } => handler::transfer(ctx, &cmd.params, &cmd.args).map_err(ZPassError::from),
+        Command {
+            op: Operation::Trash,
+            on: None,
+            ..
+        } => handler::trash(ctx, &cmd.params, &cmd.args).map_err(ZPassError::from),
+        Command {
+            op: Operation::Serve,
+            on: None,
+            ..
+        } => handler::serve(ctx, &cmd.params).map_err(ZPassError::from),
+        Command {
+            op: Operation::Agent,
+            on: None,
+            ..
+        } => handler::agent(&cmd.params).map_err(ZPassError::from),
+        Command {
+            op: Operation::Unlock,
+            on: None,
+            ..
+        } => handler::unlock(ctx, &cmd.params).map_err(ZPassError::from),
+        Command {
+            op: Operation::Lock,
+            on: None,
+            ..
+        } => handler::lock(&cmd.params).map_err(ZPassError::from),
+        Command {
+            op: Operation::Status,
+            on: None,
+            ..
+        } => handler::status(&cmd.params).map_err(ZPassError::from),
+        Command {
+            op: Operation::Exec,
+            on: None,
+            ..
+        } => handler::exec(ctx, &cmd.params, &cmd.rest).map_err(ZPassError::from),
+        Command {
+            op: Operation::Render,
+            on: None,
+            ..
+        } => handler::render(ctx, &cmd.params, &cmd.args).map_err(ZPassError::from),
+        Command {
+            op: Operation::Batch,
+            on: None,
+            ..
+        } => batch(&cmd, config, ctx, out),
+        Command {
+            op: Operation::Completions,
+            on: None,
+            ..
+        } => handler::completions(&cmd.args).map_err(ZPassError::from),
+        Command {
+            op: Operation::Help,
+            on: None,
+            ..
+        } => {
+            let text = match cmd.args.first() {
+                Some(word) => help::operation(word).ok_or_else(|| {
+                    let message = format!("Unknown operation {}\n{}", word, help::usage());
+                    ZPassError::new(Kind::Usage, message)
+                })?,
+                None => help::usage(),
+            };
+            write!(out, "{}", text).map_err(ZPassError::from)
+        }
+        #[cfg(not(feature = "import"))]
+        Command {
+            op: Operation::Import | Operation::Export | Operation::Backup | Operation::Restore,
+            ..
+        } => Err(handler::HandlerError::FeatureDisabled("import").into()),
+        _ => Err(unsupported(&cmd)),
+    }
+}
+
+/// Executes the commands in the file of the batch, or in stdin for `-`, one per line.
+/// Empty lines and lines starting with `#` are skipped. The params of the batch, such as
+/// `--yes` or `--dry-run`, apply to every command, and each key is asked for once.
+/// Stops at the first command that fails.
+fn batch(
+    cmd: &Command,
+    config: &Config,
+    ctx: &mut Context,
+    out: &mut impl Write,
+) -> Result<(), ZPassError> {
+    let path = cmd
+        .args
+        .first()
+        .ok_or_else(|| ZPassError::new(Kind::Usage, "Expected argument:\nfile, or - for stdin"))?;
+    let text = match &path[..] {
+        "-" => {
+            let mut text = String::new();
+            io::stdin().read_to_string(&mut text).map(|_| text)
+        }
+        path => fs::read_to_string(path),
+    }
+    .map_err(|e| ZPassError::from(e).context(&format!("Failed to read {}:\n", path)))?;
+    ctx.prompter = Box::new(Remembering::new(mem::replace(
+        &mut ctx.prompter,
+        Box::new(Remembering::default()),
+    )));
+    for (number, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let fail = |err: ZPassError| err.context(&format!("Line {}, `{}`:\n", number + 1, line));
+        let mut command = parse_line(line).map_err(|e| fail(e.into()))?;
+        command.params.merge(&cmd.params);
+        config.apply(&mut command);
+        execute_command(command, config, ctx, out).map_err(fail)?;
+    }
+    Ok(())
+}
+
+/// Explains that the operation doesn't work on the resource, and what it does work on.
+fn unsupported(cmd: &Command) -> ZPassError {
+    let op = OPERATIONS
+        .iter()
+        .find(|(_, o)| *o == cmd.op)
+        .map_or("", |(w, _)| w);
+    let on = RESOURCES
+        .iter()
+        .find(|(_, r)| Some(*r) == cmd.on)
+        .map_or(String::new(), |(w, _)| format!(" {}", w));
+    // a resource that doesn't parse is taken as the first argument
+    let hint = match (cmd.on, cmd.args.first()) {
+        (None, Some(word)) => suggest::did_you_mean(word, RESOURCES.iter().map(|(w, _)| *w)),
+        _ => String::new(),
+    };
+    let message = format!(
+        "`{}{}` is not supported{}\n{}",
+        op,
+        on,
+        hint,
+        help::of(cmd.op)
+    );
+    ZPassError::new(Kind::Usage, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_dispatches_and_reports() {
+        let run = |line: &str| {
+            let args: Vec<String> = line.split(' ').map(String::from).collect();
+            let mut out = Vec::new();
+            let status = super::run(&args, &mut out);
+            (status, String::from_utf8(out).unwrap())
+        };
+
+        let (status, out) = run("zpass --version");
+        assert_eq!((status, out), (SUCCESS, version::describe()));
+        let (status, out) = run("zpass help get");
+        assert_eq!(status, SUCCESS);
+        assert!(out.contains("get password"));
+        let (status, out) = run("zpass nope");
+        assert_eq!(status, USAGE);
+        assert!(out.contains("Unknown command nope"));
+        let (status, out) = run("zpass help nope");
+        assert_eq!(status, FAILURE);
+        assert!(out.contains("Unknown operation nope"));
     }
 }
@@ -0,0 +1,106 @@
+//! # Sensitive
+//! Copies passwords so clipboard histories leave them out. On Windows the text goes on the
+//! clipboard together with the formats that keep it out of the clipboard history, the cloud
+//! clipboard and the monitoring tools. The clipboard crate offers a single text format on the
+//! other desktops, so there the hints of Klipper and CopyQ can't be set and the text is copied
+//! as it is.
+
+/// Puts the text on the clipboard, marked as sensitive. Returns false if that isn't supported
+/// or failed, the text should then be copied as it is.
+pub fn copy(text: &str) -> bool {
+    sys::copy(text)
+}
+
+#[cfg(windows)]
+mod sys {
+    use crate::safe::memory;
+    use std::os::raw::{c_int, c_uint, c_void};
+    use std::ptr;
+
+    type Handle = *mut c_void;
+
+    /// The clipboard format of UTF-16 text.
+    const CF_UNICODETEXT: c_uint = 13;
+    /// Global memory the clipboard can take over.
+    const GMEM_MOVEABLE: c_uint = 0x0002;
+    /// The formats that keep the text out of the clipboard history, the cloud clipboard and
+    /// clipboard monitors, with the value each of them is given.
+    const HINTS: &[(&str, u32)] = &[
+        ("ExcludeClipboardContentFromMonitorProcessing", 0),
+        ("CanIncludeInClipboardHistory", 0),
+        ("CanUploadToCloudClipboard", 0),
+    ];
+
+    #[link(name = "user32")]
+    extern "system" {
+        fn OpenClipboard(owner: Handle) -> c_int;
+        fn EmptyClipboard() -> c_int;
+        fn SetClipboardData(format: c_uint, data: Handle) -> Handle;
+        fn CloseClipboard() -> c_int;
+        fn RegisterClipboardFormatW(name: *const u16) -> c_uint;
+    }
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn GlobalAlloc(flags: c_uint, bytes: usize) -> Handle;
+        fn GlobalLock(mem: Handle) -> *mut c_void;
+        fn GlobalUnlock(mem: Handle) -> c_int;
+        fn GlobalFree(mem: Handle) -> Handle;
+    }
+
+    /// Returns the text as a null terminated UTF-16 string.
+    fn wide(text: &str) -> Vec<u16> {
+        text.encode_utf16().chain(Some(0)).collect()
+    }
+
+    /// Puts the bytes on the open clipboard in the format, the clipboard owns them then.
+    unsafe fn set(format: c_uint, bytes: &[u8]) -> bool {
+        let mem = GlobalAlloc(GMEM_MOVEABLE, bytes.len());
+        if mem.is_null() {
+            return false;
+        }
+        let target = GlobalLock(mem) as *mut u8;
+        if target.is_null() {
+            GlobalFree(mem);
+            return false;
+        }
+        ptr::copy_nonoverlapping(bytes.as_ptr(), target, bytes.len());
+        GlobalUnlock(mem);
+        if SetClipboardData(format, mem).is_null() {
+            GlobalFree(mem);
+            return false;
+        }
+        true
+    }
+
+    pub fn copy(text: &str) -> bool {
+        let mut bytes: Vec<u8> = wide(text).iter().flat_map(|c| c.to_le_bytes()).collect();
+        let copied = unsafe {
+            if OpenClipboard(ptr::null_mut()) == 0 {
+                false
+            } else {
+                let copied = EmptyClipboard() != 0 && set(CF_UNICODETEXT, &bytes);
+                if copied {
+                    for (name, value) in HINTS {
+                        let format = RegisterClipboardFormatW(wide(name).as_ptr());
+                        if format != 0 {
+                            set(format, &value.to_le_bytes());
+                        }
+                    }
+                }
+                CloseClipboard();
+                copied
+            }
+        };
+        memory::wipe(&mut bytes);
+        copied
+    }
+}
+
+/// The clipboard crate copies the text on the other desktops.
+#[cfg(not(windows))]
+mod sys {
+    pub fn copy(_text: &str) -> bool {
+        false
+    }
+}
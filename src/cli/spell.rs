@@ -0,0 +1,95 @@
+//! # Spell
+//! Spells out passwords with the NATO phonetic alphabet, for reading them over the phone.
+//! Upper case letters are spelled in upper case: `aB7` becomes `alfa, BRAVO, seven`.
+//! Long passwords are easier to transcribe in groups, such as `xK3f-9qLm`.
+
+const LETTERS: [&str; 26] = [
+    "alfa", "bravo", "charlie", "delta", "echo", "foxtrot", "golf", "hotel", "india", "juliett",
+    "kilo", "lima", "mike", "november", "oscar", "papa", "quebec", "romeo", "sierra", "tango",
+    "uniform", "victor", "whiskey", "x-ray", "yankee", "zulu",
+];
+
+const DIGITS: [&str; 10] = [
+    "zero", "one", "two", "three", "four", "five", "six", "seven", "eight", "nine",
+];
+
+/// Spells out a password, one word per character.
+pub fn spell(password: &str) -> String {
+    password
+        .chars()
+        .map(spell_char)
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+/// Splits a password into groups of `size` characters, the last one may be shorter.
+/// A size of zero keeps the password in one group.
+pub fn groups(password: &str, size: usize) -> Vec<String> {
+    let chars: Vec<char> = password.chars().collect();
+    if size == 0 || chars.is_empty() {
+        return vec![password.to_owned()];
+    }
+    chars.chunks(size).map(|c| c.iter().collect()).collect()
+}
+
+/// Returns the password in groups of `size` characters, joined by the separator.
+pub fn group(password: &str, size: usize, separator: &str) -> String {
+    groups(password, size).join(separator)
+}
+
+/// Returns the spoken word for a character.
+fn spell_char(c: char) -> String {
+    let word = match c {
+        'a'..='z' => LETTERS[c as usize - 'a' as usize],
+        'A'..='Z' => return LETTERS[c as usize - 'A' as usize].to_uppercase(),
+        '0'..='9' => DIGITS[c as usize - '0' as usize],
+        '!' => "exclamation mark",
+        '"' => "double quote",
+        '#' => "hash",
+        '$' => "dollar",
+        '%' => "percent",
+        '&' => "ampersand",
+        '\'' => "apostrophe",
+        '(' => "left parenthesis",
+        ')' => "right parenthesis",
+        '*' => "asterisk",
+        '+' => "plus",
+        ',' => "comma",
+        '-' => "dash",
+        '.' => "period",
+        '/' => "slash",
+        ':' => "colon",
+        ';' => "semicolon",
+        '<' => "less than",
+        '=' => "equals",
+        '>' => "greater than",
+        '?' => "question mark",
+        '@' => "at sign",
+        '[' => "left bracket",
+        '\\' => "backslash",
+        ']' => "right bracket",
+        '^' => "caret",
+        '_' => "underscore",
+        '`' => "backtick",
+        '{' => "left brace",
+        '|' => "vertical bar",
+        '}' => "right brace",
+        '~' => "tilde",
+        ' ' => "space",
+        c => return format!("'{}'", c),
+    };
+    word.to_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spells_out_passwords() {
+        assert_eq!(spell("aB7-"), "alfa, BRAVO, seven, dash");
+        assert_eq!(group("xK3f9qLmZ", 4, "-"), "xK3f-9qLm-Z");
+        assert_eq!(group("xK3f", 0, "-"), "xK3f");
+        assert_eq!(groups("äöüß", 2), vec!["äö", "üß"]);
+    }
+}
@@ -0,0 +1,85 @@
+//! # Suggest
+//! "Did you mean" hints for words that are close to a known one, such as `gte` for `get` or
+//! `exmaple.com` for a domain of the vault. Closeness is the edit distance between the words.
+
+use super::i18n::{tr, Message};
+
+/// Number of suggestions given at most.
+const MAX_SUGGESTIONS: usize = 3;
+
+/// Returns the number of insertions, deletions and substitutions that turn one word into
+/// the other.
+pub fn distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// Returns the candidates that are close to the word, closest first. A candidate is close
+/// when at most a third of the word, and at least one character, differs.
+pub fn similar<'a, I>(word: &str, candidates: I) -> Vec<&'a str>
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    let limit = (word.chars().count() / 3).max(1);
+    let mut close: Vec<(usize, &str)> = candidates
+        .into_iter()
+        .map(|c| (distance(&word.to_lowercase(), &c.to_lowercase()), c))
+        .filter(|(d, c)| *d <= limit && *c != word)
+        .collect();
+    close.sort();
+    close.dedup();
+    close
+        .into_iter()
+        .take(MAX_SUGGESTIONS)
+        .map(|(_, c)| c)
+        .collect()
+}
+
+/// Returns a line that suggests the close candidates, or nothing if none is close.
+pub fn did_you_mean<'a, I>(word: &str, candidates: I) -> String
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    let quoted: Vec<String> = similar(word, candidates)
+        .iter()
+        .map(|c| format!("`{}`", c))
+        .collect();
+    match &quoted[..] {
+        [] => String::new(),
+        [one] => format!("\n{}", tr(Message::DidYouMean, &[one])),
+        [rest @ .., last] => {
+            let either = tr(Message::Or, &[&rest.join(", "), last]);
+            format!("\n{}", tr(Message::DidYouMean, &[&either]))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn suggests_close_words() {
+        assert_eq!(distance("gte", "get"), 2);
+        assert_eq!(distance("exmaple.com", "example.com"), 2);
+        assert_eq!(distance("", "abc"), 3);
+
+        let verbs = ["get", "set", "list", "delete"];
+        assert_eq!(similar("gt", verbs), vec!["get"]);
+        assert_eq!(did_you_mean("lst", verbs), "\nDid you mean `list`?");
+        assert_eq!(did_you_mean("xyz", verbs), "");
+        assert_eq!(
+            did_you_mean("example.co", ["example.com", "example.org", "other.net"]),
+            "\nDid you mean `example.com` or `example.org`?"
+        );
+    }
+}
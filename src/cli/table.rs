@@ -0,0 +1,128 @@
+//! # Table
+//! Listings as aligned columns, with the default account of each domain marked with `*` and
+//! expired passwords in red. Color is left out when `NO_COLOR` is set, and without a terminal
+//! the rows are printed tab separated, as scripts expect them.
+
+use std::env;
+
+/// Starts the color of an expired row.
+const RED: &str = "\x1b[31m";
+/// Starts the color of a default row.
+const BOLD: &str = "\x1b[1m";
+/// Ends a color.
+const RESET: &str = "\x1b[0m";
+
+/// # Row
+/// The cells of a row and how it stands out.
+struct Row {
+    // cells are the columns of the row
+    cells: Vec<String>,
+    // marked rows are the default account of their domain
+    marked: bool,
+    // expired rows are highlighted
+    expired: bool,
+}
+
+/// # Table
+/// The header and rows of a listing.
+pub struct Table {
+    // header names the columns
+    header: Vec<String>,
+    // rows in the order they are printed
+    rows: Vec<Row>,
+}
+
+impl Table {
+    /// Creates a table with the columns of the header.
+    pub fn new(header: &[&str]) -> Table {
+        Table {
+            header: header.iter().map(|h| h.to_string()).collect(),
+            rows: Vec::new(),
+        }
+    }
+
+    /// Adds a row, marked if it is the default and highlighted if it is expired.
+    pub fn push(&mut self, cells: Vec<String>, marked: bool, expired: bool) {
+        self.rows.push(Row {
+            cells,
+            marked,
+            expired,
+        });
+    }
+
+    /// Prints the table, aligned on a terminal and tab separated otherwise.
+    pub fn print(&self) {
+        if atty::is(atty::Stream::Stdout) {
+            print!("{}", self.aligned(use_color()));
+        } else {
+            print!("{}", self.plain());
+        }
+    }
+
+    /// Returns the rows tab separated, without the header and the highlights.
+    pub fn plain(&self) -> String {
+        self.rows
+            .iter()
+            .map(|row| format!("{}\n", row.cells.join("\t")))
+            .collect()
+    }
+
+    /// Returns the header and the rows in aligned columns.
+    pub fn aligned(&self, color: bool) -> String {
+        let mut widths: Vec<usize> = self.header.iter().map(|h| h.chars().count()).collect();
+        for row in &self.rows {
+            for (i, cell) in row.cells.iter().enumerate() {
+                if i < widths.len() {
+                    widths[i] = widths[i].max(cell.chars().count());
+                }
+            }
+        }
+        let line = |cells: &[String]| {
+            let padded: Vec<String> = cells
+                .iter()
+                .zip(&widths)
+                .map(|(cell, width)| format!("{:<1$}", cell, width))
+                .collect();
+            padded.join("  ").trim_end().to_owned()
+        };
+        let mut text = format!("  {}\n", line(&self.header));
+        for row in &self.rows {
+            let marker = if row.marked { "* " } else { "  " };
+            let start = match (color, row.expired, row.marked) {
+                (false, _, _) => "",
+                (true, true, _) => RED,
+                (true, false, true) => BOLD,
+                (true, false, false) => "",
+            };
+            let end = if start.is_empty() { "" } else { RESET };
+            text.push_str(&format!("{}{}{}{}\n", start, marker, line(&row.cells), end));
+        }
+        text
+    }
+}
+
+/// Returns true unless NO_COLOR is set to something, see https://no-color.org.
+pub fn use_color() -> bool {
+    env::var_os("NO_COLOR").filter(|v| !v.is_empty()).is_none()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn aligns_marks_and_highlights() {
+        let mut table = Table::new(&["DOMAIN", "USERNAME"]);
+        table.push(vec!["example.com".into(), "alice".into()], true, false);
+        table.push(vec!["b.org".into(), "bob".into()], false, true);
+
+        assert_eq!(table.plain(), "example.com\talice\nb.org\tbob\n");
+        assert_eq!(
+            table.aligned(false),
+            "  DOMAIN       USERNAME\n* example.com  alice\n  b.org        bob\n"
+        );
+        let colored = table.aligned(true);
+        assert!(colored.contains(&format!("{}* example.com  alice{}", BOLD, RESET)));
+        assert!(colored.contains(&format!("{}  b.org        bob{}", RED, RESET)));
+    }
+}
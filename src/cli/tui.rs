@@ -0,0 +1,322 @@
+//! # TUI
+//! A full-screen view of a vault: the accounts on the left, the settings of the selected one on
+//! the right. `/` searches the domains and usernames, the arrows or `j` and `k` move, enter or
+//! `c` copies the password, `r` rotates it to a new version, `e` edits its length and `q` quits.
+
+use crate::safe::preference::Preference;
+use crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use crossterm::execute;
+use crossterm::terminal::{
+    disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen,
+};
+use ratatui::backend::{Backend, CrosstermBackend};
+use ratatui::layout::{Constraint, Direction, Layout};
+use ratatui::style::{Modifier, Style};
+use ratatui::widgets::{Block, Borders, List, ListItem, ListState, Paragraph};
+use ratatui::{Frame, Terminal};
+use std::io;
+
+/// The key bindings, shown at the bottom.
+const KEYS: &str = "/ search  j/k move  enter copy  r rotate  e length  q quit";
+
+/// What has to happen to the vault after a key press, rows are given by their index.
+#[derive(Debug, PartialEq)]
+pub enum Action {
+    None,
+    Quit,
+    Copy(usize),
+    Rotate(usize),
+    SetLength(usize, usize),
+}
+
+/// What the keys do at the moment.
+#[derive(Debug, PartialEq)]
+enum Mode {
+    Browse,
+    Search,
+    Length,
+}
+
+/// # Row
+/// An account as the view shows it.
+pub struct Row {
+    // domain of the account
+    pub domain: String,
+    // username of the account
+    pub username: String,
+    // details are the lines of the detail pane
+    details: Vec<String>,
+}
+
+/// Returns the rows of the preferences, in the same order.
+pub fn rows(preferences: &[&Preference]) -> Vec<Row> {
+    preferences
+        .iter()
+        .map(|p| {
+            let mut details = vec![
+                format!("Domain: {}", p.domain),
+                format!("Username: {}", p.username),
+                format!("Length: {}", p.length),
+                format!("Version: {}", p.version),
+            ];
+            if p.is_stored() {
+                details.push("Stored, not generated".to_owned());
+            }
+            if p.unambiguous {
+                details.push("Without ambiguous characters".to_owned());
+            }
+            if p.rules.is_some() {
+                details.push("Follows the password rules of the site".to_owned());
+            }
+            if !p.tags.is_empty() {
+                details.push(format!("Tags: {}", p.tags.join(", ")));
+            }
+            if p.notes.is_some() {
+                details.push("Has a note".to_owned());
+            }
+            Row {
+                domain: p.domain.clone(),
+                username: p.username.clone(),
+                details,
+            }
+        })
+        .collect()
+}
+
+/// # App
+/// The state of the view.
+pub struct App {
+    // rows are all the accounts of the vault
+    rows: Vec<Row>,
+    // query filters the rows by domain and username
+    query: String,
+    // selected is the position among the filtered rows
+    selected: usize,
+    // mode decides what the keys do
+    mode: Mode,
+    // length is the length being typed
+    length: String,
+    // status is the outcome of the last action
+    pub status: String,
+}
+
+impl App {
+    /// Creates the view of the rows.
+    pub fn new(rows: Vec<Row>) -> App {
+        App {
+            rows,
+            query: String::new(),
+            selected: 0,
+            mode: Mode::Browse,
+            length: String::new(),
+            status: String::new(),
+        }
+    }
+
+    /// Replaces the rows after the vault changed, keeping the search.
+    pub fn set_rows(&mut self, rows: Vec<Row>) {
+        self.rows = rows;
+        self.selected = self.selected.min(self.filtered().len().saturating_sub(1));
+    }
+
+    /// Returns a row by its index.
+    pub fn row(&self, index: usize) -> &Row {
+        &self.rows[index]
+    }
+
+    /// Returns the indices of the rows that match the query.
+    fn filtered(&self) -> Vec<usize> {
+        let query = self.query.to_lowercase();
+        (0..self.rows.len())
+            .filter(|&i| {
+                let row = &self.rows[i];
+                row.domain.to_lowercase().contains(&query)
+                    || row.username.to_lowercase().contains(&query)
+            })
+            .collect()
+    }
+
+    /// Returns the index of the selected row, if any matches the query.
+    fn current(&self) -> Option<usize> {
+        self.filtered().get(self.selected).copied()
+    }
+
+    /// Moves the selection up or down, within the filtered rows.
+    fn step(&mut self, down: bool) {
+        let last = self.filtered().len().saturating_sub(1);
+        self.selected = if down {
+            (self.selected + 1).min(last)
+        } else {
+            self.selected.saturating_sub(1)
+        };
+    }
+
+    /// Updates the state for a key and returns what has to happen to the vault.
+    pub fn handle(&mut self, key: KeyCode) -> Action {
+        self.status.clear();
+        match self.mode {
+            Mode::Browse => match key {
+                KeyCode::Char('q') | KeyCode::Esc => return Action::Quit,
+                KeyCode::Char('/') => self.mode = Mode::Search,
+                KeyCode::Down | KeyCode::Char('j') => self.step(true),
+                KeyCode::Up | KeyCode::Char('k') => self.step(false),
+                KeyCode::Enter | KeyCode::Char('c') => {
+                    return self.current().map_or(Action::None, Action::Copy)
+                }
+                KeyCode::Char('r') => return self.current().map_or(Action::None, Action::Rotate),
+                KeyCode::Char('e') if self.current().is_some() => {
+                    self.length.clear();
+                    self.mode = Mode::Length;
+                }
+                _ => {}
+            },
+            Mode::Search => match key {
+                KeyCode::Esc => {
+                    self.query.clear();
+                    self.mode = Mode::Browse;
+                }
+                KeyCode::Enter => self.mode = Mode::Browse,
+                KeyCode::Down => self.step(true),
+                KeyCode::Up => self.step(false),
+                KeyCode::Backspace => {
+                    self.query.pop();
+                    self.selected = 0;
+                }
+                KeyCode::Char(c) => {
+                    self.query.push(c);
+                    self.selected = 0;
+                }
+                _ => {}
+            },
+            Mode::Length => match key {
+                KeyCode::Esc => self.mode = Mode::Browse,
+                KeyCode::Backspace => {
+                    self.length.pop();
+                }
+                KeyCode::Char(c) if c.is_ascii_digit() => self.length.push(c),
+                KeyCode::Enter => {
+                    self.mode = Mode::Browse;
+                    if let (Some(row), Ok(length)) = (self.current(), self.length.parse()) {
+                        return Action::SetLength(row, length);
+                    }
+                }
+                _ => {}
+            },
+        }
+        Action::None
+    }
+}
+
+/// Shows the view until it's quit. Actions go to `act`, which changes the vault and then
+/// updates the rows and the status of the app.
+pub fn run<F>(app: &mut App, act: F) -> io::Result<()>
+where
+    F: FnMut(&mut App, Action),
+{
+    enable_raw_mode()?;
+    execute!(io::stdout(), EnterAlternateScreen)?;
+    let mut terminal = Terminal::new(CrosstermBackend::new(io::stdout()))?;
+    let result = show(&mut terminal, app, act);
+    // restore the terminal even if the view failed
+    disable_raw_mode()?;
+    execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
+    terminal.show_cursor()?;
+    result
+}
+
+fn show<B, F>(terminal: &mut Terminal<B>, app: &mut App, mut act: F) -> io::Result<()>
+where
+    B: Backend,
+    F: FnMut(&mut App, Action),
+{
+    loop {
+        terminal.draw(|f| draw(f, app))?;
+        let key = match event::read()? {
+            Event::Key(key) if key.kind == KeyEventKind::Press => key.code,
+            _ => continue,
+        };
+        match app.handle(key) {
+            Action::Quit => return Ok(()),
+            Action::None => {}
+            action => act(app, action),
+        }
+    }
+}
+
+fn draw<B: Backend>(f: &mut Frame<B>, app: &App) {
+    let screen = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(1), Constraint::Length(1)].as_ref())
+        .split(f.size());
+    let panes = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(40), Constraint::Percentage(60)].as_ref())
+        .split(screen[0]);
+
+    let filtered = app.filtered();
+    let items: Vec<ListItem> = filtered
+        .iter()
+        .map(|&i| ListItem::new(format!("{}  {}", app.rows[i].domain, app.rows[i].username)))
+        .collect();
+    let title = match app.mode {
+        Mode::Search => format!("Search: {}_", app.query),
+        _ if !app.query.is_empty() => format!("Search: {}", app.query),
+        _ => "Accounts".to_owned(),
+    };
+    let list = List::new(items)
+        .block(Block::default().borders(Borders::ALL).title(title))
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+    let mut state = ListState::default();
+    state.select(app.current().map(|_| app.selected));
+    f.render_stateful_widget(list, panes[0], &mut state);
+
+    let details = match app.current() {
+        Some(i) => app.rows[i].details.join("\n"),
+        None => String::new(),
+    };
+    let details = Paragraph::new(details).block(Block::default().borders(Borders::ALL));
+    f.render_widget(details, panes[1]);
+
+    let status = match app.mode {
+        Mode::Length => format!("New length: {}_", app.length),
+        _ if !app.status.is_empty() => app.status.clone(),
+        _ => KEYS.to_owned(),
+    };
+    f.render_widget(Paragraph::new(status), screen[1]);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys_search_move_and_act() {
+        let row = |domain: &str, username: &str| Row {
+            domain: domain.to_owned(),
+            username: username.to_owned(),
+            details: Vec::new(),
+        };
+        let mut app = App::new(vec![
+            row("example.com", "alice"),
+            row("example.org", "bob"),
+            row("other.net", "bob"),
+        ]);
+
+        assert_eq!(app.handle(KeyCode::Char('j')), Action::None);
+        assert_eq!(app.handle(KeyCode::Enter), Action::Copy(1));
+
+        for key in "/bob".chars() {
+            app.handle(KeyCode::Char(key));
+        }
+        app.handle(KeyCode::Enter);
+        app.handle(KeyCode::Down);
+        app.handle(KeyCode::Down);
+        assert_eq!(app.handle(KeyCode::Char('r')), Action::Rotate(2));
+
+        for key in "e24".chars() {
+            app.handle(KeyCode::Char(key));
+        }
+        assert_eq!(app.handle(KeyCode::Enter), Action::SetLength(2, 24));
+        assert_eq!(app.handle(KeyCode::Char('q')), Action::Quit);
+    }
+}
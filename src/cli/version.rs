@@ -0,0 +1,42 @@
+//! # Version
+//! Describes the build, which matters when a bug report is about vault format differences:
+//! the crate version, the git commit, the build date and the enabled cargo features.
+
+/// Cargo features and whether they are enabled in this build.
+const FEATURES: &[(&str, bool)] = &[
+    ("autotype", cfg!(feature = "autotype")),
+    ("clipboard", cfg!(feature = "clipboard")),
+    ("ffi", cfg!(feature = "ffi")),
+    ("hibp", cfg!(feature = "hibp")),
+    ("import", cfg!(feature = "import")),
+    ("keychain", cfg!(feature = "keychain")),
+    ("masked-email", cfg!(feature = "masked-email")),
+    ("secret-service", cfg!(feature = "secret-service")),
+    ("ssh", cfg!(feature = "ssh")),
+    ("tui", cfg!(feature = "tui")),
+];
+
+/// Returns the features enabled in this build.
+pub fn features() -> Vec<&'static str> {
+    FEATURES
+        .iter()
+        .filter(|(_, enabled)| *enabled)
+        .map(|(name, _)| *name)
+        .collect()
+}
+
+/// Returns the description `zpass --version` prints.
+pub fn describe() -> String {
+    let features = features();
+    format!(
+        "zpass {} ({} {})\nfeatures: {}\n",
+        env!("CARGO_PKG_VERSION"),
+        env!("ZPASS_GIT_COMMIT"),
+        env!("ZPASS_BUILD_DATE"),
+        if features.is_empty() {
+            "none".to_owned()
+        } else {
+            features.join(", ")
+        }
+    )
+}
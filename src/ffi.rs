@@ -0,0 +1,197 @@
+//! # FFI
+//! C functions over the API, for apps written in Swift, Kotlin or C++. Build with
+//! `cargo build --release --features ffi` and link against the `zpass` library.
+//!
+//! ```c
+//! ZPass *zpass = zpass_open("/home/alice/.zpass");
+//! char *domains = zpass_list_domains(zpass, NULL);
+//! char *password = zpass_get_password(zpass, "work", "example.com", NULL, key);
+//! zpass_free_string(password);
+//! zpass_free_string(domains);
+//! zpass_close(zpass);
+//! ```
+//!
+//! Strings given to zpass stay owned by the caller. Strings returned by zpass are owned by the
+//! caller too, and go back through `zpass_free_string`, which overwrites them before they're
+//! freed. Errors and panics return NULL.
+
+use crate::api::{Options, VaultRef, ZPass};
+use crate::safe::memory::wipe;
+use std::ffi::{CStr, CString};
+use std::os::raw::c_char;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::Path;
+use std::ptr;
+
+/// Opens the vaults in the directory, NULL if they can't be read.
+/// Vaults are written back by `zpass_close`.
+///
+/// # Safety
+/// `root` is a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn zpass_open(root: *const c_char) -> *mut ZPass {
+    guard(|| {
+        let root = text(root)?;
+        ZPass::open(Path::new(root)).ok().map(Box::new)
+    })
+    .map_or(ptr::null_mut(), Box::into_raw)
+}
+
+/// Writes the vaults back, wipes the decrypted secrets and frees them.
+///
+/// # Safety
+/// `zpass` comes from `zpass_open` and isn't used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn zpass_close(zpass: *mut ZPass) {
+    if !zpass.is_null() {
+        guard(|| {
+            drop(Box::from_raw(zpass));
+            Some(())
+        });
+    }
+}
+
+/// Returns the distinct domains of the vault, one per line, or of the default vault when
+/// `vault` is NULL.
+///
+/// # Safety
+/// `zpass` comes from `zpass_open`, `vault` is NULL or a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn zpass_list_domains(
+    zpass: *mut ZPass,
+    vault: *const c_char,
+) -> *mut c_char {
+    guard(|| {
+        let zpass = zpass.as_mut()?;
+        let v = select(zpass, vault)?;
+        let mut domains: Vec<&str> = v.accounts().iter().map(|p| &p.domain[..]).collect();
+        domains.sort_unstable();
+        domains.dedup();
+        Some(into_c(domains.join("\n")))
+    })
+    .unwrap_or(ptr::null_mut())
+}
+
+/// Returns the password of the default account of the domain, or of the username when it
+/// isn't NULL, in the vault or in the default vault when `vault` is NULL.
+///
+/// # Safety
+/// `zpass` comes from `zpass_open`, `domain` and `key` are NUL-terminated strings,
+/// `vault` and `username` are NULL or NUL-terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn zpass_get_password(
+    zpass: *mut ZPass,
+    vault: *const c_char,
+    domain: *const c_char,
+    username: *const c_char,
+    key: *const c_char,
+) -> *mut c_char {
+    guard(|| {
+        let zpass = zpass.as_mut()?;
+        let (domain, key) = (text(domain)?, text(key)?);
+        let options = Options {
+            username: text(username).map(String::from),
+            ..Options::default()
+        };
+        let mut v = select(zpass, vault)?;
+        v.password(domain, key, &options).ok().map(into_c)
+    })
+    .unwrap_or(ptr::null_mut())
+}
+
+/// Overwrites a string returned by zpass and frees it.
+///
+/// # Safety
+/// `string` is NULL or comes from zpass and isn't used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn zpass_free_string(string: *mut c_char) {
+    if !string.is_null() {
+        wipe(&mut CString::from_raw(string).into_bytes_with_nul());
+    }
+}
+
+/// Runs the function, a panic must not unwind into the caller.
+fn guard<T, F: FnOnce() -> Option<T>>(f: F) -> Option<T> {
+    panic::catch_unwind(AssertUnwindSafe(f)).ok().flatten()
+}
+
+/// Borrows a string of the caller, None for NULL or invalid UTF-8.
+unsafe fn text<'a>(string: *const c_char) -> Option<&'a str> {
+    if string.is_null() {
+        return None;
+    }
+    CStr::from_ptr(string).to_str().ok()
+}
+
+/// Returns the named vault, or the default vault for NULL.
+unsafe fn select(zpass: &mut ZPass, vault: *const c_char) -> Option<VaultRef<'_>> {
+    match text(vault) {
+        Some(name) => zpass.vault(name).ok(),
+        None => zpass.default_vault().ok(),
+    }
+}
+
+/// Hands a string to the caller, the copy zpass held is overwritten.
+fn into_c(mut string: String) -> *mut c_char {
+    // room for the NUL, so the bytes aren't moved and left behind
+    let mut bytes = Vec::with_capacity(string.len() + 1);
+    bytes.extend_from_slice(string.as_bytes());
+    // the bytes are overwritten with zeros, which is valid UTF-8
+    wipe(unsafe { string.as_bytes_mut() });
+    match CString::new(bytes) {
+        Ok(string) => string.into_raw(),
+        Err(err) => {
+            wipe(&mut err.into_vec());
+            ptr::null_mut()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+    use std::process;
+
+    #[test]
+    fn opens_lists_and_generates() {
+        let root = env::temp_dir().join(format!("zpass-ffi-{}", process::id()));
+        {
+            let mut zpass = ZPass::open(&root).unwrap();
+            let mut work = zpass.create_vault("work", "key").unwrap();
+            work.add_account("b.com", "bob", 20).unwrap();
+            work.add_account("a.com", "alice", 20).unwrap();
+            work.add_account("a.com", "carol", 20).unwrap();
+            let c = |s: &str| CString::new(s).unwrap();
+            let (domain, username, key) = (c("a.com"), c("carol"), c("key"));
+            unsafe {
+                let domains = zpass_list_domains(&mut zpass, ptr::null());
+                assert_eq!(CStr::from_ptr(domains).to_str(), Ok("a.com\nb.com"));
+                zpass_free_string(domains);
+
+                let password = zpass_get_password(
+                    &mut zpass,
+                    ptr::null(),
+                    domain.as_ptr(),
+                    username.as_ptr(),
+                    key.as_ptr(),
+                );
+                assert_eq!(CStr::from_ptr(password).to_bytes().len(), 20);
+                zpass_free_string(password);
+
+                let missing = c("c.com");
+                assert!(zpass_get_password(
+                    &mut zpass,
+                    ptr::null(),
+                    missing.as_ptr(),
+                    ptr::null(),
+                    key.as_ptr()
+                )
+                .is_null());
+                assert!(zpass_open(ptr::null()).is_null());
+            }
+        }
+        fs::remove_dir_all(&root).ok();
+    }
+}
@@ -0,0 +1,1056 @@
+//! # Agent
+//! Keeps the keys of unlocked vaults in a long running process, so the command line and other
+//! integrations, such as a browser host or the Secret Service, ask it for passwords instead of
+//! asking for the key each time. They talk to it with a `Client`:
+//!
+//! ```no_run
+//! use zpass::agent::Client;
+//!
+//! let mut agent = Client::connect()?;
+//! agent.unlock("work", "key")?;
+//! let password = agent.password("work", "example.com", None)?;
+//! # Ok::<(), zpass::agent::AgentError>(())
+//! ```
+//!
+//! The agent listens on `agent.sock` under the root, a Unix domain socket only the user can
+//! open, or on a named pipe of the user on Windows, which rejects remote clients. Each request
+//! and response is a frame: its length as 4 bytes big endian, a tag byte, and then its fields,
+//! each again prefixed by its length as 4 bytes big endian. Fields are UTF-8.
+//!
+//! A vault can be unlocked for a while, e.g. with `zpass unlock --lock-after 30`: the agent
+//! forgets its key once the time is up, as if it was locked. The command line asks the agent
+//! for the key of an unlocked vault before it asks the user, so a session spares typing the key
+//! for each command.
+
+use crate::safe::cache;
+use crate::safe::constants;
+use crate::safe::crypto::Secret;
+use crate::safe::memory::{self, Locked};
+use crate::safe::time;
+use crate::safe::vault::{Vault, VaultError};
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::error;
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::str;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread;
+use tracing::debug;
+
+/// Name of the socket under the root.
+const SOCKET_NAME: &str = "agent.sock";
+/// The longest frame, the biggest message is a status with many vault names.
+const MAX_FRAME: usize = 64 * 1024;
+
+/// Tags of the requests.
+const UNLOCK: u8 = 1;
+const PASSWORD: u8 = 2;
+const LOCK: u8 = 3;
+const STATUS: u8 = 4;
+const SESSIONS: u8 = 5;
+const KEY: u8 = 6;
+/// Tags of the responses.
+const DONE: u8 = 0;
+const SECRET: u8 = 1;
+const UNLOCKED: u8 = 2;
+const REFUSED: u8 = 3;
+const EXPIRIES: u8 = 4;
+const UNLOCKED_KEY: u8 = 5;
+
+#[derive(Debug)]
+pub enum AgentError {
+    NotRunning(io::Error),
+    IOError(io::Error),
+    InvalidMessage(&'static str),
+    Refused(String),
+}
+
+impl fmt::Display for AgentError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::NotRunning(ref err) => write!(f, "The agent isn't running:\n{}", err),
+            Self::IOError(ref err) => write!(f, "Failed to talk to the agent:\n{}", err),
+            Self::InvalidMessage(ref reason) => write!(f, "Invalid agent message: {}", reason),
+            Self::Refused(ref reason) => write!(f, "The agent refused: {}", reason),
+        }
+    }
+}
+
+impl error::Error for AgentError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Self::NotRunning(ref err) | Self::IOError(ref err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for AgentError {
+    fn from(err: io::Error) -> Self {
+        AgentError::IOError(err)
+    }
+}
+
+/// # Request
+/// What a client asks the agent.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Request {
+    // Unlock keeps the key of a vault once it opens the vault, for the seconds of lock_after
+    // or until it is locked
+    Unlock {
+        vault: String,
+        key: Locked,
+        lock_after: Option<u64>,
+    },
+    // Password asks for the password of an account, the default one of the domain without
+    // a username
+    Password {
+        vault: String,
+        domain: String,
+        username: Option<String>,
+    },
+    // Lock forgets the key of a vault, or of all of them without one
+    Lock {
+        vault: Option<String>,
+    },
+    // Status asks for the unlocked vaults
+    Status,
+    // Sessions asks for the unlocked vaults and when they are locked
+    Sessions,
+    // Key asks for the key of an unlocked vault
+    Key {
+        vault: String,
+    },
+}
+
+/// # Response
+/// What the agent answers.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Response {
+    // Done answers an unlock or a lock
+    Done,
+    // Password answers a password request
+    Password(String),
+    // Unlocked answers a status request with the names of the unlocked vaults
+    Unlocked(Vec<String>),
+    // Refused tells why the request failed
+    Refused(String),
+    // Sessions answers a sessions request
+    Sessions(Vec<Session>),
+    // Key answers a key request
+    Key(Locked),
+}
+
+/// # Session
+/// An unlocked vault.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Session {
+    // vault is the name of the vault
+    pub vault: String,
+    // expires is when the agent locks the vault in seconds since the epoch, None if it waits
+    // for a lock request
+    pub expires: Option<u64>,
+}
+
+impl Request {
+    /// Returns the tag and the fields of the frame.
+    pub fn encode(&self) -> Vec<u8> {
+        let optional = |field: &Option<String>| field.clone().unwrap_or_default();
+        match self {
+            Self::Unlock {
+                vault,
+                key,
+                lock_after,
+            } => encode(
+                UNLOCK,
+                &[
+                    vault.as_bytes(),
+                    key,
+                    optional_number(*lock_after).as_bytes(),
+                ],
+            ),
+            Self::Password {
+                vault,
+                domain,
+                username,
+            } => encode(
+                PASSWORD,
+                &[
+                    vault.as_bytes(),
+                    domain.as_bytes(),
+                    optional(username).as_bytes(),
+                ],
+            ),
+            Self::Lock { vault } => encode(LOCK, &[optional(vault).as_bytes()]),
+            Self::Status => encode(STATUS, &[]),
+            Self::Sessions => encode(SESSIONS, &[]),
+            Self::Key { vault } => encode(KEY, &[vault.as_bytes()]),
+        }
+    }
+
+    /// Reads a request from the tag and the fields of a frame. An unlock of an older client
+    /// has no lock_after. The key goes from the frame into locked memory, it is never copied.
+    pub fn decode(body: &[u8]) -> Result<Request, AgentError> {
+        let optional = |field: String| Some(field).filter(|f| !f.is_empty());
+        let (tag, mut fields) = decode(body)?;
+        let mut next = || {
+            fields
+                .next()
+                .ok_or(AgentError::InvalidMessage("missing field"))
+        };
+        let request = match tag {
+            UNLOCK => Self::Unlock {
+                vault: text(next()?)?,
+                key: secret(next()?)?,
+                lock_after: parse_number(&next().map_or(Ok(String::new()), text)?)?,
+            },
+            PASSWORD => Self::Password {
+                vault: text(next()?)?,
+                domain: text(next()?)?,
+                username: optional(text(next()?)?),
+            },
+            LOCK => Self::Lock {
+                vault: optional(text(next()?)?),
+            },
+            STATUS => Self::Status,
+            SESSIONS => Self::Sessions,
+            KEY => Self::Key {
+                vault: text(next()?)?,
+            },
+            _ => return Err(AgentError::InvalidMessage("unknown request")),
+        };
+        match fields.next() {
+            Some(_) => Err(AgentError::InvalidMessage("extra field")),
+            None => Ok(request),
+        }
+    }
+}
+
+impl Response {
+    /// Returns the tag and the fields of the frame.
+    pub fn encode(&self) -> Vec<u8> {
+        match self {
+            Self::Done => encode(DONE, &[]),
+            Self::Password(password) => encode(SECRET, &[password.as_bytes()]),
+            Self::Unlocked(vaults) => encode(
+                UNLOCKED,
+                &vaults.iter().map(|v| v.as_bytes()).collect::<Vec<_>>(),
+            ),
+            Self::Refused(reason) => encode(REFUSED, &[reason.as_bytes()]),
+            Self::Sessions(sessions) => {
+                let fields: Vec<String> = sessions
+                    .iter()
+                    .flat_map(|s| vec![s.vault.clone(), optional_number(s.expires)])
+                    .collect();
+                encode(
+                    EXPIRIES,
+                    &fields.iter().map(|f| f.as_bytes()).collect::<Vec<_>>(),
+                )
+            }
+            Self::Key(key) => encode(UNLOCKED_KEY, &[key]),
+        }
+    }
+
+    /// Reads a response from the tag and the fields of a frame.
+    pub fn decode(body: &[u8]) -> Result<Response, AgentError> {
+        let (tag, mut fields) = decode(body)?;
+        if tag == UNLOCKED_KEY && fields.len() == 1 {
+            return fields
+                .next()
+                .map_or(Err(AgentError::InvalidMessage("missing field")), |key| {
+                    secret(key).map(Self::Key)
+                });
+        }
+        let fields: Vec<String> = fields.map(text).collect::<Result<_, _>>()?;
+        match (tag, &fields[..]) {
+            (DONE, []) => Ok(Self::Done),
+            (SECRET, [password]) => Ok(Self::Password(password.clone())),
+            (UNLOCKED, _) => Ok(Self::Unlocked(fields)),
+            (REFUSED, [reason]) => Ok(Self::Refused(reason.clone())),
+            (EXPIRIES, _) => fields
+                .chunks(2)
+                .map(|pair| match pair {
+                    [vault, expires] => Ok(Session {
+                        vault: vault.clone(),
+                        expires: parse_number(expires)?,
+                    }),
+                    _ => Err(AgentError::InvalidMessage("missing field")),
+                })
+                .collect::<Result<_, _>>()
+                .map(Self::Sessions),
+            _ => Err(AgentError::InvalidMessage("unknown response")),
+        }
+    }
+}
+
+/// Returns the number as a field, None as an empty one.
+fn optional_number(number: Option<u64>) -> String {
+    number.map(|n| n.to_string()).unwrap_or_default()
+}
+
+/// Reads a field of optional_number.
+fn parse_number(field: &str) -> Result<Option<u64>, AgentError> {
+    if field.is_empty() {
+        return Ok(None);
+    }
+    field
+        .parse()
+        .map(Some)
+        .map_err(|_| AgentError::InvalidMessage("invalid number"))
+}
+
+/// Returns the tag followed by the fields, each prefixed by its length.
+fn encode(tag: u8, fields: &[&[u8]]) -> Vec<u8> {
+    let mut body = vec![tag];
+    for field in fields {
+        body.extend_from_slice(&(field.len() as u32).to_be_bytes());
+        body.extend_from_slice(field);
+    }
+    body
+}
+
+/// Returns a field as text.
+fn text(field: Vec<u8>) -> Result<String, AgentError> {
+    String::from_utf8(field).map_err(|_| AgentError::InvalidMessage("field isn't UTF-8"))
+}
+
+/// Returns a field that holds a key, locked where it is.
+fn secret(mut field: Vec<u8>) -> Result<Locked, AgentError> {
+    if str::from_utf8(&field).is_err() {
+        memory::wipe(&mut field);
+        return Err(AgentError::InvalidMessage("field isn't UTF-8"));
+    }
+    Ok(Locked::new(field))
+}
+
+/// Returns the text of a key, the keys of requests and responses are checked to be UTF-8.
+fn key_text(key: &Locked) -> &str {
+    str::from_utf8(key).unwrap_or_default()
+}
+
+/// Returns the tag and the fields of a frame.
+fn decode(body: &[u8]) -> Result<(u8, std::vec::IntoIter<Vec<u8>>), AgentError> {
+    let (&tag, mut rest) = body
+        .split_first()
+        .ok_or(AgentError::InvalidMessage("empty frame"))?;
+    let mut fields = Vec::new();
+    while !rest.is_empty() {
+        let length = rest
+            .get(..4)
+            .and_then(|l| <[u8; 4]>::try_from(l).ok())
+            .map(|l| u32::from_be_bytes(l) as usize)
+            .ok_or(AgentError::InvalidMessage("truncated field"))?;
+        let field = rest
+            .get(4..4 + length)
+            .ok_or(AgentError::InvalidMessage("truncated field"))?;
+        fields.push(field.to_vec());
+        rest = &rest[4 + length..];
+    }
+    Ok((tag, fields.into_iter()))
+}
+
+/// Writes a frame, its length first.
+fn write_frame(stream: &mut impl Write, body: &[u8]) -> Result<(), AgentError> {
+    if body.len() > MAX_FRAME {
+        return Err(AgentError::InvalidMessage("frame too long"));
+    }
+    stream.write_all(&(body.len() as u32).to_be_bytes())?;
+    stream.write_all(body)?;
+    stream.flush()?;
+    Ok(())
+}
+
+/// Reads a frame, None if the other side closed the connection before it.
+fn read_frame(stream: &mut impl Read) -> Result<Option<Vec<u8>>, AgentError> {
+    let mut length = [0; 4];
+    match stream.read_exact(&mut length) {
+        Ok(()) => {}
+        Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(err) => return Err(err.into()),
+    }
+    let length = u32::from_be_bytes(length) as usize;
+    if length > MAX_FRAME {
+        return Err(AgentError::InvalidMessage("frame too long"));
+    }
+    let mut body = vec![0; length];
+    stream.read_exact(&mut body)?;
+    Ok(Some(body))
+}
+
+/// Returns where the agent of the root listens.
+#[cfg(unix)]
+pub fn socket_path() -> PathBuf {
+    constants::root().join(SOCKET_NAME)
+}
+
+/// Returns where the agent of the user listens, pipes have no directory to be kept in.
+#[cfg(windows)]
+pub fn socket_path() -> PathBuf {
+    let user = std::env::var("USERNAME").unwrap_or_default();
+    PathBuf::from(format!(r"\\.\pipe\zpass-agent-{}", user))
+}
+
+/// # Client
+/// A connection to the agent.
+pub struct Client {
+    // stream is the socket or the pipe
+    stream: sys::Stream,
+}
+
+impl Client {
+    /// Connects to the agent of the root.
+    pub fn connect() -> Result<Client, AgentError> {
+        Self::connect_to(&socket_path())
+    }
+
+    /// Connects to the agent that listens at the path.
+    pub fn connect_to(path: &Path) -> Result<Client, AgentError> {
+        let stream = sys::connect(path).map_err(AgentError::NotRunning)?;
+        Ok(Client { stream })
+    }
+
+    /// Sends a request and returns the response, a refusal is an error.
+    pub fn request(&mut self, request: &Request) -> Result<Response, AgentError> {
+        let mut body = request.encode();
+        let sent = write_frame(&mut self.stream, &body);
+        memory::wipe(&mut body);
+        sent?;
+        let body =
+            read_frame(&mut self.stream)?.ok_or(AgentError::InvalidMessage("connection closed"))?;
+        match Response::decode(&body)? {
+            Response::Refused(reason) => Err(AgentError::Refused(reason)),
+            response => Ok(response),
+        }
+    }
+
+    /// Has the agent keep the key of a vault, it checks the key first.
+    pub fn unlock(&mut self, vault: &str, key: &str) -> Result<(), AgentError> {
+        self.unlock_for(vault, key, None)
+    }
+
+    /// Has the agent keep the key of a vault for the seconds of lock_after, or until it is
+    /// locked without them.
+    pub fn unlock_for(
+        &mut self,
+        vault: &str,
+        key: &str,
+        lock_after: Option<u64>,
+    ) -> Result<(), AgentError> {
+        let request = Request::Unlock {
+            vault: vault.to_owned(),
+            key: Locked::concat(&[key.as_bytes()]),
+            lock_after,
+        };
+        expect_done(self.request(&request)?)
+    }
+
+    /// Returns the password of an account of an unlocked vault.
+    pub fn password(
+        &mut self,
+        vault: &str,
+        domain: &str,
+        username: Option<&str>,
+    ) -> Result<String, AgentError> {
+        let request = Request::Password {
+            vault: vault.to_owned(),
+            domain: domain.to_owned(),
+            username: username.map(String::from),
+        };
+        match self.request(&request)? {
+            Response::Password(password) => Ok(password),
+            _ => Err(AgentError::InvalidMessage("expected a password")),
+        }
+    }
+
+    /// Has the agent forget the key of a vault, or all keys without a vault.
+    pub fn lock(&mut self, vault: Option<&str>) -> Result<(), AgentError> {
+        let vault = vault.map(String::from);
+        expect_done(self.request(&Request::Lock { vault })?)
+    }
+
+    /// Returns the names of the unlocked vaults.
+    pub fn status(&mut self) -> Result<Vec<String>, AgentError> {
+        match self.request(&Request::Status)? {
+            Response::Unlocked(vaults) => Ok(vaults),
+            _ => Err(AgentError::InvalidMessage("expected the unlocked vaults")),
+        }
+    }
+
+    /// Returns the unlocked vaults and when they are locked.
+    pub fn sessions(&mut self) -> Result<Vec<Session>, AgentError> {
+        match self.request(&Request::Sessions)? {
+            Response::Sessions(sessions) => Ok(sessions),
+            _ => Err(AgentError::InvalidMessage("expected the sessions")),
+        }
+    }
+
+    /// Returns the key of an unlocked vault.
+    pub fn key(&mut self, vault: &str) -> Result<String, AgentError> {
+        let vault = vault.to_owned();
+        match self.request(&Request::Key { vault })? {
+            Response::Key(key) => Ok(key_text(&key).to_owned()),
+            _ => Err(AgentError::InvalidMessage("expected a key")),
+        }
+    }
+}
+
+/// Returns an error unless the response is done.
+fn expect_done(response: Response) -> Result<(), AgentError> {
+    match response {
+        Response::Done => Ok(()),
+        _ => Err(AgentError::InvalidMessage("expected done")),
+    }
+}
+
+/// # Agent
+/// The keys of the unlocked vaults of a root.
+pub struct Agent {
+    // root is the directory of the vaults
+    root: PathBuf,
+    // keys are the keys of the unlocked vaults by name
+    keys: HashMap<String, Locked>,
+    // expires are the times the vaults are locked at by name, in seconds since the epoch
+    expires: HashMap<String, u64>,
+    // screen_locked is set when the screen locks or the machine suspends, then every vault
+    // is locked before the next request
+    screen_locked: Arc<AtomicBool>,
+}
+
+impl Agent {
+    /// Creates an agent with no vault unlocked.
+    pub fn new(root: PathBuf) -> Agent {
+        Agent {
+            root,
+            keys: HashMap::new(),
+            expires: HashMap::new(),
+            screen_locked: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Answers a request, after locking the vaults whose time is up.
+    pub fn handle(&mut self, request: Request) -> Response {
+        self.expire(time::now());
+        let response = match request {
+            Request::Unlock {
+                vault,
+                key,
+                lock_after,
+            } => self.unlock(vault, key, lock_after).map(|_| Response::Done),
+            Request::Password {
+                vault,
+                domain,
+                username,
+            } => self
+                .password(&vault, &domain, username)
+                .map(Response::Password),
+            Request::Lock { vault } => {
+                self.lock(vault.as_deref());
+                Ok(Response::Done)
+            }
+            Request::Status => {
+                let mut vaults: Vec<String> = self.keys.keys().cloned().collect();
+                vaults.sort();
+                Ok(Response::Unlocked(vaults))
+            }
+            Request::Sessions => {
+                let mut sessions: Vec<Session> = self
+                    .keys
+                    .keys()
+                    .map(|vault| Session {
+                        vault: vault.clone(),
+                        expires: self.expires.get(vault).copied(),
+                    })
+                    .collect();
+                sessions.sort_by(|a, b| a.vault.cmp(&b.vault));
+                Ok(Response::Sessions(sessions))
+            }
+            Request::Key { vault } => self
+                .key(&vault)
+                .map(|key| Response::Key(Locked::concat(&[key]))),
+        };
+        response.unwrap_or_else(|err| Response::Refused(err.to_string()))
+    }
+
+    /// Keeps the key if it opens the vault, for the seconds of lock_after if there are any.
+    fn unlock(
+        &mut self,
+        vault: String,
+        key: Locked,
+        lock_after: Option<u64>,
+    ) -> Result<(), VaultError> {
+        // the vault is only read, it must not be written back
+        let mut v = Vault::<Secret>::open(&self.root, &vault)?;
+        v.detach();
+        v.secret().check_key(key_text(&key))?;
+        match lock_after {
+            Some(seconds) => self
+                .expires
+                .insert(vault.clone(), time::now().saturating_add(seconds)),
+            None => self.expires.remove(&vault),
+        };
+        // the key it replaces is wiped when it is dropped
+        self.keys.insert(vault, key);
+        Ok(())
+    }
+
+    /// Returns the key of an unlocked vault.
+    fn key(&self, vault: &str) -> Result<&Locked, VaultError> {
+        self.keys
+            .get(vault)
+            .ok_or_else(|| VaultError::NoMatchingVault(format!("{} (locked)", vault)))
+    }
+
+    /// Returns the password of an account of an unlocked vault.
+    fn password(
+        &mut self,
+        vault: &str,
+        domain: &str,
+        username: Option<String>,
+    ) -> Result<String, VaultError> {
+        let key = self.key(vault)?;
+        // only this vault is written back, with the time its password was used
+        Vault::<Secret>::open(&self.root, vault)?.get_password(
+            domain,
+            key_text(key),
+            username.as_deref(),
+            None,
+            None,
+        )
+    }
+
+    /// Forgets the key of a vault, or all of them, and the secrets they decrypted.
+    fn lock(&mut self, vault: Option<&str>) {
+        let names: Vec<String> = match vault {
+            Some(vault) => vec![vault.to_owned()],
+            None => self.keys.keys().cloned().collect(),
+        };
+        for name in names {
+            // the key is wiped when it is dropped
+            self.keys.remove(&name);
+            self.expires.remove(&name);
+        }
+        cache::clear();
+    }
+
+    /// Locks the vaults whose time is up at now, or all of them once the screen locked.
+    fn expire(&mut self, now: u64) {
+        if self.screen_locked.swap(false, Ordering::SeqCst) {
+            debug!("Locking every vault, the screen locked");
+            self.lock(None);
+        }
+        let expired: Vec<String> = self
+            .expires
+            .iter()
+            .filter(|(_, at)| **at <= now)
+            .map(|(vault, _)| vault.clone())
+            .collect();
+        for vault in expired {
+            debug!("The session of {} expired", vault);
+            self.lock(Some(&vault));
+        }
+    }
+}
+
+impl Drop for Agent {
+    fn drop(&mut self) {
+        self.lock(None);
+    }
+}
+
+/// Answers the requests of a connection until the client closes it.
+/// The agent is only locked while it handles a request, not while the client takes its time.
+fn answer(agent: &Mutex<&mut Agent>, stream: &mut (impl Read + Write)) -> Result<(), AgentError> {
+    while let Some(mut body) = read_frame(stream)? {
+        let request = Request::decode(&body);
+        memory::wipe(&mut body);
+        let response = match request {
+            Ok(request) => lock(agent).handle(request),
+            Err(err) => Response::Refused(err.to_string()),
+        };
+        let mut body = response.encode();
+        let sent = write_frame(stream, &body);
+        memory::wipe(&mut body);
+        sent?;
+    }
+    Ok(())
+}
+
+/// Listens at the path and answers each client on a thread of its own, until the process
+/// ends, so a client that keeps quiet holds up no other. A client that breaks the protocol
+/// only loses its connection. Sessions expire while the agent waits for clients, and with the
+/// secret-service feature every vault locks when the screen locks or the machine suspends.
+pub fn serve(agent: &mut Agent, path: &Path) -> Result<(), AgentError> {
+    let listener = sys::listen(path)?;
+    #[cfg(feature = "secret-service")]
+    {
+        let screen_locked = agent.screen_locked.clone();
+        super::secret_service::watch_lock_signals(move || {
+            screen_locked.store(true, Ordering::SeqCst)
+        });
+    }
+    let agent = Mutex::new(agent);
+    thread::scope(|scope| loop {
+        lock(&agent).expire(time::now());
+        let mut stream = match sys::accept(&listener)? {
+            Some(stream) => stream,
+            None => continue,
+        };
+        let agent = &agent;
+        scope.spawn(move || {
+            if let Err(err) = answer(agent, &mut stream) {
+                debug!("Dropped an agent client: {}", err);
+            }
+        });
+    })
+}
+
+/// Locks the agent for a request, a client thread that panicked leaves it usable.
+fn lock<'a, 'b>(agent: &'a Mutex<&'b mut Agent>) -> MutexGuard<'a, &'b mut Agent> {
+    agent
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+#[cfg(unix)]
+mod sys {
+    use std::fs;
+    use std::io;
+    use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+    use std::os::unix::net::{UnixListener, UnixStream};
+    use std::path::Path;
+    use std::process;
+    use std::thread;
+    use std::time::Duration;
+
+    /// How long accept waits for a client before the agent looks for expired sessions.
+    const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+    pub type Stream = UnixStream;
+    pub type Listener = UnixListener;
+
+    pub fn connect(path: &Path) -> io::Result<Stream> {
+        UnixStream::connect(path)
+    }
+
+    /// Binds the socket, replacing the one of an agent that is gone. Only the user may
+    /// connect to it: it is bound in a directory only the user may enter and moved to the
+    /// path once its permissions are set.
+    pub fn listen(path: &Path) -> io::Result<Listener> {
+        if path.exists() {
+            if connect(path).is_ok() {
+                return Err(io::Error::new(
+                    io::ErrorKind::AddrInUse,
+                    format!("An agent already listens on {}", path.display()),
+                ));
+            }
+            fs::remove_file(path)?;
+        }
+        let dir = path.parent().unwrap_or_else(|| Path::new("."));
+        fs::create_dir_all(dir)?;
+        let private = dir.join(format!(".agent-{}", process::id()));
+        fs::DirBuilder::new().mode(0o700).create(&private)?;
+        let bound = private.join("agent.sock");
+        let listener = UnixListener::bind(&bound).and_then(|listener| {
+            fs::set_permissions(&bound, fs::Permissions::from_mode(0o600))?;
+            fs::rename(&bound, path)?;
+            Ok(listener)
+        });
+        if listener.is_err() {
+            let _ = fs::remove_file(&bound);
+        }
+        fs::remove_dir(&private)?;
+        let listener = listener?;
+        listener.set_nonblocking(true)?;
+        Ok(listener)
+    }
+
+    /// Returns the next client, or None if none connected for a while.
+    pub fn accept(listener: &Listener) -> io::Result<Option<Stream>> {
+        match listener.accept() {
+            Ok((stream, _)) => {
+                stream.set_nonblocking(false)?;
+                Ok(Some(stream))
+            }
+            Err(err) if err.kind() == io::ErrorKind::WouldBlock => {
+                thread::sleep(POLL_INTERVAL);
+                Ok(None)
+            }
+            Err(err) => Err(err),
+        }
+    }
+}
+
+#[cfg(windows)]
+mod sys {
+    use std::fs::{File, OpenOptions};
+    use std::io;
+    use std::os::raw::{c_int, c_void};
+    use std::os::windows::io::{FromRawHandle, RawHandle};
+    use std::path::Path;
+    use std::ptr;
+
+    /// Reads and writes go both ways.
+    const PIPE_ACCESS_DUPLEX: u32 = 3;
+    /// Clients on other machines can't connect.
+    const PIPE_REJECT_REMOTE_CLIENTS: u32 = 8;
+    const PIPE_UNLIMITED_INSTANCES: u32 = 255;
+    const BUFFER_SIZE: u32 = 4096;
+    /// The client connected between creating the pipe and waiting for it.
+    const ERROR_PIPE_CONNECTED: i32 = 535;
+    /// Only the owner, the user that runs the agent, may open the pipe.
+    const OWNER_ONLY: &str = "D:P(A;;GA;;;OW)";
+
+    pub type Stream = File;
+    pub type Listener = Vec<u16>;
+
+    #[repr(C)]
+    struct SecurityAttributes {
+        length: u32,
+        descriptor: *mut c_void,
+        inherit: c_int,
+    }
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn CreateNamedPipeW(
+            name: *const u16,
+            open_mode: u32,
+            pipe_mode: u32,
+            instances: u32,
+            out_size: u32,
+            in_size: u32,
+            timeout: u32,
+            attributes: *const SecurityAttributes,
+        ) -> RawHandle;
+        fn ConnectNamedPipe(pipe: RawHandle, overlapped: *mut c_void) -> c_int;
+        fn LocalFree(memory: *mut c_void) -> *mut c_void;
+    }
+
+    #[link(name = "advapi32")]
+    extern "system" {
+        fn ConvertStringSecurityDescriptorToSecurityDescriptorW(
+            sddl: *const u16,
+            revision: u32,
+            descriptor: *mut *mut c_void,
+            size: *mut u32,
+        ) -> c_int;
+    }
+
+    /// Returns the text as a null terminated UTF-16 string.
+    fn wide(text: &str) -> Vec<u16> {
+        text.encode_utf16().chain(Some(0)).collect()
+    }
+
+    pub fn connect(path: &Path) -> io::Result<Stream> {
+        OpenOptions::new().read(true).write(true).open(path)
+    }
+
+    pub fn listen(path: &Path) -> io::Result<Listener> {
+        Ok(wide(&path.to_string_lossy()))
+    }
+
+    /// Creates an instance of the pipe and waits for a client to open it. The wait doesn't
+    /// time out, sessions expire once the next client connects.
+    pub fn accept(name: &Listener) -> io::Result<Option<Stream>> {
+        let sddl = wide(OWNER_ONLY);
+        let mut descriptor = ptr::null_mut();
+        let converted = unsafe {
+            ConvertStringSecurityDescriptorToSecurityDescriptorW(
+                sddl.as_ptr(),
+                1,
+                &mut descriptor,
+                ptr::null_mut(),
+            )
+        };
+        if converted == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let attributes = SecurityAttributes {
+            length: std::mem::size_of::<SecurityAttributes>() as u32,
+            descriptor,
+            inherit: 0,
+        };
+        let pipe = unsafe {
+            CreateNamedPipeW(
+                name.as_ptr(),
+                PIPE_ACCESS_DUPLEX,
+                PIPE_REJECT_REMOTE_CLIENTS,
+                PIPE_UNLIMITED_INSTANCES,
+                BUFFER_SIZE,
+                BUFFER_SIZE,
+                0,
+                &attributes,
+            )
+        };
+        let created = io::Error::last_os_error();
+        unsafe { LocalFree(descriptor) };
+        // INVALID_HANDLE_VALUE
+        if pipe as isize == -1 {
+            return Err(created);
+        }
+        let stream = unsafe { File::from_raw_handle(pipe) };
+        if unsafe { ConnectNamedPipe(pipe, ptr::null_mut()) } == 0 {
+            let err = io::Error::last_os_error();
+            if err.raw_os_error() != Some(ERROR_PIPE_CONNECTED) {
+                return Err(err);
+            }
+        }
+        Ok(Some(stream))
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+    use std::os::unix::net::UnixStream;
+    use std::time::Duration;
+
+    #[test]
+    fn clients_talk_to_the_agent() {
+        let requests = vec![
+            Request::Unlock {
+                vault: "work".to_owned(),
+                key: Locked::concat(&[b"key"]),
+                lock_after: None,
+            },
+            Request::Unlock {
+                vault: "work".to_owned(),
+                key: Locked::concat(&[b"key"]),
+                lock_after: Some(900),
+            },
+            Request::Password {
+                vault: "work".to_owned(),
+                domain: "example.com".to_owned(),
+                username: None,
+            },
+            Request::Lock { vault: None },
+            Request::Status,
+            Request::Sessions,
+            Request::Key {
+                vault: "work".to_owned(),
+            },
+        ];
+        for request in requests {
+            assert_eq!(Request::decode(&request.encode()).unwrap(), request);
+        }
+        let unlocked = Response::Unlocked(vec!["personal".to_owned(), "work".to_owned()]);
+        assert_eq!(Response::decode(&unlocked.encode()).unwrap(), unlocked);
+        let sessions = Response::Sessions(vec![
+            Session {
+                vault: "personal".to_owned(),
+                expires: None,
+            },
+            Session {
+                vault: "work".to_owned(),
+                expires: Some(1700000000),
+            },
+        ]);
+        assert_eq!(Response::decode(&sessions.encode()).unwrap(), sessions);
+        let key = Response::Key(Locked::concat(&[b"key"]));
+        assert_eq!(Response::decode(&key.encode()).unwrap(), key);
+        let older = encode(UNLOCK, &[b"work", b"key"]);
+        assert!(matches!(
+            Request::decode(&older).unwrap(),
+            Request::Unlock {
+                lock_after: None,
+                ..
+            }
+        ));
+        assert!(Request::decode(&[STATUS, 0, 0, 0, 9, b'x']).is_err());
+        assert!(Request::decode(&[UNLOCK]).is_err());
+
+        let (mut server, client) = UnixStream::pair().unwrap();
+        let root = std::env::temp_dir().join(format!("zpass-agent-{}", std::process::id()));
+        let served = root.clone();
+        let agent =
+            thread::spawn(move || answer(&Mutex::new(&mut Agent::new(served)), &mut server));
+        let mut client = Client { stream: client };
+        assert!(client.status().unwrap().is_empty());
+        for vault in &["work", "../work"] {
+            assert!(matches!(
+                client.unlock(vault, "key"),
+                Err(AgentError::Refused(_))
+            ));
+        }
+        assert!(!root.exists());
+        assert!(matches!(
+            client.password("work", "example.com", None),
+            Err(AgentError::Refused(_))
+        ));
+        assert!(matches!(client.key("work"), Err(AgentError::Refused(_))));
+        client.lock(Some("work")).unwrap();
+        drop(client);
+        agent.join().unwrap().unwrap();
+    }
+
+    #[test]
+    fn quiet_clients_hold_up_no_other() {
+        let root = std::env::temp_dir().join(format!("zpass-agent-serve-{}", std::process::id()));
+        let path = root.join(SOCKET_NAME);
+        let served = path.clone();
+        thread::spawn(move || serve(&mut Agent::new(root), &served));
+        for _ in 0..100 {
+            if path.exists() {
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        let quiet = UnixStream::connect(&path).unwrap();
+        assert!(Client::connect_to(&path)
+            .unwrap()
+            .status()
+            .unwrap()
+            .is_empty());
+        drop(quiet);
+        let dir = path.parent().unwrap();
+        let entries: Vec<_> = std::fs::read_dir(dir).unwrap().collect();
+        assert_eq!(entries.len(), 1);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn sessions_expire() {
+        let mut agent = Agent::new(std::env::temp_dir());
+        for vault in &["personal", "work"] {
+            agent
+                .keys
+                .insert(vault.to_string(), Locked::concat(&[b"key"]));
+        }
+        agent.expires.insert("work".to_owned(), u64::MAX);
+        agent.expire(u64::MAX - 1);
+        assert_eq!(
+            agent.handle(Request::Key {
+                vault: "work".to_owned()
+            }),
+            Response::Key(Locked::concat(&[b"key"]))
+        );
+        agent.expire(u64::MAX);
+        assert!(!agent.keys.contains_key("work"));
+        assert_eq!(
+            agent.handle(Request::Sessions),
+            Response::Sessions(vec![Session {
+                vault: "personal".to_owned(),
+                expires: None,
+            }])
+        );
+    }
+
+    #[test]
+    fn screen_lock_locks_every_vault() {
+        let mut agent = Agent::new(std::env::temp_dir());
+        for vault in &["personal", "work"] {
+            agent
+                .keys
+                .insert(vault.to_string(), Locked::concat(&[b"key"]));
+        }
+        agent.screen_locked.store(true, Ordering::SeqCst);
+        assert_eq!(agent.handle(Request::Status), Response::Unlocked(vec![]));
+        assert!(!agent.screen_locked.load(Ordering::SeqCst));
+    }
+}
@@ -0,0 +1,36 @@
+//! # Autotype
+//! Types credentials into whatever window has focus, for apps and VM consoles that the
+//! clipboard doesn't reach. A countdown leaves time to focus the login field.
+
+use enigo::{Enigo, Key, KeyboardControllable};
+use std::io::{self, Write};
+use std::thread;
+use std::time::Duration;
+
+/// Seconds to wait before typing.
+pub const COUNTDOWN_SECONDS: u64 = 3;
+
+/// Types the username, Tab and the password after the countdown.
+/// Accounts without a username only get the password typed.
+pub fn type_login(username: &str, password: &str) -> io::Result<()> {
+    countdown()?;
+    let mut enigo = Enigo::new();
+    if !username.is_empty() {
+        enigo.key_sequence(username);
+        enigo.key_click(Key::Tab);
+    }
+    enigo.key_sequence(password);
+    Ok(())
+}
+
+/// Counts down on stderr, so the focus can move to the target window.
+fn countdown() -> io::Result<()> {
+    let mut stderr = io::stderr();
+    for remaining in (1..=COUNTDOWN_SECONDS).rev() {
+        write!(stderr, "\rTyping in {}...", remaining)?;
+        stderr.flush()?;
+        thread::sleep(Duration::from_secs(1));
+    }
+    writeln!(stderr, "\rTyping...    ")?;
+    Ok(())
+}
@@ -0,0 +1,109 @@
+//! # HIBP
+//! Checks passwords against the Have I Been Pwned range API.
+//! Only the first 5 characters of the SHA-1 hash of a password leave the machine (k-anonymity),
+//! the rest of the hash is compared locally against the returned range.
+
+use sha1::{Digest, Sha1};
+use std::error;
+use std::fmt;
+use std::io;
+
+/// The range API returns every hash suffix that starts with the given prefix.
+const RANGE_API: &str = "https://api.pwnedpasswords.com/range/";
+/// Length of the hash prefix that is sent to the API.
+const PREFIX_LENGTH: usize = 5;
+
+#[derive(Debug)]
+pub enum BreachError {
+    RequestFailed(Box<ureq::Error>),
+    IOError(io::Error),
+}
+
+impl fmt::Display for BreachError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::RequestFailed(ref err) => write!(f, "Request to HIBP failed:\n{}", err),
+            Self::IOError(ref err) => write!(f, "IO error:\n{}", err),
+        }
+    }
+}
+
+impl error::Error for BreachError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Self::RequestFailed(ref err) => Some(err.as_ref()),
+            Self::IOError(ref err) => Some(err),
+        }
+    }
+}
+
+impl From<ureq::Error> for BreachError {
+    fn from(err: ureq::Error) -> Self {
+        BreachError::RequestFailed(Box::new(err))
+    }
+}
+
+impl From<io::Error> for BreachError {
+    fn from(err: io::Error) -> Self {
+        BreachError::IOError(err)
+    }
+}
+
+/// Returns how many times the password appears in known breaches.
+pub fn times_pwned(password: &str) -> Result<u64, BreachError> {
+    let hash = sha1_hex(password);
+    let (prefix, suffix) = hash.split_at(PREFIX_LENGTH);
+    let range = ureq::get(&format!("{}{}", RANGE_API, prefix))
+        .set("Add-Padding", "true")
+        .call()?
+        .into_string()?;
+    Ok(count_in_range(&range, suffix))
+}
+
+/// Returns the upper case hex encoding of the SHA-1 hash of the password.
+fn sha1_hex(password: &str) -> String {
+    Sha1::digest(password.as_bytes())
+        .iter()
+        .map(|b| format!("{:02X}", b))
+        .collect()
+}
+
+/// Finds the count for a hash suffix in a range response.
+/// Each line of the response looks like `<SUFFIX>:<COUNT>`.
+fn count_in_range(range: &str, suffix: &str) -> u64 {
+    range
+        .lines()
+        .filter_map(|line| {
+            let mut parts = line.trim().splitn(2, ':');
+            match (parts.next(), parts.next()) {
+                (Some(s), Some(count)) if s.eq_ignore_ascii_case(suffix) => count.parse().ok(),
+                _ => None,
+            }
+        })
+        .next()
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_matching_suffix() {
+        let range = "0018A45C4D1DEF81644B54AB7F969B88D65:1\r\n\
+                     00D4F6E8FA6EECAD2A3AA415EEC418D38EC:2\r\n\
+                     011053FD0102E94D6AE2F8B83D76FAF94F6:0";
+        assert_eq!(
+            count_in_range(range, "00D4F6E8FA6EECAD2A3AA415EEC418D38EC"),
+            2
+        );
+        assert_eq!(
+            count_in_range(range, "011053FD0102E94D6AE2F8B83D76FAF94F6"),
+            0
+        );
+        assert_eq!(
+            count_in_range(range, "FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF"),
+            0
+        );
+    }
+}
@@ -0,0 +1,220 @@
+//! # Keychain
+//! Keeps the keys of vaults in the credential store of the OS: the macOS Keychain, the
+//! Secret Service (GNOME Keyring, KWallet) on Linux, or the Credential Manager on Windows.
+//! Each vault opts in separately, vaults without a stored key keep prompting for it.
+//!
+//! The Credential Manager encrypts the keys with DPAPI under the login of the user. They are
+//! stored for the local machine only, so they don't roam to the other machines of a domain
+//! profile: a machine is trusted when the key is remembered on it.
+
+use std::error;
+use std::fmt;
+use std::io;
+
+/// Service name the keys are stored under, the account is the vault name.
+const SERVICE: &str = "zpass";
+
+#[derive(Debug)]
+pub enum KeychainError {
+    Unavailable(keyring::Error),
+    CredentialManager(io::Error),
+}
+
+impl fmt::Display for KeychainError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Unavailable(ref err) => write!(f, "Keychain unavailable:\n{}", err),
+            Self::CredentialManager(ref err) => write!(f, "Credential Manager error:\n{}", err),
+        }
+    }
+}
+
+impl error::Error for KeychainError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Self::Unavailable(ref err) => Some(err),
+            Self::CredentialManager(ref err) => Some(err),
+        }
+    }
+}
+
+impl From<keyring::Error> for KeychainError {
+    fn from(err: keyring::Error) -> Self {
+        KeychainError::Unavailable(err)
+    }
+}
+
+impl From<io::Error> for KeychainError {
+    fn from(err: io::Error) -> Self {
+        KeychainError::CredentialManager(err)
+    }
+}
+
+/// Stores the key of a vault, replacing the stored one.
+pub fn store(vault: &str, key: &str) -> Result<(), KeychainError> {
+    sys::store(vault, key)
+}
+
+/// Returns the stored key of a vault, if the vault opted in.
+pub fn load(vault: &str) -> Result<Option<String>, KeychainError> {
+    sys::load(vault)
+}
+
+/// Removes the stored key of a vault, it's fine if there is none.
+pub fn forget(vault: &str) -> Result<(), KeychainError> {
+    sys::forget(vault)
+}
+
+#[cfg(windows)]
+mod sys {
+    use super::{KeychainError, SERVICE};
+    use crate::safe::memory;
+    use std::io;
+    use std::os::raw::{c_int, c_void};
+    use std::{ptr, slice};
+
+    /// Credentials that only the application reading them understands.
+    const CRED_TYPE_GENERIC: u32 = 1;
+    /// Credentials that stay on this machine, for every logon session of the user.
+    const CRED_PERSIST_LOCAL_MACHINE: u32 = 2;
+    /// The error of a credential that doesn't exist.
+    const ERROR_NOT_FOUND: i32 = 1168;
+
+    #[repr(C)]
+    struct FileTime {
+        low: u32,
+        high: u32,
+    }
+
+    /// The CREDENTIALW structure.
+    #[repr(C)]
+    struct Credential {
+        flags: u32,
+        kind: u32,
+        target_name: *mut u16,
+        comment: *mut u16,
+        last_written: FileTime,
+        blob_size: u32,
+        blob: *mut u8,
+        persist: u32,
+        attribute_count: u32,
+        attributes: *mut c_void,
+        target_alias: *mut u16,
+        user_name: *mut u16,
+    }
+
+    #[link(name = "advapi32")]
+    extern "system" {
+        fn CredWriteW(credential: *const Credential, flags: u32) -> c_int;
+        fn CredReadW(
+            target: *const u16,
+            kind: u32,
+            flags: u32,
+            credential: *mut *mut Credential,
+        ) -> c_int;
+        fn CredDeleteW(target: *const u16, kind: u32, flags: u32) -> c_int;
+        fn CredFree(buffer: *mut c_void);
+    }
+
+    /// Returns the text as a null terminated UTF-16 string.
+    fn wide(text: &str) -> Vec<u16> {
+        text.encode_utf16().chain(Some(0)).collect()
+    }
+
+    /// Returns the name the key of a vault is stored under, e.g. `zpass:personal`.
+    fn target(vault: &str) -> Vec<u16> {
+        wide(&format!("{}:{}", SERVICE, vault))
+    }
+
+    /// Returns the error of the last call, None if the credential doesn't exist.
+    fn last_error() -> Option<io::Error> {
+        let err = io::Error::last_os_error();
+        match err.raw_os_error() {
+            Some(ERROR_NOT_FOUND) => None,
+            _ => Some(err),
+        }
+    }
+
+    pub fn store(vault: &str, key: &str) -> Result<(), KeychainError> {
+        let mut target = target(vault);
+        let mut user = wide(vault);
+        let mut blob = key.as_bytes().to_vec();
+        let credential = Credential {
+            flags: 0,
+            kind: CRED_TYPE_GENERIC,
+            target_name: target.as_mut_ptr(),
+            comment: ptr::null_mut(),
+            last_written: FileTime { low: 0, high: 0 },
+            blob_size: blob.len() as u32,
+            blob: blob.as_mut_ptr(),
+            persist: CRED_PERSIST_LOCAL_MACHINE,
+            attribute_count: 0,
+            attributes: ptr::null_mut(),
+            target_alias: ptr::null_mut(),
+            user_name: user.as_mut_ptr(),
+        };
+        let written = unsafe { CredWriteW(&credential, 0) } != 0;
+        let err = io::Error::last_os_error();
+        memory::wipe(&mut blob);
+        if !written {
+            return Err(err.into());
+        }
+        Ok(())
+    }
+
+    pub fn load(vault: &str) -> Result<Option<String>, KeychainError> {
+        let target = target(vault);
+        let mut credential: *mut Credential = ptr::null_mut();
+        if unsafe { CredReadW(target.as_ptr(), CRED_TYPE_GENERIC, 0, &mut credential) } == 0 {
+            return match last_error() {
+                None => Ok(None),
+                Some(err) => Err(err.into()),
+            };
+        }
+        let key = unsafe {
+            let blob =
+                slice::from_raw_parts_mut((*credential).blob, (*credential).blob_size as usize);
+            let key = String::from_utf8_lossy(blob).into_owned();
+            memory::wipe(blob);
+            CredFree(credential as *mut c_void);
+            key
+        };
+        Ok(Some(key))
+    }
+
+    pub fn forget(vault: &str) -> Result<(), KeychainError> {
+        let target = target(vault);
+        if unsafe { CredDeleteW(target.as_ptr(), CRED_TYPE_GENERIC, 0) } == 0 {
+            if let Some(err) = last_error() {
+                return Err(err.into());
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The keyring crate talks to the Keychain and the Secret Service.
+#[cfg(not(windows))]
+mod sys {
+    use super::{KeychainError, SERVICE};
+
+    pub fn store(vault: &str, key: &str) -> Result<(), KeychainError> {
+        keyring::Entry::new(SERVICE, vault).set_password(key)?;
+        Ok(())
+    }
+
+    pub fn load(vault: &str) -> Result<Option<String>, KeychainError> {
+        match keyring::Entry::new(SERVICE, vault).get_password() {
+            Ok(key) => Ok(Some(key)),
+            Err(keyring::Error::NoEntry) => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    pub fn forget(vault: &str) -> Result<(), KeychainError> {
+        match keyring::Entry::new(SERVICE, vault).delete_password() {
+            Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+            Err(err) => Err(err.into()),
+        }
+    }
+}
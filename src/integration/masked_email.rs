@@ -0,0 +1,195 @@
+//! # Masked Email
+//! Creates email aliases with SimpleLogin or Firefox Relay, so an account gets a login of its
+//! own next to its password. The API token comes from `masked_email_token` of the config, best
+//! the sealed one, or from the keychain after `zpass remember --masked-email`.
+
+use serde::Serialize;
+use serde_json::Value;
+use std::error;
+use std::fmt;
+use std::io;
+
+/// Service of the aliases when none is configured.
+pub const DEFAULT_SERVICE: &str = "simplelogin";
+/// Endpoint of SimpleLogin that creates a random alias, the hostname is the site it is for.
+const SIMPLELOGIN_API: &str = "https://app.simplelogin.io/api/alias/random/new";
+/// Endpoint of Firefox Relay that creates a random address.
+const RELAY_API: &str = "https://relay.firefox.com/api/v1/relayaddresses/";
+
+#[derive(Debug)]
+pub enum MaskedEmailError {
+    RequestFailed(Box<ureq::Error>),
+    IOError(io::Error),
+    SerializationError(serde_json::Error),
+    UnknownService(String),
+    MissingToken(Service),
+    InvalidResponse(Service),
+}
+
+impl fmt::Display for MaskedEmailError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::RequestFailed(ref err) => write!(f, "Request for an alias failed:\n{}", err),
+            Self::IOError(ref err) => write!(f, "IO error:\n{}", err),
+            Self::SerializationError(ref err) => write!(f, "de/serialization error:\n{}", err),
+            Self::UnknownService(ref name) => write!(
+                f,
+                "Unknown masked email service {}, expected simplelogin or relay",
+                name
+            ),
+            Self::MissingToken(service) => write!(
+                f,
+                "No API token of {}, set masked_email_token in the config or run `zpass remember --masked-email`",
+                service
+            ),
+            Self::InvalidResponse(service) => {
+                write!(f, "The answer of {} has no alias", service)
+            }
+        }
+    }
+}
+
+impl error::Error for MaskedEmailError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Self::RequestFailed(ref err) => Some(err.as_ref()),
+            Self::IOError(ref err) => Some(err),
+            Self::SerializationError(ref err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<ureq::Error> for MaskedEmailError {
+    fn from(err: ureq::Error) -> Self {
+        MaskedEmailError::RequestFailed(Box::new(err))
+    }
+}
+
+impl From<io::Error> for MaskedEmailError {
+    fn from(err: io::Error) -> Self {
+        MaskedEmailError::IOError(err)
+    }
+}
+
+impl From<serde_json::Error> for MaskedEmailError {
+    fn from(err: serde_json::Error) -> Self {
+        MaskedEmailError::SerializationError(err)
+    }
+}
+
+/// What SimpleLogin is asked for.
+#[derive(Serialize)]
+struct NewAlias<'a> {
+    note: &'a str,
+}
+
+/// What Firefox Relay is asked for.
+#[derive(Serialize)]
+struct NewAddress<'a> {
+    enabled: bool,
+    description: &'a str,
+    generated_for: &'a str,
+}
+
+/// A service that forwards the mail of aliases.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Service {
+    SimpleLogin,
+    Relay,
+}
+
+impl Service {
+    /// Returns the service of a name, `simplelogin` or `relay`.
+    pub fn parse(name: &str) -> Result<Service, MaskedEmailError> {
+        match name {
+            "simplelogin" => Ok(Service::SimpleLogin),
+            "relay" => Ok(Service::Relay),
+            _ => Err(MaskedEmailError::UnknownService(name.to_owned())),
+        }
+    }
+
+    /// Returns the name the token of the service is kept under in the keychain.
+    pub fn keychain_name(self) -> &'static str {
+        match self {
+            Self::SimpleLogin => "masked-email:simplelogin",
+            Self::Relay => "masked-email:relay",
+        }
+    }
+}
+
+impl fmt::Display for Service {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::SimpleLogin => write!(f, "SimpleLogin"),
+            Self::Relay => write!(f, "Firefox Relay"),
+        }
+    }
+}
+
+/// Creates an alias for the domain and returns its address.
+pub fn create(service: Service, token: &str, domain: &str) -> Result<String, MaskedEmailError> {
+    let body = match service {
+        Service::SimpleLogin => {
+            ureq::post(&format!("{}?hostname={}", SIMPLELOGIN_API, encode(domain)))
+                .set("Authentication", token)
+                .set("Content-Type", "application/json")
+                .send_string(&serde_json::to_string(&NewAlias {
+                    note: "Created by zpass",
+                })?)?
+        }
+        Service::Relay => ureq::post(RELAY_API)
+            .set("Authorization", &format!("Token {}", token))
+            .set("Content-Type", "application/json")
+            .send_string(&serde_json::to_string(&NewAddress {
+                enabled: true,
+                description: domain,
+                generated_for: domain,
+            })?)?,
+    }
+    .into_string()?;
+    address(service, &body)
+}
+
+/// Returns the address of the alias in the answer of the service.
+fn address(service: Service, body: &str) -> Result<String, MaskedEmailError> {
+    let field = match service {
+        Service::SimpleLogin => "alias",
+        Service::Relay => "full_address",
+    };
+    serde_json::from_str::<Value>(body)
+        .ok()
+        .and_then(|answer| answer.get(field)?.as_str().map(|a| a.to_owned()))
+        .filter(|address| address.contains('@'))
+        .ok_or(MaskedEmailError::InvalidResponse(service))
+}
+
+/// Percent-encodes the characters of a domain that aren't safe in a query.
+fn encode(text: &str) -> String {
+    text.bytes()
+        .map(|b| match b {
+            b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_the_alias_of_each_service() {
+        let simplelogin = r#"{"alias": "shop.x7f2@simplelogin.com", "id": 1}"#;
+        let relay = r#"{"id": 2, "full_address": "k3j9x@mozmail.com"}"#;
+        assert_eq!(
+            address(Service::SimpleLogin, simplelogin).unwrap(),
+            "shop.x7f2@simplelogin.com"
+        );
+        assert_eq!(address(Service::Relay, relay).unwrap(), "k3j9x@mozmail.com");
+        assert!(address(Service::Relay, simplelogin).is_err());
+        assert_eq!(encode("shop.example/ä"), "shop.example%2F%C3%A4");
+    }
+}
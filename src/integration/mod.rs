@@ -0,0 +1,22 @@
+// agent keeps the keys of unlocked vaults for the command line and the integrations.
+pub mod agent;
+// autotype types credentials into the focused window.
+#[cfg(feature = "autotype")]
+pub mod autotype;
+// hibp checks passwords against the Have I Been Pwned breach database.
+#[cfg(feature = "hibp")]
+pub mod hibp;
+// keychain keeps vault keys in the credential store of the OS.
+#[cfg(feature = "keychain")]
+pub mod keychain;
+// masked_email creates email aliases with SimpleLogin or Firefox Relay.
+#[cfg(feature = "masked-email")]
+pub mod masked_email;
+// notify shows desktop notifications.
+pub mod notify;
+// secret_service serves a vault to other applications over D-Bus.
+#[cfg(feature = "secret-service")]
+pub mod secret_service;
+// ssh loads derived keys into the running ssh-agent.
+#[cfg(feature = "ssh")]
+pub mod ssh;
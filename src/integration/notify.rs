@@ -0,0 +1,57 @@
+//! # Notify
+//! Shows desktop notifications with the tools the platform comes with: `notify-send` on Linux
+//! and the BSDs, `osascript` on macOS and PowerShell on Windows. Notifications are a courtesy,
+//! a desktop without them only misses the message.
+
+use std::io;
+use std::process::{Command, Stdio};
+
+/// Title of the notifications.
+const TITLE: &str = "zpass";
+
+/// Shows a notification with the message, without waiting for it to go away.
+pub fn show(message: &str) -> io::Result<()> {
+    command(message)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+    Ok(())
+}
+
+#[cfg(target_os = "macos")]
+fn command(message: &str) -> Command {
+    let mut command = Command::new("osascript");
+    command.arg("-e").arg(format!(
+        "display notification {:?} with title {:?}",
+        message, TITLE
+    ));
+    command
+}
+
+/// A balloon of the notification area, it needs no app to be registered for toasts.
+#[cfg(windows)]
+fn command(message: &str) -> Command {
+    let quote = |text: &str| format!("'{}'", text.replace('\'', "''"));
+    let script = format!(
+        "Add-Type -AssemblyName System.Windows.Forms; \
+         $icon = New-Object System.Windows.Forms.NotifyIcon; \
+         $icon.Icon = [System.Drawing.SystemIcons]::Information; \
+         $icon.Visible = $true; \
+         $icon.ShowBalloonTip(5000, {}, {}, 'Info'); \
+         Start-Sleep -Seconds 5; \
+         $icon.Dispose()",
+        quote(TITLE),
+        quote(message)
+    );
+    let mut command = Command::new("powershell");
+    command.args(["-NoProfile", "-NonInteractive", "-Command", script.as_str()]);
+    command
+}
+
+#[cfg(not(any(target_os = "macos", windows)))]
+fn command(message: &str) -> Command {
+    let mut command = Command::new("notify-send");
+    command.args(["--app-name", TITLE, TITLE, message]);
+    command
+}
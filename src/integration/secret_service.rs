@@ -0,0 +1,674 @@
+//! # Secret Service
+//! Serves a vault over the org.freedesktop.secrets D-Bus API, so Linux applications such as
+//! NetworkManager, browsers and email clients can look up credentials like they would in
+//! GNOME Keyring.
+//!
+//! The vault is unlocked once when the daemon starts and exposed as a single read-only
+//! collection, which is also the `default` alias. Only the `plain` session algorithm is offered,
+//! the session bus is already private to the user.
+//!
+//! The passwords are wiped as soon as the screen locks or the machine suspends, as logind and
+//! the screen savers announce it, or when an application asks to lock the collection. The items
+//! can still be found then, but their secrets can't be read until `zpass serve` runs again.
+
+use crate::interop::{self, Account};
+use crate::safe::cache;
+use crate::safe::memory;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::error;
+use std::fmt;
+use std::mem;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::thread;
+use zbus::{dbus_interface, fdo, Connection, Message, ObjectServer};
+use zvariant::{OwnedObjectPath, OwnedValue, Value};
+
+/// Well known name of the Secret Service on the session bus.
+const BUS_NAME: &str = "org.freedesktop.secrets";
+/// Object path of the service.
+const SERVICE_PATH: &str = "/org/freedesktop/secrets";
+/// Object path of the collections.
+const COLLECTION_PREFIX: &str = "/org/freedesktop/secrets/collection";
+/// Object path shared by all the sessions, plain sessions have no state.
+const SESSION_PATH: &str = "/org/freedesktop/secrets/session/plain";
+/// The path that means no prompt is needed.
+const NO_PROMPT: &str = "/";
+/// The only session algorithm offered: secrets are sent as they are.
+const PLAIN: &str = "plain";
+/// Content type of the secrets.
+const CONTENT_TYPE: &str = "text/plain; charset=utf8";
+
+/// Interfaces and members of the signals that lock the vault: logind before a suspend and when
+/// the session is locked, on the system bus, and the screen savers, on the session bus.
+const LOCK_SIGNALS: &[(&str, &str)] = &[
+    ("org.freedesktop.login1.Manager", "PrepareForSleep"),
+    ("org.freedesktop.login1.Session", "Lock"),
+    ("org.freedesktop.ScreenSaver", "ActiveChanged"),
+    ("org.gnome.ScreenSaver", "ActiveChanged"),
+];
+
+/// A secret as sent over D-Bus: session, parameters, value and content type.
+type Secret = (OwnedObjectPath, Vec<u8>, Vec<u8>, String);
+
+#[derive(Debug)]
+pub enum SecretServiceError {
+    AlreadyRunning,
+    DBusError(zbus::Error),
+    NameRequestFailed(fdo::Error),
+    SubscriptionFailed(fdo::Error),
+    InvalidPath(zvariant::Error),
+}
+
+impl fmt::Display for SecretServiceError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::AlreadyRunning => write!(
+                f,
+                "Another Secret Service, such as GNOME Keyring, already owns {}",
+                BUS_NAME
+            ),
+            Self::DBusError(ref err) => write!(f, "D-Bus error:\n{}", err),
+            Self::NameRequestFailed(ref err) => {
+                write!(f, "Failed to request {}:\n{}", BUS_NAME, err)
+            }
+            Self::SubscriptionFailed(ref err) => {
+                write!(f, "Failed to watch for the screen lock:\n{}", err)
+            }
+            Self::InvalidPath(ref err) => write!(f, "Invalid object path:\n{}", err),
+        }
+    }
+}
+
+impl error::Error for SecretServiceError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Self::DBusError(ref err) => Some(err),
+            Self::NameRequestFailed(ref err) | Self::SubscriptionFailed(ref err) => Some(err),
+            Self::InvalidPath(ref err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<zbus::Error> for SecretServiceError {
+    fn from(err: zbus::Error) -> Self {
+        SecretServiceError::DBusError(err)
+    }
+}
+
+impl From<fdo::Error> for SecretServiceError {
+    fn from(err: fdo::Error) -> Self {
+        SecretServiceError::NameRequestFailed(err)
+    }
+}
+
+impl From<zvariant::Error> for SecretServiceError {
+    fn from(err: zvariant::Error) -> Self {
+        SecretServiceError::InvalidPath(err)
+    }
+}
+
+/// # Store
+/// The accounts of the served vault and their object paths.
+struct Store {
+    // label is the name of the vault
+    label: String,
+    // collection is the object path of the vault
+    collection: OwnedObjectPath,
+    // items are the accounts and their object paths, without their secrets
+    items: Vec<(OwnedObjectPath, Account)>,
+    // passwords of the items, in the same order, wiped when the store locks
+    passwords: Mutex<Vec<String>>,
+    // locked is true once the passwords are wiped
+    locked: AtomicBool,
+}
+
+impl Store {
+    /// Assigns object paths to the vault and its accounts. Only the passwords are kept apart,
+    /// the notes and second factors aren't served so they are wiped right away.
+    fn new(vault: &str, accounts: Vec<Account>) -> Result<Store, SecretServiceError> {
+        let collection = format!("{}/{}", COLLECTION_PREFIX, path_element(vault));
+        let mut passwords = Vec::new();
+        let items = accounts
+            .into_iter()
+            .enumerate()
+            .map(|(i, mut a)| {
+                passwords.push(mem::take(&mut a.password));
+                for text in a.notes.iter_mut().chain(a.totp.iter_mut()) {
+                    wipe(text);
+                }
+                Ok((
+                    OwnedObjectPath::try_from(format!("{}/{}", collection, i))?,
+                    a,
+                ))
+            })
+            .collect::<Result<_, SecretServiceError>>()?;
+        Ok(Store {
+            label: vault.to_owned(),
+            collection: OwnedObjectPath::try_from(collection)?,
+            items,
+            passwords: Mutex::new(passwords),
+            locked: AtomicBool::new(false),
+        })
+    }
+
+    /// Returns the passwords, a panic while they were held doesn't keep them from being wiped.
+    fn passwords(&self) -> MutexGuard<'_, Vec<String>> {
+        self.passwords
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Returns true once the passwords are wiped.
+    fn is_locked(&self) -> bool {
+        self.locked.load(Ordering::SeqCst)
+    }
+
+    /// Wipes the passwords and the decrypted vault secret, they can't be read again.
+    fn lock(&self) {
+        self.locked.store(true, Ordering::SeqCst);
+        for password in self.passwords().iter_mut() {
+            wipe(password);
+        }
+        cache::clear();
+    }
+
+    /// Returns the paths of the items with all the attributes.
+    fn search(&self, attributes: &HashMap<String, String>) -> Vec<OwnedObjectPath> {
+        self.items
+            .iter()
+            .filter(|(_, a)| matches(a, attributes))
+            .map(|(path, _)| path.clone())
+            .collect()
+    }
+
+    /// Returns the secret of an item.
+    fn secret(&self, item: &OwnedObjectPath) -> fdo::Result<Secret> {
+        let index = self
+            .items
+            .iter()
+            .position(|(path, _)| path == item)
+            .ok_or_else(|| fdo::Error::UnknownObject(item.as_str().to_owned()))?;
+        self.secret_at(index)
+    }
+
+    /// Returns the secret of the item at the index, unless the store is locked.
+    fn secret_at(&self, index: usize) -> fdo::Result<Secret> {
+        if self.is_locked() {
+            return Err(fdo::Error::Failed(format!(
+                "{} is locked, run zpass serve again to unlock it",
+                self.label
+            )));
+        }
+        secret(&self.passwords()[index])
+    }
+}
+
+/// # Service
+/// The org.freedesktop.Secret.Service interface.
+struct Service {
+    store: Arc<Store>,
+}
+
+#[dbus_interface(name = "org.freedesktop.Secret.Service")]
+impl Service {
+    fn open_session(
+        &self,
+        algorithm: &str,
+        _input: OwnedValue,
+    ) -> fdo::Result<(OwnedValue, OwnedObjectPath)> {
+        if algorithm != PLAIN {
+            return Err(fdo::Error::NotSupported(format!(
+                "Only the {} algorithm is supported",
+                PLAIN
+            )));
+        }
+        Ok((Value::from("").into(), owned(SESSION_PATH)?))
+    }
+
+    fn create_collection(
+        &self,
+        _properties: HashMap<String, OwnedValue>,
+        _alias: &str,
+    ) -> fdo::Result<(OwnedObjectPath, OwnedObjectPath)> {
+        Err(read_only())
+    }
+
+    fn search_items(
+        &self,
+        attributes: HashMap<String, String>,
+    ) -> (Vec<OwnedObjectPath>, Vec<OwnedObjectPath>) {
+        (self.store.search(&attributes), Vec::new())
+    }
+
+    /// Nothing is unlocked once the store is locked, the key is gone.
+    fn unlock(
+        &self,
+        objects: Vec<OwnedObjectPath>,
+    ) -> fdo::Result<(Vec<OwnedObjectPath>, OwnedObjectPath)> {
+        if self.store.is_locked() {
+            return Ok((Vec::new(), owned(NO_PROMPT)?));
+        }
+        Ok((objects, owned(NO_PROMPT)?))
+    }
+
+    /// Locking any object locks the whole vault.
+    fn lock(
+        &self,
+        objects: Vec<OwnedObjectPath>,
+    ) -> fdo::Result<(Vec<OwnedObjectPath>, OwnedObjectPath)> {
+        self.store.lock();
+        Ok((objects, owned(NO_PROMPT)?))
+    }
+
+    fn get_secrets(
+        &self,
+        items: Vec<OwnedObjectPath>,
+        _session: OwnedObjectPath,
+    ) -> fdo::Result<HashMap<OwnedObjectPath, Secret>> {
+        items
+            .into_iter()
+            .map(|item| Ok((item.clone(), self.store.secret(&item)?)))
+            .collect()
+    }
+
+    fn read_alias(&self, name: &str) -> fdo::Result<OwnedObjectPath> {
+        match name {
+            "default" => Ok(self.store.collection.clone()),
+            _ => owned(NO_PROMPT),
+        }
+    }
+
+    fn set_alias(&self, _name: &str, _collection: OwnedObjectPath) -> fdo::Result<()> {
+        Err(read_only())
+    }
+
+    #[dbus_interface(property)]
+    fn collections(&self) -> Vec<OwnedObjectPath> {
+        vec![self.store.collection.clone()]
+    }
+}
+
+/// # Collection
+/// The org.freedesktop.Secret.Collection interface of the vault.
+struct Collection {
+    store: Arc<Store>,
+}
+
+#[dbus_interface(name = "org.freedesktop.Secret.Collection")]
+impl Collection {
+    fn delete(&self) -> fdo::Result<OwnedObjectPath> {
+        Err(read_only())
+    }
+
+    fn search_items(&self, attributes: HashMap<String, String>) -> Vec<OwnedObjectPath> {
+        self.store.search(&attributes)
+    }
+
+    fn create_item(
+        &self,
+        _properties: HashMap<String, OwnedValue>,
+        _secret: Secret,
+        _replace: bool,
+    ) -> fdo::Result<(OwnedObjectPath, OwnedObjectPath)> {
+        Err(read_only())
+    }
+
+    #[dbus_interface(property)]
+    fn items(&self) -> Vec<OwnedObjectPath> {
+        self.store
+            .items
+            .iter()
+            .map(|(path, _)| path.clone())
+            .collect()
+    }
+
+    #[dbus_interface(property)]
+    fn label(&self) -> String {
+        self.store.label.clone()
+    }
+
+    #[dbus_interface(property)]
+    fn locked(&self) -> bool {
+        self.store.is_locked()
+    }
+
+    #[dbus_interface(property)]
+    fn created(&self) -> u64 {
+        0
+    }
+
+    #[dbus_interface(property)]
+    fn modified(&self) -> u64 {
+        0
+    }
+}
+
+/// # Item
+/// The org.freedesktop.Secret.Item interface of an account.
+struct Item {
+    store: Arc<Store>,
+    // index of the account in the store
+    index: usize,
+}
+
+impl Item {
+    fn account(&self) -> &Account {
+        &self.store.items[self.index].1
+    }
+}
+
+#[dbus_interface(name = "org.freedesktop.Secret.Item")]
+impl Item {
+    fn delete(&self) -> fdo::Result<OwnedObjectPath> {
+        Err(read_only())
+    }
+
+    fn get_secret(&self, _session: OwnedObjectPath) -> fdo::Result<Secret> {
+        self.store.secret_at(self.index)
+    }
+
+    fn set_secret(&self, _secret: Secret) -> fdo::Result<()> {
+        Err(read_only())
+    }
+
+    #[dbus_interface(property)]
+    fn locked(&self) -> bool {
+        self.store.is_locked()
+    }
+
+    #[dbus_interface(property)]
+    fn attributes(&self) -> HashMap<String, String> {
+        attributes(self.account())
+    }
+
+    #[dbus_interface(property)]
+    fn label(&self) -> String {
+        label(self.account())
+    }
+
+    #[dbus_interface(property)]
+    fn created(&self) -> u64 {
+        0
+    }
+
+    #[dbus_interface(property)]
+    fn modified(&self) -> u64 {
+        0
+    }
+}
+
+/// # Session
+/// The org.freedesktop.Secret.Session interface, closing a plain session is a no-op.
+struct Session;
+
+#[dbus_interface(name = "org.freedesktop.Secret.Session")]
+impl Session {
+    fn close(&self) {}
+}
+
+/// Takes the Secret Service name on the session bus and answers requests until killed.
+/// The vault locks when the screen locks or the machine suspends.
+pub fn serve(vault: &str, accounts: Vec<Account>) -> Result<(), SecretServiceError> {
+    let connection = Connection::new_session()?;
+    let reply = fdo::DBusProxy::new(&connection)?
+        .request_name(BUS_NAME, fdo::RequestNameFlags::DoNotQueue.into())?;
+    if !matches!(reply, fdo::RequestNameReply::PrimaryOwner) {
+        return Err(SecretServiceError::AlreadyRunning);
+    }
+
+    subscribe(&connection)?;
+    let store = Arc::new(Store::new(vault, accounts)?);
+    watch_system_bus(store.clone());
+    let mut server = ObjectServer::new(&connection);
+    server.at(
+        SERVICE_PATH,
+        Service {
+            store: store.clone(),
+        },
+    )?;
+    server.at(SESSION_PATH, Session)?;
+    server.at(
+        store.collection.as_str(),
+        Collection {
+            store: store.clone(),
+        },
+    )?;
+    for (index, (path, _)) in store.items.iter().enumerate() {
+        let item = Item {
+            store: store.clone(),
+            index,
+        };
+        server.at(path.as_str(), item)?;
+    }
+    // the messages the server doesn't handle are the signals of the screen savers
+    loop {
+        match server.try_handle_next() {
+            Ok(Some(message)) => on_message(&store, &message),
+            Ok(None) => {}
+            Err(err) => eprintln!("Secret Service error:\n{}", err),
+        }
+    }
+}
+
+/// Asks the bus for the lock signals that are sent on it.
+fn subscribe(connection: &Connection) -> Result<(), SecretServiceError> {
+    let proxy = fdo::DBusProxy::new(connection)?;
+    for (interface, member) in LOCK_SIGNALS {
+        let rule = format!(
+            "type='signal',interface='{}',member='{}'",
+            interface, member
+        );
+        proxy
+            .add_match(&rule)
+            .map_err(SecretServiceError::SubscriptionFailed)?;
+    }
+    Ok(())
+}
+
+/// Locks the store on the signals of logind, which are sent on the system bus. Without the
+/// system bus, e.g. in a container, only the screen savers lock the store.
+fn watch_system_bus(store: Arc<Store>) {
+    watch(Connection::new_system, "suspend", move || {
+        lock_store(&store)
+    });
+}
+
+/// Calls on_lock when the screen locks or the machine suspends, for programs that keep keys
+/// without serving the Secret Service, such as the agent. Each bus is watched on a thread of
+/// its own, a bus that isn't there is only reported.
+pub fn watch_lock_signals(on_lock: impl Fn() + Clone + Send + 'static) {
+    watch(Connection::new_session, "the screen lock", on_lock.clone());
+    watch(Connection::new_system, "suspend", on_lock);
+}
+
+/// Subscribes to the lock signals on a connection to a bus and calls on_lock on each of them,
+/// on a thread of its own. What names the event in the errors.
+fn watch(
+    connect: fn() -> zbus::Result<Connection>,
+    what: &'static str,
+    on_lock: impl Fn() + Send + 'static,
+) {
+    thread::spawn(move || {
+        let connection = match connect() {
+            Ok(connection) => connection,
+            Err(err) => return eprintln!("Failed to watch for {}:\n{}", what, err),
+        };
+        if let Err(err) = subscribe(&connection) {
+            return eprintln!("{}", err);
+        }
+        loop {
+            match connection.receive_message() {
+                Ok(message) if is_lock_signal(&message) => on_lock(),
+                Ok(_) => {}
+                Err(err) => return eprintln!("Failed to watch for {}:\n{}", what, err),
+            }
+        }
+    });
+}
+
+/// Locks the store if the message is one of the lock signals.
+fn on_message(store: &Store, message: &Message) {
+    if is_lock_signal(message) {
+        lock_store(store);
+    }
+}
+
+/// Locks the store unless it is locked already.
+fn lock_store(store: &Store) {
+    if !store.is_locked() {
+        store.lock();
+        eprintln!("Locked {}, run zpass serve again to unlock it", store.label);
+    }
+}
+
+/// Returns true if the message is one of the lock signals.
+fn is_lock_signal(message: &Message) -> bool {
+    let header = match message.header() {
+        Ok(header) => header,
+        Err(_) => return false,
+    };
+    match (header.interface(), header.member()) {
+        (Ok(Some(interface)), Ok(Some(member))) => {
+            locks(interface, member, message.body::<bool>().ok())
+        }
+        _ => false,
+    }
+}
+
+/// Returns true if a signal locks the store. PrepareForSleep and ActiveChanged carry true when
+/// the machine is about to sleep or the screen saver starts, and false when they are over.
+fn locks(interface: &str, member: &str, active: Option<bool>) -> bool {
+    LOCK_SIGNALS.contains(&(interface, member)) && active != Some(false)
+}
+
+/// Overwrites a text with zeros and empties it.
+fn wipe(text: &mut String) {
+    // zeros are valid UTF-8, the string stays a string
+    memory::wipe(unsafe { text.as_bytes_mut() });
+    text.clear();
+}
+
+/// Returns true if the account has all the attributes. Applications name the site and the
+/// user in different ways, the common names are understood, other attributes never match.
+fn matches(account: &Account, attributes: &HashMap<String, String>) -> bool {
+    attributes.iter().all(|(name, value)| match name.as_str() {
+        "xdg:schema" => true,
+        "domain" | "server" | "host" | "service" | "origin" | "url" => {
+            interop::domain_of(value) == account.domain
+        }
+        "username" | "user" | "account" => *value == account.username,
+        _ => false,
+    })
+}
+
+/// Returns the attributes an account is stored with.
+fn attributes(account: &Account) -> HashMap<String, String> {
+    let mut attributes = HashMap::new();
+    attributes.insert("domain".to_owned(), account.domain.clone());
+    attributes.insert("username".to_owned(), account.username.clone());
+    attributes
+}
+
+/// Returns the name an account is shown with.
+fn label(account: &Account) -> String {
+    if account.username.is_empty() {
+        account.domain.clone()
+    } else {
+        format!("{} ({})", account.domain, account.username)
+    }
+}
+
+/// Returns a password as a plain secret.
+fn secret(password: &str) -> fdo::Result<Secret> {
+    Ok((
+        owned(SESSION_PATH)?,
+        Vec::new(),
+        password.as_bytes().to_vec(),
+        CONTENT_TYPE.to_owned(),
+    ))
+}
+
+/// Escapes a name into an object path element, which only allows [A-Za-z0-9_].
+fn path_element(name: &str) -> String {
+    name.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' => (b as char).to_string(),
+            _ => format!("_{:02x}", b),
+        })
+        .collect()
+}
+
+/// Converts a constant path.
+fn owned(path: &str) -> fdo::Result<OwnedObjectPath> {
+    OwnedObjectPath::try_from(path).map_err(|e| fdo::Error::Failed(e.to_string()))
+}
+
+/// The error of every request that would change the vault.
+fn read_only() -> fdo::Error {
+    fdo::Error::NotSupported("zpass serves vaults read-only".to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_common_attributes() {
+        let account = Account {
+            domain: "example.com".to_owned(),
+            username: "alice".to_owned(),
+            ..Account::default()
+        };
+        let search = |pairs: &[(&str, &str)]| {
+            let attributes: HashMap<String, String> = pairs
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect();
+            matches(&account, &attributes)
+        };
+
+        assert!(search(&[]));
+        assert!(search(&[("server", "example.com"), ("user", "alice")]));
+        assert!(search(&[("url", "https://www.example.com/login")]));
+        assert!(search(&[(
+            "xdg:schema",
+            "org.gnome.keyring.NetworkPassword"
+        )]));
+        assert!(!search(&[("user", "bob")]));
+        assert!(!search(&[("ssid", "home")]));
+        assert_eq!(path_element("my-vault"), "my_2dvault");
+    }
+
+    #[test]
+    fn locks_on_screen_lock_and_suspend() {
+        assert!(locks("org.freedesktop.login1.Session", "Lock", None));
+        assert!(locks(
+            "org.freedesktop.login1.Manager",
+            "PrepareForSleep",
+            Some(true)
+        ));
+        assert!(!locks(
+            "org.freedesktop.login1.Manager",
+            "PrepareForSleep",
+            Some(false)
+        ));
+        assert!(!locks("org.freedesktop.login1.Session", "Unlock", None));
+
+        let account = Account {
+            domain: "example.com".to_owned(),
+            password: "secret".to_owned(),
+            notes: Some("recovery codes".to_owned()),
+            ..Account::default()
+        };
+        let store = Store::new("example", vec![account]).unwrap();
+        assert_eq!(store.items[0].1.notes.as_deref(), Some(""));
+        let path = store.items[0].0.clone();
+        assert_eq!(store.secret(&path).unwrap().2, b"secret".to_vec());
+        store.lock();
+        assert!(store.is_locked() && store.secret(&path).is_err());
+        assert_eq!(store.passwords()[0], "");
+    }
+}
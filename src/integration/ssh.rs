@@ -0,0 +1,187 @@
+//! # SSH
+//! Loads Ed25519 keys derived from the vault secret into the running ssh-agent,
+//! speaking the agent protocol over the socket in `SSH_AUTH_SOCK`, so private keys never touch
+//! the disk. The same site always derives the same key, so its public key only has to be
+//! authorized once.
+
+use ed25519_dalek::{PublicKey, SecretKey};
+use std::env;
+use std::error;
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::os::unix::net::UnixStream;
+
+/// Environment variable holding the path of the agent socket.
+const AGENT_SOCKET_VARIABLE: &str = "SSH_AUTH_SOCK";
+/// Key type name used in the agent protocol and in authorized_keys.
+const KEY_TYPE: &str = "ssh-ed25519";
+/// Message asking the agent to hold a key.
+const SSH2_AGENTC_ADD_IDENTITY: u8 = 17;
+/// Reply of the agent when the key was added.
+const SSH_AGENT_SUCCESS: u8 = 6;
+/// Replies longer than this are not from an agent.
+const MAX_REPLY_LENGTH: usize = 256 * 1024;
+/// Standard base64 alphabet, used by authorized_keys.
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+#[derive(Debug)]
+pub enum SshError {
+    NoAgent,
+    IOError(io::Error),
+    InvalidKey,
+    Refused,
+}
+
+impl fmt::Display for SshError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::NoAgent => write!(
+                f,
+                "No ssh-agent is running, {} is not set",
+                AGENT_SOCKET_VARIABLE
+            ),
+            Self::IOError(ref err) => write!(f, "Failed to talk to the ssh-agent:\n{}", err),
+            Self::InvalidKey => write!(f, "The derived bytes are not an Ed25519 key"),
+            Self::Refused => write!(f, "The ssh-agent refused the key"),
+        }
+    }
+}
+
+impl error::Error for SshError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Self::IOError(ref err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for SshError {
+    fn from(err: io::Error) -> Self {
+        SshError::IOError(err)
+    }
+}
+
+/// # KeyPair
+/// An Ed25519 key derived from the vault secret.
+pub struct KeyPair {
+    // secret is the 32 byte seed of the key
+    secret: SecretKey,
+    // public is the key to put in authorized_keys
+    public: PublicKey,
+}
+
+impl KeyPair {
+    /// Creates the key pair from a 32 byte seed.
+    pub fn from_seed(seed: &[u8]) -> Result<KeyPair, SshError> {
+        let secret = SecretKey::from_bytes(seed).map_err(|_| SshError::InvalidKey)?;
+        let public = PublicKey::from(&secret);
+        Ok(KeyPair { secret, public })
+    }
+
+    /// Returns the public key as an authorized_keys line.
+    pub fn authorized_key(&self, comment: &str) -> String {
+        let mut blob = Vec::new();
+        put_string(&mut blob, KEY_TYPE.as_bytes());
+        put_string(&mut blob, self.public.as_bytes());
+        format!("{} {} {}", KEY_TYPE, base64(&blob), comment)
+    }
+
+    /// Returns the agent message that adds the key under the comment.
+    fn add_identity_message(&self, comment: &str) -> Vec<u8> {
+        let mut private = self.secret.as_bytes().to_vec();
+        private.extend_from_slice(self.public.as_bytes());
+        let mut body = vec![SSH2_AGENTC_ADD_IDENTITY];
+        put_string(&mut body, KEY_TYPE.as_bytes());
+        put_string(&mut body, self.public.as_bytes());
+        put_string(&mut body, &private);
+        put_string(&mut body, comment.as_bytes());
+        let mut message = (body.len() as u32).to_be_bytes().to_vec();
+        message.extend(body);
+        message
+    }
+}
+
+/// Hands the key to the running agent, which keeps it in memory only.
+pub fn add(pair: &KeyPair, comment: &str) -> Result<(), SshError> {
+    let socket = env::var_os(AGENT_SOCKET_VARIABLE).ok_or(SshError::NoAgent)?;
+    let mut agent = UnixStream::connect(socket)?;
+    agent.write_all(&pair.add_identity_message(comment))?;
+    let mut length = [0; 4];
+    agent.read_exact(&mut length)?;
+    let length = u32::from_be_bytes(length) as usize;
+    if length == 0 || length > MAX_REPLY_LENGTH {
+        return Err(SshError::Refused);
+    }
+    let mut reply = vec![0; length];
+    agent.read_exact(&mut reply)?;
+    match reply[0] {
+        SSH_AGENT_SUCCESS => Ok(()),
+        _ => Err(SshError::Refused),
+    }
+}
+
+/// Connects to the running agent, to find out if `add` can reach it.
+pub fn reach_agent() -> Result<(), SshError> {
+    let socket = env::var_os(AGENT_SOCKET_VARIABLE).ok_or(SshError::NoAgent)?;
+    UnixStream::connect(socket)?;
+    Ok(())
+}
+
+/// Appends a length prefixed string, as defined in RFC 4251.
+fn put_string(buffer: &mut Vec<u8>, bytes: &[u8]) {
+    buffer.extend_from_slice(&(bytes.len() as u32).to_be_bytes());
+    buffer.extend_from_slice(bytes);
+}
+
+/// Encodes bytes as padded base64.
+fn base64(bytes: &[u8]) -> String {
+    let mut encoded = String::new();
+    for chunk in bytes.chunks(3) {
+        let mut group = [0; 3];
+        group[..chunk.len()].copy_from_slice(chunk);
+        let bits = (group[0] as u32) << 16 | (group[1] as u32) << 8 | group[2] as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(BASE64_ALPHABET[(bits >> (18 - 6 * i)) as usize & 63] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn base64_test_vectors() {
+        for (plain, encoded) in &[
+            ("f", "Zg=="),
+            ("fo", "Zm8="),
+            ("foo", "Zm9v"),
+            ("foob", "Zm9vYg=="),
+            ("foobar", "Zm9vYmFy"),
+        ] {
+            assert_eq!(base64(plain.as_bytes()), *encoded);
+        }
+    }
+
+    #[test]
+    fn add_identity_layout() {
+        let pair = KeyPair::from_seed(&[7; 32]).unwrap();
+        let message = pair.add_identity_message("zpass:host.example");
+        let body = &message[4..];
+
+        assert_eq!(message[..4], (body.len() as u32).to_be_bytes());
+        assert_eq!(body[0], SSH2_AGENTC_ADD_IDENTITY);
+        assert_eq!(&body[1..5], &(KEY_TYPE.len() as u32).to_be_bytes());
+        assert_eq!(body.len(), 1 + (4 + 11) + (4 + 32) + (4 + 64) + (4 + 18));
+        assert!(pair
+            .authorized_key("zpass:host.example")
+            .starts_with("ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAI"));
+    }
+}
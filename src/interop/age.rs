@@ -0,0 +1,77 @@
+//! # Age
+//! Encrypts and decrypts with age (https://age-encryption.org) through its command line tool,
+//! which has to be installed. Recipients are age or ssh public keys, identities are key files.
+
+use std::error;
+use std::fmt;
+use std::io::{self, Write};
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::thread;
+
+#[derive(Debug)]
+pub enum AgeError {
+    IOError(io::Error),
+    Failed(String),
+}
+
+impl fmt::Display for AgeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::IOError(ref err) => write!(f, "Failed to run age:\n{}", err),
+            Self::Failed(ref reason) => write!(f, "age failed:\n{}", reason),
+        }
+    }
+}
+
+impl error::Error for AgeError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Self::IOError(ref err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for AgeError {
+    fn from(err: io::Error) -> Self {
+        AgeError::IOError(err)
+    }
+}
+
+/// Encrypts the plaintext to the recipient.
+pub fn encrypt(recipient: &str, plaintext: &[u8]) -> Result<Vec<u8>, AgeError> {
+    let mut command = Command::new("age");
+    command.args(["--encrypt", "--recipient", recipient]);
+    run(command, plaintext)
+}
+
+/// Decrypts the ciphertext with the identity file.
+pub fn decrypt(identity: &Path, ciphertext: &[u8]) -> Result<Vec<u8>, AgeError> {
+    let mut command = Command::new("age");
+    command.arg("--decrypt").arg("--identity").arg(identity);
+    run(command, ciphertext)
+}
+
+/// Runs age with the input on stdin and returns its stdout.
+/// The input is written from another thread, so age never blocks on a full stdout pipe.
+fn run(mut command: Command, input: &[u8]) -> Result<Vec<u8>, AgeError> {
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    let writer = child.stdin.take().map(|mut stdin| {
+        let input = input.to_vec();
+        thread::spawn(move || stdin.write_all(&input))
+    });
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        let reason = String::from_utf8_lossy(&output.stderr).trim().to_owned();
+        return Err(AgeError::Failed(reason));
+    }
+    if let Some(Ok(written)) = writer.map(|w| w.join()) {
+        written?;
+    }
+    Ok(output.stdout)
+}
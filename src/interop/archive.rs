@@ -0,0 +1,98 @@
+//! # Archive
+//! Bundles serialized vaults into a single document, so they can be encrypted and moved as one.
+//! Archives carry a format version and newer archives are rejected instead of misread.
+
+use serde::{Deserialize, Serialize};
+use std::error;
+use std::fmt;
+
+/// Version of the archive format written by this build.
+pub const FORMAT_VERSION: u32 = 1;
+
+#[derive(Debug)]
+pub enum ArchiveError {
+    SerializationError(serde_json::Error),
+    UnsupportedVersion(u32),
+}
+
+impl fmt::Display for ArchiveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::SerializationError(ref err) => write!(f, "Not an archive:\n{}", err),
+            Self::UnsupportedVersion(version) => write!(
+                f,
+                "Archive format {} is newer than the supported format {}, upgrade zpass",
+                version, FORMAT_VERSION
+            ),
+        }
+    }
+}
+
+impl error::Error for ArchiveError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Self::SerializationError(ref err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<serde_json::Error> for ArchiveError {
+    fn from(err: serde_json::Error) -> Self {
+        ArchiveError::SerializationError(err)
+    }
+}
+
+/// # Archive
+/// A set of vaults as they are stored on disk.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct Archive {
+    // version is the format of the archive
+    pub version: u32,
+    // vaults are the serialized vaults
+    pub vaults: Vec<String>,
+}
+
+impl Archive {
+    /// Creates an archive of serialized vaults.
+    pub fn new(vaults: Vec<String>) -> Archive {
+        Archive {
+            version: FORMAT_VERSION,
+            vaults,
+        }
+    }
+
+    /// Serializes the archive.
+    pub fn pack(&self) -> Result<Vec<u8>, ArchiveError> {
+        Ok(serde_json::to_vec(self)?)
+    }
+
+    /// Deserializes an archive, checking that its format is supported.
+    pub fn unpack(bytes: &[u8]) -> Result<Archive, ArchiveError> {
+        let archive: Archive = serde_json::from_slice(bytes)?;
+        check_version(archive.version)?;
+        Ok(archive)
+    }
+}
+
+/// Fails for formats that are newer than this build understands.
+fn check_version(version: u32) -> Result<(), ArchiveError> {
+    if version > FORMAT_VERSION {
+        return Err(ArchiveError::UnsupportedVersion(version));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rejects_newer_formats() {
+        assert!(check_version(FORMAT_VERSION).is_ok());
+        assert!(matches!(
+            check_version(FORMAT_VERSION + 1),
+            Err(ArchiveError::UnsupportedVersion(_))
+        ));
+    }
+}
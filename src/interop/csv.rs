@@ -0,0 +1,238 @@
+//! # CSV
+//! Reads the CSV exports of other password managers (RFC 4180).
+//! Columns are located by their header name so their order doesn't matter.
+
+use super::{domain_of, Account, Rows};
+use std::error;
+use std::fmt;
+use std::str::FromStr;
+
+/// LastPass exports secure notes as rows with this url.
+const LASTPASS_NOTE_URL: &str = "http://sn";
+
+#[derive(Debug, PartialEq)]
+pub enum CsvError {
+    UnterminatedQuote,
+    UnknownFormat(String),
+    MissingColumn(&'static str),
+}
+
+impl fmt::Display for CsvError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::UnterminatedQuote => write!(f, "A quoted field is never closed"),
+            Self::UnknownFormat(ref format) => write!(
+                f,
+                "Unknown format {}, expected lastpass, chrome or generic",
+                format
+            ),
+            Self::MissingColumn(column) => write!(f, "Missing the {} column", column),
+        }
+    }
+}
+
+impl error::Error for CsvError {}
+
+/// The password managers whose exports can be read.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Format {
+    LastPass,
+    Chrome,
+    Generic,
+}
+
+impl FromStr for Format {
+    type Err = CsvError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match &s.to_lowercase()[..] {
+            "lastpass" => Ok(Format::LastPass),
+            "chrome" => Ok(Format::Chrome),
+            "generic" => Ok(Format::Generic),
+            _ => Err(CsvError::UnknownFormat(s.to_owned())),
+        }
+    }
+}
+
+impl Format {
+    /// Returns the accepted header names of the domain, username and password columns.
+    fn headers(self) -> [&'static [&'static str]; 3] {
+        match self {
+            Format::LastPass | Format::Chrome => [&["url"], &["username"], &["password"]],
+            Format::Generic => [
+                &["domain", "url", "website", "site"],
+                &["username", "user", "login", "email"],
+                &["password"],
+            ],
+        }
+    }
+}
+
+/// Reads the accounts of an export in the given format.
+pub fn read_accounts(input: &str, format: Format) -> Result<Rows, CsvError> {
+    let mut records = records(input)?.into_iter();
+    let header = records.next().unwrap_or_default();
+    let [domains, usernames, passwords] = format.headers();
+    let domain = column(&header, domains).ok_or(CsvError::MissingColumn(domains[0]))?;
+    let username = column(&header, usernames).ok_or(CsvError::MissingColumn(usernames[0]))?;
+    let password = column(&header, passwords).ok_or(CsvError::MissingColumn(passwords[0]))?;
+
+    let mut rows = Rows {
+        accounts: Vec::new(),
+        skipped: 0,
+    };
+    for record in records {
+        let field = |i: usize| record.get(i).map_or("", |f| f.trim());
+        if format == Format::LastPass && field(domain) == LASTPASS_NOTE_URL {
+            rows.skipped += 1;
+            continue;
+        }
+        let site = domain_of(field(domain));
+        if site.is_empty() || field(password).is_empty() {
+            rows.skipped += 1;
+            continue;
+        }
+        rows.accounts.push(Account {
+            domain: site,
+            username: field(username).to_owned(),
+            password: record[password].clone(),
+            tags: Vec::new(),
+            notes: None,
+            totp: None,
+        });
+    }
+    Ok(rows)
+}
+
+/// Writes the accounts as domain, username and password rows, which the generic format reads back.
+pub fn write_accounts(accounts: &[Account]) -> String {
+    let mut output = String::from("domain,username,password\r\n");
+    for a in accounts {
+        output.push_str(&format!(
+            "{},{},{}\r\n",
+            quote(&a.domain),
+            quote(&a.username),
+            quote(&a.password)
+        ));
+    }
+    output
+}
+
+/// Quotes a field if it contains a separator, a quote or a line break.
+fn quote(field: &str) -> String {
+    if field.contains(&[',', '"', '\r', '\n'][..]) || field.trim() != field {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_owned()
+    }
+}
+
+/// Returns the position of the first header matching one of the names.
+fn column(header: &[String], names: &[&str]) -> Option<usize> {
+    header
+        .iter()
+        .position(|h| names.iter().any(|n| h.trim().eq_ignore_ascii_case(n)))
+}
+
+/// Splits the input in records of fields. Blank lines are ignored.
+fn records(input: &str) -> Result<Vec<Vec<String>>, CsvError> {
+    let input = input.trim_start_matches('\u{feff}');
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = input.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (quoted, c) {
+            (true, '"') if chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            (true, '"') => quoted = false,
+            (true, c) => field.push(c),
+            (false, '"') => quoted = true,
+            (false, ',') => record.push(std::mem::take(&mut field)),
+            (false, '\r') => {}
+            (false, '\n') => {
+                record.push(std::mem::take(&mut field));
+                let record = std::mem::take(&mut record);
+                if record != [""] {
+                    records.push(record);
+                }
+            }
+            (false, c) => field.push(c),
+        }
+    }
+    if quoted {
+        return Err(CsvError::UnterminatedQuote);
+    }
+    if !field.is_empty() || !record.is_empty() {
+        record.push(field);
+        records.push(record);
+    }
+    Ok(records)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_exports() {
+        let lastpass = "\u{feff}url,username,password,totp,extra,name,grouping,fav\r\n\
+                        https://www.example.com/login,alice,\"p,a\"\"ss\",,,Example,,0\r\n\
+                        http://sn,,,,a note,Note,,0\r\n";
+        let rows = read_accounts(lastpass, Format::LastPass).unwrap();
+        assert_eq!(
+            rows.accounts,
+            vec![Account {
+                domain: "example.com".to_owned(),
+                username: "alice".to_owned(),
+                password: "p,a\"ss".to_owned(),
+                tags: Vec::new(),
+                notes: None,
+                totp: None,
+            }]
+        );
+        assert_eq!(rows.skipped, 1);
+
+        let generic = "Password,Login,Website\nsecret,bob,mail.example.org\n";
+        let rows = read_accounts(generic, Format::Generic).unwrap();
+        assert_eq!(rows.accounts[0].domain, "mail.example.org");
+        assert_eq!(rows.accounts[0].username, "bob");
+
+        assert_eq!(
+            read_accounts("name,url\n", Format::Chrome),
+            Err(CsvError::MissingColumn("username"))
+        );
+        assert_eq!(
+            read_accounts("url,username,password\n\"x,y,z", Format::Chrome),
+            Err(CsvError::UnterminatedQuote)
+        );
+    }
+
+    #[test]
+    fn writes_what_it_reads() {
+        let accounts = vec![
+            Account {
+                domain: "example.com".to_owned(),
+                username: "alice".to_owned(),
+                password: "a,\"b\"\nc".to_owned(),
+                tags: Vec::new(),
+                notes: None,
+                totp: None,
+            },
+            Account {
+                domain: "example.org".to_owned(),
+                username: "".to_owned(),
+                password: " padded ".to_owned(),
+                tags: Vec::new(),
+                notes: None,
+                totp: None,
+            },
+        ];
+        let rows = read_accounts(&write_accounts(&accounts), Format::Generic).unwrap();
+        assert_eq!(rows.accounts, accounts);
+        assert_eq!(rows.skipped, 0);
+    }
+}
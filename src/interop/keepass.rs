@@ -0,0 +1,92 @@
+//! # KeePass
+//! Writes accounts as a KeePass 2.x XML file, which KeePass and KeePassXC can import
+//! (e.g. `keepassxc-cli import export.xml passwords.kdbx`).
+//! The XML is not encrypted, the passwords in it are plaintext.
+
+use super::Account;
+
+/// Returns the KeePass XML document holding the accounts in a single group.
+pub fn to_xml(group: &str, accounts: &[Account]) -> String {
+    let mut xml = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>\n\
+         <KeePassFile>\n\
+         \t<Meta>\n\
+         \t\t<Generator>zpass</Generator>\n\
+         \t</Meta>\n\
+         \t<Root>\n\
+         \t\t<Group>\n",
+    );
+    xml.push_str(&format!("\t\t\t<Name>{}</Name>\n", escape(group)));
+    for a in accounts {
+        xml.push_str("\t\t\t<Entry>\n");
+        xml.push_str(&field("Title", &a.domain, false));
+        xml.push_str(&field("UserName", &a.username, false));
+        xml.push_str(&field("Password", &a.password, true));
+        xml.push_str(&field("URL", &format!("https://{}", a.domain), false));
+        if let Some(notes) = &a.notes {
+            xml.push_str(&field("Notes", notes, false));
+        }
+        if let Some(totp) = &a.totp {
+            xml.push_str(&field("otp", totp, true));
+        }
+        xml.push_str("\t\t\t</Entry>\n");
+    }
+    xml.push_str("\t\t</Group>\n\t</Root>\n</KeePassFile>\n");
+    xml
+}
+
+/// Returns a String element of an entry.
+fn field(key: &str, value: &str, protected: bool) -> String {
+    let protect = if protected {
+        " ProtectInMemory=\"True\""
+    } else {
+        ""
+    };
+    format!(
+        "\t\t\t\t<String><Key>{}</Key><Value{}>{}</Value></String>\n",
+        key,
+        protect,
+        escape(value)
+    )
+}
+
+/// Escapes the characters that have a meaning in XML.
+fn escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escapes_entries() {
+        let xml = to_xml(
+            "personal",
+            &[Account {
+                domain: "example.com".to_owned(),
+                username: "alice".to_owned(),
+                password: "<a&b>".to_owned(),
+                tags: Vec::new(),
+                notes: None,
+                totp: None,
+            }],
+        );
+        assert!(xml.contains("<Name>personal</Name>"));
+        assert!(xml.contains(
+            "<String><Key>Password</Key><Value ProtectInMemory=\"True\">&lt;a&amp;b&gt;</Value></String>"
+        ));
+        assert!(xml.contains("<Value>https://example.com</Value>"));
+    }
+}
@@ -0,0 +1,322 @@
+//! # Interop
+//! Moves accounts between zpass and other password managers.
+
+// age encrypts archives for backups and transfers.
+#[cfg(feature = "import")]
+pub mod age;
+// archive bundles vaults into a single document.
+#[cfg(feature = "import")]
+pub mod archive;
+// csv reads the CSV exports of other password managers.
+#[cfg(feature = "import")]
+pub mod csv;
+// keepass writes files that KeePass and KeePassXC can import.
+#[cfg(feature = "import")]
+pub mod keepass;
+// onepassword reads the 1PUX exports of 1Password.
+#[cfg(feature = "import")]
+pub mod onepassword;
+// paper encodes vault secrets for printed backups.
+#[cfg(feature = "import")]
+pub mod paper;
+// pass reads and writes the password store of pass(1).
+#[cfg(feature = "import")]
+pub mod pass;
+// template renders secrets into files.
+pub mod template;
+// transfer moves a vault to another machine as a series of encrypted QR codes.
+#[cfg(feature = "import")]
+pub mod transfer;
+// zvault moves a single vault between machines as one encrypted file.
+#[cfg(feature = "import")]
+pub mod zvault;
+
+use crate::safe::crypto::{PasswordGenerator, Sealed, SecretStore};
+use crate::safe::hooks::{self, Event};
+use crate::safe::idn;
+use crate::safe::preference::Preference;
+use crate::safe::vault::{Vault, VaultError};
+use serde::Serialize;
+use std::collections::HashSet;
+use std::panic;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+/// An account as it is exchanged with other password managers.
+#[derive(Debug, Default, PartialEq)]
+pub struct Account {
+    // domain is the bare host name of the site, e.g. example.com
+    pub domain: String,
+    pub username: String,
+    pub password: String,
+    // tags group the account, e.g. the folders it was in
+    pub tags: Vec<String>,
+    pub notes: Option<String>,
+    // totp is the otpauth:// URI of the second factor
+    pub totp: Option<String>,
+}
+
+/// The outcome of an import.
+#[derive(Debug, Default, PartialEq)]
+pub struct Summary {
+    // added is the number of accounts that were stored in the vault
+    pub added: usize,
+    // duplicates is the number of accounts that were already in the vault or earlier in the export
+    pub duplicates: usize,
+    // failed are the accounts that couldn't be stored, in the order of the export
+    pub failed: Vec<Failure>,
+}
+
+/// An account that couldn't be imported.
+#[derive(Debug, PartialEq)]
+pub struct Failure {
+    pub domain: String,
+    pub username: String,
+    // reason is why the account was left out
+    pub reason: String,
+}
+
+/// The encrypted values of an account, ready to be stored.
+struct SealedAccount {
+    password: Sealed,
+    notes: Option<Sealed>,
+    totp: Option<Sealed>,
+}
+
+/// The accounts read from an export.
+#[derive(Debug, PartialEq)]
+pub struct Rows {
+    pub accounts: Vec<Account>,
+    // skipped is the number of entries that can't be imported, e.g. rows without a password
+    pub skipped: usize,
+}
+
+/// Stores the accounts in the vault, skipping the ones whose domain and username already exist
+/// or came earlier. Domains are stored in punycode, as the commands add them. The accounts are validated and encrypted on every core, `progress` counts
+/// the ones that are done; an account that fails is reported in the summary.
+pub fn import<S: Serialize + SecretStore + Sync>(
+    vault: &mut Vault<S>,
+    key: &str,
+    accounts: Vec<Account>,
+    progress: &AtomicUsize,
+) -> Result<Summary, VaultError> {
+    let mut summary = Summary::default();
+    let mut seen = HashSet::new();
+    let mut unique = Vec::new();
+    for mut a in accounts {
+        a.domain = idn::to_ascii(&a.domain);
+        let known = vault.preferences.account(&a.domain, &a.username).is_some();
+        if known || !seen.insert((a.domain.clone(), a.username.clone())) {
+            summary.duplicates += 1;
+            progress.fetch_add(1, Ordering::Relaxed);
+            continue;
+        }
+        unique.push(a);
+    }
+    let sealed = seal_all(vault, key, &unique, progress);
+    for (a, sealed) in unique.into_iter().zip(sealed) {
+        let sealed = match sealed {
+            Ok(sealed) => sealed,
+            Err(reason) => {
+                summary.failed.push(Failure {
+                    domain: a.domain,
+                    username: a.username,
+                    reason,
+                });
+                continue;
+            }
+        };
+        let mut preference = Preference::new(&a.domain, &a.username, a.password.chars().count());
+        preference.stored = Some(sealed.password);
+        preference.tags = a.tags;
+        preference.notes = sealed.notes;
+        preference.totp = sealed.totp;
+        vault.preferences.add(preference)?;
+        hooks::run(
+            &vault.dir(),
+            Event::PreferenceAdded,
+            vault.name(),
+            &a.domain,
+            &a.username,
+        );
+        summary.added += 1;
+    }
+    Ok(summary)
+}
+
+/// Validates and encrypts the accounts in chunks, one thread for each core. The results are
+/// in the order of the accounts.
+fn seal_all<S: Serialize + SecretStore + Sync>(
+    vault: &Vault<S>,
+    key: &str,
+    accounts: &[Account],
+    progress: &AtomicUsize,
+) -> Vec<Result<SealedAccount, String>> {
+    let threads = thread::available_parallelism().map_or(1, |n| n.get());
+    let chunk = ((accounts.len() + threads - 1) / threads).max(1);
+    thread::scope(|scope| {
+        let workers: Vec<_> = accounts
+            .chunks(chunk)
+            .map(|chunk| {
+                scope.spawn(move || {
+                    chunk
+                        .iter()
+                        .map(|a| {
+                            let sealed = seal_account(vault, key, a);
+                            progress.fetch_add(1, Ordering::Relaxed);
+                            sealed
+                        })
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+        workers
+            .into_iter()
+            .flat_map(|w| w.join().unwrap_or_else(|err| panic::resume_unwind(err)))
+            .collect()
+    })
+}
+
+fn seal_account<S: Serialize + SecretStore>(
+    vault: &Vault<S>,
+    key: &str,
+    a: &Account,
+) -> Result<SealedAccount, String> {
+    if a.domain.chars().any(char::is_whitespace) {
+        return Err(format!("The domain {:?} has a space", a.domain));
+    }
+    if a.username.contains(['\r', '\n']) {
+        return Err("The username has a line break".to_owned());
+    }
+    let seal = |value: &str| vault.seal(key, value).map_err(|err| err.to_string());
+    Ok(SealedAccount {
+        password: seal(&a.password)?,
+        notes: a.notes.as_deref().map(seal).transpose()?,
+        totp: a.totp.as_deref().map(seal).transpose()?,
+    })
+}
+
+/// Returns the accounts of the vault that satisfy the predicate with their password,
+/// generated passwords are derived here.
+pub fn export<S, F>(vault: &Vault<S>, key: &str, f: F) -> Result<Vec<Account>, VaultError>
+where
+    S: Serialize + PasswordGenerator + SecretStore,
+    F: Fn(&Preference) -> bool,
+{
+    vault
+        .preferences
+        .iter()
+        .filter(|p| f(p))
+        .map(|p| {
+            Ok(Account {
+                domain: p.domain.clone(),
+                username: p.username.clone(),
+                password: vault.derive_password(key, p)?,
+                tags: p.tags.clone(),
+                notes: p.notes.as_ref().map(|n| vault.unseal(key, n)).transpose()?,
+                totp: p.totp.as_ref().map(|t| vault.unseal(key, t)).transpose()?,
+            })
+        })
+        .collect()
+}
+
+/// Reduces a url to its host name, e.g. https://www.example.com:443/login becomes example.com.
+pub fn domain_of(url: &str) -> String {
+    let rest = match url.find("://") {
+        Some(i) => &url[i + 3..],
+        None => url,
+    };
+    let host = rest.split(&['/', '?', '#'][..]).next().unwrap_or("");
+    let host = host.rsplit('@').next().unwrap_or("");
+    let host = host.split(':').next().unwrap_or("");
+    let host = host.trim_start_matches("www.");
+    host.to_lowercase()
+}
+
+/// Returns true if the domain is one of the domains of the filter or one of their subdomains.
+/// The filter is a comma separated list of domains, e.g. `example.com,example.org`.
+pub fn matches_filter(domain: &str, filter: &str) -> bool {
+    filter
+        .split(',')
+        .map(|d| d.trim())
+        .filter(|d| !d.is_empty())
+        .any(|d| domain == d || domain.ends_with(&format!(".{}", d)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn filters_domains() {
+        assert!(matches_filter("example.com", "example.com"));
+        assert!(matches_filter(
+            "mail.example.com",
+            "example.org, example.com"
+        ));
+        assert!(!matches_filter("badexample.com", "example.com"));
+        assert!(!matches_filter("example.com", ""));
+    }
+
+    #[test]
+    fn imports_in_parallel_and_reports_each_row() {
+        let key = "KEY";
+        let root = std::env::temp_dir().join(format!("zpass-import-{}", std::process::id()));
+        let secret = crate::safe::crypto::Secret::new(key, "IV", 40).unwrap();
+        let mut vault = Vault::new("import", secret, true);
+        vault.set_root(root.clone());
+        let account = |domain: &str, username: &str| Account {
+            domain: domain.to_owned(),
+            username: username.to_owned(),
+            password: format!("{}-{}", domain, username),
+            ..Account::default()
+        };
+        vault
+            .preferences
+            .add(Preference::new("a.com", "me", 20))
+            .unwrap();
+        let mut accounts: Vec<Account> = (0..50)
+            .map(|i| account(&format!("{}.com", i), "me"))
+            .collect();
+        accounts.push(account("a.com", "me"));
+        accounts.push(account("7.com", "me"));
+        accounts.push(account("bad domain", "me"));
+        let progress = AtomicUsize::new(0);
+
+        let summary = import(&mut vault, key, accounts, &progress).unwrap();
+        assert_eq!(summary.added, 50);
+        assert_eq!(summary.duplicates, 2);
+        assert_eq!(summary.failed.len(), 1);
+        assert_eq!(summary.failed[0].domain, "bad domain");
+        assert_eq!(progress.load(Ordering::Relaxed), 53);
+        let p = vault.preferences.account("42.com", "me").unwrap();
+        assert_eq!(
+            vault.unseal(key, p.stored.as_ref().unwrap()).unwrap(),
+            "42.com-me"
+        );
+        drop(vault);
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn imports_international_domains_in_punycode() {
+        let key = "KEY";
+        let secret = crate::safe::crypto::Secret::new(key, "IV", 40).unwrap();
+        let mut vault = Vault::new("import", secret, true);
+        vault.detach();
+        let accounts = ["bücher.example", "xn--bcher-kva.example"]
+            .iter()
+            .map(|domain| Account {
+                domain: domain.to_string(),
+                username: "me".to_owned(),
+                password: "password".to_owned(),
+                ..Account::default()
+            })
+            .collect();
+
+        let summary = import(&mut vault, key, accounts, &AtomicUsize::new(0)).unwrap();
+        assert_eq!((summary.added, summary.duplicates), (1, 1));
+        assert!(vault.preferences.has_domain("xn--bcher-kva.example"));
+        assert!(!vault.preferences.has_domain("bücher.example"));
+    }
+}
@@ -0,0 +1,275 @@
+//! # 1Password
+//! Reads 1PUX exports, zip archives holding the accounts as JSON in `export.data`.
+//! Logins, passwords and secure notes are imported, archived items are skipped.
+//! The name of the 1Password vault and the tags of an item become its tags.
+
+use super::{domain_of, Account, Rows};
+use serde::Deserialize;
+use std::error;
+use std::fmt;
+use std::fs::File;
+use std::io;
+use std::path::Path;
+
+/// The entry of the archive that holds the accounts.
+const EXPORT_DATA: &str = "export.data";
+/// Category of login items.
+const LOGIN: &str = "001";
+/// Category of secure note items.
+const SECURE_NOTE: &str = "003";
+/// Category of password items.
+const PASSWORD: &str = "005";
+
+#[derive(Debug)]
+pub enum OnePasswordError {
+    IOError(io::Error),
+    ArchiveError(zip::result::ZipError),
+    SerializationError(serde_json::Error),
+}
+
+impl fmt::Display for OnePasswordError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::IOError(ref err) => write!(f, "IO error:\n{}", err),
+            Self::ArchiveError(ref err) => write!(f, "Not a 1PUX archive:\n{}", err),
+            Self::SerializationError(ref err) => write!(f, "Unexpected export data:\n{}", err),
+        }
+    }
+}
+
+impl error::Error for OnePasswordError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Self::IOError(ref err) => Some(err),
+            Self::ArchiveError(ref err) => Some(err),
+            Self::SerializationError(ref err) => Some(err),
+        }
+    }
+}
+
+impl From<io::Error> for OnePasswordError {
+    fn from(err: io::Error) -> Self {
+        OnePasswordError::IOError(err)
+    }
+}
+
+impl From<zip::result::ZipError> for OnePasswordError {
+    fn from(err: zip::result::ZipError) -> Self {
+        OnePasswordError::ArchiveError(err)
+    }
+}
+
+impl From<serde_json::Error> for OnePasswordError {
+    fn from(err: serde_json::Error) -> Self {
+        OnePasswordError::SerializationError(err)
+    }
+}
+
+// Only the parts of the export that are imported are modeled, everything else is ignored.
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct Export {
+    accounts: Vec<ExportAccount>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct ExportAccount {
+    vaults: Vec<ExportVault>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct ExportVault {
+    attrs: VaultAttrs,
+    items: Vec<Item>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct VaultAttrs {
+    name: String,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, rename_all = "camelCase")]
+struct Item {
+    category_uuid: String,
+    // state is either active or archived
+    state: String,
+    overview: Overview,
+    details: Details,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct Overview {
+    title: String,
+    url: String,
+    tags: Vec<String>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, rename_all = "camelCase")]
+struct Details {
+    login_fields: Vec<LoginField>,
+    notes_plain: Option<String>,
+    sections: Vec<Section>,
+    // password is only set on password items, logins keep it in the login fields
+    password: Option<String>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct LoginField {
+    value: String,
+    // designation is either username or password for the fields that matter
+    designation: String,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct Section {
+    fields: Vec<Field>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct Field {
+    // value is an object keyed by the type of the field, e.g. {"totp": "otpauth://..."}
+    value: serde_json::Value,
+}
+
+/// Reads the accounts of a 1PUX archive.
+pub fn read_export(path: &Path) -> Result<Rows, OnePasswordError> {
+    let mut archive = zip::ZipArchive::new(File::open(path)?)?;
+    let data = archive.by_name(EXPORT_DATA)?;
+    let export: Export = serde_json::from_reader(data)?;
+    let mut rows = Rows {
+        accounts: Vec::new(),
+        skipped: 0,
+    };
+    for vault in export.accounts.iter().flat_map(|a| &a.vaults) {
+        for item in &vault.items {
+            match account_of(&vault.attrs.name, item) {
+                Some(account) => rows.accounts.push(account),
+                None => rows.skipped += 1,
+            }
+        }
+    }
+    Ok(rows)
+}
+
+/// Maps an item to an account, items of other categories and archived items map to nothing.
+fn account_of(vault: &str, item: &Item) -> Option<Account> {
+    if item.state == "archived" {
+        return None;
+    }
+    let designated = |designation: &str| {
+        item.details
+            .login_fields
+            .iter()
+            .find(|f| f.designation == designation)
+            .map(|f| f.value.clone())
+    };
+    let (domain, username, password) = match &item.category_uuid[..] {
+        LOGIN => (
+            domain_of(&item.overview.url),
+            designated("username").unwrap_or_default(),
+            designated("password")?,
+        ),
+        PASSWORD => (
+            domain_of(&item.overview.url),
+            String::new(),
+            item.details.password.clone()?,
+        ),
+        SECURE_NOTE => (
+            item.overview.title.to_lowercase(),
+            String::new(),
+            String::new(),
+        ),
+        _ => return None,
+    };
+    // Items without a url are still worth keeping, their title is the next best name.
+    let domain = if domain.is_empty() {
+        item.overview.title.to_lowercase()
+    } else {
+        domain
+    };
+    if domain.is_empty() {
+        return None;
+    }
+    let totp = item
+        .details
+        .sections
+        .iter()
+        .flat_map(|s| &s.fields)
+        .find_map(|f| f.value.get("totp").and_then(|t| t.as_str()))
+        .map(|t| t.to_owned());
+    let mut tags = vec![vault.to_owned()];
+    tags.extend(item.overview.tags.iter().cloned());
+    Some(Account {
+        domain,
+        username,
+        password,
+        tags,
+        notes: item.details.notes_plain.clone().filter(|n| !n.is_empty()),
+        totp,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn login_field(designation: &str, value: &str) -> LoginField {
+        LoginField {
+            value: value.to_owned(),
+            designation: designation.to_owned(),
+        }
+    }
+
+    #[test]
+    fn maps_items() {
+        let login = Item {
+            category_uuid: LOGIN.to_owned(),
+            state: "active".to_owned(),
+            overview: Overview {
+                title: "Example".to_owned(),
+                url: "https://www.example.com/login".to_owned(),
+                tags: vec!["work".to_owned()],
+            },
+            details: Details {
+                login_fields: vec![
+                    login_field("username", "alice"),
+                    login_field("password", "secret"),
+                ],
+                notes_plain: Some("recovery codes in the safe".to_owned()),
+                ..Details::default()
+            },
+        };
+        let account = account_of("Private", &login).unwrap();
+        assert_eq!(account.domain, "example.com");
+        assert_eq!(account.username, "alice");
+        assert_eq!(account.password, "secret");
+        assert_eq!(account.tags, vec!["Private", "work"]);
+        assert_eq!(account.notes.as_deref(), Some("recovery codes in the safe"));
+
+        let note = Item {
+            category_uuid: SECURE_NOTE.to_owned(),
+            overview: Overview {
+                title: "Alarm code".to_owned(),
+                ..Overview::default()
+            },
+            ..Item::default()
+        };
+        assert_eq!(account_of("Private", &note).unwrap().domain, "alarm code");
+
+        let archived = Item {
+            state: "archived".to_owned(),
+            ..login
+        };
+        assert!(account_of("Private", &archived).is_none());
+    }
+}
@@ -0,0 +1,241 @@
+//! # Paper
+//! Encodes the encrypted secret of a vault as a code that can be printed and typed back in.
+//! The code is base32 (RFC 4648, without padding) so it survives handwriting and OCR,
+//! and the QR code holds the same text, which scanners read back verbatim.
+//!
+//! The payload is: magic, format, scheme, name length, name, iv length, iv, secret, checksum.
+//! Preferences are not part of the backup, only the secret is needed to derive passwords.
+
+use qrcode::render::unicode;
+use qrcode::QrCode;
+use sha3::{Digest, Sha3_256};
+use std::error;
+use std::fmt;
+
+/// Marks a payload as a zpass paper backup.
+const MAGIC: &[u8] = b"ZP";
+/// Version of the payload layout.
+const FORMAT_VERSION: u8 = 1;
+/// Bytes of the SHA-3 hash of the payload that are appended to catch typos.
+const CHECKSUM_LENGTH: usize = 4;
+/// Characters of a group when the code is printed.
+const GROUP_LENGTH: usize = 4;
+/// Groups per printed line.
+const GROUPS_PER_LINE: usize = 8;
+/// RFC 4648 base32 alphabet.
+const ALPHABET: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+#[derive(Debug, PartialEq)]
+pub enum PaperError {
+    InvalidCharacter(char),
+    ChecksumMismatch,
+    NotABackup,
+    UnsupportedVersion(u8),
+    UnsupportedScheme(u8),
+    TooLong,
+    QrError(String),
+}
+
+impl fmt::Display for PaperError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::InvalidCharacter(c) => write!(f, "{} is not part of a backup code", c),
+            Self::ChecksumMismatch => write!(f, "The code has a typo, the checksum doesn't match"),
+            Self::NotABackup => write!(f, "The code is not a zpass paper backup"),
+            Self::UnsupportedVersion(version) => {
+                write!(
+                    f,
+                    "Backup format {} is not supported, upgrade zpass",
+                    version
+                )
+            }
+            Self::UnsupportedScheme(scheme) => write!(
+                f,
+                "The backup was made for password scheme {}, upgrade zpass",
+                scheme
+            ),
+            Self::TooLong => write!(f, "The vault name is too long for a paper backup"),
+            Self::QrError(ref err) => write!(f, "Failed to render the QR code:\n{}", err),
+        }
+    }
+}
+
+impl error::Error for PaperError {}
+
+/// # Backup
+/// What it takes to restore a vault.
+#[derive(Debug, PartialEq)]
+pub struct Backup {
+    // name of the vault
+    pub name: String,
+    // scheme is the newest password scheme of the zpass that made the backup
+    pub scheme: u8,
+    // iv is the initial vector the secret was encrypted with
+    pub iv: String,
+    // encrypted_secret is the secret as it is stored in the vault
+    pub encrypted_secret: Vec<u8>,
+}
+
+impl Backup {
+    /// Returns the typeable code of the backup.
+    pub fn encode(&self) -> Result<String, PaperError> {
+        if self.name.len() > u8::MAX as usize || self.iv.len() > u8::MAX as usize {
+            return Err(PaperError::TooLong);
+        }
+        let mut payload = MAGIC.to_vec();
+        payload.push(FORMAT_VERSION);
+        payload.push(self.scheme);
+        payload.push(self.name.len() as u8);
+        payload.extend_from_slice(self.name.as_bytes());
+        payload.push(self.iv.len() as u8);
+        payload.extend_from_slice(self.iv.as_bytes());
+        payload.extend_from_slice(&self.encrypted_secret);
+        let checksum = checksum(&payload);
+        payload.extend_from_slice(&checksum);
+        Ok(base32_encode(&payload))
+    }
+
+    /// Reads a code back, ignoring case, spaces and dashes.
+    pub fn decode(code: &str) -> Result<Backup, PaperError> {
+        let payload = base32_decode(code)?;
+        if payload.len() < MAGIC.len() + CHECKSUM_LENGTH || !payload.starts_with(MAGIC) {
+            return Err(PaperError::NotABackup);
+        }
+        let (payload, expected) = payload.split_at(payload.len() - CHECKSUM_LENGTH);
+        if checksum(payload) != expected {
+            return Err(PaperError::ChecksumMismatch);
+        }
+        let mut rest = &payload[MAGIC.len()..];
+        let version = take(&mut rest, 1)?[0];
+        if version != FORMAT_VERSION {
+            return Err(PaperError::UnsupportedVersion(version));
+        }
+        let scheme = take(&mut rest, 1)?[0];
+        let length = take(&mut rest, 1)?[0] as usize;
+        let name = String::from_utf8_lossy(take(&mut rest, length)?).into_owned();
+        let length = take(&mut rest, 1)?[0] as usize;
+        let iv = String::from_utf8_lossy(take(&mut rest, length)?).into_owned();
+        Ok(Backup {
+            name,
+            scheme,
+            iv,
+            encrypted_secret: rest.to_vec(),
+        })
+    }
+}
+
+/// Renders the code as a QR code made of unicode blocks.
+pub fn qr(code: &str) -> Result<String, PaperError> {
+    let qr = QrCode::new(code.as_bytes()).map_err(|e| PaperError::QrError(e.to_string()))?;
+    Ok(qr.render::<unicode::Dense1x2>().build())
+}
+
+/// Splits the code into short groups and lines, which are easier to type.
+pub fn grouped(code: &str) -> String {
+    let groups: Vec<&str> = code
+        .as_bytes()
+        .chunks(GROUP_LENGTH)
+        .map(|g| std::str::from_utf8(g).unwrap_or(""))
+        .collect();
+    groups
+        .chunks(GROUPS_PER_LINE)
+        .map(|line| line.join(" "))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Removes the next n bytes from the front of the slice.
+fn take<'a>(rest: &mut &'a [u8], n: usize) -> Result<&'a [u8], PaperError> {
+    if rest.len() < n {
+        return Err(PaperError::NotABackup);
+    }
+    let (taken, remaining) = rest.split_at(n);
+    *rest = remaining;
+    Ok(taken)
+}
+
+/// Returns the first bytes of the SHA-3 hash of the payload.
+fn checksum(payload: &[u8]) -> Vec<u8> {
+    Sha3_256::digest(payload)[..CHECKSUM_LENGTH].to_vec()
+}
+
+/// Encodes bytes as base32 without padding.
+pub(super) fn base32_encode(bytes: &[u8]) -> String {
+    let mut code = String::new();
+    let mut buffer: u32 = 0;
+    let mut bits = 0;
+    for b in bytes {
+        buffer = (buffer << 8) | *b as u32;
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            code.push(ALPHABET[(buffer >> bits) as usize & 31] as char);
+        }
+    }
+    if bits > 0 {
+        code.push(ALPHABET[(buffer << (5 - bits)) as usize & 31] as char);
+    }
+    code
+}
+
+/// Decodes base32, ignoring case, whitespace, dashes and padding.
+pub(super) fn base32_decode(code: &str) -> Result<Vec<u8>, PaperError> {
+    let mut bytes = Vec::new();
+    let mut buffer: u32 = 0;
+    let mut bits = 0;
+    for c in code.chars() {
+        if c.is_whitespace() || c == '-' || c == '=' {
+            continue;
+        }
+        let upper = c.to_ascii_uppercase() as u8;
+        let value = ALPHABET
+            .iter()
+            .position(|a| *a == upper)
+            .ok_or(PaperError::InvalidCharacter(c))?;
+        buffer = (buffer << 5) | value as u32;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((buffer >> bits) as u8);
+        }
+    }
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn base32_test_vectors() {
+        for (plain, encoded) in &[
+            ("f", "MY"),
+            ("fo", "MZXQ"),
+            ("foo", "MZXW6"),
+            ("foob", "MZXW6YQ"),
+            ("fooba", "MZXW6YTB"),
+            ("foobar", "MZXW6YTBOI"),
+        ] {
+            assert_eq!(base32_encode(plain.as_bytes()), *encoded);
+            assert_eq!(base32_decode(encoded).unwrap(), plain.as_bytes());
+        }
+    }
+
+    #[test]
+    fn backup_roundtrip() {
+        let backup = Backup {
+            name: "personal".to_owned(),
+            scheme: 1,
+            iv: "personal".to_owned(),
+            encrypted_secret: (0..=255).collect(),
+        };
+        let code = backup.encode().unwrap();
+        let typed = grouped(&code).to_lowercase();
+        assert_eq!(Backup::decode(&typed).unwrap(), backup);
+
+        let mut typo: Vec<char> = code.chars().collect();
+        typo[10] = if typo[10] == 'A' { 'B' } else { 'A' };
+        let typo: String = typo.into_iter().collect();
+        assert_eq!(Backup::decode(&typo), Err(PaperError::ChecksumMismatch));
+    }
+}
@@ -0,0 +1,296 @@
+//! # Pass
+//! Reads and writes pass(1) password stores, directory trees of gpg encrypted files.
+//! The first line of a file is the password, the other lines may hold a `login:` and
+//! the folders of an entry become its tags. gpg has to be installed and hold the keys.
+
+use super::Account;
+use std::env;
+use std::error;
+use std::ffi::OsStr;
+use std::fmt;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::string::FromUtf8Error;
+
+/// Extension of the encrypted entries.
+const ENTRY_EXTENSION: &str = "gpg";
+/// File that names the gpg key a store is encrypted to.
+const GPG_ID_FILE: &str = ".gpg-id";
+/// Keys that hold the username in the extra lines of an entry.
+const USERNAME_KEYS: [&str; 4] = ["login", "username", "user", "email"];
+
+#[derive(Debug)]
+pub enum PassError {
+    IOError(io::Error),
+    DecryptionFailed(PathBuf, String),
+    EncryptionFailed(PathBuf, String),
+    InvalidUtf8(FromUtf8Error),
+}
+
+impl fmt::Display for PassError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::IOError(ref err) => write!(f, "IO error:\n{}", err),
+            Self::DecryptionFailed(ref path, ref reason) => {
+                write!(f, "gpg failed to decrypt {}:\n{}", path.display(), reason)
+            }
+            Self::EncryptionFailed(ref path, ref reason) => {
+                write!(f, "gpg failed to encrypt {}:\n{}", path.display(), reason)
+            }
+            Self::InvalidUtf8(ref err) => write!(f, "Entry is not valid UTF-8:\n{}", err),
+        }
+    }
+}
+
+impl error::Error for PassError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Self::IOError(ref err) => Some(err),
+            Self::InvalidUtf8(ref err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for PassError {
+    fn from(err: io::Error) -> Self {
+        PassError::IOError(err)
+    }
+}
+
+impl From<FromUtf8Error> for PassError {
+    fn from(err: FromUtf8Error) -> Self {
+        PassError::InvalidUtf8(err)
+    }
+}
+
+/// Returns the store that pass uses: $PASSWORD_STORE_DIR or ~/.password-store.
+pub fn default_store() -> PathBuf {
+    if let Some(dir) = env::var_os("PASSWORD_STORE_DIR") {
+        return PathBuf::from(dir);
+    }
+    let home = env::var_os("HOME").unwrap_or_default();
+    Path::new(&home).join(".password-store")
+}
+
+/// Decrypts every entry of the store.
+pub fn read_store(root: &Path) -> Result<Vec<Account>, PassError> {
+    let mut entries = Vec::new();
+    find_entries(root, &mut entries)?;
+    entries.sort();
+    let mut accounts = Vec::new();
+    for path in entries {
+        let contents = decrypt(&path)?;
+        let relative = path.strip_prefix(root).unwrap_or(&path);
+        accounts.push(account_of(relative, &contents));
+    }
+    Ok(accounts)
+}
+
+/// Encrypts the accounts to the gpg key and writes them to the store.
+/// Existing entries are never overwritten, returns the number of entries written.
+pub fn write_store(root: &Path, gpg_id: &str, accounts: &[Account]) -> Result<usize, PassError> {
+    fs::create_dir_all(root)?;
+    let id_file = root.join(GPG_ID_FILE);
+    if !id_file.exists() {
+        fs::write(id_file, format!("{}\n", gpg_id))?;
+    }
+    let mut written = 0;
+    for a in accounts {
+        let path = root.join(entry_path(a));
+        if path.exists() {
+            continue;
+        }
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        encrypt(&path, gpg_id, &entry_contents(a))?;
+        written += 1;
+    }
+    Ok(written)
+}
+
+/// Returns where an account goes in a store: `<tags>/<domain>/<username>.gpg`,
+/// or `<tags>/<domain>.gpg` without a username.
+fn entry_path(account: &Account) -> PathBuf {
+    let mut path: PathBuf = account.tags.iter().map(|t| path_safe(t)).collect();
+    if account.username.is_empty() {
+        path.push(format!(
+            "{}.{}",
+            path_safe(&account.domain),
+            ENTRY_EXTENSION
+        ));
+    } else {
+        path.push(path_safe(&account.domain));
+        path.push(format!(
+            "{}.{}",
+            path_safe(&account.username),
+            ENTRY_EXTENSION
+        ));
+    }
+    path
+}
+
+/// Returns the contents of an entry in the layout that pass extensions understand.
+fn entry_contents(account: &Account) -> String {
+    let mut contents = format!("{}\n", account.password);
+    if !account.username.is_empty() {
+        contents.push_str(&format!("login: {}\n", account.username));
+    }
+    contents.push_str(&format!("url: https://{}\n", account.domain));
+    contents
+}
+
+/// Replaces the characters that can't be part of a file name.
+fn path_safe(name: &str) -> String {
+    let name = name.replace(&['/', '\\'][..], "_");
+    match &name[..] {
+        "" | "." | ".." => "_".to_owned(),
+        _ => name,
+    }
+}
+
+/// Collects the entries under a directory, skipping hidden files such as .git and .gpg-id.
+fn find_entries(dir: &Path, entries: &mut Vec<PathBuf>) -> Result<(), PassError> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let hidden = matches!(path.file_name(), Some(n) if n.to_string_lossy().starts_with('.'));
+        if hidden {
+            continue;
+        }
+        if path.is_dir() {
+            find_entries(&path, entries)?;
+        } else if path.extension() == Some(OsStr::new(ENTRY_EXTENSION)) {
+            entries.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Decrypts an entry with gpg.
+fn decrypt(path: &Path) -> Result<String, PassError> {
+    let output = Command::new("gpg")
+        .args(["--quiet", "--batch", "--decrypt"])
+        .arg(path)
+        .output()?;
+    if !output.status.success() {
+        let reason = String::from_utf8_lossy(&output.stderr).trim().to_owned();
+        return Err(PassError::DecryptionFailed(path.to_owned(), reason));
+    }
+    Ok(String::from_utf8(output.stdout)?)
+}
+
+/// Encrypts the contents to the gpg key and writes them to the path.
+fn encrypt(path: &Path, gpg_id: &str, contents: &str) -> Result<(), PassError> {
+    let mut child = Command::new("gpg")
+        .args([
+            "--quiet",
+            "--batch",
+            "--yes",
+            "--encrypt",
+            "--recipient",
+            gpg_id,
+        ])
+        .arg("--output")
+        .arg(path)
+        .stdin(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(contents.as_bytes())?;
+    }
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        let reason = String::from_utf8_lossy(&output.stderr).trim().to_owned();
+        return Err(PassError::EncryptionFailed(path.to_owned(), reason));
+    }
+    Ok(())
+}
+
+/// Maps an entry to an account. Both `example.com.gpg` and `example.com/alice.gpg` are common,
+/// so the domain is the file name if it looks like a domain and isn't the login,
+/// otherwise its folder.
+fn account_of(relative: &Path, contents: &str) -> Account {
+    let mut folders: Vec<String> = relative
+        .with_extension("")
+        .iter()
+        .map(|c| c.to_string_lossy().into_owned())
+        .collect();
+    let name = folders.pop().unwrap_or_default();
+    let mut lines = contents.lines();
+    let password = lines.next().unwrap_or("").to_owned();
+    let login = lines.find_map(|line| {
+        let mut parts = line.splitn(2, ':');
+        let key = parts.next()?.trim().to_lowercase();
+        let value = parts.next()?.trim();
+        if USERNAME_KEYS.contains(&&key[..]) {
+            Some(value.to_owned())
+        } else {
+            None
+        }
+    });
+
+    let (domain, username) = match folders.last() {
+        Some(folder)
+            if folder.contains('.')
+                && (!name.contains('.') || login.as_deref() == Some(&name[..])) =>
+        {
+            let folder = folders.pop().unwrap_or_default();
+            (folder, login.unwrap_or(name))
+        }
+        _ => (name, login.unwrap_or_default()),
+    };
+    Account {
+        domain: domain.to_lowercase(),
+        username,
+        password,
+        tags: folders,
+        notes: None,
+        totp: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maps_entries() {
+        let a = account_of(
+            Path::new("Work/example.com.gpg"),
+            "secret\nlogin: alice\nurl: https://example.com",
+        );
+        assert_eq!(a.domain, "example.com");
+        assert_eq!(a.username, "alice");
+        assert_eq!(a.password, "secret");
+        assert_eq!(a.tags, vec!["Work"]);
+
+        let a = account_of(Path::new("Personal/Mail/example.org/bob.gpg"), "secret\n");
+        assert_eq!(a.domain, "example.org");
+        assert_eq!(a.username, "bob");
+        assert_eq!(a.tags, vec!["Personal", "Mail"]);
+
+        let a = account_of(Path::new("wifi.gpg"), "secret");
+        assert_eq!(a.domain, "wifi");
+        assert_eq!(a.username, "");
+        assert!(a.tags.is_empty());
+    }
+
+    #[test]
+    fn reads_what_it_writes() {
+        for username in &["", "bob", "bob@example.org"] {
+            let account = Account {
+                domain: "example.org".to_owned(),
+                username: username.to_string(),
+                password: "secret".to_owned(),
+                tags: vec!["Personal".to_owned()],
+                notes: None,
+                totp: None,
+            };
+            let contents = entry_contents(&account);
+            assert_eq!(account_of(&entry_path(&account), &contents), account);
+        }
+    }
+}
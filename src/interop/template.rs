@@ -0,0 +1,158 @@
+//! # Template
+//! Renders secrets into files, e.g. to provision the `.env` of a development environment.
+//! Templates are plain text with `{{ password "api.example" }}` or
+//! `{{ password "api.example" "bot" }}` where the password of the account goes.
+//! Arguments are double quoted, `\"` and `\\` escape quotes and backslashes.
+
+use crate::safe::vault::VaultError;
+use std::error;
+use std::fmt;
+
+/// Opens a placeholder.
+const OPEN: &str = "{{";
+/// Closes a placeholder.
+const CLOSE: &str = "}}";
+
+#[derive(Debug)]
+pub enum TemplateError {
+    Unclosed { line: usize },
+    UnknownFunction { line: usize, name: String },
+    InvalidArguments { line: usize },
+    VaultError { line: usize, err: VaultError },
+}
+
+impl fmt::Display for TemplateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Unclosed { line } => write!(f, "Line {}: {} is never closed", line, OPEN),
+            Self::UnknownFunction { line, ref name } => {
+                write!(f, "Line {}: unknown function {}", line, name)
+            }
+            Self::InvalidArguments { line } => write!(
+                f,
+                "Line {}: expected password \"domain\" or password \"domain\" \"username\"",
+                line
+            ),
+            Self::VaultError { line, ref err } => write!(f, "Line {}:\n{}", line, err),
+        }
+    }
+}
+
+impl error::Error for TemplateError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Self::VaultError { ref err, .. } => Some(err),
+            _ => None,
+        }
+    }
+}
+
+/// Replaces each placeholder with the password the lookup returns for its domain and username.
+pub fn render<F>(template: &str, mut lookup: F) -> Result<String, TemplateError>
+where
+    F: FnMut(&str, Option<&str>) -> Result<String, VaultError>,
+{
+    let mut output = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find(OPEN) {
+        let line = line_of(template, template.len() - rest.len() + start);
+        output.push_str(&rest[..start]);
+        let inner = &rest[start + OPEN.len()..];
+        let end = inner.find(CLOSE).ok_or(TemplateError::Unclosed { line })?;
+        let words = words(&inner[..end]).ok_or(TemplateError::InvalidArguments { line })?;
+        match words.split_first() {
+            Some((name, args)) if name == "password" => {
+                let (domain, username) = match args {
+                    [domain] => (domain, None),
+                    [domain, username] => (domain, Some(username.as_str())),
+                    _ => return Err(TemplateError::InvalidArguments { line }),
+                };
+                let password = lookup(domain, username)
+                    .map_err(|err| TemplateError::VaultError { line, err })?;
+                output.push_str(&password);
+            }
+            Some((name, _)) => {
+                return Err(TemplateError::UnknownFunction {
+                    line,
+                    name: name.to_owned(),
+                })
+            }
+            None => return Err(TemplateError::InvalidArguments { line }),
+        }
+        rest = &inner[end + CLOSE.len()..];
+    }
+    output.push_str(rest);
+    Ok(output)
+}
+
+/// Splits a placeholder into the function name and its quoted arguments.
+/// Returns None if an argument isn't quoted or a quote isn't closed.
+fn words(placeholder: &str) -> Option<Vec<String>> {
+    let mut words = Vec::new();
+    let mut chars = placeholder.trim().chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            ' ' | '\t' => continue,
+            '"' => {
+                let mut word = String::new();
+                loop {
+                    match chars.next()? {
+                        '"' => break,
+                        '\\' => word.push(chars.next()?),
+                        c => word.push(c),
+                    }
+                }
+                words.push(word);
+            }
+            c if words.is_empty() => {
+                let mut word = c.to_string();
+                while let Some(c) = chars.next_if(|c| !c.is_whitespace()) {
+                    word.push(c);
+                }
+                words.push(word);
+            }
+            _ => return None,
+        }
+    }
+    Some(words)
+}
+
+/// Returns the line number of a byte offset, starting at 1.
+fn line_of(text: &str, offset: usize) -> usize {
+    text[..offset].matches('\n').count() + 1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_placeholders() {
+        let template =
+            "TOKEN={{ password \"api.example\" \"bot\" }}\nDB={{password \"db.example\"}}\n";
+        let rendered = render(template, |domain, username| {
+            Ok(format!("{}:{}", domain, username.unwrap_or("-")))
+        })
+        .unwrap();
+
+        assert_eq!(rendered, "TOKEN=api.example:bot\nDB=db.example:-\n");
+    }
+
+    #[test]
+    fn reports_the_line() {
+        let lookup = |_: &str, _: Option<&str>| Ok(String::new());
+
+        assert!(matches!(
+            render("A=1\nB={{ password \"x\"", lookup),
+            Err(TemplateError::Unclosed { line: 2 })
+        ));
+        assert!(matches!(
+            render("{{ totp \"x\" }}", lookup),
+            Err(TemplateError::UnknownFunction { line: 1, .. })
+        ));
+        assert!(matches!(
+            render("{{ password x }}", lookup),
+            Err(TemplateError::InvalidArguments { line: 1 })
+        ));
+    }
+}
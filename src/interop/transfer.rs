@@ -0,0 +1,258 @@
+//! # Transfer
+//! Moves a vault from one machine to another as a series of QR codes, without a cloud or a USB
+//! stick in between. The sender shows a one-time code and the frames, the receiver types the
+//! code and scans the frames in any order, e.g. with a scanner that types what it reads.
+//!
+//! The vault is encrypted with the code, so the frames are useless to whoever films the screen
+//! without seeing the code. A frame is `ZPT1:<id>:<number>:<count>:<base32>`, only characters
+//! of the alphanumeric mode of QR codes, which keeps the codes small.
+
+use super::archive::{Archive, ArchiveError};
+use super::paper;
+use crate::safe::crypto::{self, CryptoError};
+use rand::Rng;
+use sha3::{Digest, Sha3_256};
+use std::collections::BTreeMap;
+use std::error;
+use std::fmt;
+
+/// Marks a frame as part of a transfer, with the version of the layout.
+const PREFIX: &str = "ZPT1";
+/// Base32 characters of the vault in a frame, small enough for a QR code on a terminal.
+pub const FRAME_LENGTH: usize = 300;
+/// Base32 characters of the one-time code, they hold 12 random bytes.
+const CODE_LENGTH: usize = 20;
+/// Characters of a group when the code is shown.
+const CODE_GROUP_LENGTH: usize = 4;
+/// Base32 characters of the id that tells the frames of two transfers apart.
+const ID_LENGTH: usize = 6;
+/// Bytes of the hash of the archive that are encrypted with it, to tell a wrong code.
+const CHECKSUM_LENGTH: usize = 8;
+
+#[derive(Debug)]
+pub enum TransferError {
+    InvalidFrame(String),
+    OtherTransfer(String),
+    Incomplete(Vec<usize>),
+    WrongCode,
+    SecretError(CryptoError),
+    ArchiveError(ArchiveError),
+}
+
+impl fmt::Display for TransferError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::InvalidFrame(ref frame) => write!(f, "Not a frame of a transfer: {}", frame),
+            Self::OtherTransfer(ref id) => {
+                write!(f, "The frame belongs to another transfer ({})", id)
+            }
+            Self::Incomplete(ref missing) => write!(
+                f,
+                "Frames {} are missing",
+                missing
+                    .iter()
+                    .map(|n| n.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            Self::WrongCode => write!(f, "Wrong code or damaged frames"),
+            Self::SecretError(ref err) => write!(f, "Failed to encrypt the vault:\n{}", err),
+            Self::ArchiveError(ref err) => write!(f, "Damaged vault:\n{}", err),
+        }
+    }
+}
+
+impl error::Error for TransferError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Self::SecretError(ref err) => Some(err),
+            Self::ArchiveError(ref err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<CryptoError> for TransferError {
+    fn from(err: CryptoError) -> Self {
+        TransferError::SecretError(err)
+    }
+}
+
+impl From<ArchiveError> for TransferError {
+    fn from(err: ArchiveError) -> Self {
+        TransferError::ArchiveError(err)
+    }
+}
+
+/// Returns a new one-time code, in groups that are easy to read out.
+pub fn code() -> String {
+    let mut rng = rand::thread_rng();
+    let bytes: Vec<u8> = (0..CODE_LENGTH * 5 / 8).map(|_| rng.gen()).collect();
+    paper::base32_encode(&bytes)
+        .as_bytes()
+        .chunks(CODE_GROUP_LENGTH)
+        .map(|g| String::from_utf8_lossy(g).into_owned())
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+/// Returns the code as it is typed, without case, spaces and dashes.
+fn normalize(code: &str) -> String {
+    code.chars()
+        .filter(|c| !c.is_whitespace() && *c != '-')
+        .map(|c| c.to_ascii_uppercase())
+        .collect()
+}
+
+/// Returns the frames of a serialized vault, encrypted with the code.
+pub fn frames(code: &str, serialized: String) -> Result<Vec<String>, TransferError> {
+    let archive = Archive::new(vec![serialized]).pack()?;
+    let mut plaintext = checksum(&archive);
+    plaintext.extend(archive);
+    let encoded = paper::base32_encode(&crypto::encrypt(&normalize(code), &plaintext)?);
+    let id: String = paper::base32_encode(&Sha3_256::digest(encoded.as_bytes()))
+        .chars()
+        .take(ID_LENGTH)
+        .collect();
+    let chunks: Vec<&[u8]> = encoded.as_bytes().chunks(FRAME_LENGTH).collect();
+    Ok(chunks
+        .iter()
+        .enumerate()
+        .map(|(i, chunk)| {
+            format!(
+                "{}:{}:{}:{}:{}",
+                PREFIX,
+                id,
+                i + 1,
+                chunks.len(),
+                String::from_utf8_lossy(chunk)
+            )
+        })
+        .collect())
+}
+
+/// # Receiver
+/// Collects the frames of a transfer in the order they are scanned.
+#[derive(Debug, Default)]
+pub struct Receiver {
+    // id of the transfer, from the first frame
+    id: Option<String>,
+    // count of the frames of the transfer
+    count: usize,
+    // chunks by the number of their frame
+    chunks: BTreeMap<usize, String>,
+}
+
+impl Receiver {
+    pub fn new() -> Receiver {
+        Receiver::default()
+    }
+
+    /// Adds a scanned frame, returns false if it was scanned before.
+    pub fn add(&mut self, frame: &str) -> Result<bool, TransferError> {
+        let invalid = || TransferError::InvalidFrame(frame.to_owned());
+        let parts: Vec<&str> = frame.trim().splitn(5, ':').collect();
+        let (id, number, count, chunk) = match parts[..] {
+            [PREFIX, id, number, count, chunk] => (id, number, count, chunk),
+            _ => return Err(invalid()),
+        };
+        let number: usize = number.parse().map_err(|_| invalid())?;
+        let count: usize = count.parse().map_err(|_| invalid())?;
+        if number == 0 || number > count {
+            return Err(invalid());
+        }
+        match &self.id {
+            Some(known) if known != id || self.count != count => {
+                return Err(TransferError::OtherTransfer(id.to_owned()))
+            }
+            Some(_) => {}
+            None => {
+                self.id = Some(id.to_owned());
+                self.count = count;
+            }
+        }
+        Ok(self.chunks.insert(number, chunk.to_owned()).is_none())
+    }
+
+    /// Returns the numbers of the frames that weren't scanned yet.
+    pub fn missing(&self) -> Vec<usize> {
+        (1..=self.count)
+            .filter(|n| !self.chunks.contains_key(n))
+            .collect()
+    }
+
+    /// Returns true once every frame is scanned.
+    pub fn is_complete(&self) -> bool {
+        self.id.is_some() && self.missing().is_empty()
+    }
+
+    /// Decrypts the frames with the code and returns the serialized vault.
+    pub fn finish(&self, code: &str) -> Result<String, TransferError> {
+        if !self.is_complete() {
+            return Err(TransferError::Incomplete(self.missing()));
+        }
+        let encoded: String = self.chunks.values().map(|c| c.as_str()).collect();
+        let encrypted =
+            paper::base32_decode(&encoded).map_err(|_| TransferError::InvalidFrame(encoded))?;
+        let plaintext =
+            crypto::decrypt(&normalize(code), &encrypted).map_err(|_| TransferError::WrongCode)?;
+        if plaintext.len() < CHECKSUM_LENGTH {
+            return Err(TransferError::WrongCode);
+        }
+        let (expected, archive) = plaintext.split_at(CHECKSUM_LENGTH);
+        if checksum(archive) != expected {
+            return Err(TransferError::WrongCode);
+        }
+        let mut archive = Archive::unpack(archive)?;
+        match archive.vaults.len() {
+            1 => Ok(archive.vaults.remove(0)),
+            _ => Err(TransferError::WrongCode),
+        }
+    }
+}
+
+fn checksum(bytes: &[u8]) -> Vec<u8> {
+    Sha3_256::digest(bytes)[..CHECKSUM_LENGTH].to_vec()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn collects_frames_in_any_order() {
+        let code = code();
+        assert_eq!(normalize(&code).len(), CODE_LENGTH);
+        let frames = frames(&code, "x".repeat(1000)).unwrap();
+        assert!(frames.len() > 1);
+
+        let mut receiver = Receiver::new();
+        for frame in frames.iter().rev().skip(1) {
+            assert!(receiver.add(frame).unwrap());
+        }
+        assert!(!receiver.add(&frames[1]).unwrap());
+        assert_eq!(receiver.missing(), vec![frames.len()]);
+        assert!(matches!(
+            receiver.finish(&code),
+            Err(TransferError::Incomplete(_))
+        ));
+        assert!(matches!(
+            receiver.add("ZPT1:OTHER1:1:2:AAAA"),
+            Err(TransferError::OtherTransfer(_))
+        ));
+        assert!(matches!(
+            receiver.add("hello"),
+            Err(TransferError::InvalidFrame(_))
+        ));
+        receiver.add(frames.last().unwrap()).unwrap();
+        assert!(receiver.is_complete());
+        assert_eq!(
+            receiver.finish(&code.to_lowercase()).unwrap(),
+            "x".repeat(1000)
+        );
+        assert!(matches!(
+            receiver.finish("AAAA-BBBB-CCCC-DDDD-EEEE"),
+            Err(TransferError::WrongCode)
+        ));
+    }
+}
@@ -0,0 +1,115 @@
+//! # ZVault
+//! A single vault as one encrypted file, for moving it between machines.
+//! The file is a header (magic and format version) followed by an archive of the vault
+//! that is encrypted with the key of the vault, so no extra passphrase is needed.
+
+use super::archive::{Archive, ArchiveError};
+use crate::safe::crypto::{self, CryptoError};
+use std::error;
+use std::fmt;
+
+/// Marks a file as an exported vault.
+const MAGIC: &[u8] = b"ZVAULT";
+/// Version of the file layout.
+const FORMAT_VERSION: u8 = 1;
+
+#[derive(Debug)]
+pub enum ZVaultError {
+    NotAVault,
+    UnsupportedVersion(u8),
+    SecretError(CryptoError),
+    ArchiveError(ArchiveError),
+}
+
+impl fmt::Display for ZVaultError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::NotAVault => write!(f, "The file is not an exported vault"),
+            Self::UnsupportedVersion(version) => write!(
+                f,
+                "Vault file format {} is newer than the supported format {}, upgrade zpass",
+                version, FORMAT_VERSION
+            ),
+            Self::SecretError(ref err) => write!(f, "Wrong key or damaged file:\n{}", err),
+            Self::ArchiveError(ref err) => write!(f, "Damaged file:\n{}", err),
+        }
+    }
+}
+
+impl error::Error for ZVaultError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Self::SecretError(ref err) => Some(err),
+            Self::ArchiveError(ref err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<CryptoError> for ZVaultError {
+    fn from(err: CryptoError) -> Self {
+        ZVaultError::SecretError(err)
+    }
+}
+
+impl From<ArchiveError> for ZVaultError {
+    fn from(err: ArchiveError) -> Self {
+        ZVaultError::ArchiveError(err)
+    }
+}
+
+/// Encrypts a serialized vault with its key.
+pub fn pack(key: &str, serialized: String) -> Result<Vec<u8>, ZVaultError> {
+    let archive = Archive::new(vec![serialized]).pack()?;
+    let mut file = MAGIC.to_vec();
+    file.push(FORMAT_VERSION);
+    file.extend(crypto::encrypt(key, &archive)?);
+    Ok(file)
+}
+
+/// Decrypts a file written by `pack` and returns the serialized vault.
+pub fn unpack(key: &str, file: &[u8]) -> Result<String, ZVaultError> {
+    let version = check_header(file)?;
+    if version > FORMAT_VERSION {
+        return Err(ZVaultError::UnsupportedVersion(version));
+    }
+    let archive = crypto::decrypt(key, &file[MAGIC.len() + 1..])?;
+    let mut archive = Archive::unpack(&archive)?;
+    match archive.vaults.len() {
+        1 => Ok(archive.vaults.remove(0)),
+        _ => Err(ZVaultError::NotAVault),
+    }
+}
+
+/// Returns true if the file was written by `pack`.
+pub fn is_vault(file: &[u8]) -> bool {
+    check_header(file).is_ok()
+}
+
+/// Returns the format version of the file.
+fn check_header(file: &[u8]) -> Result<u8, ZVaultError> {
+    if file.len() <= MAGIC.len() || !file.starts_with(MAGIC) {
+        return Err(ZVaultError::NotAVault);
+    }
+    Ok(file[MAGIC.len()])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validates_header() {
+        assert!(matches!(
+            check_header(b"{\"name\":\"work\"}"),
+            Err(ZVaultError::NotAVault)
+        ));
+        assert!(matches!(
+            unpack("KEY", b"ZVAULT\x02"),
+            Err(ZVaultError::UnsupportedVersion(2))
+        ));
+        let file = pack("KEY", "{}".to_owned()).unwrap();
+        assert_eq!(check_header(&file).unwrap(), FORMAT_VERSION);
+        assert!(unpack("WRONG", &file).is_err());
+    }
+}
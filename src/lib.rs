@@ -1,9 +1,29 @@
+pub mod api;
+pub use api::ZPass;
+
+#[cfg(feature = "cli")]
 pub mod cli;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(feature = "cli")]
 pub use cli::parser;
+#[cfg(feature = "cli")]
 pub use cli::run;
 
+#[cfg(feature = "cli")]
+pub mod integration;
+#[cfg(feature = "cli")]
+pub use integration::agent;
+
+#[cfg(feature = "cli")]
+pub mod interop;
+
 pub mod safe;
+pub use safe::audit;
 pub use safe::collection;
 pub use safe::crypto;
 pub use safe::preference;
+pub use safe::rules;
+pub use safe::schema;
+pub use safe::strength;
 pub use safe::vault;
@@ -1,3 +1,6 @@
+use std::{env, io, process};
+
 fn main() {
-    zpass::run::start();
+    let args: Vec<String> = env::args().collect();
+    process::exit(zpass::run::run(&args, &mut io::stdout()));
 }
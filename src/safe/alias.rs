@@ -0,0 +1,77 @@
+//! # Alias
+//! Login identities derived from the vault secret, so each site gets its own and it can be
+//! derived again later: a pronounceable username such as `kavo-rite42`, or a plus address such
+//! as `me+x7k2@example.com` for a mailbox that accepts them.
+
+/// Consonants and vowels of the syllables of usernames.
+const CONSONANTS: &[u8] = b"bdfghklmnprstvz";
+const VOWELS: &[u8] = b"aeiou";
+/// Characters of the tags of plus addresses.
+const TAG_CHARACTERS: &[u8] = b"abcdefghijkmnpqrstuvwxyz23456789";
+/// Length of the tag of a plus address.
+const TAG_LENGTH: usize = 4;
+
+/// Returns the context of the bytes an alias is derived from, a new version gives a new alias.
+pub fn context(domain: &str, version: usize) -> String {
+    match version {
+        0 => format!("alias:{}", domain),
+        version => format!("alias:{}:{}", domain, version),
+    }
+}
+
+/// Returns the alias the derived bytes make of the base: a plus address of an email address, a
+/// tagged name of another base, or a username without one.
+pub fn alias(bytes: &[u8], base: Option<&str>) -> String {
+    match base {
+        Some(base) => match base.split_once('@') {
+            Some((local, domain)) => format!("{}+{}@{}", local, tag(bytes), domain),
+            None => format!("{}-{}", base, tag(bytes)),
+        },
+        None => username(bytes),
+    }
+}
+
+/// Returns two words of two syllables each and two digits, e.g. `kavo-rite42`.
+fn username(bytes: &[u8]) -> String {
+    let pick = |set: &[u8], i: usize| set[bytes[i % bytes.len()] as usize % set.len()] as char;
+    let mut name = String::new();
+    for word in 0..2 {
+        if word > 0 {
+            name.push('-');
+        }
+        for syllable in 0..2 {
+            let i = word * 4 + syllable * 2;
+            name.push(pick(CONSONANTS, i));
+            name.push(pick(VOWELS, i + 1));
+        }
+    }
+    let number = bytes[8 % bytes.len()] as usize % 100;
+    format!("{}{:02}", name, number)
+}
+
+/// Returns a short tag of letters and digits that are hard to confuse.
+fn tag(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .cycle()
+        .skip(16)
+        .take(TAG_LENGTH)
+        .map(|&b| TAG_CHARACTERS[b as usize % TAG_CHARACTERS.len()] as char)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn aliases_are_derived_from_the_bytes() {
+        let bytes: Vec<u8> = (0..32).collect();
+        assert_eq!(alias(&bytes, None), "befo-hali08");
+        assert_eq!(alias(&bytes, Some("me@example.com")), "me+stuv@example.com");
+        assert_eq!(alias(&bytes, Some("me")), "me-stuv");
+        assert_ne!(alias(&[7; 32], None), alias(&bytes, None));
+        assert_eq!(context("shop.example", 0), "alias:shop.example");
+        assert_eq!(context("shop.example", 2), "alias:shop.example:2");
+    }
+}
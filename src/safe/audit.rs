@@ -0,0 +1,300 @@
+//! # Audit
+//! Scans preferences for weak spots such as short passwords or passwords that were never rotated,
+//! and across the vaults for accounts that could be consolidated.
+
+use super::constants;
+use super::crypto;
+use super::preference::{Preference, Preferences};
+use super::strength;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+/// Domains a username may share the same settings on before they are reported.
+const SHARED_SETTINGS_DOMAINS: usize = 3;
+
+/// A weak spot found in a preference.
+#[derive(Serialize, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Issue {
+    // the password is shorter than the recommended length
+    ShortLength { length: usize },
+    // the password is still on its first version
+    NeverRotated,
+    // the same username is used for several domains
+    ReusedUsername { domains: usize },
+    // the current version of the password is older than the maximum age
+    Expired { days: u64 },
+    // the password is generated with an outdated scheme
+    LegacyScheme { scheme: usize },
+    // the password appears in known data breaches
+    Breached { count: u64 },
+    // the current version of the password is older than the rotation period of the preference
+    RotationDue { every_days: u64 },
+    // the same domain and username are in other vaults too
+    DuplicateAccount { vaults: Vec<String> },
+    // the username has the same settings on many domains
+    SharedSettings { domains: usize },
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::ShortLength { length } => write!(
+                f,
+                "short password ({} characters, {})",
+                length,
+                strength::estimate(*length, crypto::CHARSET_SIZE)
+            ),
+            Self::NeverRotated => write!(f, "never rotated"),
+            Self::ReusedUsername { domains } => write!(f, "username used on {} domains", domains),
+            Self::Expired { days } => write!(f, "password is {} days old", days),
+            Self::LegacyScheme { scheme } => write!(f, "legacy password scheme ({})", scheme),
+            Self::Breached { count } => write!(f, "found in {} breaches", count),
+            Self::RotationDue { every_days } => {
+                write!(f, "rotation is due (every {} days)", every_days)
+            }
+            Self::DuplicateAccount { vaults } => write!(
+                f,
+                "also in {}, keep the account in one vault",
+                vaults.join(", ")
+            ),
+            Self::SharedSettings { domains } => write!(
+                f,
+                "same settings on {} domains, consider one tag for them",
+                domains
+            ),
+        }
+    }
+}
+
+/// # Finding
+/// An issue together with the preference it was found in.
+#[derive(Serialize, Debug, PartialEq, Eq)]
+pub struct Finding {
+    pub vault: String,
+    pub domain: String,
+    pub username: String,
+    pub issue: Issue,
+}
+
+/// # Entropy
+/// The effective entropy of the settings of a generated password.
+#[derive(Serialize, Debug, PartialEq)]
+pub struct Entropy {
+    pub vault: String,
+    pub domain: String,
+    pub username: String,
+    pub bits: f64,
+    // weak is true if the bits are below the minimum
+    pub weak: bool,
+}
+
+/// Returns the entropy of each generated password of a vault, stored passwords have none that
+/// the settings tell.
+pub fn entropy(vault: &str, preferences: &Preferences, minimum: usize) -> Vec<Entropy> {
+    preferences
+        .iter()
+        .filter(|p| !p.is_stored())
+        .map(|p| {
+            let bits = strength::of(p).bits;
+            Entropy {
+                vault: vault.to_owned(),
+                domain: p.domain.clone(),
+                username: p.username.clone(),
+                bits,
+                weak: bits < minimum as f64,
+            }
+        })
+        .collect()
+}
+
+/// Returns all the issues found in the preferences of a vault.
+pub fn audit(vault: &str, preferences: &Preferences, now: u64) -> Vec<Finding> {
+    let mut domains_by_username: HashMap<&str, HashSet<&str>> = HashMap::new();
+    for p in preferences.iter() {
+        domains_by_username
+            .entry(&p.username)
+            .or_default()
+            .insert(&p.domain);
+    }
+
+    let mut findings = Vec::new();
+    for p in preferences.iter() {
+        let mut issues = Vec::new();
+        if p.length < constants::MIN_PASSWORD_LENGTH {
+            issues.push(Issue::ShortLength { length: p.length });
+        }
+        if p.version == 0 {
+            issues.push(Issue::NeverRotated);
+        }
+        let domains = domains_by_username[&p.username[..]].len();
+        if domains > 1 {
+            issues.push(Issue::ReusedUsername { domains });
+        }
+        if let Some(changed) = p.changed {
+            let age = now.saturating_sub(changed);
+            if age > constants::MAX_PASSWORD_AGE {
+                issues.push(Issue::Expired {
+                    days: age / (24 * 60 * 60),
+                });
+            }
+        }
+        if p.scheme < crypto::CURRENT_SCHEME {
+            issues.push(Issue::LegacyScheme { scheme: p.scheme });
+        }
+        if let Some(every) = p.rotate_every.filter(|_| p.is_due(now)) {
+            issues.push(Issue::RotationDue {
+                every_days: every / (24 * 60 * 60),
+            });
+        }
+
+        findings.extend(issues.into_iter().map(|issue| finding(vault, p, issue)));
+    }
+    findings
+}
+
+/// Returns the accounts that are in several vaults, and the usernames that have the same
+/// generated settings on many domains.
+pub fn duplicates(vaults: &[(&str, &Preferences)]) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    for (vault, preferences) in vaults {
+        for p in preferences.iter() {
+            let others: Vec<String> = vaults
+                .iter()
+                .filter(|(other, prefs)| {
+                    other != vault && prefs.account(&p.domain, &p.username).is_some()
+                })
+                .map(|(other, _)| other.to_string())
+                .collect();
+            if !others.is_empty() {
+                findings.push(finding(
+                    vault,
+                    p,
+                    Issue::DuplicateAccount { vaults: others },
+                ));
+            }
+
+            let domains = preferences
+                .iter()
+                .filter(|o| o.username == p.username && same_settings(o, p))
+                .count();
+            if domains >= SHARED_SETTINGS_DOMAINS {
+                findings.push(finding(vault, p, Issue::SharedSettings { domains }));
+            }
+        }
+    }
+    findings
+}
+
+/// Returns true if both passwords are generated with the same settings.
+fn same_settings(a: &Preference, b: &Preference) -> bool {
+    !a.is_stored()
+        && !b.is_stored()
+        && a.length == b.length
+        && a.scheme == b.scheme
+        && a.rules == b.rules
+        && a.unambiguous == b.unambiguous
+}
+
+/// Returns the finding of an issue in a preference.
+fn finding(vault: &str, p: &Preference, issue: Issue) -> Finding {
+    Finding {
+        vault: vault.to_owned(),
+        domain: p.domain.clone(),
+        username: p.username.clone(),
+        issue,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::preference::Preference;
+    use super::*;
+
+    #[test]
+    fn finds_weak_spots() {
+        let mut preferences = Preferences::new();
+        preferences
+            .add(Preference::new("a.com", "alice", 8))
+            .unwrap();
+        preferences
+            .add(Preference::new("b.com", "alice", 32))
+            .unwrap();
+        preferences
+            .add(Preference::new("c.com", "carol", 32))
+            .unwrap();
+        preferences.apply(|p| p.version = 1);
+        let c = preferences.get_mut(|p| p.domain == "c.com").unwrap();
+        c.changed = Some(0);
+        c.scheme = crypto::LEGACY_SCHEME;
+        c.rotate_every = Some(90 * 24 * 60 * 60);
+
+        let now = constants::MAX_PASSWORD_AGE + 24 * 60 * 60;
+        let issues: Vec<(String, Issue)> = audit("v", &preferences, now)
+            .into_iter()
+            .map(|f| (f.domain, f.issue))
+            .collect();
+
+        assert_eq!(
+            issues,
+            vec![
+                ("a.com".to_owned(), Issue::ShortLength { length: 8 }),
+                ("a.com".to_owned(), Issue::ReusedUsername { domains: 2 }),
+                ("b.com".to_owned(), Issue::ReusedUsername { domains: 2 }),
+                ("c.com".to_owned(), Issue::Expired { days: 366 }),
+                ("c.com".to_owned(), Issue::LegacyScheme { scheme: 0 }),
+                ("c.com".to_owned(), Issue::RotationDue { every_days: 90 }),
+            ]
+        );
+
+        let weak: Vec<(String, bool)> = entropy("v", &preferences, constants::MIN_ENTROPY)
+            .into_iter()
+            .map(|e| (e.domain, e.weak))
+            .collect();
+        assert_eq!(
+            weak,
+            vec![
+                ("a.com".to_owned(), true),
+                ("b.com".to_owned(), false),
+                ("c.com".to_owned(), false),
+            ]
+        );
+
+        let mut other = Preferences::new();
+        other.add(Preference::new("a.com", "alice", 8)).unwrap();
+        for domain in &["x.com", "y.com", "z.com"] {
+            other.add(Preference::new(domain, "bob", 24)).unwrap();
+        }
+        let issues: Vec<(String, String, Issue)> =
+            duplicates(&[("v", &preferences), ("w", &other)])
+                .into_iter()
+                .map(|f| (f.vault, f.domain, f.issue))
+                .filter(|(_, domain, _)| domain != "y.com" && domain != "z.com")
+                .collect();
+        assert_eq!(
+            issues,
+            vec![
+                (
+                    "v".to_owned(),
+                    "a.com".to_owned(),
+                    Issue::DuplicateAccount {
+                        vaults: vec!["w".to_owned()]
+                    }
+                ),
+                (
+                    "w".to_owned(),
+                    "a.com".to_owned(),
+                    Issue::DuplicateAccount {
+                        vaults: vec!["v".to_owned()]
+                    }
+                ),
+                (
+                    "w".to_owned(),
+                    "x.com".to_owned(),
+                    Issue::SharedSettings { domains: 3 }
+                ),
+            ]
+        );
+    }
+}
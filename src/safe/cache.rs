@@ -0,0 +1,79 @@
+//! # Cache
+//! Keeps the decrypted vault secrets for the rest of the process, so a batch, the menu or the
+//! TUI decrypts each secret once instead of for every password. An entry is found by the
+//! encrypted secret of its vault and a hash of the key, so a wrong key misses, and it is
+//! forgotten when the key of the vault changes. The secrets stay in locked memory and are wiped
+//! when the cache is cleared, before the process exits or when an `api::ZPass` is dropped.
+
+use super::memory::Locked;
+use sha3::{Digest, Sha3_256};
+use std::sync::Mutex;
+
+/// A decrypted secret.
+struct Entry {
+    // encrypted is the secret as the vault keeps it
+    encrypted: Vec<u8>,
+    // key is the hash of the key that decrypts it
+    key: Locked,
+    // secret is the decrypted secret
+    secret: Locked,
+}
+
+/// The decrypted secrets of this process.
+static ENTRIES: Mutex<Vec<Entry>> = Mutex::new(Vec::new());
+
+/// Returns the hash of a key, the key itself isn't kept.
+fn hash(key: &str) -> Locked {
+    Locked::new(Sha3_256::digest(key.as_bytes()).to_vec())
+}
+
+/// Returns a copy of the decrypted secret, if the key decrypted it before.
+pub fn get(encrypted: &[u8], key: &str) -> Option<Locked> {
+    let entries = ENTRIES.lock().ok()?;
+    let key = hash(key);
+    entries
+        .iter()
+        .find(|e| e.encrypted == encrypted && *e.key == *key)
+        .map(|e| Locked::concat(&[&e.secret]))
+}
+
+/// Keeps a copy of a secret the key decrypted.
+pub fn put(encrypted: &[u8], key: &str, secret: &[u8]) {
+    if let Ok(mut entries) = ENTRIES.lock() {
+        entries.retain(|e| e.encrypted != encrypted);
+        entries.push(Entry {
+            encrypted: encrypted.to_vec(),
+            key: hash(key),
+            secret: Locked::concat(&[secret]),
+        });
+    }
+}
+
+/// Forgets the decrypted secret, e.g. before its key changes.
+pub fn forget(encrypted: &[u8]) {
+    if let Ok(mut entries) = ENTRIES.lock() {
+        entries.retain(|e| e.encrypted != encrypted);
+    }
+}
+
+/// Wipes every decrypted secret.
+pub fn clear() {
+    if let Ok(mut entries) = ENTRIES.lock() {
+        entries.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_secrets_by_key() {
+        let encrypted = b"encrypted secret of the cache test";
+        put(encrypted, "KEY", b"secret");
+        assert_eq!(get(encrypted, "KEY").as_deref(), Some(&b"secret"[..]));
+        assert!(get(encrypted, "WRONG_KEY").is_none());
+        forget(encrypted);
+        assert!(get(encrypted, "KEY").is_none());
+    }
+}
@@ -1,12 +1,14 @@
 //! # Collection
 //! Wrappers for working with data structures that act like collections.
 
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use std::cmp::PartialEq;
+use std::cmp::{Ordering, PartialEq};
+use std::iter::FromIterator;
 
 /// # List
 /// Represents a sequence of items. It is a wrapper around Vec that does not expose the underlying Vec.
-#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+#[derive(Serialize, Deserialize, JsonSchema, Debug, PartialEq, Eq)]
 pub struct List<T> {
     items: Vec<T>,
 }
@@ -60,6 +62,51 @@ impl<T> List<T> {
         }
     }
 
+    /// Returns an immutable reference to the item at the position.
+    pub fn at(&self, position: usize) -> Option<&T> {
+        self.items.get(position)
+    }
+
+    /// Returns a mutable reference to the item at the position.
+    pub fn at_mut(&mut self, position: usize) -> Option<&mut T> {
+        self.items.get_mut(position)
+    }
+
+    /// Returns immutable references to all the items that satisfy the predicate, in order.
+    pub fn find_all<F>(&self, f: F) -> Vec<&T>
+    where
+        F: Fn(&T) -> bool,
+    {
+        self.items.iter().filter(|item| f(item)).collect()
+    }
+
+    /// Returns mutable references to all the items that satisfy the predicate, in order.
+    pub fn find_all_mut<F>(&mut self, f: F) -> Vec<&mut T>
+    where
+        F: Fn(&T) -> bool,
+    {
+        self.items.iter_mut().filter(|item| f(item)).collect()
+    }
+
+    /// Removes the first item that satisfies the predicate and returns it.
+    pub fn remove<F>(&mut self, f: F) -> Option<T>
+    where
+        F: Fn(&T) -> bool,
+    {
+        let position = self.items.iter().position(f)?;
+        Some(self.items.remove(position))
+    }
+
+    /// Keeps the items that satisfy the predicate and returns the others, in their order.
+    pub fn retain<F>(&mut self, f: F) -> Vec<T>
+    where
+        F: Fn(&T) -> bool,
+    {
+        let (kept, removed) = self.items.drain(..).partition(|item| f(item));
+        self.items = kept;
+        removed
+    }
+
     /// Applies a closure to all the items in the List.
     pub fn apply<F>(&mut self, f: F) -> ()
     where
@@ -68,8 +115,118 @@ impl<T> List<T> {
         self.items.iter_mut().for_each(f);
     }
 
+    /// Returns an iterator over the items in the List.
+    pub fn iter(&self) -> std::slice::Iter<'_, T> {
+        self.items.iter()
+    }
+
+    /// Returns an iterator over mutable references to the items in the List.
+    pub fn iter_mut(&mut self) -> std::slice::IterMut<'_, T> {
+        self.items.iter_mut()
+    }
+
+    /// Returns the number of items in the List.
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    /// Sorts the items with the comparator, items that compare equal keep their order.
+    pub fn sort_by<F>(&mut self, compare: F)
+    where
+        F: FnMut(&T, &T) -> Ordering,
+    {
+        self.items.sort_by(compare);
+    }
+
+    /// Returns true if each item is ordered before or equal to the next one by the comparator.
+    pub fn is_sorted_by<F>(&self, mut compare: F) -> bool
+    where
+        F: FnMut(&T, &T) -> Ordering,
+    {
+        self.items
+            .windows(2)
+            .all(|pair| compare(&pair[0], &pair[1]) != Ordering::Greater)
+    }
+
     /// Returns true if List contains no items.
     pub fn is_empty(&self) -> bool {
         self.items.is_empty()
     }
 }
+
+impl<T> IntoIterator for List<T> {
+    type Item = T;
+    type IntoIter = std::vec::IntoIter<T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.items.into_iter()
+    }
+}
+
+impl<'a, T> IntoIterator for &'a List<T> {
+    type Item = &'a T;
+    type IntoIter = std::slice::Iter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.items.iter()
+    }
+}
+
+impl<'a, T> IntoIterator for &'a mut List<T> {
+    type Item = &'a mut T;
+    type IntoIter = std::slice::IterMut<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.items.iter_mut()
+    }
+}
+
+impl<T> FromIterator<T> for List<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        List {
+            items: iter.into_iter().collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_removes_and_retains() {
+        let mut list = List::from(vec![1, 2, 3, 4, 5]);
+        assert_eq!(list.find_all(|&i| i > 3), vec![&4, &5]);
+        for i in list.find_all_mut(|&i| i == 5) {
+            *i += 1;
+        }
+        assert_eq!(list.get(|&i| i > 4), Some(&6));
+        list.find_all_mut(|&i| i == 6)
+            .into_iter()
+            .for_each(|i| *i = 5);
+
+        assert_eq!(list.remove(|&i| i > 1), Some(2));
+        assert_eq!(list.remove(|&i| i > 9), None);
+        assert_eq!(list.retain(|&i| i % 2 == 1), vec![4]);
+        assert_eq!(list, List::from(vec![1, 3, 5]));
+        assert_eq!(list.retain(|_| false), vec![1, 3, 5]);
+        assert!(list.is_empty());
+    }
+
+    #[test]
+    fn iterates_sorts_and_collects() {
+        let mut list: List<i32> = (1..4).collect();
+        for i in &mut list {
+            *i *= 10;
+        }
+        assert_eq!(list.len(), 3);
+        assert!(list.is_sorted_by(|a, b| a.cmp(b)));
+        list.sort_by(|a, b| b.cmp(a));
+        assert!(!list.is_sorted_by(|a, b| a.cmp(b)));
+        list.sort_by(|a, b| a.cmp(b));
+        let borrowed: Vec<&i32> = (&list).into_iter().collect();
+        assert_eq!(borrowed, vec![&10, &20, &30]);
+        list.iter_mut().for_each(|i| *i += 1);
+        assert_eq!(list.into_iter().collect::<Vec<_>>(), vec![11, 21, 31]);
+    }
+}
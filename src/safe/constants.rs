@@ -1,4 +1,126 @@
-/// ROOT_PATH indicates the location of vaults in the file system
+/// ROOT_PATH is the location of the vaults before they moved to the data directory, and where they
+/// stay without one
 pub const ROOT_PATH: &str = "./.zpass";
 /// SECRET_LENGTH indicates the length of the vault-secret in characters
 pub const SECRET_LENGTH: usize = 256;
+/// MIN_PASSWORD_LENGTH is the shortest password length that is not reported as weak
+pub const MIN_PASSWORD_LENGTH: usize = 16;
+/// MAX_PASSWORD_AGE is the time in seconds after which a password is reported as expired
+pub const MAX_PASSWORD_AGE: u64 = 365 * 24 * 60 * 60;
+/// MIN_ENTROPY is the fewest bits of a password that `audit --entropy` doesn't report as weak
+pub const MIN_ENTROPY: usize = 80;
+/// UNUSED_AGE is the time in seconds after which an account that wasn't used is reported as unused
+pub const UNUSED_AGE: u64 = 365 * 24 * 60 * 60;
+/// MAX_CHANGES is the number of earlier settings each preference keeps to roll back to
+pub const MAX_CHANGES: usize = 10;
+/// HOME_VARIABLE names the environment variable that moves the root path, e.g. for scripts and tests
+pub const HOME_VARIABLE: &str = "ZPASS_HOME";
+/// HOOKS_DIR is the directory under the root path that holds the hook scripts
+pub const HOOKS_DIR: &str = "hooks";
+/// JOURNAL_DIR is the directory under the root path that keeps the vault files for undo
+pub const JOURNAL_DIR: &str = ".undo";
+/// LOG_DIR is the directory under the root path that keeps the history of each vault
+pub const LOG_DIR: &str = "log";
+/// BACKUP_DIR is the directory under the root path that keeps the vault files as they were before
+/// they were migrated
+pub const BACKUP_DIR: &str = "backups";
+/// ATTEMPTS_DIR is the directory under the root path that counts the wrong keys of each vault
+pub const ATTEMPTS_DIR: &str = ".attempts";
+/// DRY_RUN_VARIABLE names the environment variable that turns writing files into reporting them
+pub const DRY_RUN_VARIABLE: &str = "ZPASS_DRY_RUN";
+
+/// Returns the directory of the vaults: $ZPASS_HOME if it is set, the data directory otherwise.
+/// Vaults in ROOT_PATH that weren't moved yet are found there until they are.
+pub fn root() -> std::path::PathBuf {
+    if let Some(home) = std::env::var_os(HOME_VARIABLE) {
+        return home.into();
+    }
+    match data_dir() {
+        Some(dir) if dir.exists() || !has_old_root() => dir,
+        _ => ROOT_PATH.into(),
+    }
+}
+
+/// Returns true if vaults may still be in ROOT_PATH, the tests never look there.
+fn has_old_root() -> bool {
+    !cfg!(test) && std::path::Path::new(ROOT_PATH).is_dir()
+}
+
+/// Moves the vaults from ROOT_PATH to the data directory once, unless $ZPASS_HOME names the
+/// root. Returns the data directory if they were moved.
+pub fn migrate_root() -> std::io::Result<Option<std::path::PathBuf>> {
+    match (std::env::var_os(HOME_VARIABLE), data_dir()) {
+        (None, Some(dir)) if !is_dry_run() && has_old_root() => {
+            let migrated = migrate(std::path::Path::new(ROOT_PATH), &dir)?;
+            Ok(if migrated { Some(dir) } else { None })
+        }
+        _ => Ok(None),
+    }
+}
+
+/// Moves the old directory to the new one if only the old one exists. Returns true if it did.
+fn migrate(old: &std::path::Path, new: &std::path::Path) -> std::io::Result<bool> {
+    if !old.is_dir() || new.exists() {
+        return Ok(false);
+    }
+    if let Some(parent) = new.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::rename(old, new)?;
+    Ok(true)
+}
+
+/// Returns the directory where the platform keeps application data, e.g. `~/.local/share/zpass`.
+/// The tests get a directory of their own instead, so they never write into the one of the user.
+pub fn data_dir() -> Option<std::path::PathBuf> {
+    if cfg!(test) {
+        let dir = format!("zpass-test-{}", std::process::id());
+        return Some(std::env::temp_dir().join(dir));
+    }
+    let var = |name| std::env::var_os(name).map(std::path::PathBuf::from);
+    let dir = if cfg!(windows) {
+        var("APPDATA")
+    } else if cfg!(target_os = "macos") {
+        var("HOME").map(|home| home.join("Library").join("Application Support"))
+    } else {
+        var("XDG_DATA_HOME").or_else(|| var("HOME").map(|home| home.join(".local").join("share")))
+    };
+    dir.map(|dir| dir.join("zpass"))
+}
+
+/// Returns true if files are only reported instead of written, removed or renamed
+pub fn is_dry_run() -> bool {
+    std::env::var_os(DRY_RUN_VARIABLE).is_some()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn migrate_moves_the_old_root_once() {
+        let tmp = std::env::temp_dir().join(format!("zpass-migrate-{}", std::process::id()));
+        let (old, new) = (tmp.join(".zpass"), tmp.join("data").join("zpass"));
+        assert!(!migrate(&old, &new).unwrap());
+
+        std::fs::create_dir_all(&old).unwrap();
+        std::fs::write(old.join("personal.json"), "{}").unwrap();
+        assert!(migrate(&old, &new).unwrap());
+        assert!(new.join("personal.json").exists());
+        assert!(!old.exists());
+
+        std::fs::create_dir_all(&old).unwrap();
+        assert!(!migrate(&old, &new).unwrap());
+        std::fs::remove_dir_all(&tmp).unwrap();
+    }
+
+    #[test]
+    fn tests_keep_out_of_the_data_directory() {
+        let temp = std::env::temp_dir();
+        assert!(data_dir().unwrap().starts_with(&temp));
+        if std::env::var_os(HOME_VARIABLE).is_none() {
+            assert!(root().starts_with(&temp));
+        }
+        assert!(migrate_root().unwrap().is_none());
+    }
+}
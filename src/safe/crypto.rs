@@ -9,18 +9,30 @@ use block_modes::{BlockMode, BlockModeError, Cbc, InvalidKeyIvLength};
 use rand::Rng;
 // Hashing
 use sha3::{Digest, Sha3_256};
+// Password rules
+use super::cache;
+use super::memory::{self, Locked};
+use super::rules::Rules;
 // Serialization
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 // Comparision
 use std::cmp::PartialEq;
 // Error
 use std::error;
 use std::fmt;
+use std::string::FromUtf8Error;
+use tracing::{debug, trace, trace_span};
 
 #[derive(Debug)]
 pub enum CryptoError {
     FailedToDecrypt(BlockModeError),
     InvalidKeyIvLength(InvalidKeyIvLength),
+    UnsatisfiableRules,
+    InvalidUtf8(FromUtf8Error),
+    Truncated,
+    SelfTestFailed,
+    WrongKey,
 }
 
 impl fmt::Display for CryptoError {
@@ -28,6 +40,11 @@ impl fmt::Display for CryptoError {
         match self {
             Self::FailedToDecrypt(ref err) => write!(f, "Failed to decrypt:\n{}", err),
             Self::InvalidKeyIvLength(ref err) => write!(f, "Invalid Key or IV length:\n{}", err),
+            Self::UnsatisfiableRules => write!(f, "Failed to satisfy the password rules"),
+            Self::InvalidUtf8(ref err) => write!(f, "Decrypted value is not UTF-8:\n{}", err),
+            Self::Truncated => write!(f, "The encrypted data is truncated"),
+            Self::SelfTestFailed => write!(f, "The crypto self-test gave wrong results"),
+            Self::WrongKey => write!(f, "The key doesn't decrypt the secret"),
         }
     }
 }
@@ -37,6 +54,11 @@ impl error::Error for CryptoError {
         match self {
             Self::FailedToDecrypt(ref err) => Some(err),
             Self::InvalidKeyIvLength(ref err) => Some(err),
+            Self::UnsatisfiableRules => None,
+            Self::InvalidUtf8(ref err) => Some(err),
+            Self::Truncated => None,
+            Self::SelfTestFailed => None,
+            Self::WrongKey => None,
         }
     }
 }
@@ -53,12 +75,38 @@ impl From<InvalidKeyIvLength> for CryptoError {
     }
 }
 
+impl From<FromUtf8Error> for CryptoError {
+    fn from(err: FromUtf8Error) -> Self {
+        CryptoError::InvalidUtf8(err)
+    }
+}
+
+/// The original password scheme: the password only depends on the secret.
+pub const LEGACY_SCHEME: usize = 0;
+/// The password scheme used for new preferences: the domain, username, version and length
+/// are part of the preimage.
+pub const CURRENT_SCHEME: usize = 1;
+
+/// Number of distinct characters a password is made of.
+pub const CHARSET_SIZE: usize = 92;
+/// Length of the passwords generated by the legacy scheme, which ignores the requested length.
+pub const LEGACY_PASSWORD_LENGTH: usize = 32;
+/// How many candidates are derived at most when looking for a password that satisfies the rules.
+const MAX_RULE_ATTEMPTS: u64 = 1000;
+/// Length of a sha3-256 hash in bytes.
+const HASH_LENGTH: usize = 32;
+/// Characters that are easily confused when read off paper or dictated.
+pub const AMBIGUOUS_CHARACTERS: &str = "0Oo1lI|`'\"";
+
 /// Parameters that affect the generated password.
 pub struct PasswordParam<'a> {
     pub domain: &'a str,
     pub username: &'a str,
     pub length: usize,
     pub version: usize,
+    pub scheme: usize,
+    pub rules: Option<&'a Rules>,
+    pub unambiguous: bool,
 }
 
 /// Defines the interface for generating passwords.
@@ -69,27 +117,125 @@ pub trait PasswordGenerator {
     fn get(&self, key: &str, param: PasswordParam) -> Result<String, CryptoError>; // TODO: this should return a generic error: Box<dyn Error>
 }
 
+/// Defines the interface for keeping values that can't be generated, such as imported passwords.
+pub trait SecretStore {
+    /// Encrypts a value, so that only the holders of the key can read it.
+    fn seal(&self, key: &str, plaintext: &str) -> Result<Sealed, CryptoError>;
+    /// Decrypts a sealed value.
+    fn unseal(&self, key: &str, sealed: &Sealed) -> Result<String, CryptoError>;
+}
+
+/// # Sealed
+/// A value encrypted with a key derived from the vault secret.
+/// Since the vault secret stays the same when the key changes, sealed values don't have to be
+/// re-encrypted.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, PartialEq, Eq, Clone)]
+pub struct Sealed {
+    ciphertext: Vec<u8>,
+    iv: Vec<u8>,
+}
+
 /// # Secret
 /// Implements PasswordGenerator trait so it can be used to create passwords.
 /// Implements Serialize and Deserialize so it can be included in the vault.
-#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+/// A wrong key leaves valid padding behind about once in 256 tries, so the secret keeps a hash
+/// of itself that only the right key decrypts to.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, PartialEq, Eq)]
 pub struct Secret {
     encrypted_secret: Vec<u8>,
     iv: String,
+    // check is the key check value of the secret, secrets from before it and restored from
+    // backups have none until their key changes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    check: Option<Vec<u8>>,
 }
 
 impl Secret {
     /// Creates a secret given a key, initial vector IV and expected secret length.
     pub fn new(key: &str, iv: &str, length: usize) -> Result<Secret, CryptoError> {
-        let secret = Self::random_secret(length);
-        let encrypted_secret = Cipher::new(key, iv)?.encrypt(&secret);
-        let iv = iv.to_owned();
+        let secret = Locked::new(Self::random_secret(length));
+        Self::from_revealed(&secret, key, iv)
+    }
+
+    /// Encrypts a known secret with a key, e.g. the secret a vault was shared with.
+    pub fn from_revealed(secret: &[u8], key: &str, iv: &str) -> Result<Secret, CryptoError> {
+        let encrypted_secret = Cipher::new(key, iv)?.encrypt(secret);
         Ok(Secret {
             encrypted_secret,
-            iv,
+            iv: iv.to_owned(),
+            check: Some(Self::check_value(secret)),
         })
     }
 
+    /// Restores a secret from its encrypted parts, e.g. from a backup.
+    pub fn from_parts(encrypted_secret: Vec<u8>, iv: String) -> Secret {
+        Secret {
+            encrypted_secret,
+            iv,
+            check: None,
+        }
+    }
+
+    /// Returns the encrypted secret and the initial vector it was encrypted with.
+    pub fn parts(&self) -> (&[u8], &str) {
+        (&self.encrypted_secret, &self.iv)
+    }
+
+    /// Fails if the key doesn't decrypt the secret. Without a key check value, see
+    /// `is_checked`, only the padding of the decrypted secret is checked, which about one in
+    /// 256 wrong keys passes.
+    pub fn check_key(&self, key: &str) -> Result<(), CryptoError> {
+        self.decrypt(key)?;
+        Ok(())
+    }
+
+    /// Returns true if the secret has a key check value, so check_key tells every wrong key.
+    pub fn is_checked(&self) -> bool {
+        self.check.is_some()
+    }
+
+    /// Encrypts the secret with a new key. The secret itself stays the same, so do the
+    /// passwords and the sealed values. A secret without a key check value gets one.
+    pub fn change_key(&mut self, key: &str, new_key: &str) -> Result<(), CryptoError> {
+        debug!("Encrypting the secret with the new key");
+        let secret = self.decrypt(key)?;
+        cache::forget(&self.encrypted_secret);
+        self.encrypted_secret = Cipher::new(new_key, &self.iv)?.encrypt(&secret);
+        self.check.get_or_insert_with(|| Self::check_value(&secret));
+        Ok(())
+    }
+
+    /// Derives 32 bytes for uses other than passwords, such as ssh keys.
+    /// The context names the use and the site, so the bytes never match a password or a sealing
+    /// key.
+    pub fn derive_bytes(&self, key: &str, context: &str) -> Result<Vec<u8>, CryptoError> {
+        let secret = self.decrypt(key)?;
+        Ok(Self::hash(&Locked::concat(&[&secret, &[0], context.as_bytes()])).to_vec())
+    }
+
+    /// Decrypts the secret into memory that is locked and wiped after use. A secret the key
+    /// decrypted before in this process comes from the cache.
+    pub fn decrypt(&self, key: &str) -> Result<Locked, CryptoError> {
+        if let Some(secret) = cache::get(&self.encrypted_secret, key) {
+            trace!("Found the decrypted secret in the cache");
+            return Ok(secret);
+        }
+        let secret = Locked::new(Cipher::new(key, &self.iv)?.decrypt(&self.encrypted_secret)?);
+        match self.check {
+            Some(ref check) if *check != Self::check_value(&secret) => Err(CryptoError::WrongKey),
+            _ => {
+                cache::put(&self.encrypted_secret, key, &secret);
+                Ok(secret)
+            }
+        }
+    }
+
+    /// Returns the key check value of a secret, a hash that never matches a derived password,
+    /// key or sealed value, since none of them hash the secret after this prefix.
+    fn check_value(secret: &[u8]) -> Vec<u8> {
+        Self::hash(&Locked::concat(&[b"zpass key check\0", secret])).to_vec()
+    }
+
     /// Returns a sequence of random bytes of the given length
     fn random_secret(length: usize) -> Vec<u8> {
         let mut rng = rand::thread_rng();
@@ -98,26 +244,170 @@ impl Secret {
     }
 
     /// Maps bytes to a subset of ascii character range.
-    fn to_ascii_range(v: Vec<u8>) -> String {
-        v.iter().map(|b| (b % 92 + 33) as char).collect()
+    fn to_ascii_range(v: &[u8]) -> String {
+        let mut ascii = String::with_capacity(v.len());
+        ascii.extend(v.iter().map(|b| (b % CHARSET_SIZE as u8 + 33) as char));
+        ascii
     }
 
-    /// Hashs data to 256 bits or 16 bytes.
-    fn hash(data: &Vec<u8>) -> Vec<u8> {
-        let hash = Sha3_256::digest(&data);
-        hash.iter().map(|b| *b).collect()
+    /// Hashs data to 256 bits or 32 bytes, without allocating.
+    fn hash(data: &[u8]) -> [u8; HASH_LENGTH] {
+        let mut hash = [0; HASH_LENGTH];
+        hash.copy_from_slice(&Sha3_256::digest(data));
+        hash
+    }
+
+    /// Derives as many bytes as the password length from the secret and the password params.
+    /// The preimage is hashed together with a counter until enough bytes are produced. It holds
+    /// the length, so a shorter password of the account isn't a prefix of a longer one.
+    /// Further attempts, needed to satisfy password rules, extend the preimage with the attempt.
+    /// The counter is written in place at the end of one buffer, so each block needs no copy.
+    fn derive(secret: &[u8], params: &PasswordParam, attempt: u64) -> Locked {
+        let attempt_bytes = attempt.to_le_bytes();
+        let attempt: &[u8] = if attempt > 0 { &attempt_bytes } else { &[] };
+        let mut block = Locked::concat(&[
+            secret,
+            params.domain.as_bytes(),
+            &[0],
+            params.username.as_bytes(),
+            &[0],
+            &(params.version as u64).to_le_bytes(),
+            &(params.length as u64).to_le_bytes(),
+            attempt,
+            &0u64.to_le_bytes(),
+        ]);
+        let counter_start = block.len() - 8;
+
+        let mut bytes = Locked::new(vec![0; params.length]);
+        for (counter, chunk) in bytes.chunks_mut(HASH_LENGTH).enumerate() {
+            block[counter_start..].copy_from_slice(&(counter as u64).to_le_bytes());
+            let mut hash = Self::hash(&block);
+            chunk.copy_from_slice(&hash[..chunk.len()]);
+            memory::wipe(&mut hash);
+        }
+        bytes
+    }
+
+    /// Derives the key for sealing values from the decrypted secret.
+    fn sealing_key(&self, key: &str) -> Result<Locked, CryptoError> {
+        let secret = self.decrypt(key)?;
+        let mut hash = Self::hash(&Locked::concat(&[&secret, b"sealed"]));
+        let sealing_key = Locked::concat(&[&hash]);
+        memory::wipe(&mut hash);
+        Ok(sealing_key)
+    }
+
+    /// Derives candidates until one satisfies the rules, if there are any.
+    /// Each derived byte picks a character from the charset.
+    fn derive_from_charset(secret: &[u8], params: &PasswordParam) -> Result<String, CryptoError> {
+        let charset = charset(params.rules, params.unambiguous);
+        if charset.is_empty() {
+            return Err(CryptoError::UnsatisfiableRules);
+        }
+        for attempt in 0..MAX_RULE_ATTEMPTS {
+            let password: String = Self::derive(secret, params, attempt)
+                .iter()
+                .map(|b| charset[*b as usize % charset.len()])
+                .collect();
+            let accepted = match params.rules {
+                Some(rules) => rules.accepts(&password),
+                None => true,
+            };
+            if accepted {
+                return Ok(password);
+            }
+            trace!("Candidate {} doesn't satisfy the rules", attempt);
+        }
+        Err(CryptoError::UnsatisfiableRules)
     }
 }
 
 impl PasswordGenerator for Secret {
-    fn get(&self, key: &str, _params: PasswordParam) -> Result<String, CryptoError> {
-        let secret = Cipher::new(key, &self.iv)?.decrypt(&self.encrypted_secret)?;
-        // TODO: include the password params in the preimage
-        let ascii_password = Self::to_ascii_range(Self::hash(&secret));
+    fn get(&self, key: &str, params: PasswordParam) -> Result<String, CryptoError> {
+        let _span = trace_span!("derive", scheme = params.scheme, length = params.length).entered();
+        let secret = self.decrypt(key)?;
+        let ascii_password = match params.scheme {
+            LEGACY_SCHEME => Self::to_ascii_range(&Self::hash(&secret)),
+            _ => Self::derive_from_charset(&secret, &params)?,
+        };
         Ok(ascii_password)
     }
 }
 
+impl SecretStore for Secret {
+    fn seal(&self, key: &str, plaintext: &str) -> Result<Sealed, CryptoError> {
+        let sealing_key = self.sealing_key(key)?;
+        let iv = Self::random_secret(IV_LENGTH_FOR_AES_256_IN_BYTES);
+        let ciphertext = Cipher::from_bytes(&sealing_key, &iv)?.encrypt(plaintext.as_bytes());
+        Ok(Sealed { ciphertext, iv })
+    }
+
+    fn unseal(&self, key: &str, sealed: &Sealed) -> Result<String, CryptoError> {
+        let sealing_key = self.sealing_key(key)?;
+        let plaintext =
+            Cipher::from_bytes(&sealing_key, &sealed.iv)?.decrypt(&sealed.ciphertext)?;
+        Ok(String::from_utf8(plaintext)?)
+    }
+}
+
+/// Returns the characters passwords are made of: the allowed characters of the rules or the
+/// default ascii range, without the visually ambiguous characters if requested.
+pub fn charset(rules: Option<&Rules>, unambiguous: bool) -> Vec<char> {
+    let charset: Vec<char> = match rules {
+        Some(rules) => rules.allowed.chars().collect(),
+        None => (0..CHARSET_SIZE as u8).map(|b| (b + 33) as char).collect(),
+    };
+    charset
+        .into_iter()
+        .filter(|c| !(unambiguous && AMBIGUOUS_CHARACTERS.contains(*c)))
+        .collect()
+}
+
+/// Runs a throwaway secret through a key change, password generation and sealing,
+/// to find a broken build or platform before a vault is trusted to it.
+pub fn self_test() -> Result<(), CryptoError> {
+    let (key, new_key, plaintext) = ("SELF_TEST_KEY", "SELF_TEST_NEW_KEY", "SELF_TEST");
+    let mut secret = Secret::new(key, "SELF_TEST_IV", 64)?;
+    let param = || PasswordParam {
+        domain: "self.test",
+        username: "self-test",
+        length: 32,
+        version: 0,
+        scheme: CURRENT_SCHEME,
+        rules: None,
+        unambiguous: false,
+    };
+    let password = secret.get(key, param())?;
+    secret.change_key(key, new_key)?;
+    let sealed = secret.seal(new_key, plaintext)?;
+    let passed = password.chars().count() == 32
+        && secret.get(new_key, param())? == password
+        && secret.unseal(new_key, &sealed)? == plaintext
+        && decrypt(key, &encrypt(key, plaintext.as_bytes())?)? == plaintext.as_bytes();
+    if passed {
+        Ok(())
+    } else {
+        Err(CryptoError::SelfTestFailed)
+    }
+}
+
+/// Encrypts data with a key, the random initial vector is prepended to the ciphertext.
+pub fn encrypt(key: &str, plaintext: &[u8]) -> Result<Vec<u8>, CryptoError> {
+    let mut iv = Secret::random_secret(IV_LENGTH_FOR_AES_256_IN_BYTES);
+    let ciphertext = Cipher::from_bytes(&Cipher::hash(key), &iv)?.encrypt(plaintext);
+    iv.extend(ciphertext);
+    Ok(iv)
+}
+
+/// Decrypts data that was encrypted with `encrypt`.
+pub fn decrypt(key: &str, data: &[u8]) -> Result<Vec<u8>, CryptoError> {
+    if data.len() < IV_LENGTH_FOR_AES_256_IN_BYTES {
+        return Err(CryptoError::Truncated);
+    }
+    let (iv, ciphertext) = data.split_at(IV_LENGTH_FOR_AES_256_IN_BYTES);
+    Cipher::from_bytes(&Cipher::hash(key), iv)?.decrypt(ciphertext)
+}
+
 /// Cipher Block Chaining
 type Aes256Cbc = Cbc<Aes256, Pkcs7>;
 /// Initial Vector length for AES 256
@@ -134,26 +424,29 @@ impl Cipher {
     pub fn new(key: &str, iv: &str) -> Result<Cipher, CryptoError> {
         let key = Self::hash(key);
         let iv = Self::hash(iv);
-        let alg = Aes256Cbc::new_var(&key, &iv[..IV_LENGTH_FOR_AES_256_IN_BYTES])?;
+        Self::from_bytes(&key, &iv[..IV_LENGTH_FOR_AES_256_IN_BYTES])
+    }
+
+    /// Retuns a new Cipher given a raw 256 bit key and initial vector IV.
+    pub fn from_bytes(key: &[u8], iv: &[u8]) -> Result<Cipher, CryptoError> {
+        let alg = Aes256Cbc::new_var(key, iv)?;
         Ok(Cipher { alg })
     }
 
     /// Encrypts a plain text
-    pub fn encrypt(self, plaintext: &Vec<u8>) -> Vec<u8> {
+    pub fn encrypt(self, plaintext: &[u8]) -> Vec<u8> {
         self.alg.encrypt_vec(plaintext)
     }
 
     /// Decrypts a cipher text
-    pub fn decrypt(self, ciphertext: &Vec<u8>) -> Result<Vec<u8>, CryptoError> {
+    pub fn decrypt(self, ciphertext: &[u8]) -> Result<Vec<u8>, CryptoError> {
         let plaintext = self.alg.decrypt_vec(ciphertext)?;
         Ok(plaintext)
     }
 
-    /// Hashs a given string slice to 256 bits or 16 bytes
-    fn hash(data: &str) -> Vec<u8> {
-        let data = data.as_bytes().to_vec();
-        let hash = Sha3_256::digest(&data);
-        hash.iter().map(|b| *b).collect()
+    /// Hashs a given string slice to 256 bits or 32 bytes, kept in locked memory.
+    fn hash(data: &str) -> Locked {
+        Locked::concat(&[&Sha3_256::digest(data.as_bytes())])
     }
 }
 
@@ -171,4 +464,124 @@ mod tests {
 
         assert_eq!(message, secret);
     }
+
+    #[test]
+    fn change_key_keeps_the_secret() {
+        let mut secret = Secret::new("KEY", "IV", 40).unwrap();
+        let before = secret.derive_bytes("KEY", "test").unwrap();
+
+        secret.change_key("KEY", "NEW_KEY").unwrap();
+
+        assert_eq!(secret.derive_bytes("NEW_KEY", "test").unwrap(), before);
+        assert!(secret.change_key("KEY", "OTHER_KEY").is_err());
+    }
+
+    #[test]
+    fn key_check_tells_wrong_keys_with_valid_padding() {
+        let secret = Secret::new("KEY", "IV", 40).unwrap();
+        assert!(secret.is_checked());
+        assert!(secret.check_key("KEY").is_ok());
+        // find a wrong key that leaves valid padding behind, as one in about 256 do
+        let right = secret.decrypt("KEY").unwrap();
+        let wrong = (0..100_000)
+            .map(|n| format!("WRONG_KEY_{}", n))
+            .find(|key| {
+                let decrypted = Cipher::new(key, "IV")
+                    .and_then(|cipher| cipher.decrypt(&secret.encrypted_secret));
+                matches!(decrypted, Ok(ref bytes) if bytes[..] != right[..])
+            })
+            .unwrap();
+        assert!(matches!(
+            secret.check_key(&wrong),
+            Err(CryptoError::WrongKey)
+        ));
+
+        // an old secret gets a check value when its key changes
+        let (encrypted, iv) = secret.parts();
+        let mut old = Secret::from_parts(encrypted.to_vec(), iv.to_owned());
+        assert!(!old.is_checked());
+        old.change_key("KEY", "NEW_KEY").unwrap();
+        assert!(old.is_checked());
+        assert!(old.check_key("NEW_KEY").is_ok());
+    }
+
+    #[test]
+    fn password_depends_on_params() {
+        let secret = Secret::new("KEY", "IV", 40).unwrap();
+        let param = |domain, version| PasswordParam {
+            domain,
+            username: "USER",
+            length: 40,
+            version,
+            scheme: CURRENT_SCHEME,
+            rules: None,
+            unambiguous: false,
+        };
+        let password = secret.get("KEY", param("a.com", 0)).unwrap();
+
+        assert_eq!(password.len(), 40);
+        assert_eq!(password, secret.get("KEY", param("a.com", 0)).unwrap());
+        assert_ne!(password, secret.get("KEY", param("b.com", 0)).unwrap());
+        assert_ne!(password, secret.get("KEY", param("a.com", 1)).unwrap());
+        let shorter = secret
+            .get(
+                "KEY",
+                PasswordParam {
+                    length: 20,
+                    ..param("a.com", 0)
+                },
+            )
+            .unwrap();
+        assert!(!password.starts_with(&shorter));
+    }
+
+    #[test]
+    fn password_satisfies_rules() {
+        let secret = Secret::new("KEY", "IV", 40).unwrap();
+        let rules = super::super::rules::parse("required: digit; required: [-];").unwrap();
+        let password = secret
+            .get(
+                "KEY",
+                PasswordParam {
+                    domain: "a.com",
+                    username: "USER",
+                    length: 12,
+                    version: 0,
+                    scheme: CURRENT_SCHEME,
+                    rules: Some(&rules),
+                    unambiguous: false,
+                },
+            )
+            .unwrap();
+
+        assert_eq!(password.len(), 12);
+        assert!(password.contains('-'));
+        assert!(password.chars().all(|c| c == '-' || c.is_ascii_digit()));
+    }
+
+    #[test]
+    fn seal_inverse() {
+        let secret = Secret::new("KEY", "IV", 40).unwrap();
+        let sealed = secret.seal("KEY", "imported password").unwrap();
+
+        assert_eq!(secret.unseal("KEY", &sealed).unwrap(), "imported password");
+        assert!(secret.unseal("WRONG_KEY", &sealed).is_err());
+    }
+
+    #[test]
+    fn encrypt_inverse() {
+        let plaintext = b"vault".to_vec();
+        let encrypted = encrypt("KEY", &plaintext).unwrap();
+        assert_eq!(decrypt("KEY", &encrypted).unwrap(), plaintext);
+        assert!(decrypt("KEY", &encrypted[..4]).is_err());
+    }
+
+    #[test]
+    fn unambiguous_charset() {
+        let unambiguous = charset(None, true);
+
+        assert_eq!(unambiguous.len(), CHARSET_SIZE - AMBIGUOUS_CHARACTERS.len());
+        assert!(!unambiguous.contains(&'0') && !unambiguous.contains(&'l'));
+        assert_eq!(charset(None, false).len(), CHARSET_SIZE);
+    }
 }
@@ -0,0 +1,61 @@
+//! # Glob
+//! Matches domains against shell-like patterns such as `*.google.*`: `*` stands for any run of
+//! characters, `?` for a single one, everything else for itself. Domains are matched without
+//! regard to case.
+
+/// Returns true if the whole text matches the pattern.
+pub fn matches(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.to_lowercase().chars().collect();
+    let text: Vec<char> = text.to_lowercase().chars().collect();
+    let (mut p, mut t) = (0, 0);
+    // the last star and the text position it was tried at, to backtrack to
+    let mut star: Option<(usize, usize)> = None;
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, t));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match star {
+                // the star takes one more character
+                Some((star_p, star_t)) => {
+                    star = Some((star_p, star_t + 1));
+                    p = star_p + 1;
+                    t = star_t + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Returns true if the text matches any of the comma separated patterns.
+pub fn matches_any(patterns: &str, text: &str) -> bool {
+    patterns
+        .split(',')
+        .any(|pattern| matches(pattern.trim(), text))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stars_and_question_marks() {
+        assert!(matches("*.google.*", "mail.google.com"));
+        assert!(matches("*.google.*", "accounts.Google.co.uk"));
+        assert!(!matches("*.google.*", "google.com"));
+        assert!(matches("example.com", "example.com"));
+        assert!(!matches("example.com", "example.org"));
+        assert!(matches("exampl?.*", "example.org"));
+        assert!(matches("*", ""));
+        assert!(matches("a*b*c", "aXbYbZc"));
+        assert!(!matches("a*b*c", "aXbYbZ"));
+        assert!(matches_any("*.org, *.google.*", "example.org"));
+    }
+}
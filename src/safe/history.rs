@@ -0,0 +1,227 @@
+//! # History
+//! An append-only log of the changes to each vault: when it was created, renamed or deleted,
+//! and when an account was added, updated, rotated to a new version or deleted, by which user.
+//! The log of a vault is `log/<vault>.log` under the root path, one sealed entry per line, so
+//! only the holders of the vault key can read it.
+//!
+//! Most changes are made without the key, so their entries wait in `log/<vault>.pending`, which
+//! names no more than the vault file itself does. They are sealed and appended to the log the
+//! next time the key is given, e.g. to `zpass log`.
+
+use super::constants;
+use super::preference::{Preference, Preferences};
+use super::time;
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// Extension of the sealed log of a vault.
+const LOG_EXTENSION: &str = "log";
+/// Extension of the entries that wait for the key.
+const PENDING_EXTENSION: &str = "pending";
+
+/// # Entry
+/// A change to a vault.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct Entry {
+    // time is the unix timestamp of the change
+    pub time: u64,
+    // user is the login of whoever made the change
+    pub user: String,
+    // action describes the change, e.g. `rotate to version 3`
+    pub action: String,
+    // domain of the account that changed, empty for changes to the whole vault
+    pub domain: String,
+    // username of the account that changed, empty for changes to the whole vault
+    pub username: String,
+}
+
+impl Entry {
+    /// Creates an entry for a change the current user makes now.
+    pub fn new(action: &str, domain: &str, username: &str) -> Entry {
+        Entry {
+            time: time::now(),
+            user: user(),
+            action: action.to_owned(),
+            domain: domain.to_owned(),
+            username: username.to_owned(),
+        }
+    }
+}
+
+/// Returns the login of the current user.
+fn user() -> String {
+    env::var("USER")
+        .or_else(|_| env::var("USERNAME"))
+        .unwrap_or_else(|_| "unknown".to_owned())
+}
+
+/// Returns the file of a vault with the extension.
+fn file(root: &Path, vault: &str, extension: &str) -> PathBuf {
+    root.join(constants::LOG_DIR)
+        .join(vault)
+        .with_extension(extension)
+}
+
+/// Returns the entries of the changes from the earlier preferences to the later ones.
+/// Without earlier preferences, the vault was created. Only using a password is no change.
+pub fn entries(before: Option<&Preferences>, after: &Preferences) -> Vec<Entry> {
+    let mut entries = Vec::new();
+    let empty = Preferences::new();
+    let before = before.unwrap_or_else(|| {
+        entries.push(Entry::new("create vault", "", ""));
+        &empty
+    });
+    for p in after.iter() {
+        let action = match before.account(&p.domain, &p.username) {
+            None => "add".to_owned(),
+            Some(old) if old.version != p.version => format!("rotate to version {}", p.version),
+            Some(old) if is_changed(old, p) => "update".to_owned(),
+            Some(_) => continue,
+        };
+        entries.push(Entry::new(&action, &p.domain, &p.username));
+    }
+    for p in before.iter() {
+        if after.account(&p.domain, &p.username).is_none() {
+            entries.push(Entry::new("delete", &p.domain, &p.username));
+        }
+    }
+    entries
+}
+
+/// Returns true if the settings of an account differ, apart from when it was used.
+fn is_changed(old: &Preference, new: &Preference) -> bool {
+    old.length != new.length
+        || old.is_default() != new.is_default()
+        || old.scheme != new.scheme
+        || old.rules != new.rules
+        || old.unambiguous != new.unambiguous
+        || old.stored != new.stored
+        || old.tags != new.tags
+        || old.notes != new.notes
+        || old.totp != new.totp
+        || old.rotate_every != new.rotate_every
+        || old.token != new.token
+        || old.preset != new.preset
+        || old.recipe != new.recipe
+        || old.archived != new.archived
+}
+
+/// Appends the entries to the ones that wait for the key.
+pub fn record(root: &Path, vault: &str, entries: &[Entry]) -> io::Result<()> {
+    if entries.is_empty() {
+        return Ok(());
+    }
+    let mut lines = String::new();
+    for entry in entries {
+        lines.push_str(&serde_json::to_string(entry)?);
+        lines.push('\n');
+    }
+    append(&file(root, vault, PENDING_EXTENSION), &lines)
+}
+
+/// Returns the entries that wait for the key, as they were recorded.
+pub fn pending(root: &Path, vault: &str) -> io::Result<Vec<String>> {
+    read_lines(&file(root, vault, PENDING_EXTENSION))
+}
+
+/// Appends sealed entries to the log and forgets the entries that waited for the key.
+pub fn append_sealed(root: &Path, vault: &str, sealed: &[String]) -> io::Result<()> {
+    let lines: String = sealed.iter().map(|s| format!("{}\n", s)).collect();
+    append(&file(root, vault, LOG_EXTENSION), &lines)?;
+    let pending = file(root, vault, PENDING_EXTENSION);
+    if pending.exists() {
+        fs::remove_file(pending)?;
+    }
+    Ok(())
+}
+
+/// Returns the sealed entries of the log, oldest first.
+pub fn sealed(root: &Path, vault: &str) -> io::Result<Vec<String>> {
+    read_lines(&file(root, vault, LOG_EXTENSION))
+}
+
+/// Moves the log and the waiting entries of a vault to its new name.
+pub fn rename(root: &Path, vault: &str, new_name: &str) -> io::Result<()> {
+    for extension in &[LOG_EXTENSION, PENDING_EXTENSION] {
+        let (from, to) = (
+            file(root, vault, extension),
+            file(root, new_name, extension),
+        );
+        // a vault of the new name may have been deleted, its history is kept
+        if from.exists() {
+            append(&to, &fs::read_to_string(&from)?)?;
+            fs::remove_file(from)?;
+        }
+    }
+    Ok(())
+}
+
+/// Appends text to a file, which is created with the log directory if it doesn't exist.
+fn append(path: &Path, text: &str) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?
+        .write_all(text.as_bytes())
+}
+
+/// Returns the lines of a file that isn't empty, a missing file has none.
+fn read_lines(path: &Path) -> io::Result<Vec<String>> {
+    match fs::read_to_string(path) {
+        Ok(text) => Ok(text
+            .lines()
+            .filter(|l| !l.is_empty())
+            .map(String::from)
+            .collect()),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(err) => Err(err),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn entries_describe_the_changes() {
+        let actions = |before: Option<&Preferences>, after: &Preferences| -> Vec<String> {
+            entries(before, after)
+                .into_iter()
+                .map(|e| format!("{} {} {}", e.action, e.domain, e.username))
+                .collect()
+        };
+        let mut before = Preferences::new();
+        before.add(Preference::new("a.com", "a", 20)).unwrap();
+        before.add(Preference::new("b.com", "b", 20)).unwrap();
+        before.add(Preference::new("c.com", "c", 20)).unwrap();
+        assert_eq!(actions(None, &Preferences::new()), vec!["create vault  "]);
+
+        let mut after = Preferences::new();
+        let mut rotated = Preference::new("a.com", "a", 20);
+        rotated.set_version(2);
+        after.add(rotated).unwrap();
+        after.add(Preference::new("b.com", "b", 24)).unwrap();
+        let mut used = Preference::new("c.com", "c", 20);
+        used.last_used = Some(1);
+        after.add(used).unwrap();
+        after.add(Preference::new("d.com", "d", 20)).unwrap();
+        assert_eq!(
+            actions(Some(&before), &after),
+            vec![
+                "rotate to version 2 a.com a",
+                "update b.com b",
+                "add d.com d"
+            ]
+        );
+        assert_eq!(
+            actions(Some(&after), &before).last().map(String::as_str),
+            Some("delete d.com d")
+        );
+    }
+}
@@ -0,0 +1,89 @@
+//! # Hooks
+//! Runs user scripts after events, e.g. to trigger a sync, a desktop notification or an audit
+//! log forwarder. The script of an event is the executable named after the event in the hooks
+//! directory next to the vault, e.g. `.zpass/hooks/vault-saved`.
+//!
+//! Scripts get the event in `ZPASS_EVENT` and its details in `ZPASS_VAULT`, `ZPASS_DOMAIN` and
+//! `ZPASS_USERNAME`, never any secret. A failing script is reported but doesn't fail the
+//! operation that triggered it. `vault-saved` also gets `ZPASS_SYNC`, `local` for a vault that
+//! stays on this machine and `remote` for one that roams, so a sync script can skip it.
+
+use super::{constants, plan};
+use std::path::Path;
+use std::process::Command;
+
+/// Something that happened to a vault.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event {
+    // a password was generated or decrypted for use
+    PasswordGenerated,
+    // a preference was added to a vault
+    PreferenceAdded,
+    // a changed vault was written to disk
+    VaultSaved,
+}
+
+impl Event {
+    /// Returns the name of the event, which is also the name of its script.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Event::PasswordGenerated => "password-generated",
+            Event::PreferenceAdded => "preference-added",
+            Event::VaultSaved => "vault-saved",
+        }
+    }
+}
+
+/// Environment variable that tells the scripts whether a vault is synced.
+pub const SYNC_VARIABLE: &str = "ZPASS_SYNC";
+
+/// Runs the script of the event in the hooks directory of root, the directory of the vault, if
+/// there is one, and waits for it. Domain and username are empty for events that are about a
+/// whole vault.
+pub fn run(root: &Path, event: Event, vault: &str, domain: &str, username: &str) {
+    run_with(root, event, vault, domain, username, &[]);
+}
+
+/// Runs the script of the event with more variables.
+pub fn run_with(
+    root: &Path,
+    event: Event,
+    vault: &str,
+    domain: &str,
+    username: &str,
+    variables: &[(&str, &str)],
+) {
+    let script = root.join(constants::HOOKS_DIR).join(event.name());
+    if !is_executable(&script) {
+        return;
+    }
+    if constants::is_dry_run() {
+        plan::add(format!("Would run {}", script.display()));
+        return;
+    }
+    let status = Command::new(&script)
+        .env("ZPASS_EVENT", event.name())
+        .env("ZPASS_VAULT", vault)
+        .env("ZPASS_DOMAIN", domain)
+        .env("ZPASS_USERNAME", username)
+        .envs(variables.iter().copied())
+        .status();
+    match status {
+        Ok(status) if status.success() => {}
+        Ok(status) => eprintln!("The {} hook failed with {}", event.name(), status),
+        Err(err) => eprintln!("Failed to run the {} hook:\n{}", event.name(), err),
+    }
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    path.metadata()
+        .map(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+        .unwrap_or(false)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
@@ -0,0 +1,305 @@
+//! # IDN
+//! Internationalized domains, such as `bücher.example`, can be typed as Unicode or as punycode
+//! (`xn--bcher-kva.example`). The domain is part of what a password is derived from, so zpass
+//! turns both into punycode (RFC 3492) before it looks for or adds a password. Unicode labels are
+//! lowercased first, ASCII labels are kept as they are typed.
+
+/// Prefix of the labels that are punycode.
+const ACE_PREFIX: &str = "xn--";
+const BASE: u32 = 36;
+const T_MIN: u32 = 1;
+const T_MAX: u32 = 26;
+const SKEW: u32 = 38;
+const DAMP: u32 = 700;
+const INITIAL_BIAS: u32 = 72;
+const INITIAL_N: u32 = 128;
+/// Public suffixes of more than one label, under which anyone can register a domain, from the
+/// public suffix list (publicsuffix.org). Only the common ones are built in, sorted.
+const MULTI_LABEL_SUFFIXES: &[&str] = &[
+    "ac.il",
+    "ac.in",
+    "ac.jp",
+    "ac.nz",
+    "ac.uk",
+    "appspot.com",
+    "azurewebsites.net",
+    "blogspot.com",
+    "co.at",
+    "co.id",
+    "co.il",
+    "co.in",
+    "co.jp",
+    "co.kr",
+    "co.nz",
+    "co.th",
+    "co.uk",
+    "co.za",
+    "com.ar",
+    "com.au",
+    "com.br",
+    "com.cn",
+    "com.co",
+    "com.eg",
+    "com.es",
+    "com.gr",
+    "com.hk",
+    "com.mx",
+    "com.my",
+    "com.ph",
+    "com.pk",
+    "com.pl",
+    "com.sa",
+    "com.sg",
+    "com.tr",
+    "com.tw",
+    "com.ua",
+    "com.vn",
+    "edu.au",
+    "edu.cn",
+    "github.io",
+    "gitlab.io",
+    "gov.au",
+    "gov.br",
+    "gov.cn",
+    "gov.in",
+    "gov.uk",
+    "gov.za",
+    "herokuapp.com",
+    "ltd.uk",
+    "me.uk",
+    "ne.jp",
+    "net.au",
+    "net.br",
+    "net.cn",
+    "net.in",
+    "net.nz",
+    "netlify.app",
+    "or.at",
+    "or.jp",
+    "or.kr",
+    "org.au",
+    "org.br",
+    "org.cn",
+    "org.il",
+    "org.in",
+    "org.nz",
+    "org.uk",
+    "org.za",
+    "pages.dev",
+    "plc.uk",
+    "vercel.app",
+    "workers.dev",
+];
+
+/// Returns the domain with its Unicode labels in punycode.
+pub fn to_ascii(domain: &str) -> String {
+    domain
+        .split('.')
+        .map(|label| {
+            if label.to_ascii_lowercase().starts_with(ACE_PREFIX) {
+                return label.to_ascii_lowercase();
+            }
+            if label.is_ascii() {
+                return label.to_owned();
+            }
+            let lowered: Vec<char> = label.to_lowercase().chars().collect();
+            match encode(&lowered) {
+                Some(encoded) => format!("{}{}", ACE_PREFIX, encoded),
+                None => label.to_owned(),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+/// Returns the domain with its punycode labels in Unicode, labels that aren't valid punycode
+/// are kept.
+pub fn to_unicode(domain: &str) -> String {
+    domain
+        .split('.')
+        .map(|label| {
+            let lowered = label.to_ascii_lowercase();
+            lowered
+                .strip_prefix(ACE_PREFIX)
+                .and_then(decode)
+                .filter(|decoded| !decoded.is_empty())
+                .unwrap_or_else(|| label.to_owned())
+        })
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+/// Returns the domains the domain is a subdomain of, the nearest first, down to the registrable
+/// domain. Public suffixes are left out, `app.example.com` gives `example.com` and
+/// `a.example.co.uk` gives `example.co.uk`.
+pub fn parents(domain: &str) -> Vec<&str> {
+    domain
+        .match_indices('.')
+        .map(|(i, _)| &domain[i + 1..])
+        .take_while(|parent| !is_public_suffix(parent))
+        .collect()
+}
+
+/// Returns true if anyone can register a domain under the domain, such as `com` or `co.uk`.
+fn is_public_suffix(domain: &str) -> bool {
+    let domain = domain.to_ascii_lowercase();
+    !domain.contains('.') || MULTI_LABEL_SUFFIXES.binary_search(&&domain[..]).is_ok()
+}
+
+/// Returns true if the domain has a Unicode or a punycode label.
+pub fn is_international(domain: &str) -> bool {
+    domain
+        .split('.')
+        .any(|label| !label.is_ascii() || label.to_ascii_lowercase().starts_with(ACE_PREFIX))
+}
+
+fn adapt(delta: u32, points: u32, first: bool) -> u32 {
+    let mut delta = if first { delta / DAMP } else { delta / 2 };
+    delta += delta / points;
+    let mut k = 0;
+    while delta > ((BASE - T_MIN) * T_MAX) / 2 {
+        delta /= BASE - T_MIN;
+        k += BASE;
+    }
+    k + (BASE - T_MIN + 1) * delta / (delta + SKEW)
+}
+
+fn threshold(k: u32, bias: u32) -> u32 {
+    if k <= bias {
+        T_MIN
+    } else if k >= bias + T_MAX {
+        T_MAX
+    } else {
+        k - bias
+    }
+}
+
+fn digit(d: u32) -> char {
+    match d {
+        0..=25 => (b'a' + d as u8) as char,
+        _ => (b'0' + (d - 26) as u8) as char,
+    }
+}
+
+fn value(c: char) -> Option<u32> {
+    match c {
+        'a'..='z' => Some(c as u32 - 'a' as u32),
+        'A'..='Z' => Some(c as u32 - 'A' as u32),
+        '0'..='9' => Some(c as u32 - '0' as u32 + 26),
+        _ => None,
+    }
+}
+
+/// Encodes a label as punycode, without the prefix. None if it overflows.
+fn encode(input: &[char]) -> Option<String> {
+    let mut output: String = input.iter().filter(|c| c.is_ascii()).collect();
+    let basic = output.len() as u32;
+    let mut handled = basic;
+    if basic > 0 {
+        output.push('-');
+    }
+    let (mut n, mut delta, mut bias) = (INITIAL_N, 0u32, INITIAL_BIAS);
+    while (handled as usize) < input.len() {
+        let m = input.iter().map(|&c| c as u32).filter(|&c| c >= n).min()?;
+        delta = delta.checked_add((m - n).checked_mul(handled + 1)?)?;
+        n = m;
+        for &c in input {
+            let c = c as u32;
+            if c < n {
+                delta = delta.checked_add(1)?;
+            }
+            if c == n {
+                let mut q = delta;
+                let mut k = BASE;
+                loop {
+                    let t = threshold(k, bias);
+                    if q < t {
+                        break;
+                    }
+                    output.push(digit(t + (q - t) % (BASE - t)));
+                    q = (q - t) / (BASE - t);
+                    k += BASE;
+                }
+                output.push(digit(q));
+                bias = adapt(delta, handled + 1, handled == basic);
+                delta = 0;
+                handled += 1;
+            }
+        }
+        delta = delta.checked_add(1)?;
+        n += 1;
+    }
+    Some(output)
+}
+
+/// Decodes a punycode label, without the prefix. None if it isn't valid.
+fn decode(input: &str) -> Option<String> {
+    let (basic, rest) = match input.rfind('-') {
+        Some(i) => (&input[..i], &input[i + 1..]),
+        None => ("", input),
+    };
+    if !basic.is_ascii() {
+        return None;
+    }
+    let mut output: Vec<char> = basic.chars().collect();
+    let (mut n, mut i, mut bias) = (INITIAL_N, 0u32, INITIAL_BIAS);
+    let mut digits = rest.chars().peekable();
+    while digits.peek().is_some() {
+        let old = i;
+        let mut w = 1u32;
+        let mut k = BASE;
+        loop {
+            let d = value(digits.next()?)?;
+            i = i.checked_add(d.checked_mul(w)?)?;
+            let t = threshold(k, bias);
+            if d < t {
+                break;
+            }
+            w = w.checked_mul(BASE - t)?;
+            k += BASE;
+        }
+        let length = output.len() as u32 + 1;
+        bias = adapt(i - old, length, old == 0);
+        n = n.checked_add(i / length)?;
+        i %= length;
+        output.insert(i as usize, char::from_u32(n)?);
+        i += 1;
+    }
+    Some(output.into_iter().collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn converts_between_unicode_and_punycode() {
+        assert_eq!(to_ascii("bücher.example"), "xn--bcher-kva.example");
+        assert_eq!(to_ascii("MÜNCHEN.de"), "xn--mnchen-3ya.de");
+        assert_eq!(to_ascii("例え.jp"), "xn--r8jz45g.jp");
+        assert_eq!(to_ascii("HomeNetwork"), "HomeNetwork");
+        assert_eq!(to_ascii("XN--BCHER-KVA.example"), "xn--bcher-kva.example");
+        assert_eq!(to_unicode("xn--bcher-kva.example"), "bücher.example");
+        assert_eq!(to_unicode("XN--R8JZ45G.jp"), "例え.jp");
+        assert_eq!(to_unicode("xn--.example"), "xn--.example");
+        assert!(is_international("bücher.example"));
+        assert!(!is_international("example.com"));
+    }
+
+    #[test]
+    fn lists_parent_domains() {
+        assert_eq!(
+            parents("a.app.example.com"),
+            ["app.example.com", "example.com"]
+        );
+        assert!(parents("example.com").is_empty());
+    }
+
+    #[test]
+    fn stops_at_the_registrable_domain() {
+        assert_eq!(parents("a.example.co.uk"), ["example.co.uk"]);
+        assert!(parents("example.co.uk").is_empty());
+        assert!(parents("alice.GitHub.io").is_empty());
+        assert!(MULTI_LABEL_SUFFIXES.windows(2).all(|w| w[0] < w[1]));
+    }
+}
@@ -0,0 +1,125 @@
+//! # Journal
+//! Keeps the vault files as they were before the last command that changed them, so the command
+//! can be undone. The journal belongs to the process that began it: the vaults that other
+//! commands write, e.g. to record when a password was used, don't overwrite it.
+
+use super::constants;
+use super::shred;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process;
+
+/// Holds the id of the process that writes the journal.
+const OWNER_FILE: &str = "owner";
+/// Marks a vault file that didn't exist before the command.
+const ABSENT_SUFFIX: &str = ".absent";
+
+/// Returns the directory of the journal.
+fn dir(root: &Path) -> PathBuf {
+    root.join(constants::JOURNAL_DIR)
+}
+
+/// Forgets the previous journal and records the files this process changes from now on.
+/// The kept files are overwritten first, they may hold deleted secrets.
+pub fn begin(root: &Path) -> io::Result<()> {
+    let dir = dir(root);
+    if dir.exists() {
+        for entry in fs::read_dir(&dir)? {
+            let path = entry?.path();
+            if path.is_file() {
+                shred::remove(&path)?;
+            }
+        }
+        fs::remove_dir_all(&dir)?;
+    }
+    fs::create_dir_all(&dir)?;
+    fs::write(dir.join(OWNER_FILE), process::id().to_string())
+}
+
+/// Keeps the file as it is before it is changed for the first time in this process,
+/// unless another process began the journal.
+pub fn record(root: &Path, file: &Path) -> io::Result<()> {
+    let dir = dir(root);
+    let owner = fs::read_to_string(dir.join(OWNER_FILE)).unwrap_or_default();
+    let name = match file.file_name() {
+        Some(name) if owner == process::id().to_string() => name.to_string_lossy(),
+        _ => return Ok(()),
+    };
+    let (kept, absent) = (
+        dir.join(&*name),
+        dir.join(format!("{}{}", name, ABSENT_SUFFIX)),
+    );
+    if kept.exists() || absent.exists() {
+        return Ok(());
+    }
+    match fs::read(file) {
+        Ok(contents) => fs::write(kept, contents),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => fs::write(absent, ""),
+        Err(err) => Err(err),
+    }
+}
+
+/// Puts the recorded files back as they were and clears the journal.
+/// Returns the files that were restored or deleted.
+pub fn undo(root: &Path) -> io::Result<Vec<PathBuf>> {
+    let dir = dir(root);
+    let mut restored = Vec::new();
+    if !dir.exists() {
+        return Ok(restored);
+    }
+    for entry in fs::read_dir(&dir)? {
+        let path = entry?.path();
+        let name = path.file_name().map(|n| n.to_string_lossy().into_owned());
+        match name.as_deref() {
+            None | Some(OWNER_FILE) => continue,
+            Some(name) => match name.strip_suffix(ABSENT_SUFFIX) {
+                Some(name) => {
+                    let file = root.join(name);
+                    if file.exists() {
+                        fs::remove_file(&file)?;
+                    }
+                    restored.push(file);
+                }
+                None => {
+                    let file = root.join(name);
+                    fs::copy(&path, &file)?;
+                    restored.push(file);
+                }
+            },
+        }
+    }
+    fs::remove_dir_all(&dir)?;
+    restored.sort();
+    Ok(restored)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn undo_restores_and_removes() {
+        let root = std::env::temp_dir().join(format!("zpass-journal-{}", process::id()));
+        fs::create_dir_all(&root).unwrap();
+        let (changed, added) = (root.join("a.json"), root.join("b.json"));
+        fs::write(&changed, "before").unwrap();
+
+        // nothing is recorded before the journal begins
+        record(&root, &changed).unwrap();
+        assert!(!dir(&root).exists());
+
+        begin(&root).unwrap();
+        record(&root, &changed).unwrap();
+        fs::write(&changed, "after").unwrap();
+        record(&root, &changed).unwrap();
+        record(&root, &added).unwrap();
+        fs::write(&added, "new").unwrap();
+
+        assert_eq!(undo(&root).unwrap(), vec![changed.clone(), added.clone()]);
+        assert_eq!(fs::read_to_string(&changed).unwrap(), "before");
+        assert!(!added.exists());
+        assert!(undo(&root).unwrap().is_empty());
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,234 @@
+//! # Memory
+//! Buffers for the decrypted vault secret and what is derived from it. Their pages are locked in
+//! memory with `mlock`, or `VirtualLock` on Windows, so they are never written to swap, and they
+//! are overwritten with zeros when they are dropped. Locking is best effort: when the limit of
+//! locked memory is reached, the buffer is only wiped.
+//!
+//! Small buffers share pages, and unlocking a page unlocks it for every buffer on it. So the
+//! locked buffers are counted in chunks of CHUNK bytes, and a chunk is only unlocked when the
+//! last buffer on it is dropped.
+
+use std::fmt;
+use std::ops::{Deref, DerefMut, Range};
+use std::ptr;
+use std::sync::atomic::{self, Ordering};
+use std::sync::{Mutex, PoisonError};
+use tracing::debug;
+
+/// CHUNK is the size the locks are counted in, a multiple of the page sizes of the platforms,
+/// 4 KiB, 16 KiB on Apple silicon and up to 64 KiB on some ARM and POWER systems.
+const CHUNK: usize = 1 << 16;
+
+/// The number of locked buffers on each chunk that has any, by the index of the chunk.
+static HOLDERS: Mutex<Vec<(usize, usize)>> = Mutex::new(Vec::new());
+
+/// # Locked
+/// Bytes that stay in memory and are wiped when dropped. The buffer never grows, so it is
+/// never moved.
+pub struct Locked {
+    // bytes are the locked buffer
+    bytes: Vec<u8>,
+    // locked is true if the pages of the buffer could be locked
+    locked: bool,
+}
+
+impl Locked {
+    /// Locks the bytes where they are.
+    pub fn new(bytes: Vec<u8>) -> Locked {
+        let locked = lock(&bytes);
+        Locked { bytes, locked }
+    }
+
+    /// Returns the parts one after the other, copied into a buffer that is locked before.
+    pub fn concat(parts: &[&[u8]]) -> Locked {
+        let length = parts.iter().map(|p| p.len()).sum();
+        let mut locked = Locked::new(Vec::with_capacity(length));
+        for part in parts {
+            locked.bytes.extend_from_slice(part);
+        }
+        locked
+    }
+}
+
+impl Deref for Locked {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.bytes
+    }
+}
+
+impl DerefMut for Locked {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        &mut self.bytes
+    }
+}
+
+/// Shows the length only, the bytes are secret.
+impl fmt::Debug for Locked {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Locked({} bytes)", self.bytes.len())
+    }
+}
+
+impl PartialEq for Locked {
+    fn eq(&self, other: &Self) -> bool {
+        self.bytes == other.bytes
+    }
+}
+
+impl Eq for Locked {}
+
+impl Drop for Locked {
+    fn drop(&mut self) {
+        wipe(&mut self.bytes);
+        if self.locked {
+            unlock(&self.bytes);
+        }
+    }
+}
+
+/// Overwrites the bytes with zeros, in a way the compiler keeps.
+pub fn wipe(bytes: &mut [u8]) {
+    for byte in bytes.iter_mut() {
+        unsafe { ptr::write_volatile(byte, 0) };
+    }
+    atomic::compiler_fence(Ordering::SeqCst);
+}
+
+/// Locks the pages of the whole buffer, up to its capacity. Returns false if they aren't.
+fn lock(bytes: &Vec<u8>) -> bool {
+    if bytes.capacity() == 0 {
+        return false;
+    }
+    // an unlock in between the lock and the count would undo the lock
+    let mut holders = HOLDERS.lock().unwrap_or_else(PoisonError::into_inner);
+    let locked = sys::lock(bytes.as_ptr(), bytes.capacity());
+    if locked {
+        hold(
+            &mut holders,
+            chunks(bytes.as_ptr() as usize, bytes.capacity()),
+        );
+    } else {
+        debug!("Failed to lock {} bytes in memory", bytes.capacity());
+    }
+    locked
+}
+
+/// Unlocks the pages of the buffer that no other locked buffer is on.
+fn unlock(bytes: &Vec<u8>) {
+    let (start, end) = (
+        bytes.as_ptr() as usize,
+        bytes.as_ptr() as usize + bytes.capacity(),
+    );
+    let mut holders = HOLDERS.lock().unwrap_or_else(PoisonError::into_inner);
+    for chunk in release(&mut holders, chunks(start, bytes.capacity())) {
+        let from = start.max(chunk * CHUNK);
+        let to = end.min((chunk + 1) * CHUNK);
+        sys::unlock(from as *const u8, to - from);
+    }
+}
+
+/// Returns the indices of the chunks of the bytes at the address, the length isn't 0.
+fn chunks(address: usize, length: usize) -> Range<usize> {
+    address / CHUNK..(address + length - 1) / CHUNK + 1
+}
+
+/// Counts one more locked buffer on each of the chunks.
+fn hold(holders: &mut Vec<(usize, usize)>, chunks: Range<usize>) {
+    for chunk in chunks {
+        match holders.iter_mut().find(|(index, _)| *index == chunk) {
+            Some((_, count)) => *count += 1,
+            None => holders.push((chunk, 1)),
+        }
+    }
+}
+
+/// Counts one less locked buffer on each of the chunks. Returns the chunks without any left.
+fn release(holders: &mut Vec<(usize, usize)>, chunks: Range<usize>) -> Vec<usize> {
+    let mut free = Vec::new();
+    for chunk in chunks {
+        if let Some((_, count)) = holders.iter_mut().find(|(index, _)| *index == chunk) {
+            *count -= 1;
+            if *count == 0 {
+                free.push(chunk);
+            }
+        }
+    }
+    holders.retain(|(_, count)| *count > 0);
+    free
+}
+
+#[cfg(unix)]
+mod sys {
+    use std::os::raw::{c_int, c_void};
+
+    extern "C" {
+        fn mlock(addr: *const c_void, len: usize) -> c_int;
+        fn munlock(addr: *const c_void, len: usize) -> c_int;
+    }
+
+    pub fn lock(addr: *const u8, len: usize) -> bool {
+        unsafe { mlock(addr as *const c_void, len) == 0 }
+    }
+
+    pub fn unlock(addr: *const u8, len: usize) {
+        unsafe { munlock(addr as *const c_void, len) };
+    }
+}
+
+#[cfg(windows)]
+mod sys {
+    use std::os::raw::{c_int, c_void};
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn VirtualLock(addr: *const c_void, len: usize) -> c_int;
+        fn VirtualUnlock(addr: *const c_void, len: usize) -> c_int;
+    }
+
+    pub fn lock(addr: *const u8, len: usize) -> bool {
+        unsafe { VirtualLock(addr as *const c_void, len) != 0 }
+    }
+
+    pub fn unlock(addr: *const u8, len: usize) {
+        unsafe { VirtualUnlock(addr as *const c_void, len) };
+    }
+}
+
+/// Without swap to keep the secrets from, e.g. in the browser, nothing is locked.
+#[cfg(not(any(unix, windows)))]
+mod sys {
+    pub fn lock(_addr: *const u8, _len: usize) -> bool {
+        false
+    }
+
+    pub fn unlock(_addr: *const u8, _len: usize) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn concat_keeps_the_parts_in_place() {
+        let locked = Locked::concat(&[b"secret", &[0], b"context"]);
+        assert_eq!(&*locked, b"secret\0context");
+
+        let mut bytes = vec![1, 2, 3];
+        wipe(&mut bytes);
+        assert_eq!(bytes, vec![0, 0, 0]);
+    }
+
+    #[test]
+    fn shared_chunks_stay_locked_until_the_last_buffer() {
+        let mut holders = Vec::new();
+        // the second buffer starts on the last chunk of the first one
+        hold(&mut holders, chunks(10, 2 * CHUNK));
+        hold(&mut holders, chunks(2 * CHUNK + 20, 10));
+        assert_eq!(chunks(10, 2 * CHUNK), 0..3);
+        assert_eq!(release(&mut holders, chunks(10, 2 * CHUNK)), vec![0, 1]);
+        assert_eq!(release(&mut holders, chunks(2 * CHUNK + 20, 10)), vec![2]);
+        assert!(holders.is_empty());
+    }
+}
@@ -4,7 +4,49 @@ pub mod constants;
 pub mod collection;
 // crypto is wrapper around crypto constructs
 pub mod crypto;
+// memory keeps the decrypted secret out of swap.
+pub mod memory;
+// cache keeps the decrypted secrets for the rest of the process.
+pub mod cache;
+// rules are the password constraints of websites
+pub mod rules;
+// time has helpers for the timestamps kept in the vaults
+pub mod time;
+// idn turns internationalized domains into punycode.
+pub mod idn;
+// glob matches domains against patterns such as `*.google.*`.
+pub mod glob;
+// token gives generated passwords the shape of API keys.
+pub mod token;
+// preset holds the settings of passwords typed on devices, such as Wi-Fi passphrases.
+pub mod preset;
+// recipe puts the fixed parts some systems require into passwords.
+pub mod recipe;
+// wordlist picks the words of passphrases.
+pub mod wordlist;
 // preferences are managed through a vault and they are not exposed directly to the client.
 pub mod preference;
+// plan keeps what a dry run would have changed.
+pub mod plan;
+// hooks run user scripts after events.
+pub mod hooks;
+// shred overwrites the files of deleted secrets before they are deleted.
+pub mod shred;
+// journal keeps the vault files as they were before the last command that changed them.
+pub mod journal;
+// throttle slows down guessing the key of a vault.
+pub mod throttle;
+// history logs the changes to each vault, sealed with the vault secret.
+pub mod history;
 // vault manages preferences and answers most queries.
 pub mod vault;
+// alias derives login identities from the vault secret.
+pub mod alias;
+// audit reports weak spots in the preferences.
+pub mod audit;
+// stats sums up the vaults for hygiene reviews.
+pub mod stats;
+// strength estimates how hard generated passwords are to guess.
+pub mod strength;
+// schema describes and validates the vault files.
+pub mod schema;
@@ -0,0 +1,46 @@
+//! # Plan
+//! Keeps what a dry run would have changed: the files it would write or delete and the hooks
+//! it would run. Vaults are written when they are dropped, deep below the callers, so the
+//! changes are collected here and the caller takes them to show them, e.g. the CLI prints them
+//! after each command.
+
+use std::sync::{Mutex, PoisonError};
+
+/// The changes planned since they were last taken.
+static PLANNED: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+/// Adds a change a dry run skipped, such as `Would delete <path>`.
+pub fn add(change: String) {
+    PLANNED
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .push(change);
+}
+
+/// Returns the planned changes in the order they were added, and forgets them.
+pub fn take() -> Vec<String> {
+    std::mem::take(&mut *PLANNED.lock().unwrap_or_else(PoisonError::into_inner))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn takes_changes_in_order() {
+        // other tests may plan changes too, only the order of these ones is certain
+        add("Would write the first test file".to_owned());
+        add("Would delete the second test file".to_owned());
+        let planned: Vec<String> = take()
+            .into_iter()
+            .filter(|change| change.ends_with("test file"))
+            .collect();
+        assert_eq!(
+            planned,
+            [
+                "Would write the first test file",
+                "Would delete the second test file"
+            ]
+        );
+    }
+}
@@ -1,7 +1,20 @@
 use super::collection::List;
+use super::constants;
+use super::crypto::{self, Sealed};
+use super::glob;
+use super::idn;
+use super::preset::Preset;
+use super::recipe::Recipe;
+use super::rules::Rules;
+use super::time;
+use super::token::Token;
+use super::wordlist::Wordlist;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use std::cmp::PartialEq;
+use std::cmp::{Ordering, PartialEq};
+use std::collections::HashMap;
 use std::ops::{Deref, DerefMut};
+use std::sync::{Mutex, PoisonError};
 
 use std::error;
 use std::fmt;
@@ -10,6 +23,7 @@ use std::fmt;
 pub enum PreferenceError {
     PreferenceExists,
     NoMatchingPreferenceFound,
+    NoSuchChange(usize),
 }
 
 impl fmt::Display for PreferenceError {
@@ -17,6 +31,7 @@ impl fmt::Display for PreferenceError {
         match self {
             Self::PreferenceExists => write!(f, "Precodition violation: preference already exists"),
             Self::NoMatchingPreferenceFound => write!(f, "Failed to find a matching preference"),
+            Self::NoSuchChange(n) => write!(f, "Failed to find change {} of the preference", n),
         }
     }
 }
@@ -25,7 +40,7 @@ impl error::Error for PreferenceError {}
 
 /// # Preference
 /// Associated with each domain + username are default parameters based on previous user interactions
-#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+#[derive(Serialize, Deserialize, JsonSchema, Debug, PartialEq, Eq)]
 pub struct Preference {
     // domain name such as "google.com"
     pub domain: String,
@@ -37,6 +52,81 @@ pub struct Preference {
     pub version: usize,
     // default indicates wheather this is the default preference for the domain
     default: bool,
+    // last_used is the unix timestamp of the last time a password was generated
+    #[serde(default)]
+    pub last_used: Option<u64>,
+    // changed is the unix timestamp of when the current version of the password was set
+    #[serde(default)]
+    pub changed: Option<u64>,
+    // scheme is the password scheme used to generate the password, missing means legacy
+    #[serde(default)]
+    pub scheme: usize,
+    // max_version is the highest version the password ever had
+    #[serde(default)]
+    max_version: usize,
+    // rules are the password constraints of the domain, if any
+    #[serde(default)]
+    pub rules: Option<Rules>,
+    // unambiguous excludes characters that are easily confused, such as 0 and O
+    #[serde(default)]
+    pub unambiguous: bool,
+    // stored is a password that is kept encrypted instead of being generated, e.g. an imported one
+    #[serde(default)]
+    pub stored: Option<Sealed>,
+    // tags group preferences, e.g. the folders of an imported password store
+    #[serde(default)]
+    pub tags: Vec<String>,
+    // notes are free text kept encrypted alongside the password
+    #[serde(default)]
+    pub notes: Option<Sealed>,
+    // totp is the encrypted otpauth:// URI of the second factor, if any
+    #[serde(default)]
+    pub totp: Option<Sealed>,
+    // rotate_every is the number of seconds after which the password is due for a new version
+    #[serde(default)]
+    pub rotate_every: Option<u64>,
+    // uses is the number of times the password was generated or decrypted
+    #[serde(default)]
+    pub uses: u64,
+    // token is the prefix and encoding of a password that is an API key, if it is one
+    #[serde(default)]
+    pub token: Option<Token>,
+    // preset the password was added with, it shapes the generated password
+    #[serde(default)]
+    pub preset: Option<Preset>,
+    // recipe holds the fixed parts of the password, applied on top of the derived characters
+    #[serde(default)]
+    pub recipe: Option<Recipe>,
+    // changes are the settings before the last changes, oldest first
+    #[serde(default)]
+    pub changes: Vec<Change>,
+    // archived leaves the account out of the listings and the suggestions
+    #[serde(default)]
+    pub archived: bool,
+    // wordlist the passphrase is picked from, length is the number of words then
+    #[serde(default)]
+    pub wordlist: Option<Wordlist>,
+    // match_subdomains gives the subdomains of the domain this password, e.g. app.example.com
+    #[serde(default)]
+    pub match_subdomains: bool,
+}
+
+/// # Change
+/// The settings a password was generated with before they were changed.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, PartialEq, Eq, Clone)]
+pub struct Change {
+    // time is the unix timestamp of the change
+    pub time: u64,
+    // username of the account before the change
+    pub username: String,
+    // length of the password before the change
+    pub length: usize,
+    // version of the password before the change
+    pub version: usize,
+    // rules of the domain before the change
+    pub rules: Option<Rules>,
+    // unambiguous before the change
+    pub unambiguous: bool,
 }
 
 impl Preference {
@@ -48,6 +138,169 @@ impl Preference {
             length,
             version: 0,
             default: false,
+            last_used: None,
+            changed: Some(time::now()),
+            scheme: crypto::CURRENT_SCHEME,
+            max_version: 0,
+            rules: None,
+            unambiguous: false,
+            stored: None,
+            tags: Vec::new(),
+            notes: None,
+            totp: None,
+            rotate_every: None,
+            uses: 0,
+            token: None,
+            preset: None,
+            recipe: None,
+            changes: Vec::new(),
+            archived: false,
+            wordlist: None,
+            match_subdomains: false,
+        }
+    }
+
+    /// Returns true if this is the preference used when only the domain is given.
+    pub fn is_default(&self) -> bool {
+        self.default
+    }
+
+    /// Returns true if the current version of the password is older than MAX_PASSWORD_AGE.
+    pub fn is_expired(&self, now: u64) -> bool {
+        let age = self.changed.map(|changed| now.saturating_sub(changed));
+        age.unwrap_or(0) > constants::MAX_PASSWORD_AGE
+    }
+
+    /// Returns true if the current version of the password is older than its rotation period.
+    pub fn is_due(&self, now: u64) -> bool {
+        match (self.rotate_every, self.changed) {
+            (Some(every), Some(changed)) => now.saturating_sub(changed) >= every,
+            _ => false,
+        }
+    }
+
+    /// Returns true if the account wasn't used for UNUSED_AGE, or since it was added if it was
+    /// never used.
+    pub fn is_unused(&self, now: u64) -> bool {
+        let since = self.last_used.or(self.changed);
+        matches!(since, Some(since) if now.saturating_sub(since) > constants::UNUSED_AGE)
+    }
+
+    /// Returns true if the password is stored rather than generated.
+    pub fn is_stored(&self) -> bool {
+        self.stored.is_some()
+    }
+
+    /// Returns a new preference of the account with the same settings, for another vault. What
+    /// is sealed with the secret of this vault, the stored password, notes and totp, is left out.
+    pub fn copy_settings(&self) -> Preference {
+        Preference {
+            rules: self.rules.clone(),
+            unambiguous: self.unambiguous,
+            tags: self.tags.clone(),
+            rotate_every: self.rotate_every,
+            token: self.token.clone(),
+            preset: self.preset,
+            recipe: self.recipe.clone(),
+            match_subdomains: self.match_subdomains,
+            ..Preference::new(&self.domain, &self.username, self.length)
+        }
+    }
+
+    /// Returns the rules the password is generated with, a token only takes the characters of
+    /// its encoding.
+    pub fn generation_rules(&self) -> Option<Rules> {
+        match &self.token {
+            Some(token) => Some(token.rules()),
+            None => self.rules.clone(),
+        }
+    }
+
+    /// Returns a generated password after its recipe, in the shape of the token or the preset,
+    /// if any.
+    pub fn shape(&self, password: String) -> String {
+        let password = match &self.recipe {
+            Some(recipe) => recipe.apply(&password),
+            None => password,
+        };
+        match (&self.token, self.preset) {
+            (Some(token), _) => token.wrap(&password),
+            (None, Some(preset)) => preset.shape(&password),
+            (None, None) => password,
+        }
+    }
+
+    /// Applies password rules to the preference, adjusting the length to what the rules allow.
+    pub fn with_rules(self, rules: Rules) -> Preference {
+        Preference {
+            length: rules.clamp_length(self.length),
+            rules: Some(rules),
+            ..self
+        }
+    }
+
+    /// Sets the version of the password and remembers the highest version ever used.
+    pub fn set_version(&mut self, version: usize) {
+        self.max_version = self.highest_version().max(version);
+        self.version = version;
+        self.changed = Some(time::now());
+    }
+
+    /// Returns the highest version the password ever had.
+    pub fn highest_version(&self) -> usize {
+        self.max_version.max(self.version)
+    }
+
+    /// Keeps the settings the password is generated with before they change, only the last
+    /// MAX_CHANGES of them are kept.
+    pub fn record_change(&mut self, time: u64) {
+        self.changes.push(Change {
+            time,
+            username: self.username.clone(),
+            length: self.length,
+            version: self.version,
+            rules: self.rules.clone(),
+            unambiguous: self.unambiguous,
+        });
+        let extra = self.changes.len().saturating_sub(constants::MAX_CHANGES);
+        self.changes.drain(..extra);
+    }
+
+    /// Returns the nth last change, 1 is the most recent one.
+    pub fn change(&self, n: usize) -> Option<&Change> {
+        self.changes.iter().rev().nth(n.checked_sub(1)?)
+    }
+}
+
+/// # Sort
+/// An order the preferences are listed in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Sort {
+    Domain,
+    Username,
+    // the most recently used first, the ones never used last
+    LastUsed,
+    Length,
+}
+
+impl Sort {
+    /// Returns the order of a name, such as `last-used`.
+    pub fn parse(name: &str) -> Option<Sort> {
+        match name {
+            "domain" => Some(Sort::Domain),
+            "username" => Some(Sort::Username),
+            "last-used" => Some(Sort::LastUsed),
+            "length" => Some(Sort::Length),
+            _ => None,
+        }
+    }
+
+    fn compare(self, a: &Preference, b: &Preference) -> Ordering {
+        match self {
+            Self::Domain => a.domain.cmp(&b.domain).then(a.username.cmp(&b.username)),
+            Self::Username => a.username.cmp(&b.username).then(a.domain.cmp(&b.domain)),
+            Self::LastUsed => b.last_used.cmp(&a.last_used),
+            Self::Length => a.length.cmp(&b.length),
         }
     }
 }
@@ -55,28 +308,140 @@ impl Preference {
 /// # Preferences
 /// A collection of preference items.
 /// Enforces a constraint that only one preference for each domain can be the default preference.
-#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+/// Lookups by domain go through an index of the positions of each domain, it is built on the
+/// first lookup and dropped whenever the items are changed from outside the collection.
+#[derive(Serialize, Deserialize, JsonSchema, Debug)]
 pub struct Preferences {
     #[serde(flatten)]
     items: List<Preference>,
+    // index holds the positions of the preferences of each domain
+    #[serde(skip)]
+    index: Mutex<Option<HashMap<String, Vec<usize>>>>,
 }
 
 impl Preferences {
     /// Creates an empty Preferences collection.
     pub fn new() -> Preferences {
-        Preferences { items: List::new() }
+        Preferences {
+            items: List::new(),
+            index: Mutex::new(None),
+        }
+    }
+
+    /// Calls f with the positions of the preferences of each domain, building them if needed.
+    fn with_index<R>(&self, f: impl FnOnce(&HashMap<String, Vec<usize>>) -> R) -> R {
+        let mut index = self.index.lock().unwrap_or_else(PoisonError::into_inner);
+        let index = index.get_or_insert_with(|| {
+            let mut index: HashMap<String, Vec<usize>> = HashMap::new();
+            for (position, p) in self.items.iter().enumerate() {
+                index.entry(p.domain.clone()).or_default().push(position);
+            }
+            index
+        });
+        f(index)
+    }
+
+    /// Drops the index, the next lookup builds it again.
+    fn forget_index(&mut self) {
+        *self.index.get_mut().unwrap_or_else(PoisonError::into_inner) = None;
+    }
+
+    /// Returns the positions of the preferences of the domain.
+    fn positions(&self, domain: &str) -> Vec<usize> {
+        self.with_index(|index| index.get(domain).cloned().unwrap_or_default())
+    }
+
+    /// Returns the preferences of the domain, in the order they were added.
+    pub fn of_domain(&self, domain: &str) -> Vec<&Preference> {
+        self.positions(domain)
+            .into_iter()
+            .filter_map(|position| self.items.at(position))
+            .collect()
+    }
+
+    /// Returns the preference of the nearest parent domain that gives its password to its
+    /// subdomains: the account of the username, or else the default account of that domain.
+    pub fn inherited(&self, domain: &str, username: Option<&str>) -> Option<&Preference> {
+        idn::parents(domain).into_iter().find_map(|parent| {
+            let shared: Vec<&Preference> = self
+                .of_domain(parent)
+                .into_iter()
+                .filter(|p| p.match_subdomains && !p.archived)
+                .filter(|p| username.map_or(true, |u| p.username == u))
+                .collect();
+            shared
+                .iter()
+                .find(|p| p.is_default())
+                .or_else(|| shared.first())
+                .copied()
+        })
+    }
+
+    /// Returns true if a preference has the domain.
+    pub fn has_domain(&self, domain: &str) -> bool {
+        !self.positions(domain).is_empty()
+    }
+
+    /// Returns the domains that match the glob pattern, sorted.
+    pub fn domains_matching(&self, pattern: &str) -> Vec<&str> {
+        let positions: Vec<usize> = self.with_index(|index| {
+            index
+                .iter()
+                .filter(|(domain, _)| glob::matches_any(pattern, domain))
+                .map(|(_, positions)| positions[0])
+                .collect()
+        });
+        let mut domains: Vec<&str> = positions
+            .into_iter()
+            .filter_map(|position| self.items.at(position))
+            .map(|p| &p.domain[..])
+            .collect();
+        domains.sort_unstable();
+        domains
+    }
+
+    /// Returns the preference of the account.
+    pub fn account(&self, domain: &str, username: &str) -> Option<&Preference> {
+        self.of_domain(domain)
+            .into_iter()
+            .find(|p| p.username == username)
+    }
+
+    /// Returns a mutable reference to the preference of the account.
+    /// Its domain is used by the index, it must not be changed.
+    pub fn account_mut(&mut self, domain: &str, username: &str) -> Option<&mut Preference> {
+        let position = self
+            .positions(domain)
+            .into_iter()
+            .find(|&position| self.items.at(position).map(|p| &p.username[..]) == Some(username))?;
+        self.items.at_mut(position)
+    }
+
+    /// Returns the default preference of the domain.
+    pub fn default_of(&self, domain: &str) -> Option<&Preference> {
+        self.of_domain(domain).into_iter().find(|p| p.default)
     }
 
     /// Adds a preference item to the collection.
     pub fn add(&mut self, preference: Preference) -> Result<(), PreferenceError> {
-        if self.has(|p| p.domain == preference.domain && p.username == preference.username) {
+        if self
+            .account(&preference.domain, &preference.username)
+            .is_some()
+        {
             return Err(PreferenceError::PreferenceExists);
         }
-        let default = !self.has_default(|p| p.domain == preference.domain);
+        let default = self.default_of(&preference.domain).is_none();
         let preference = Preference {
             default,
             ..preference
         };
+        let position = self.items.len();
+        if let Some(index) = self.index.get_mut().unwrap_or_else(PoisonError::into_inner) {
+            index
+                .entry(preference.domain.clone())
+                .or_default()
+                .push(position);
+        }
         self.items.add(preference);
         Ok(())
     }
@@ -100,7 +465,7 @@ impl Preferences {
     /// Sets a new default preference for a domain. This method ensures that the there is only one
     /// default preference for each domain.
     pub fn set_default(&mut self, domain: &str, username: &str) -> Result<(), PreferenceError> {
-        if !self.has(|p| p.domain == domain && p.username == username) {
+        if self.account(domain, username).is_none() {
             return Err(PreferenceError::NoMatchingPreferenceFound);
         }
 
@@ -112,6 +477,93 @@ impl Preferences {
 
         Ok(())
     }
+
+    /// Removes a preference. If it was the default of its domain, another account of the
+    /// domain becomes the default.
+    pub fn remove(&mut self, domain: &str, username: &str) -> Result<Preference, PreferenceError> {
+        let removed = self
+            .items
+            .remove(|p| p.domain == domain && p.username == username)
+            .ok_or(PreferenceError::NoMatchingPreferenceFound)?;
+        // the positions after the removed one moved
+        self.forget_index();
+        if removed.default {
+            if let Some(p) = self.items.get_mut(|p| p.domain == domain) {
+                p.default = true;
+            }
+        }
+        Ok(removed)
+    }
+
+    /// Goes back to the settings before the nth last change of an account, 1 is the most recent
+    /// one. The rollback is a change too, so it can be rolled back in turn.
+    pub fn rollback(
+        &mut self,
+        domain: &str,
+        username: &str,
+        n: usize,
+        time: u64,
+    ) -> Result<(), PreferenceError> {
+        let p = self
+            .account(domain, username)
+            .ok_or(PreferenceError::NoMatchingPreferenceFound)?;
+        let change = p.change(n).ok_or(PreferenceError::NoSuchChange(n))?.clone();
+        if change.username != username && self.account(domain, &change.username).is_some() {
+            return Err(PreferenceError::PreferenceExists);
+        }
+        let p = self
+            .account_mut(domain, username)
+            .ok_or(PreferenceError::NoMatchingPreferenceFound)?;
+        p.record_change(time);
+        p.username = change.username;
+        p.length = change.length;
+        p.rules = change.rules;
+        p.unambiguous = change.unambiguous;
+        p.set_version(change.version);
+        Ok(())
+    }
+
+    /// Returns all the preferences ordered by the last time they were used, most recent first.
+    /// Preferences that were never used come last.
+    pub fn by_recency(&self) -> Vec<&Preference> {
+        self.sorted(Sort::LastUsed, false)
+    }
+
+    /// Returns the preferences in the order, or the reverse order. Preferences that compare
+    /// equal keep the order they were added in.
+    pub fn sorted(&self, sort: Sort, reverse: bool) -> Vec<&Preference> {
+        let mut preferences: List<&Preference> = self.items.iter().collect();
+        preferences.sort_by(|a, b| {
+            if reverse {
+                sort.compare(b, a)
+            } else {
+                sort.compare(a, b)
+            }
+        });
+        preferences.into_iter().collect()
+    }
+
+    /// Describes the preferences that were added, changed or deleted since the earlier state.
+    pub fn changes(&self, before: &Preferences) -> Vec<String> {
+        let find = |preferences: &Preferences, p: &Preference| {
+            preferences
+                .iter()
+                .find(|o| o.domain == p.domain && o.username == p.username)
+                .map(|o| o == p)
+        };
+        let mut changes = Vec::new();
+        for p in self.iter() {
+            match find(before, p) {
+                None => changes.push(format!("add {} {}", p.domain, p.username)),
+                Some(false) => changes.push(format!("update {} {}", p.domain, p.username)),
+                Some(true) => {}
+            }
+        }
+        for p in before.iter().filter(|p| find(self, p).is_none()) {
+            changes.push(format!("delete {} {}", p.domain, p.username));
+        }
+        changes
+    }
 }
 
 impl Deref for Preferences {
@@ -124,6 +576,214 @@ impl Deref for Preferences {
 
 impl DerefMut for Preferences {
     fn deref_mut(&mut self) -> &mut Self::Target {
+        // the items may be moved or their domains changed
+        self.forget_index();
         &mut self.items
     }
 }
+
+impl PartialEq for Preferences {
+    fn eq(&self, other: &Self) -> bool {
+        self.items == other.items
+    }
+}
+
+impl Eq for Preferences {}
+
+impl<'a> IntoIterator for &'a Preferences {
+    type Item = &'a Preference;
+    type IntoIter = std::slice::Iter<'a, Preference>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.items.iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a mut Preferences {
+    type Item = &'a mut Preference;
+    type IntoIter = std::slice::IterMut<'a, Preference>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.forget_index();
+        self.items.iter_mut()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn by_recency() {
+        let mut preferences = Preferences::new();
+        preferences.add(Preference::new("a.com", "a", 20)).unwrap();
+        preferences.add(Preference::new("b.com", "b", 20)).unwrap();
+        preferences.add(Preference::new("c.com", "c", 20)).unwrap();
+        preferences
+            .get_mut(|p| p.domain == "a.com")
+            .unwrap()
+            .last_used = Some(10);
+        preferences
+            .get_mut(|p| p.domain == "c.com")
+            .unwrap()
+            .last_used = Some(20);
+
+        let domains: Vec<&str> = preferences
+            .by_recency()
+            .iter()
+            .map(|p| &p.domain[..])
+            .collect();
+
+        assert_eq!(domains, vec!["c.com", "a.com", "b.com"]);
+    }
+
+    #[test]
+    fn sorts_listings() {
+        let mut preferences = Preferences::new();
+        preferences.add(Preference::new("a.com", "a", 20)).unwrap();
+        preferences.add(Preference::new("b.com", "b", 20)).unwrap();
+        preferences.add(Preference::new("c.com", "c", 20)).unwrap();
+        preferences.add(Preference::new("a.com", "0", 30)).unwrap();
+        preferences.account_mut("a.com", "a").unwrap().last_used = Some(10);
+        preferences.account_mut("c.com", "c").unwrap().last_used = Some(20);
+
+        let sorted = |sort: &str, reverse: bool| -> Vec<String> {
+            preferences
+                .sorted(Sort::parse(sort).unwrap(), reverse)
+                .iter()
+                .map(|p| format!("{}/{}", p.domain, p.username))
+                .collect()
+        };
+        assert_eq!(
+            sorted("domain", false),
+            ["a.com/0", "a.com/a", "b.com/b", "c.com/c"]
+        );
+        assert_eq!(
+            sorted("username", true),
+            ["c.com/c", "b.com/b", "a.com/a", "a.com/0"]
+        );
+        assert_eq!(sorted("length", true)[0], "a.com/0");
+        assert_eq!(
+            sorted("last-used", true),
+            ["b.com/b", "a.com/0", "a.com/a", "c.com/c"]
+        );
+        assert!(Sort::parse("uses").is_none());
+    }
+
+    #[test]
+    fn looks_up_by_domain() {
+        let mut preferences = Preferences::new();
+        preferences.add(Preference::new("a.com", "a", 20)).unwrap();
+        preferences.add(Preference::new("b.com", "b", 20)).unwrap();
+        preferences.add(Preference::new("a.com", "c", 20)).unwrap();
+
+        let usernames = |p: &Preferences, domain| -> Vec<String> {
+            p.of_domain(domain)
+                .iter()
+                .map(|p| p.username.clone())
+                .collect()
+        };
+        assert_eq!(usernames(&preferences, "a.com"), vec!["a", "c"]);
+        assert!(!preferences.has_domain("c.com"));
+        assert_eq!(preferences.default_of("a.com").unwrap().username, "a");
+
+        // the index follows additions, removals and changes from outside
+        preferences.add(Preference::new("b.com", "d", 20)).unwrap();
+        preferences.remove("a.com", "a").unwrap();
+        assert_eq!(usernames(&preferences, "b.com"), vec!["b", "d"]);
+        assert_eq!(preferences.default_of("a.com").unwrap().username, "c");
+        preferences.get_mut(|p| p.username == "d").unwrap().domain = "c.com".to_owned();
+        assert_eq!(usernames(&preferences, "c.com"), vec!["d"]);
+        preferences.account_mut("c.com", "d").unwrap().length = 30;
+        assert_eq!(preferences.account("c.com", "d").unwrap().length, 30);
+    }
+
+    #[test]
+    fn subdomains_inherit_opted_in_accounts() {
+        let mut preferences = Preferences::new();
+        preferences.add(Preference::new("c.com", "d", 20)).unwrap();
+        preferences.add(Preference::new("c.com", "f", 20)).unwrap();
+
+        // only the accounts that opt in are shared with the subdomains
+        assert!(preferences.inherited("app.c.com", None).is_none());
+        preferences
+            .account_mut("c.com", "f")
+            .unwrap()
+            .match_subdomains = true;
+        assert_eq!(
+            preferences.inherited("x.app.c.com", None).unwrap().username,
+            "f"
+        );
+        assert_eq!(
+            preferences
+                .inherited("app.c.com", Some("f"))
+                .unwrap()
+                .username,
+            "f"
+        );
+        assert!(preferences.inherited("app.c.com", Some("d")).is_none());
+        assert!(preferences.inherited("c.com.evil", None).is_none());
+    }
+
+    #[test]
+    fn remove_moves_the_default() {
+        let mut preferences = Preferences::new();
+        preferences.add(Preference::new("a.com", "a", 20)).unwrap();
+        preferences.add(Preference::new("a.com", "b", 20)).unwrap();
+
+        let removed = preferences.remove("a.com", "a").unwrap();
+
+        assert!(removed.default);
+        assert!(preferences.has_default(|p| p.username == "b"));
+        assert!(preferences.remove("a.com", "a").is_err());
+    }
+
+    #[test]
+    fn changes_since_before() {
+        let mut before = Preferences::new();
+        before.add(Preference::new("a.com", "a", 20)).unwrap();
+        before.add(Preference::new("b.com", "b", 20)).unwrap();
+        let mut after = Preferences::new();
+        let mut a = Preference::new("a.com", "a", 20);
+        a.changed = before.get(|p| p.domain == "a.com").unwrap().changed;
+        after.add(a).unwrap();
+        after.add(Preference::new("c.com", "c", 20)).unwrap();
+        assert_eq!(
+            after.changes(&before),
+            vec!["add c.com c", "delete b.com b"]
+        );
+
+        after
+            .get_mut(|p| p.domain == "a.com")
+            .unwrap()
+            .set_version(1);
+        assert_eq!(after.changes(&before)[0], "update a.com a");
+    }
+
+    #[test]
+    fn rolls_back_changes() {
+        let mut preferences = Preferences::new();
+        preferences.add(Preference::new("a.com", "a", 20)).unwrap();
+        let p = preferences.account_mut("a.com", "a").unwrap();
+        for length in 21..21 + constants::MAX_CHANGES {
+            p.record_change(1);
+            p.length = length;
+        }
+        p.record_change(2);
+        p.set_version(1);
+        assert_eq!(p.changes.len(), constants::MAX_CHANGES);
+        assert_eq!(p.change(1).map(|c| c.version), Some(0));
+        assert!(p.change(0).is_none());
+
+        preferences.rollback("a.com", "a", 3, 3).unwrap();
+        let p = preferences.account("a.com", "a").unwrap();
+        assert_eq!((p.length, p.version), (28, 0));
+        preferences.rollback("a.com", "a", 1, 4).unwrap();
+        let p = preferences.account("a.com", "a").unwrap();
+        assert_eq!((p.length, p.version), (30, 1));
+        assert!(matches!(
+            preferences.rollback("a.com", "a", 99, 5),
+            Err(PreferenceError::NoSuchChange(99))
+        ));
+    }
+}
@@ -0,0 +1,85 @@
+//! # Preset
+//! Settings for passwords that are typed on devices rather than pasted. The `wifi` preset makes
+//! WPA2/WPA3 passphrases: 8 to 63 printable characters without quotes or the characters the
+//! `WIFI:` QR format escapes, in groups of four that are easy to type with a TV remote.
+
+use super::rules::{self, Rules};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// Characters of a group, the groups are joined by dashes.
+const GROUP_LENGTH: usize = 4;
+/// Rules of the characters of a Wi-Fi passphrase. 51 characters in groups make 63.
+const WIFI_RULES: &str = "minlength: 8; maxlength: 51; required: lower; required: upper; required: digit; allowed: [!#$%&*+=?@^_~];";
+
+/// A preset of password settings.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, PartialEq, Eq, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum Preset {
+    Wifi,
+}
+
+impl Preset {
+    /// Returns the preset of a name, such as `wifi`.
+    pub fn parse(name: &str) -> Option<Preset> {
+        match name {
+            "wifi" => Some(Preset::Wifi),
+            _ => None,
+        }
+    }
+
+    /// Returns the rules of the characters the password is generated from.
+    pub fn rules(self) -> Rules {
+        match self {
+            Self::Wifi => rules::parse(WIFI_RULES).expect("the rules of the presets are valid"),
+        }
+    }
+
+    /// Returns the password as it is typed, in groups.
+    pub fn shape(self, password: &str) -> String {
+        let chars: Vec<char> = password.chars().collect();
+        chars
+            .chunks(GROUP_LENGTH)
+            .map(|group| group.iter().collect::<String>())
+            .collect::<Vec<_>>()
+            .join("-")
+    }
+}
+
+/// Returns the text of the QR code that joins a device to a WPA network.
+pub fn wifi_qr_text(ssid: &str, passphrase: &str) -> String {
+    format!("WIFI:T:WPA;S:{};P:{};;", escape(ssid), escape(passphrase))
+}
+
+/// Escapes the characters that are special in the `WIFI:` format.
+fn escape(text: &str) -> String {
+    let mut escaped = String::new();
+    for c in text.chars() {
+        if "\\;,:\"".contains(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wifi_passphrases_are_grouped() {
+        let rules = Preset::Wifi.rules();
+        assert_eq!(rules.clamp_length(80), 51);
+        assert!(!rules
+            .allowed
+            .contains(&['"', '\'', ';', ':', ',', '\\', '-'][..]));
+        let passphrase = Preset::Wifi.shape(&"a".repeat(51));
+        assert_eq!(passphrase.len(), 63);
+        assert_eq!(Preset::Wifi.shape("abcdefghij"), "abcd-efgh-ij");
+        assert_eq!(
+            wifi_qr_text("Home;Net", "abcd-efgh"),
+            "WIFI:T:WPA;S:Home\\;Net;P:abcd-efgh;;"
+        );
+    }
+}
@@ -0,0 +1,147 @@
+//! # Recipe
+//! Fixed parts that some systems require of passwords, such as a project code at the start or a
+//! digit at the end. A recipe is written like password rules:
+//! `prefix: PRJ-; suffix: !; position 0: upper; position -1: digit;`
+//!
+//! It is applied on top of the derived password, so it stays deterministic: the character at a
+//! required position is replaced by the character of the class it picks, negative positions
+//! count from the end, and the prefix and suffix are added around the derived characters.
+
+use super::rules::{self, RulesError};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
+use std::error;
+use std::fmt;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum RecipeError {
+    InvalidPart(String),
+    InvalidPosition(String),
+    RulesError(RulesError),
+}
+
+impl fmt::Display for RecipeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::InvalidPart(part) => write!(f, "Invalid part of the recipe: {}", part),
+            Self::InvalidPosition(position) => write!(f, "Invalid position: {}", position),
+            Self::RulesError(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl error::Error for RecipeError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Self::RulesError(ref err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<RulesError> for RecipeError {
+    fn from(err: RulesError) -> Self {
+        RecipeError::RulesError(err)
+    }
+}
+
+/// # Position
+/// A position of the password that takes a character of a class.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, PartialEq, Eq, Clone)]
+pub struct Position {
+    // index of the character, negative indexes count from the end
+    pub index: i64,
+    // characters the position takes, sorted
+    pub characters: String,
+}
+
+/// # Recipe
+/// The fixed parts of a password.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, PartialEq, Eq, Clone, Default)]
+pub struct Recipe {
+    // prefix comes before the derived characters
+    pub prefix: String,
+    // suffix comes after the derived characters
+    pub suffix: String,
+    // positions of the derived characters that take a class
+    pub positions: Vec<Position>,
+}
+
+impl Recipe {
+    /// Returns the password with the required characters in place, the prefix and the suffix.
+    pub fn apply(&self, password: &str) -> String {
+        let mut chars: Vec<char> = password.chars().collect();
+        for position in &self.positions {
+            let index = match position.index {
+                index if index < 0 => chars.len() as i64 + index,
+                index => index,
+            };
+            let class: Vec<char> = position.characters.chars().collect();
+            if let Some(c) = usize::try_from(index).ok().and_then(|i| chars.get_mut(i)) {
+                *c = class[*c as usize % class.len()];
+            }
+        }
+        let body: String = chars.into_iter().collect();
+        format!("{}{}{}", self.prefix, body, self.suffix)
+    }
+}
+
+/// Parses a recipe such as `prefix: PRJ-; position -1: digit;`.
+pub fn parse(input: &str) -> Result<Recipe, RecipeError> {
+    let mut recipe = Recipe::default();
+    for part in rules::split_outside_brackets(input, ';') {
+        let part = part.trim();
+        if part.is_empty() {
+            continue;
+        }
+        let (name, value) = part
+            .split_once(':')
+            .map(|(name, value)| (name.trim(), value.trim()))
+            .ok_or_else(|| RecipeError::InvalidPart(part.to_owned()))?;
+        match name {
+            "prefix" => recipe.prefix = value.to_owned(),
+            "suffix" => recipe.suffix = value.to_owned(),
+            _ => {
+                let index = name
+                    .strip_prefix("position")
+                    .ok_or_else(|| RecipeError::InvalidPart(part.to_owned()))?
+                    .trim();
+                let index = index
+                    .parse()
+                    .map_err(|_| RecipeError::InvalidPosition(index.to_owned()))?;
+                let characters: String = rules::characters(value)?.into_iter().collect();
+                if characters.is_empty() {
+                    return Err(RulesError::NoAllowedCharacters.into());
+                }
+                recipe.positions.push(Position { index, characters });
+            }
+        }
+    }
+    Ok(recipe)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn applies_fixed_parts() {
+        let recipe =
+            parse("prefix: PRJ-; suffix: !; position 0: upper; position -1: digit").unwrap();
+        let password = recipe.apply("abcdef");
+        assert!(password.starts_with("PRJ-"));
+        assert!(password.ends_with('!'));
+        let body: Vec<char> = password[4..password.len() - 1].chars().collect();
+        assert!(body[0].is_ascii_uppercase());
+        assert!(body[5].is_ascii_digit());
+        assert_eq!(&body[1..5], &['b', 'c', 'd', 'e']);
+        assert_eq!(recipe.apply("abcdef"), password);
+
+        assert_eq!(
+            parse("position x: digit"),
+            Err(RecipeError::InvalidPosition("x".to_owned()))
+        );
+        assert!(parse("infix: -").is_err());
+    }
+}
@@ -0,0 +1,239 @@
+//! # Rules
+//! Password constraints of websites, written in the format of Apple's password rules:
+//! `minlength: 8; maxlength: 32; required: lower, upper; required: digit; allowed: [-_.];`
+//! See https://github.com/apple/password-manager-resources for the format.
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::cmp::PartialEq;
+use std::collections::BTreeSet;
+use std::error;
+use std::fmt;
+
+/// Constraints of a few well known websites.
+const KNOWN_RULES: &[(&str, &str)] = &[
+    ("americanexpress.com", "minlength: 8; maxlength: 20; max-consecutive: 4; required: lower, upper; required: digit; allowed: [%&_?#=];"),
+    ("apple.com", "minlength: 8; maxlength: 63; required: lower; required: upper; required: digit; allowed: ascii-printable;"),
+    ("bankofamerica.com", "minlength: 8; maxlength: 20; max-consecutive: 3; required: lower; required: upper; required: digit; allowed: [-@#*()+={}/?~;,._];"),
+    ("chase.com", "minlength: 8; maxlength: 32; max-consecutive: 2; required: lower, upper; required: digit; required: [!#$%+/=@~];"),
+    ("citi.com", "minlength: 6; maxlength: 50; max-consecutive: 2; required: lower, upper; required: digit; allowed: [_!@$];"),
+    ("ebay.com", "minlength: 6; maxlength: 64; required: lower, upper; allowed: digit, [!@#$%^&*];"),
+    ("paypal.com", "minlength: 8; maxlength: 20; max-consecutive: 3; required: lower, upper; required: digit, [!@#$%^&*()];"),
+    ("wellsfargo.com", "minlength: 8; maxlength: 32; required: lower; required: upper; required: digit;"),
+];
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum RulesError {
+    InvalidRule(String),
+    UnknownRule(String),
+    UnknownCharacterClass(String),
+    InvalidNumber(String),
+    NoAllowedCharacters,
+}
+
+impl fmt::Display for RulesError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::InvalidRule(rule) => write!(f, "Invalid rule: {}", rule),
+            Self::UnknownRule(name) => write!(f, "Unknown rule: {}", name),
+            Self::UnknownCharacterClass(class) => write!(f, "Unknown character class: {}", class),
+            Self::InvalidNumber(number) => write!(f, "Invalid number: {}", number),
+            Self::NoAllowedCharacters => write!(f, "The rules don't allow any characters"),
+        }
+    }
+}
+
+impl error::Error for RulesError {}
+
+/// # Rules
+/// The constraints a password has to satisfy.
+/// Character sets are stored expanded and sorted, so that the generated passwords don't change
+/// when the definition of a character class or the embedded rules change.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, PartialEq, Eq, Clone)]
+pub struct Rules {
+    // min_length is the minimum length of the password
+    pub min_length: Option<usize>,
+    // max_length is the maximum length of the password
+    pub max_length: Option<usize>,
+    // max_consecutive is the maximum number of identical consecutive characters
+    pub max_consecutive: Option<usize>,
+    // allowed are all the characters that may appear in the password
+    pub allowed: String,
+    // required are groups of characters, the password contains at least one of each group
+    pub required: Vec<String>,
+}
+
+impl Rules {
+    /// Returns the length closest to the given length that the rules allow.
+    pub fn clamp_length(&self, length: usize) -> usize {
+        let length = self.max_length.map_or(length, |max| length.min(max));
+        self.min_length.map_or(length, |min| length.max(min))
+    }
+
+    /// Returns true if the password satisfies the required groups and the consecutive limit.
+    pub fn accepts(&self, password: &str) -> bool {
+        let has_required = self
+            .required
+            .iter()
+            .all(|group| password.chars().any(|c| group.contains(c)));
+        let max_run = password
+            .as_bytes()
+            .windows(2)
+            .fold((1, 1), |(run, max), w| {
+                let run = if w[0] == w[1] { run + 1 } else { 1 };
+                (run, max.max(run))
+            })
+            .1;
+        let within_consecutive = !matches!(self.max_consecutive, Some(max) if max_run > max);
+        has_required && within_consecutive
+    }
+}
+
+/// Returns the known rules for a domain or any of its parent domains.
+pub fn for_domain(domain: &str) -> Option<Rules> {
+    KNOWN_RULES
+        .iter()
+        .find(|(known, _)| domain == *known || domain.ends_with(&format!(".{}", known)))
+        .map(|(_, rules)| parse(rules).expect("embedded password rules are valid"))
+}
+
+/// Parses rules written in the password rules format.
+pub fn parse(input: &str) -> Result<Rules, RulesError> {
+    let mut min_length = None;
+    let mut max_length = None;
+    let mut max_consecutive = None;
+    let mut allowed = BTreeSet::new();
+    let mut required = Vec::new();
+
+    for rule in split_outside_brackets(input, ';') {
+        let rule = rule.trim();
+        if rule.is_empty() {
+            continue;
+        }
+        let mut parts = rule.splitn(2, ':');
+        let (name, value) = match (parts.next(), parts.next()) {
+            (Some(name), Some(value)) => (name.trim(), value.trim()),
+            _ => return Err(RulesError::InvalidRule(rule.to_owned())),
+        };
+        match name {
+            "minlength" => min_length = Some(number(value)?),
+            "maxlength" => max_length = Some(number(value)?),
+            "max-consecutive" => max_consecutive = Some(number(value)?),
+            "allowed" => allowed.extend(characters(value)?),
+            "required" => {
+                let group = characters(value)?;
+                allowed.extend(group.iter());
+                required.push(group.into_iter().collect());
+            }
+            _ => return Err(RulesError::UnknownRule(name.to_owned())),
+        }
+    }
+
+    if allowed.is_empty() {
+        allowed = class("ascii-printable")?;
+    }
+    if allowed.is_empty() || required.iter().any(|g: &String| g.is_empty()) {
+        return Err(RulesError::NoAllowedCharacters);
+    }
+    Ok(Rules {
+        min_length,
+        max_length,
+        max_consecutive,
+        allowed: allowed.into_iter().collect(),
+        required,
+    })
+}
+
+/// Splits the input at the separator, ignoring separators inside of square brackets.
+pub fn split_outside_brackets(input: &str, separator: char) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut in_brackets = false;
+    let mut start = 0;
+    for (i, c) in input.char_indices() {
+        match c {
+            '[' => in_brackets = true,
+            ']' => in_brackets = false,
+            c if c == separator && !in_brackets => {
+                parts.push(&input[start..i]);
+                start = i + c.len_utf8();
+            }
+            _ => (),
+        }
+    }
+    parts.push(&input[start..]);
+    parts
+}
+
+/// Parses a non-negative number.
+fn number(value: &str) -> Result<usize, RulesError> {
+    value
+        .parse()
+        .map_err(|_| RulesError::InvalidNumber(value.to_owned()))
+}
+
+/// Parses a comma separated list of character classes into the set of their characters.
+pub fn characters(value: &str) -> Result<BTreeSet<char>, RulesError> {
+    let mut characters = BTreeSet::new();
+    for c in split_outside_brackets(value, ',') {
+        let c = c.trim();
+        if c.starts_with('[') && c.ends_with(']') && c.len() >= 2 {
+            characters.extend(c[1..c.len() - 1].chars().filter(|c| !c.is_whitespace()));
+        } else {
+            characters.extend(class(c)?);
+        }
+    }
+    Ok(characters)
+}
+
+/// Returns the characters of a named character class. Whitespace is never generated.
+fn class(name: &str) -> Result<BTreeSet<char>, RulesError> {
+    let characters = match name {
+        "upper" => ('A'..='Z').collect(),
+        "lower" => ('a'..='z').collect(),
+        "digit" => ('0'..='9').collect(),
+        "special" => "-~!@#$%^&*_+=`|(){}[:;\"'<>,.?]/\\".chars().collect(),
+        "ascii-printable" | "unicode" => ('!'..='~').collect(),
+        _ => return Err(RulesError::UnknownCharacterClass(name.to_owned())),
+    };
+    Ok(characters)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_password_rules() {
+        let rules = parse("minlength: 8; maxlength: 12; required: digit; allowed: [;,]").unwrap();
+
+        assert_eq!(rules.min_length, Some(8));
+        assert_eq!(rules.max_length, Some(12));
+        assert_eq!(rules.allowed, ",0123456789;");
+        assert_eq!(rules.required, vec!["0123456789".to_owned()]);
+        assert_eq!(rules.clamp_length(40), 12);
+        assert_eq!(rules.clamp_length(4), 8);
+        assert!(rules.accepts("ab;1"));
+        assert!(!rules.accepts("ab;,"));
+    }
+
+    #[test]
+    fn rejects_unknown_rules() {
+        assert_eq!(
+            parse("minlength: 8; color: blue"),
+            Err(RulesError::UnknownRule("color".to_owned()))
+        );
+        assert_eq!(
+            parse("allowed: emoji"),
+            Err(RulesError::UnknownCharacterClass("emoji".to_owned()))
+        );
+    }
+
+    #[test]
+    fn known_rules_are_valid() {
+        for (domain, _) in KNOWN_RULES {
+            assert!(for_domain(domain).is_some());
+        }
+        assert!(for_domain("secure.chase.com").is_some());
+        assert!(for_domain("notchase.com").is_none());
+    }
+}
@@ -0,0 +1,69 @@
+//! # Schema
+//! The JSON schema of the vault files, generated from the same types that read and write them,
+//! and validation of files against it.
+
+use super::crypto::Secret;
+use super::vault::Vault;
+use schemars::schema_for;
+use std::fmt;
+use std::mem;
+
+/// # Invalid
+/// Where and why a file doesn't match the schema.
+#[derive(Debug, PartialEq)]
+pub struct Invalid {
+    // path to the offending value, e.g. preferences.items[2].length
+    pub path: String,
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl fmt::Display for Invalid {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}:{} at {}: {}",
+            self.line, self.column, self.path, self.message
+        )
+    }
+}
+
+/// Returns the JSON schema of a vault file.
+pub fn vault_schema() -> Result<String, serde_json::Error> {
+    serde_json::to_string_pretty(&schema_for!(Vault<Secret>))
+}
+
+/// Checks that the contents of a file are a valid vault.
+pub fn validate(contents: &str) -> Result<(), Invalid> {
+    let mut deserializer = serde_json::Deserializer::from_str(contents);
+    let located = |err: serde_json::Error, path: String| Invalid {
+        path,
+        line: err.line(),
+        column: err.column(),
+        message: err.to_string(),
+    };
+    match serde_path_to_error::deserialize::<_, Vault<Secret>>(&mut deserializer) {
+        // A dropped vault writes itself to disk, a file that is only validated must not be.
+        Ok(vault) => mem::forget(vault),
+        Err(err) => {
+            let path = err.path().to_string();
+            return Err(located(err.into_inner(), path));
+        }
+    }
+    deserializer
+        .end()
+        .map_err(|err| located(err, ".".to_owned()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn locates_invalid_values() {
+        let invalid = validate("{\n  \"name\": 42\n}").unwrap_err();
+        assert_eq!(invalid.path, "name");
+        assert_eq!(invalid.line, 2);
+    }
+}
@@ -0,0 +1,142 @@
+//! # Shred
+//! Overwrites files before they are deleted, so the secrets of a deleted vault or of an emptied
+//! trash don't linger in the free blocks of the disk. It's best effort: copy-on-write
+//! filesystems (Btrfs, ZFS, bcachefs and APFS) write the zeros into new blocks and keep the old
+//! ones, so there the files are only deleted, with a warning. SSDs may keep old blocks as well.
+
+use super::constants;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Filesystems that never overwrite a block in place.
+const COPY_ON_WRITE: &[&str] = &["btrfs", "zfs", "bcachefs", "apfs"];
+/// Bytes of zeros written at once.
+const CHUNK_LENGTH: usize = 64 * 1024;
+
+/// Set once the copy-on-write warning was shown, it's shown once per process.
+static WARNED: AtomicBool = AtomicBool::new(false);
+
+/// Overwrites the file and deletes it.
+pub fn remove(path: &Path) -> io::Result<()> {
+    overwrite(path)?;
+    fs::remove_file(path)
+}
+
+/// Overwrites the contents of the file with zeros in place, unless it is on a copy-on-write
+/// filesystem.
+pub fn overwrite(path: &Path) -> io::Result<()> {
+    if let Some(filesystem) = copy_on_write(path) {
+        if !WARNED.swap(true, Ordering::SeqCst) {
+            eprintln!(
+                "Warning: {} is on {}, which keeps the old blocks of overwritten files, deleted secrets may stay on the disk",
+                path.display(),
+                filesystem
+            );
+        }
+        return Ok(());
+    }
+    let mut left = fs::metadata(path)?.len() as usize;
+    let mut file = OpenOptions::new().write(true).open(path)?;
+    let zeros = [0u8; CHUNK_LENGTH];
+    while left > 0 {
+        let length = left.min(CHUNK_LENGTH);
+        file.write_all(&zeros[..length])?;
+        left -= length;
+    }
+    file.sync_all()
+}
+
+/// Overwrites and deletes the copies of the vault in the backup directory of the root.
+/// Returns how many there were.
+pub fn remove_backups(root: &Path, vault: &str) -> io::Result<usize> {
+    let backups = root.join(constants::BACKUP_DIR);
+    if !backups.exists() {
+        return Ok(0);
+    }
+    let mut removed = 0;
+    for entry in fs::read_dir(backups)? {
+        let path = entry?.path();
+        let is_copy = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .and_then(|name| name.strip_prefix(vault)?.strip_prefix('.'))
+            .and_then(|rest| rest.strip_suffix(".json"))
+            .map_or(false, |time| {
+                !time.is_empty() && time.chars().all(|c| c.is_ascii_digit())
+            });
+        if is_copy {
+            remove(&path)?;
+            removed += 1;
+        }
+    }
+    Ok(removed)
+}
+
+/// Returns the filesystem of the path if it is a copy-on-write one.
+fn copy_on_write(path: &Path) -> Option<String> {
+    filesystem(path).filter(|filesystem| COPY_ON_WRITE.contains(&filesystem.as_str()))
+}
+
+/// Returns the type of the filesystem of the mount point that holds the path.
+#[cfg(target_os = "linux")]
+fn filesystem(path: &Path) -> Option<String> {
+    let path = path.canonicalize().ok()?;
+    let mounts = fs::read_to_string("/proc/self/mounts").ok()?;
+    mounts
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace().skip(1);
+            let mount_point = fields.next()?.replace("\\040", " ");
+            Some((mount_point, fields.next()?.to_owned()))
+        })
+        .filter(|(mount_point, _)| path.starts_with(mount_point))
+        // the last of the longest, a later mount hides an earlier one at the same point
+        .max_by_key(|(mount_point, _)| mount_point.len())
+        .map(|(_, filesystem)| filesystem)
+}
+
+/// Every macOS since High Sierra keeps its disks in APFS.
+#[cfg(target_os = "macos")]
+fn filesystem(_path: &Path) -> Option<String> {
+    Some("apfs".to_owned())
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn filesystem(_path: &Path) -> Option<String> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn removes_the_backups_of_a_vault() {
+        let root = std::env::temp_dir().join(format!("zpass-shred-{}", std::process::id()));
+        let backups = root.join(constants::BACKUP_DIR);
+        fs::create_dir_all(&backups).unwrap();
+        let names = [
+            "work.1700000000.json",
+            "work.b.1700000000.json",
+            "work.json",
+        ];
+        for name in &names {
+            fs::write(backups.join(name), "secret").unwrap();
+        }
+
+        assert_eq!(remove_backups(&root, "work").unwrap(), 1);
+        assert!(!backups.join(names[0]).exists());
+        assert!(backups.join(names[1]).exists());
+        assert!(backups.join(names[2]).exists());
+
+        let file = root.join("vault.json");
+        fs::write(&file, "secret").unwrap();
+        overwrite(&file).unwrap();
+        assert_eq!(fs::metadata(&file).unwrap().len(), 6);
+        remove(&file).unwrap();
+        assert!(!file.exists());
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
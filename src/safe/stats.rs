@@ -0,0 +1,121 @@
+//! # Stats
+//! Sums up a vault for hygiene reviews: how many passwords it holds, how long they are, how
+//! often they are used, which ones weren't used for a year and which one has gone longest
+//! without being rotated.
+
+use super::preference::Preferences;
+use serde::Serialize;
+use std::collections::HashSet;
+
+/// # Oldest
+/// The password that has been on its first version the longest.
+#[derive(Serialize, Debug, PartialEq, Eq)]
+pub struct Oldest {
+    pub domain: String,
+    pub username: String,
+    pub days: u64,
+}
+
+/// # Stats
+/// The summary of a vault.
+#[derive(Serialize, Debug, PartialEq)]
+pub struct Stats {
+    pub vault: String,
+    // preferences is the number of accounts in the vault
+    pub preferences: usize,
+    // stored is the number of passwords that are kept rather than generated
+    pub stored: usize,
+    // tags is the number of distinct tags
+    pub tags: usize,
+    // average_length of the passwords, 0 for an empty vault
+    pub average_length: f64,
+    // uses is the number of times the passwords were used
+    pub uses: u64,
+    // unused is the number of accounts that weren't used for a year
+    pub unused: usize,
+    // oldest_never_rotated is the oldest password that is still on its first version
+    pub oldest_never_rotated: Option<Oldest>,
+    // bytes is the size of the vault file, filled in by whoever knows where it is
+    pub bytes: u64,
+}
+
+/// Returns the summary of the preferences of a vault.
+pub fn stats(vault: &str, preferences: &Preferences, now: u64) -> Stats {
+    let count = preferences.iter().count();
+    let total_length: usize = preferences.iter().map(|p| p.length).sum();
+    let tags: HashSet<&str> = preferences
+        .iter()
+        .flat_map(|p| p.tags.iter().map(|t| t.as_str()))
+        .collect();
+    let oldest_never_rotated = preferences
+        .iter()
+        .filter(|p| p.version == 0)
+        .filter_map(|p| p.changed.map(|changed| (changed, p)))
+        .min_by_key(|(changed, _)| *changed)
+        .map(|(changed, p)| Oldest {
+            domain: p.domain.clone(),
+            username: p.username.clone(),
+            days: now.saturating_sub(changed) / (24 * 60 * 60),
+        });
+    Stats {
+        vault: vault.to_owned(),
+        preferences: count,
+        stored: preferences.iter().filter(|p| p.is_stored()).count(),
+        tags: tags.len(),
+        average_length: if count == 0 {
+            0.0
+        } else {
+            total_length as f64 / count as f64
+        },
+        uses: preferences.iter().map(|p| p.uses).sum(),
+        unused: preferences.iter().filter(|p| p.is_unused(now)).count(),
+        oldest_never_rotated,
+        bytes: 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::constants::UNUSED_AGE;
+    use super::super::preference::Preference;
+    use super::*;
+
+    #[test]
+    fn sums_up_preferences() {
+        let mut preferences = Preferences::new();
+        for (domain, length, changed) in [("a.com", 20, 200), ("b.com", 30, 100), ("c.com", 40, 0)]
+        {
+            let mut p = Preference::new(domain, "alice", length);
+            p.changed = Some(changed);
+            p.tags = vec!["work".to_owned(), domain.to_owned()];
+            p.uses = length as u64;
+            preferences.add(p).unwrap();
+        }
+        preferences
+            .get_mut(|p| p.domain == "c.com")
+            .unwrap()
+            .version = 1;
+
+        let stats = stats("v", &preferences, 100 + 3 * 24 * 60 * 60);
+        assert_eq!(stats.preferences, 3);
+        assert_eq!(stats.stored, 0);
+        assert_eq!(stats.tags, 4);
+        assert_eq!(stats.average_length, 30.0);
+        assert_eq!(stats.uses, 90);
+        assert_eq!(stats.unused, 0);
+        let a_year_later = 200 + UNUSED_AGE;
+        assert_eq!(super::stats("v", &preferences, a_year_later).unused, 2);
+        assert_eq!(
+            stats.oldest_never_rotated,
+            Some(Oldest {
+                domain: "b.com".to_owned(),
+                username: "alice".to_owned(),
+                days: 3,
+            })
+        );
+        assert_eq!(
+            super::stats("v", &Preferences::new(), 0).average_length,
+            0.0
+        );
+    }
+}
@@ -0,0 +1,135 @@
+//! # Strength
+//! Estimates how hard generated passwords are to guess.
+//! Generated passwords are uniformly random over the charset, so the entropy is exact:
+//! `length * log2(charset size)`. Passphrases are `words * log2(wordlist size)`, and the
+//! positions a recipe gives a class only count its characters. The crack time assumes an
+//! offline attack on a fast hash.
+
+use super::crypto;
+use super::preference::Preference;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fmt;
+
+/// Guesses per second of an offline attack against a fast hash.
+const GUESSES_PER_SECOND: f64 = 1e10;
+
+/// # Strength
+/// Entropy and expected crack time of a password.
+#[derive(Debug, PartialEq)]
+pub struct Strength {
+    pub bits: f64,
+    pub crack_seconds: f64,
+}
+
+/// Estimates the strength of a random password of the given length over the given charset.
+pub fn estimate(length: usize, charset_size: usize) -> Strength {
+    from_bits(length as f64 * (charset_size as f64).log2())
+}
+
+/// Returns the strength of a password of the given entropy.
+fn from_bits(bits: f64) -> Strength {
+    // on average half of the key space has to be searched
+    let crack_seconds = 2f64.powf(bits - 1.0) / GUESSES_PER_SECOND;
+    Strength {
+        bits,
+        crack_seconds,
+    }
+}
+
+/// Estimates the strength of the password generated for a preference.
+pub fn of(preference: &Preference) -> Strength {
+    if preference.scheme == crypto::LEGACY_SCHEME {
+        return estimate(crypto::LEGACY_PASSWORD_LENGTH, crypto::CHARSET_SIZE);
+    }
+    if let Some(list) = &preference.wordlist {
+        return estimate(preference.length, list.size);
+    }
+    let charset = crypto::charset(
+        preference.generation_rules().as_ref(),
+        preference.unambiguous,
+    )
+    .len();
+    let mut classes: HashMap<usize, usize> = HashMap::new();
+    for position in preference.recipe.iter().flat_map(|r| &r.positions) {
+        let index = match position.index {
+            index if index < 0 => preference.length as i64 + index,
+            index => index,
+        };
+        if let Ok(index) = usize::try_from(index) {
+            if index < preference.length {
+                classes.insert(index, position.characters.chars().count().min(charset));
+            }
+        }
+    }
+    let free = preference.length - classes.len();
+    let bits = free as f64 * (charset as f64).log2()
+        + classes.values().map(|&c| (c as f64).log2()).sum::<f64>();
+    from_bits(bits)
+}
+
+impl fmt::Display for Strength {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:.0} bits, {} to crack",
+            self.bits,
+            describe_duration(self.crack_seconds)
+        )
+    }
+}
+
+/// Describes a duration in seconds in the largest fitting unit.
+fn describe_duration(seconds: f64) -> String {
+    const MINUTE: f64 = 60.0;
+    const HOUR: f64 = 60.0 * MINUTE;
+    const DAY: f64 = 24.0 * HOUR;
+    const YEAR: f64 = 365.0 * DAY;
+    const CENTURY: f64 = 100.0 * YEAR;
+
+    let units = [
+        (YEAR, "years"),
+        (DAY, "days"),
+        (HOUR, "hours"),
+        (MINUTE, "minutes"),
+        (1.0, "seconds"),
+    ];
+    if seconds < 1.0 {
+        return "less than a second".to_owned();
+    }
+    if seconds >= CENTURY {
+        return "centuries".to_owned();
+    }
+    let (unit, name) = units
+        .iter()
+        .find(|(unit, _)| seconds >= *unit)
+        .unwrap_or(&(1.0, "seconds"));
+    format!("{:.0} {}", seconds / unit, name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn longer_is_stronger() {
+        let short = estimate(8, crypto::CHARSET_SIZE);
+        let long = estimate(16, crypto::CHARSET_SIZE);
+
+        assert!((long.bits - 2.0 * short.bits).abs() < 1e-9);
+        assert_eq!(describe_duration(short.crack_seconds), "3 days");
+        assert_eq!(describe_duration(long.crack_seconds), "centuries");
+
+        let mut p = Preference::new("example.com", "example", 16);
+        p.recipe = Some(super::super::recipe::parse("prefix: PRJ-; position -1: digit;").unwrap());
+        let expected = 15.0 * (crypto::CHARSET_SIZE as f64).log2() + 10f64.log2();
+        assert!((of(&p).bits - expected).abs() < 1e-9);
+        p.wordlist = Some(super::super::wordlist::Wordlist {
+            name: "eff_large".to_owned(),
+            checksum: String::new(),
+            size: 7776,
+        });
+        p.length = 6;
+        assert_eq!(of(&p).bits.round(), 78.0);
+    }
+}
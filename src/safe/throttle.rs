@@ -0,0 +1,92 @@
+//! # Throttle
+//! Slows down guessing the key of a vault. The wrong keys given for a vault are counted in a
+//! small file under the root path, and after a few of them every further attempt has to wait,
+//! twice as long after each wrong key. The right key clears the count.
+
+use super::constants;
+use super::time;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Wrong keys that don't slow down the next attempt, everybody mistypes.
+const FREE_ATTEMPTS: u32 = 3;
+/// The longest wait in seconds.
+const MAX_DELAY: u64 = 5 * 60;
+
+/// Returns the file that counts the wrong keys of a vault.
+fn file(root: &Path, vault: &str) -> PathBuf {
+    root.join(constants::ATTEMPTS_DIR).join(vault)
+}
+
+/// Returns the number of wrong keys in a row and when the last one was given.
+fn read(root: &Path, vault: &str) -> (u32, u64) {
+    let text = fs::read_to_string(file(root, vault)).unwrap_or_default();
+    let mut numbers = text.split_whitespace().map(|n| n.parse().unwrap_or(0));
+    let failures = numbers.next().unwrap_or(0);
+    let last = numbers.next().unwrap_or(0);
+    (failures as u32, last)
+}
+
+/// Returns the seconds to wait after a number of wrong keys in a row.
+pub fn delay(failures: u32) -> u64 {
+    match failures.checked_sub(FREE_ATTEMPTS) {
+        None => 0,
+        Some(extra) => 2u64.saturating_pow(extra).min(MAX_DELAY),
+    }
+}
+
+/// Returns the seconds left before the key of a vault may be tried again, zero if it may be
+/// tried now.
+pub fn remaining(root: &Path, vault: &str, now: u64) -> u64 {
+    let (failures, last) = read(root, vault);
+    (last + delay(failures)).saturating_sub(now)
+}
+
+/// Counts a wrong key for a vault.
+pub fn failed(root: &Path, vault: &str) -> io::Result<()> {
+    if constants::is_dry_run() {
+        return Ok(());
+    }
+    let (failures, _) = read(root, vault);
+    let path = file(root, vault);
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(path, format!("{} {}", failures + 1, time::now()))
+}
+
+/// Clears the count of wrong keys of a vault after the right key.
+pub fn succeeded(root: &Path, vault: &str) -> io::Result<()> {
+    let path = file(root, vault);
+    if path.exists() && !constants::is_dry_run() {
+        fs::remove_file(path)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process;
+
+    #[test]
+    fn wrong_keys_slow_down_the_next_attempt() {
+        assert_eq!(
+            (0..6).map(delay).collect::<Vec<_>>(),
+            vec![0, 0, 0, 1, 2, 4]
+        );
+        assert_eq!(delay(40), MAX_DELAY);
+
+        let root = std::env::temp_dir().join(format!("zpass-throttle-{}", process::id()));
+        for _ in 0..5 {
+            failed(&root, "example").unwrap();
+        }
+        let now = time::now();
+        assert!(remaining(&root, "example", now) > 0);
+        assert_eq!(remaining(&root, "example", now + delay(5)), 0);
+        succeeded(&root, "example").unwrap();
+        assert_eq!(remaining(&root, "example", now), 0);
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,88 @@
+//! # Time
+//! Helpers for working with timestamps stored in the vaults.
+
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Returns the current time as seconds since the unix epoch.
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Returns the current time as seconds since the unix epoch, the system clock of the browser
+/// as there is no other.
+#[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+pub fn now() -> u64 {
+    (js_sys::Date::now() / 1000.0) as u64
+}
+
+/// Formats a timestamp as the date and time in UTC, e.g. `2024-03-01 14:05`.
+pub fn format(timestamp: u64) -> String {
+    let (days, seconds) = (timestamp / 86400, timestamp % 86400);
+    // the civil date of the day, see http://howardhinnant.github.io/date_algorithms.html
+    let z = days + 719468;
+    let (era, doe) = (z / 146097, z % 146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = era * 400 + yoe + u64::from(month <= 2);
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}",
+        year,
+        month,
+        day,
+        seconds / 3600,
+        seconds % 3600 / 60
+    )
+}
+
+/// Seconds in a day, the unit of the periods.
+const DAY: u64 = 24 * 60 * 60;
+
+/// Parses a period of days or weeks, e.g. `90d` or `12w`, into seconds.
+pub fn parse_period(text: &str) -> Option<u64> {
+    let (count, unit) = text.split_at(text.len().checked_sub(1)?);
+    let unit = match unit {
+        "d" => DAY,
+        "w" => 7 * DAY,
+        _ => return None,
+    };
+    count
+        .parse::<u64>()
+        .ok()
+        .filter(|c| *c > 0)?
+        .checked_mul(unit)
+}
+
+/// Describes a period in days, e.g. `90d`.
+pub fn describe_period(seconds: u64) -> String {
+    format!("{}d", seconds / DAY)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_dates_in_utc() {
+        assert_eq!(format(0), "1970-01-01 00:00");
+        assert_eq!(format(1709301900), "2024-03-01 14:05");
+        assert_eq!(format(951782400), "2000-02-29 00:00");
+    }
+
+    #[test]
+    fn parses_periods() {
+        assert_eq!(parse_period("90d"), Some(90 * DAY));
+        assert_eq!(parse_period("2w"), Some(14 * DAY));
+        assert_eq!(describe_period(14 * DAY), "14d");
+        for text in &["", "d", "0d", "90", "3m", "-1d"] {
+            assert_eq!(parse_period(text), None);
+        }
+    }
+}
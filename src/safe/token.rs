@@ -0,0 +1,89 @@
+//! # Token
+//! Service credentials and API keys in the shape backends expect: a fixed prefix that names the
+//! issuer, such as `zp_`, followed by characters of a single encoding. A token is generated from
+//! the vault secret like a password, its preference only keeps the prefix and the encoding.
+
+use super::rules::Rules;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// Username of the token of a domain, when no username is given.
+pub const USERNAME: &str = "token";
+/// Length of the encoded part of a token, when no length is given.
+pub const LENGTH: usize = 40;
+
+/// The characters the encoded part of a token is made of.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, PartialEq, Eq, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum Encoding {
+    Base62,
+    Base32,
+    Hex,
+}
+
+impl Encoding {
+    /// Returns the encoding of a name, such as `base62`.
+    pub fn parse(name: &str) -> Option<Encoding> {
+        match name {
+            "base62" => Some(Encoding::Base62),
+            "base32" => Some(Encoding::Base32),
+            "hex" => Some(Encoding::Hex),
+            _ => None,
+        }
+    }
+
+    /// Returns the characters of the encoding, sorted.
+    fn characters(self) -> &'static str {
+        match self {
+            Self::Base62 => "0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz",
+            Self::Base32 => "234567ABCDEFGHIJKLMNOPQRSTUVWXYZ",
+            Self::Hex => "0123456789abcdef",
+        }
+    }
+}
+
+/// # Token
+/// The shape of a token.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, PartialEq, Eq, Clone)]
+pub struct Token {
+    // prefix comes before the encoded part, e.g. `zp_`
+    pub prefix: String,
+    // encoding of the part that is generated
+    pub encoding: Encoding,
+}
+
+impl Token {
+    /// Returns the rules that limit a generated password to the characters of the encoding.
+    pub fn rules(&self) -> Rules {
+        Rules {
+            min_length: None,
+            max_length: None,
+            max_consecutive: None,
+            allowed: self.encoding.characters().to_owned(),
+            required: Vec::new(),
+        }
+    }
+
+    /// Returns the token of a password generated with the rules.
+    pub fn wrap(&self, password: &str) -> String {
+        format!("{}{}", self.prefix, password)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encodings_limit_the_characters() {
+        assert_eq!(Encoding::parse("base62"), Some(Encoding::Base62));
+        assert_eq!(Encoding::parse("base64"), None);
+        let token = Token {
+            prefix: "zp_".to_owned(),
+            encoding: Encoding::Hex,
+        };
+        assert_eq!(token.rules().allowed.len(), 16);
+        assert!(token.rules().accepts("00ff"));
+        assert_eq!(token.wrap("00ff"), "zp_00ff");
+    }
+}
@@ -1,8 +1,16 @@
 use super::collection::List;
 use super::constants;
 use super::crypto;
+use super::history;
+use super::hooks::{self, Event};
+use super::journal;
+use super::plan;
 use super::preference;
+use super::shred;
+use super::time;
+use super::wordlist::{self, WordlistError};
 // Serialization and deserialization
+use schemars::JsonSchema;
 use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 use serde_json::Error as SerializationError;
@@ -11,8 +19,9 @@ use std::{
     fs, io,
     ops::{Deref, DerefMut},
     path,
-    path::{Path, PathBuf},
+    path::PathBuf,
 };
+use tracing::{debug, trace};
 // Error
 use std::error;
 use std::fmt;
@@ -24,7 +33,15 @@ pub enum VaultError {
     SerializationError(SerializationError),
     IOError(io::Error),
     NoMatchingPreference,
+    NoMatchingVault(String),
     VaultAlreadyExists,
+    InvalidVaultName(String),
+    MemberAlreadyExists(String),
+    NoMatchingMember(String),
+    VersionOutOfRange { requested: usize, highest: usize },
+    LegacyVersion(usize),
+    MigrationFailed(String),
+    WordlistError(WordlistError),
 }
 
 impl fmt::Display for VaultError {
@@ -35,7 +52,31 @@ impl fmt::Display for VaultError {
             Self::SerializationError(ref err) => write!(f, "de/serialization error:\n{}", err),
             Self::IOError(ref err) => write!(f, "IO error:\n{}", err),
             Self::NoMatchingPreference => write!(f, "No matching preference found"),
+            Self::NoMatchingVault(ref name) => write!(f, "No vault named {}", name),
             Self::VaultAlreadyExists => write!(f, "Vault already exists"),
+            Self::InvalidVaultName(ref name) => write!(f, "Invalid vault name: {}", name),
+            Self::MemberAlreadyExists(ref recipient) => {
+                write!(f, "{} is already a member of the vault", recipient)
+            }
+            Self::NoMatchingMember(ref recipient) => {
+                write!(f, "{} is not a member of the vault", recipient)
+            }
+            Self::VersionOutOfRange { requested, highest } => write!(
+                f,
+                "Version {} was never used, the highest version is {}",
+                requested, highest
+            ),
+            Self::LegacyVersion(version) => write!(
+                f,
+                "The password uses the legacy scheme, which has no versions, version {} would be the same password",
+                version
+            ),
+            Self::MigrationFailed(ref name) => write!(
+                f,
+                "{} didn't read back as it was written, the vaults were restored",
+                name
+            ),
+            Self::WordlistError(ref err) => write!(f, "{}", err),
         }
     }
 }
@@ -47,6 +88,7 @@ impl error::Error for VaultError {
             Self::PreferenceError(ref err) => Some(err),
             Self::SerializationError(ref err) => Some(err),
             Self::IOError(ref err) => Some(err),
+            Self::WordlistError(ref err) => Some(err),
             _ => None,
         }
     }
@@ -70,6 +112,12 @@ impl From<SerializationError> for VaultError {
     }
 }
 
+impl From<WordlistError> for VaultError {
+    fn from(err: WordlistError) -> Self {
+        VaultError::WordlistError(err)
+    }
+}
+
 impl From<io::Error> for VaultError {
     fn from(err: io::Error) -> Self {
         VaultError::IOError(err)
@@ -78,7 +126,7 @@ impl From<io::Error> for VaultError {
 
 /// # Vault
 /// Has a secret and keeps the user preferences
-#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+#[derive(Serialize, Deserialize, JsonSchema, Debug, PartialEq, Eq)]
 pub struct Vault<S: Serialize> {
     // name is the identifier for the vault
     name: String,
@@ -88,6 +136,33 @@ pub struct Vault<S: Serialize> {
     pub preferences: preference::Preferences,
     // default indicates wheather this is the default vault
     default: bool,
+    // members the vault is shared with, each has the secret wrapped to their public key
+    #[serde(default)]
+    members: Vec<Member>,
+    // hint reminds of the key after a wrong one, it is not secret
+    #[serde(default)]
+    hint: Option<String>,
+    // trash keeps the deleted preferences until it is emptied
+    #[serde(default)]
+    trash: Vec<Trashed>,
+    // local_only keeps the vault on this machine, sync scripts are told not to push it
+    #[serde(default)]
+    local_only: bool,
+    // default_length of the passwords added without a length, a preference keeps its own
+    #[serde(default)]
+    default_length: Option<usize>,
+    // default_username of the passwords added without one, e.g. the usual email address
+    #[serde(default)]
+    default_username: Option<String>,
+    // root is the directory the vault is stored in, the root path if it is unset
+    #[serde(skip)]
+    root: Option<PathBuf>,
+    // shred overwrites the file and its backups before the next write, after secrets were deleted
+    #[serde(skip)]
+    shred: bool,
+    // detached vaults are only read, they aren't written when they are dropped
+    #[serde(skip)]
+    detached: bool,
 }
 
 impl<S: Serialize> Vault<S> {
@@ -100,9 +175,146 @@ impl<S: Serialize> Vault<S> {
             secret,
             preferences,
             default,
+            members: Vec::new(),
+            hint: None,
+            trash: Vec::new(),
+            local_only: false,
+            default_length: None,
+            default_username: None,
+            root: None,
+            shred: false,
+            detached: false,
         }
     }
 
+    /// Returns the name of the vault.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the secret of the vault.
+    pub fn secret(&self) -> &S {
+        &self.secret
+    }
+
+    /// Returns the secret of the vault for changes, such as a new key.
+    pub fn secret_mut(&mut self) -> &mut S {
+        &mut self.secret
+    }
+
+    /// Returns true if this is the default vault.
+    pub fn is_default(&self) -> bool {
+        self.default
+    }
+
+    /// Returns the hint of the key, if there is one.
+    pub fn hint(&self) -> Option<&str> {
+        self.hint.as_deref()
+    }
+
+    /// Sets or removes the hint of the key, it is stored in plain text.
+    pub fn set_hint(&mut self, hint: Option<String>) {
+        self.hint = hint;
+    }
+
+    /// Returns true if the vault stays on this machine and isn't synced.
+    pub fn is_local_only(&self) -> bool {
+        self.local_only
+    }
+
+    /// Keeps the vault on this machine, or lets it roam with the other vaults.
+    pub fn set_local_only(&mut self, local_only: bool) {
+        self.local_only = local_only;
+    }
+
+    /// Returns the length of the passwords that are added without one, if there is one.
+    pub fn default_length(&self) -> Option<usize> {
+        self.default_length
+    }
+
+    /// Sets or removes the length of the passwords that are added without one.
+    pub fn set_default_length(&mut self, length: Option<usize>) {
+        self.default_length = length;
+    }
+
+    /// Returns the username of the passwords that are added without one, if there is one.
+    pub fn default_username(&self) -> Option<&str> {
+        self.default_username.as_deref()
+    }
+
+    /// Sets or removes the username of the passwords that are added without one.
+    pub fn set_default_username(&mut self, username: Option<String>) {
+        self.default_username = username;
+    }
+
+    /// Returns the members the vault is shared with.
+    pub fn members(&self) -> &[Member] {
+        &self.members
+    }
+
+    /// Shares the vault with a member, unless it is shared with them already.
+    pub fn add_member(&mut self, member: Member) -> Result<(), VaultError> {
+        if self.members.iter().any(|m| m.recipient == member.recipient) {
+            return Err(VaultError::MemberAlreadyExists(member.recipient));
+        }
+        self.members.push(member);
+        Ok(())
+    }
+
+    /// Stops sharing the vault with a member.
+    pub fn remove_member(&mut self, recipient: &str) -> Result<(), VaultError> {
+        let count = self.members.len();
+        self.members.retain(|m| m.recipient != recipient);
+        if self.members.len() == count {
+            return Err(VaultError::NoMatchingMember(recipient.to_owned()));
+        }
+        Ok(())
+    }
+
+    /// Returns the deleted preferences, oldest first.
+    pub fn trash(&self) -> &[Trashed] {
+        &self.trash
+    }
+
+    /// Deletes a preference into the trash, from where it can be restored.
+    pub fn move_to_trash(
+        &mut self,
+        domain: &str,
+        username: &str,
+        now: u64,
+    ) -> Result<(), VaultError> {
+        let preference = self.preferences.remove(domain, username)?;
+        self.trash.push(Trashed {
+            deleted: now,
+            preference,
+        });
+        Ok(())
+    }
+
+    /// Puts a deleted preference back, the one deleted last if it was deleted more than once.
+    /// It stays in the trash if the account was added again since.
+    pub fn restore(&mut self, domain: &str, username: &str) -> Result<(), VaultError> {
+        let position = self
+            .trash
+            .iter()
+            .rposition(|t| t.preference.domain == domain && t.preference.username == username)
+            .ok_or(VaultError::NoMatchingPreference)?;
+        if self.preferences.account(domain, username).is_some() {
+            return Err(preference::PreferenceError::PreferenceExists.into());
+        }
+        let trashed = self.trash.remove(position);
+        self.preferences.add(trashed.preference)?;
+        Ok(())
+    }
+
+    /// Deletes the preferences in the trash for good. Returns how many there were.
+    /// The file and its backups are overwritten when the vault is written.
+    pub fn empty_trash(&mut self) -> usize {
+        let count = self.trash.drain(..).count();
+        self.shred |= count > 0;
+        count
+    }
+
     /// Returns a mutable reference to the matching preference
     pub fn get_preference_mut(
         &mut self,
@@ -110,79 +322,328 @@ impl<S: Serialize> Vault<S> {
         username: &str,
     ) -> Result<&mut preference::Preference, VaultError> {
         self.preferences
-            .get_mut(|p| p.domain == domain && p.username == username)
+            .account_mut(domain, username)
             .ok_or(VaultError::NoMatchingPreference)
     }
 }
 
-impl<S: Serialize + crypto::PasswordGenerator> Vault<S> {
+impl<S: Serialize + crypto::PasswordGenerator + crypto::SecretStore> Vault<S> {
     /// Generates a password. All the password parameters other than domain and key
     /// are populated from the default preference if not specified.
+    /// Stored passwords are decrypted instead.
+    /// Previous versions of the password can be retrieved, future versions can not.
+    /// The time of generation is recorded on the matching preference.
     pub fn get_password(
-        &self,
+        &mut self,
         domain: &str,
         key: &str,
         username: Option<&str>,
         length: Option<usize>,
         version: Option<usize>,
     ) -> Result<String, VaultError> {
-        let preference = if let Some(username) = username {
-            self.preferences
-                .get(|p| p.domain == domain && p.username == username)
-        } else {
-            self.preferences.get_default(|p| p.domain == domain)
+        let preference = match username {
+            Some(username) => self.preferences.account(domain, username),
+            None => self.preferences.default_of(domain),
         };
 
         let preference = preference.ok_or(VaultError::NoMatchingPreference)?;
-        let username = username.unwrap_or(&preference.username);
+        debug!(
+            "Picked the preference of {} {}, {}at version {} with length {}",
+            preference.domain,
+            preference.username,
+            if preference.is_default() {
+                "the default, "
+            } else {
+                ""
+            },
+            preference.version,
+            preference.length
+        );
+        let username = username.unwrap_or(&preference.username).to_owned();
         let length = length.unwrap_or(preference.length);
         let version = version.unwrap_or(preference.version);
+        let scheme = preference.scheme;
+        let rules = preference.generation_rules();
+        let unambiguous = preference.unambiguous;
+        let stored = preference.stored.clone();
+        let words = match (&stored, &preference.wordlist) {
+            (None, Some(list)) => Some(list.words(&self.dir())?),
+            _ => None,
+        };
+        let (length, rules, unambiguous) = match words {
+            Some(_) => (length * wordlist::CHARS_PER_WORD, None, false),
+            None => (length, rules, unambiguous),
+        };
+        let highest = preference.highest_version();
+        let legacy = scheme == crypto::LEGACY_SCHEME && stored.is_none();
+        if legacy && version != preference.version {
+            return Err(VaultError::LegacyVersion(version));
+        }
+        if version > highest {
+            return Err(VaultError::VersionOutOfRange {
+                requested: version,
+                highest,
+            });
+        }
+
+        let generated = match &stored {
+            Some(sealed) => self.secret.unseal(key, sealed)?,
+            None => self.secret.get(
+                key,
+                crypto::PasswordParam {
+                    domain,
+                    username: &username,
+                    length,
+                    version,
+                    scheme,
+                    rules: rules.as_ref(),
+                    unambiguous,
+                },
+            )?,
+        };
+        let preference = self.get_preference_mut(domain, &username)?;
+        let password = match (stored, words) {
+            (Some(_), _) => generated,
+            (None, Some(words)) => wordlist::passphrase(&words, &generated),
+            (None, None) => preference.shape(generated),
+        };
+        preference.last_used = Some(time::now());
+        preference.uses += 1;
+        hooks::run(
+            &self.dir(),
+            Event::PasswordGenerated,
+            &self.name,
+            domain,
+            &username,
+        );
+        Ok(password)
+    }
 
+    /// Generates the password for a preference as it is stored, without recording its use.
+    pub fn derive_password(
+        &self,
+        key: &str,
+        preference: &preference::Preference,
+    ) -> Result<String, VaultError> {
+        if let Some(sealed) = &preference.stored {
+            return Ok(self.secret.unseal(key, sealed)?);
+        }
+        if let Some(list) = &preference.wordlist {
+            let words = list.words(&self.dir())?;
+            let generated = self.secret.get(
+                key,
+                crypto::PasswordParam {
+                    domain: &preference.domain,
+                    username: &preference.username,
+                    length: preference.length * wordlist::CHARS_PER_WORD,
+                    version: preference.version,
+                    scheme: preference.scheme,
+                    rules: None,
+                    unambiguous: false,
+                },
+            )?;
+            return Ok(wordlist::passphrase(&words, &generated));
+        }
         let password = self.secret.get(
             key,
             crypto::PasswordParam {
-                domain,
-                username,
-                length,
-                version,
+                domain: &preference.domain,
+                username: &preference.username,
+                length: preference.length,
+                version: preference.version,
+                scheme: preference.scheme,
+                rules: preference.generation_rules().as_ref(),
+                unambiguous: preference.unambiguous,
             },
         )?;
-        Ok(password)
+        Ok(preference.shape(password))
+    }
+}
+
+impl<S: Serialize + crypto::SecretStore> Vault<S> {
+    /// Adds a preference with a password that is stored encrypted instead of being generated.
+    pub fn store_password(
+        &mut self,
+        key: &str,
+        domain: &str,
+        username: &str,
+        password: &str,
+    ) -> Result<(), VaultError> {
+        let mut preference =
+            preference::Preference::new(domain, username, password.chars().count());
+        preference.stored = Some(self.secret.seal(key, password)?);
+        self.preferences.add(preference)?;
+        hooks::run(
+            &self.dir(),
+            Event::PreferenceAdded,
+            &self.name,
+            domain,
+            username,
+        );
+        Ok(())
+    }
+
+    /// Encrypts a value with the vault secret.
+    pub fn seal(&self, key: &str, plaintext: &str) -> Result<crypto::Sealed, VaultError> {
+        Ok(self.secret.seal(key, plaintext)?)
+    }
+
+    /// Decrypts a value that was sealed with the vault secret.
+    pub fn unseal(&self, key: &str, sealed: &crypto::Sealed) -> Result<String, VaultError> {
+        Ok(self.secret.unseal(key, sealed)?)
+    }
+
+    /// Seals the changes that wait for the key into the history of the vault and returns the
+    /// whole history, oldest first. Check the key before, a wrong one seals garbage.
+    pub fn history(&self, key: &str) -> Result<Vec<history::Entry>, VaultError> {
+        let root = self.dir();
+        let mut entries = Vec::new();
+        for line in history::sealed(&root, &self.name)? {
+            let sealed = serde_json::from_str(&line)?;
+            entries.push(serde_json::from_str(&self.unseal(key, &sealed)?)?);
+        }
+        let pending = history::pending(&root, &self.name)?;
+        if !pending.is_empty() && !constants::is_dry_run() {
+            let mut sealed = Vec::new();
+            for line in &pending {
+                sealed.push(serde_json::to_string(&self.seal(key, line)?)?);
+            }
+            history::append_sealed(&root, &self.name, &sealed)?;
+        }
+        for line in pending {
+            entries.push(serde_json::from_str(&line)?);
+        }
+        Ok(entries)
     }
 }
 
 impl<S: Serialize + DeserializeOwned> Vault<S> {
+    /// Reads the vault of the name under the root, without reading the others. It is written
+    /// back when it's dropped, unless it's detached.
+    pub fn open(root: &path::Path, name: &str) -> Result<Vault<S>, VaultError> {
+        let path = root.join(name).with_extension("json");
+        if !is_valid_name(name) || !path.is_file() {
+            return Err(VaultError::NoMatchingVault(name.to_owned()));
+        }
+        let mut vault = Vault::read(&path)?;
+        vault.root = Some(root.to_path_buf());
+        Ok(vault)
+    }
+
     /// Deserializes a Vault from a JSON object.
     pub fn deserialize(serialized: String) -> Result<Vault<S>, VaultError> {
         let deserialized = serde_json::from_slice(serialized.as_bytes())?;
         Ok(deserialized)
     }
+
+    /// Reads a Vault from a file as it is parsed, large vaults are never held as text.
+    pub fn read(path: &path::Path) -> Result<Vault<S>, VaultError> {
+        trace!("Reading {}", path.display());
+        let reader = io::BufReader::new(fs::File::open(path)?);
+        Ok(serde_json::from_reader(reader)?)
+    }
+
+    /// Checks that the file of the vault reads back as the vault.
+    fn verify(&self) -> Result<(), VaultError> {
+        let mut read: Vault<S> = Vault::read(&self.path())?;
+        read.root = self.root.clone();
+        if read.serialize()? != self.serialize()? {
+            // the copy that was read must not write the file when it's dropped
+            std::mem::forget(read);
+            return Err(VaultError::MigrationFailed(self.name.clone()));
+        }
+        Ok(())
+    }
 }
 
 impl<S: Serialize> Vault<S> {
     /// Serializes a Vault into a JSON string
-    fn serialize(&self) -> Result<String, VaultError> {
+    pub fn serialize(&self) -> Result<String, VaultError> {
         let serialized = serde_json::to_string_pretty(self)?;
         Ok(serialized)
     }
 
     /// Returns the path to where the vault is stored on disk.
-    fn path(&self) -> PathBuf {
-        let mut path = PathBuf::new();
-        path.push(constants::ROOT_PATH);
+    pub fn path(&self) -> PathBuf {
+        let mut path = self.dir();
         path.push(&self.name);
         path.with_extension("json")
     }
 
-    /// Serializes the Vault and stores it on disk.
+    /// Returns true if the file of the vault isn't in the newest format, migrating rewrites it.
+    pub fn is_outdated(&self) -> Result<bool, VaultError> {
+        let serialized = self.serialize()?;
+        Ok(matches!(fs::read_to_string(self.path()), Ok(stored) if stored != serialized))
+    }
+
+    /// Returns the directory the vault is stored in.
+    pub fn dir(&self) -> PathBuf {
+        self.root.clone().unwrap_or_else(constants::root)
+    }
+
+    /// Keeps the vault from being written when it's dropped, e.g. one that is only checked.
+    pub fn detach(&mut self) {
+        self.detached = true;
+    }
+
+    /// Keeps the vault under the root, for the tests of other modules.
+    #[cfg(test)]
+    pub(crate) fn set_root(&mut self, root: PathBuf) {
+        self.root = Some(root);
+    }
+
+    /// Serializes the Vault and stores it on disk, unless the file is up to date.
+    /// In a dry run, the changes to the file are printed instead.
     fn store(&self) -> Result<(), VaultError> {
-        let root = Path::new(constants::ROOT_PATH);
+        let stored = fs::read_to_string(self.path()).ok();
+        self.store_over(stored)
+    }
+
+    /// Stores the Vault on disk, unless it is the same as the stored one, the file as it was
+    /// before. The changes since the stored vault go to the history.
+    fn store_over(&self, stored: Option<String>) -> Result<(), VaultError> {
+        let serialized = self.serialize()?;
+        let path = self.path();
+        if stored.as_ref() == Some(&serialized) {
+            trace!("{} is up to date", path.display());
+            return Ok(());
+        }
+        let before = stored
+            .and_then(|s| serde_json::from_str::<StoredPreferences>(&s).ok())
+            .map(|s| s.preferences);
+        if constants::is_dry_run() {
+            let before = before.unwrap_or_else(preference::Preferences::new);
+            let mut planned = format!("Would write {}", path.display());
+            for change in self.preferences.changes(&before) {
+                planned.push_str(&format!("\n  {}", change));
+            }
+            plan::add(planned);
+            return Ok(());
+        }
+        let root = self.dir();
         if !root.exists() {
             // create the root directory if it doesn't exists
-            fs::create_dir(root)?;
+            fs::create_dir(&root)?;
         }
-        let serialized = self.serialize()?;
-        fs::write(self.path(), serialized)?;
+        journal::record(&root, &path)?;
+        if self.shred && path.exists() {
+            debug!("Overwriting {} and its backups", path.display());
+            shred::overwrite(&path)?;
+            shred::remove_backups(&root, &self.name)?;
+        }
+        debug!("Writing {}", path.display());
+        fs::write(path, serialized)?;
+        let entries = history::entries(before.as_ref(), &self.preferences);
+        history::record(&root, &self.name, &entries)?;
+        let sync = if self.local_only { "local" } else { "remote" };
+        hooks::run_with(
+            &root,
+            Event::VaultSaved,
+            &self.name,
+            "",
+            "",
+            &[(hooks::SYNC_VARIABLE, sync)],
+        );
         Ok(())
     }
 }
@@ -192,29 +653,215 @@ impl<S: Serialize> Drop for Vault<S> {
     /// So you never have to think about persisting changes after a mutation.
     /// Just before the memory for the vault is reclaimed, we store on disk.
     fn drop(&mut self) {
-        self.store().unwrap()
+        if !self.detached {
+            self.store().unwrap()
+        }
     }
 }
 
+/// # Member
+/// Someone a vault is shared with. The vault secret is wrapped to their public key, so they can
+/// unwrap it and keep it under a key of their own.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, PartialEq, Eq)]
+pub struct Member {
+    // recipient is the public key of the member, e.g. an age recipient
+    pub recipient: String,
+    // wrapped is the vault secret encrypted to the recipient
+    pub wrapped: Vec<u8>,
+}
+
+/// # Trashed
+/// A deleted preference, which can be restored until the trash is emptied.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, PartialEq, Eq)]
+pub struct Trashed {
+    // deleted is the unix timestamp of the deletion
+    pub deleted: u64,
+    // preference as it was when it was deleted
+    pub preference: preference::Preference,
+}
+
+/// Just the name of a serialized vault.
+#[derive(Deserialize)]
+struct VaultName {
+    name: String,
+}
+
+/// Just the name and the members of a serialized vault.
+#[derive(Deserialize)]
+struct VaultMembers {
+    name: String,
+    #[serde(default)]
+    members: Vec<Member>,
+}
+
+/// Returns the name and the members of a serialized vault, without reading it as a vault that
+/// would be written when it's dropped.
+pub fn members_of(serialized: &str) -> Result<(String, Vec<Member>), VaultError> {
+    let VaultMembers { name, members } = serde_json::from_str(serialized)?;
+    Ok((name, members))
+}
+
+/// Just the preferences of a serialized vault.
+#[derive(Deserialize)]
+struct StoredPreferences {
+    preferences: preference::Preferences,
+}
+
+/// # Migration
+/// What migrating did to a vault file.
+pub struct Migration {
+    // name of the vault
+    pub name: String,
+    // rewritten is true if the file wasn't in the newest format
+    pub rewritten: bool,
+    // backup is the copy of the file as it was, None if it wasn't rewritten or in a dry run
+    pub backup: Option<PathBuf>,
+    // legacy is the number of accounts that still derive their password with the legacy scheme
+    pub legacy: usize,
+}
+
 pub struct Vaults<S: Serialize> {
     items: List<Vault<S>>,
+    // root is the directory the vaults are stored in
+    root: PathBuf,
 }
 
 impl<S: Serialize + DeserializeOwned> Vaults<S> {
     /// Reads all the vaults under the root-path into memory.
     pub fn new() -> Result<Vaults<S>, VaultError> {
-        let root = path::Path::new(constants::ROOT_PATH);
+        Self::open(&constants::root())
+    }
+
+    /// Reads all the vaults in a directory into memory, they are written back to it.
+    pub fn open(root: &path::Path) -> Result<Vaults<S>, VaultError> {
+        let root = root.to_path_buf();
         if !root.exists() {
-            return Ok(Vaults { items: List::new() });
+            return Ok(Vaults {
+                items: List::new(),
+                root,
+            });
         };
 
-        let contents = get_dir_contents(root)?;
-        let vaults = contents
-            .into_iter()
-            .map(|c| Vault::deserialize(c).unwrap())
-            .collect();
+        debug!("Reading the vaults in {}", root.display());
+        let mut vaults = Vec::new();
+        for path in vault_files(&root)? {
+            let mut vault = Vault::read(&path)?;
+            vault.root = Some(root.clone());
+            vaults.push(vault);
+        }
         let vaults = List::from(vaults);
-        return Ok(Vaults { items: vaults });
+        return Ok(Vaults {
+            items: vaults,
+            root,
+        });
+    }
+
+    /// Adds a serialized vault, e.g. one that was exported on another machine.
+    /// The vault keeps its name, which must not be taken, and only becomes the default
+    /// if there are no other vaults. Returns the name of the vault.
+    pub fn import(&mut self, serialized: String) -> Result<String, VaultError> {
+        let mut vault = self.check_import(serialized)?;
+        vault.detached = false;
+        let name = vault.name.clone();
+        vault.default = self.is_empty();
+        vault.root = Some(self.root.clone());
+        self.items.add(vault);
+        Ok(name)
+    }
+
+    /// Returns the vault that `import` would add, without adding it. The vault is detached, so
+    /// it isn't written when it's dropped.
+    pub fn check_import(&self, serialized: String) -> Result<Vault<S>, VaultError> {
+        // Check the name before deserializing, a dropped vault overwrites the file of its name.
+        let VaultName { name } = serde_json::from_str(&serialized)?;
+        if !is_valid_name(&name) {
+            return Err(VaultError::InvalidVaultName(name));
+        }
+        if self.has(|v| v.name == name) {
+            return Err(VaultError::VaultAlreadyExists);
+        }
+        let mut vault = Vault::deserialize(serialized)?;
+        vault.detach();
+        Ok(vault)
+    }
+
+    /// Adds a copy of a vault under a new name, with the same secret, preferences and members.
+    /// The copy is never the default vault, its file is written when it's dropped.
+    pub fn duplicate(&mut self, name: &str, new_name: &str) -> Result<(), VaultError> {
+        if !is_valid_name(new_name) {
+            return Err(VaultError::InvalidVaultName(new_name.to_owned()));
+        }
+        if self.has(|v| v.name == new_name) {
+            return Err(VaultError::VaultAlreadyExists);
+        }
+        let serialized = self
+            .items
+            .get(|v| v.name == name)
+            .ok_or_else(|| VaultError::NoMatchingVault(name.to_owned()))?
+            .serialize()?;
+        let mut copy: Vault<S> = Vault::deserialize(serialized)?;
+        copy.name = new_name.to_owned();
+        copy.default = false;
+        copy.root = Some(self.root.clone());
+        self.items.add(copy);
+        Ok(())
+    }
+}
+
+impl<S: Serialize + DeserializeOwned> Vaults<S> {
+    /// Rewrites every vault file in the newest format. The files that change are copied to the
+    /// backup directory first, and each rewritten file is read back and compared. If any of
+    /// them fails, all of them are put back as they were, and the vaults are let go without
+    /// being written again.
+    pub fn migrate(self) -> Result<Vec<Migration>, VaultError> {
+        let backups = self.root.join(constants::BACKUP_DIR);
+        let mut migrations = Vec::new();
+        let mut outdated = Vec::new();
+        for v in self.iter() {
+            let stored = fs::read_to_string(v.path()).ok();
+            let rewritten = matches!(stored, Some(ref s) if *s != v.serialize()?);
+            let backup = match stored {
+                Some(ref stored) if rewritten && !constants::is_dry_run() => {
+                    fs::create_dir_all(&backups)?;
+                    let backup = backups.join(format!("{}.{}.json", v.name, time::now()));
+                    fs::write(&backup, stored)?;
+                    Some(backup)
+                }
+                _ => None,
+            };
+            if rewritten {
+                outdated.push((v, stored));
+            }
+            migrations.push(Migration {
+                name: v.name.clone(),
+                rewritten,
+                backup,
+                legacy: v
+                    .preferences
+                    .iter()
+                    .filter(|p| p.scheme == crypto::LEGACY_SCHEME)
+                    .count(),
+            });
+        }
+        for (v, stored) in &outdated {
+            let written = v.store_over(stored.clone()).and_then(|_| {
+                if constants::is_dry_run() {
+                    Ok(())
+                } else {
+                    v.verify()
+                }
+            });
+            if let Err(err) = written {
+                for (v, stored) in &outdated {
+                    if let Some(stored) = stored {
+                        fs::write(v.path(), stored)?;
+                    }
+                }
+                std::mem::forget(self);
+                return Err(err);
+            }
+        }
+        Ok(migrations)
     }
 }
 
@@ -229,14 +876,88 @@ impl<S: Serialize> Vaults<S> {
 
         // if this is the first vault, label it as default
         let default = self.is_empty();
-        self.items.add(Vault::new(name, secret, default));
+        let mut vault = Vault::new(name, secret, default);
+        vault.root = Some(self.root.clone());
+        self.items.add(vault);
         Ok(())
     }
 
+    /// Keeps the vaults from being written when they are dropped, e.g. when they are only read.
+    pub fn detach(&mut self) {
+        self.items.apply(|v| v.detach());
+    }
+
     /// Returns the default vault.
     pub fn get_default_mut(&mut self) -> Option<&mut Vault<S>> {
         self.get_mut(|p| p.default == true)
     }
+
+    /// Makes the named vault the default vault.
+    pub fn set_default(&mut self, name: &str) -> Result<(), VaultError> {
+        if !self.has(|v| v.name == name) {
+            return Err(VaultError::NoMatchingVault(name.to_owned()));
+        }
+        self.items.apply(|v| v.default = v.name == name);
+        Ok(())
+    }
+
+    /// Removes a vault and deletes its file. If it was the default vault,
+    /// the next vault becomes the default.
+    pub fn remove(&mut self, name: &str) -> Result<(), VaultError> {
+        let vault = self
+            .items
+            .remove(|v| v.name == name)
+            .ok_or_else(|| VaultError::NoMatchingVault(name.to_owned()))?;
+        let (path, default) = (vault.path(), vault.default);
+        // dropping writes the file one last time, so it's deleted afterwards
+        drop(vault);
+        if constants::is_dry_run() {
+            plan::add(format!("Would delete {}", path.display()));
+        } else {
+            journal::record(&self.root, &path)?;
+            debug!("Removing {} and its backups", path.display());
+            shred::remove(&path)?;
+            shred::remove_backups(&self.root, name)?;
+            let entry = history::Entry::new("delete vault", "", "");
+            history::record(&self.root, name, &[entry])?;
+        }
+        if default {
+            if let Some(v) = self.items.get_mut(|_| true) {
+                v.default = true;
+            }
+        }
+        Ok(())
+    }
+
+    /// Renames a vault. The file under the new name is written before the old one is deleted.
+    pub fn rename(&mut self, name: &str, new_name: &str) -> Result<(), VaultError> {
+        if !is_valid_name(new_name) {
+            return Err(VaultError::InvalidVaultName(new_name.to_owned()));
+        }
+        if self.has(|v| v.name == new_name) {
+            return Err(VaultError::VaultAlreadyExists);
+        }
+        let vault = self
+            .items
+            .get_mut(|v| v.name == name)
+            .ok_or_else(|| VaultError::NoMatchingVault(name.to_owned()))?;
+        let old_path = vault.path();
+        vault.name = new_name.to_owned();
+        if constants::is_dry_run() {
+            // the new file is reported when the vault is dropped
+            plan::add(format!("Would delete {}", old_path.display()));
+            return Ok(());
+        }
+        // compared with the old file, only the name changed
+        vault.store_over(fs::read_to_string(&old_path).ok())?;
+        journal::record(&self.root, &old_path)?;
+        debug!("Removing {}", old_path.display());
+        fs::remove_file(old_path)?;
+        history::rename(&self.root, name, new_name)?;
+        let entry = history::Entry::new(&format!("rename vault from {}", name), "", "");
+        history::record(&self.root, new_name, &[entry])?;
+        Ok(())
+    }
 }
 
 impl<S: Serialize> Deref for Vaults<S> {
@@ -253,18 +974,44 @@ impl<S: Serialize> DerefMut for Vaults<S> {
     }
 }
 
-/// Returns all the files in a directory as a sequence of strings.
-fn get_dir_contents(root: &path::Path) -> Result<Vec<String>, VaultError> {
-    let mut contents = Vec::new();
+impl<'a, S: Serialize> IntoIterator for &'a Vaults<S> {
+    type Item = &'a Vault<S>;
+    type IntoIter = std::slice::Iter<'a, Vault<S>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.items.iter()
+    }
+}
+
+impl<'a, S: Serialize> IntoIterator for &'a mut Vaults<S> {
+    type Item = &'a mut Vault<S>;
+    type IntoIter = std::slice::IterMut<'a, Vault<S>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.items.iter_mut()
+    }
+}
+
+/// Returns true if the name can be used as the file name of a vault.
+fn is_valid_name(name: &str) -> bool {
+    !name.is_empty() && !name.contains(&['/', '\\'][..]) && !name.starts_with('.')
+}
+
+/// Returns the paths of all the vault files in a directory, they are read one at a time.
+/// Other entries, such as the hooks directory, are skipped.
+fn vault_files(root: &path::Path) -> Result<Vec<PathBuf>, VaultError> {
+    let mut paths = Vec::new();
     let reader = fs::read_dir(root)?;
 
     for path in reader {
         let path = path?.path();
-        let content = fs::read_to_string(path)?;
-        contents.push(content);
+        if !path.is_file() || path.extension() != Some("json".as_ref()) {
+            continue;
+        }
+        paths.push(path);
     }
 
-    Ok(contents)
+    Ok(paths)
 }
 
 #[cfg(test)]
@@ -285,4 +1032,53 @@ mod tests {
 
         assert_eq!(vault, deserialized);
     }
+
+    #[test]
+    fn trash_keeps_deleted_preferences() {
+        let root = std::env::temp_dir().join(format!("zpass-trash-{}", std::process::id()));
+        let secret = Secret::new("KEY", "IV", 40).unwrap();
+        let mut vault = Vault::new("example", secret, true);
+        vault.root = Some(root.clone());
+        vault
+            .preferences
+            .add(preference::Preference::new("example.com", "alice", 20))
+            .unwrap();
+
+        vault.move_to_trash("example.com", "alice", 100).unwrap();
+        assert!(vault.preferences.account("example.com", "alice").is_none());
+        assert_eq!(vault.trash()[0].deleted, 100);
+        vault.restore("example.com", "alice").unwrap();
+        assert!(vault.preferences.account("example.com", "alice").is_some());
+        assert!(vault.restore("example.com", "alice").is_err());
+
+        vault.move_to_trash("example.com", "alice", 200).unwrap();
+        assert_eq!(vault.empty_trash(), 1);
+        assert!(vault.trash().is_empty());
+        drop(vault);
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn legacy_passwords_have_no_versions() {
+        let root = std::env::temp_dir().join(format!("zpass-legacy-{}", std::process::id()));
+        let secret = Secret::new("KEY", "IV", 40).unwrap();
+        let mut vault = Vault::new("example", secret, true);
+        vault.root = Some(root.clone());
+        let mut preference = preference::Preference::new("example.com", "alice", 20);
+        preference.scheme = crypto::LEGACY_SCHEME;
+        preference.version = 1;
+        vault.preferences.add(preference).unwrap();
+
+        let get = |vault: &mut Vault<Secret>, version| {
+            vault.get_password("example.com", "KEY", None, None, version)
+        };
+        assert!(get(&mut vault, None).is_ok());
+        assert!(get(&mut vault, Some(1)).is_ok());
+        assert!(matches!(
+            get(&mut vault, Some(0)),
+            Err(VaultError::LegacyVersion(0))
+        ));
+        drop(vault);
+        fs::remove_dir_all(&root).unwrap();
+    }
 }
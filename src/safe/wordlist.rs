@@ -0,0 +1,247 @@
+//! # Wordlist
+//! Passphrases are words picked from a list, e.g. the EFF large or short list or one of
+//! another language. The lists are text files in the `wordlists` directory of the root, one
+//! word per line; the dice numbers of the EFF format are skipped. A preference keeps the name of
+//! its list and a checksum of the words, so a list that changed fails instead of generating
+//! another passphrase.
+//!
+//! The words are picked with generated characters, three for each word, so a passphrase is
+//! derived like any other password of the vault.
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use sha3::{Digest, Sha3_256};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+// Error
+use std::error;
+use std::fmt;
+
+/// Directory of the root the wordlists are kept in.
+pub const DIRECTORY: &str = "wordlists";
+/// Generated characters that pick a word.
+pub const CHARS_PER_WORD: usize = 3;
+/// Fewest words a list has, fewer would make guessable passphrases.
+pub const MIN_WORDS: usize = 1024;
+/// Characters the words are picked with, the default charset of the vault secret.
+const CHARSET: std::ops::Range<u8> = 33..125;
+/// Separator of the words of a passphrase.
+const SEPARATOR: &str = "-";
+
+#[derive(Debug)]
+pub enum WordlistError {
+    IOError(io::Error),
+    NoSuchList(String),
+    TooShort { name: String, words: usize },
+    Changed(String),
+    InvalidName(String),
+    AlreadyExists(String),
+}
+
+impl fmt::Display for WordlistError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::IOError(ref err) => write!(f, "IO error:\n{}", err),
+            Self::NoSuchList(ref name) => write!(
+                f,
+                "No wordlist named {}, put it into the {} directory of the root",
+                name, DIRECTORY
+            ),
+            Self::TooShort { ref name, words } => write!(
+                f,
+                "The wordlist {} has {} words, at least {} are needed",
+                name, words, MIN_WORDS
+            ),
+            Self::Changed(ref name) => write!(
+                f,
+                "The wordlist {} changed since the passphrase was added, restore it to generate the same passphrase",
+                name
+            ),
+            Self::InvalidName(ref name) => write!(f, "Invalid wordlist name: {}", name),
+            Self::AlreadyExists(ref name) => {
+                write!(f, "Another wordlist named {} already exists", name)
+            }
+        }
+    }
+}
+
+impl error::Error for WordlistError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Self::IOError(ref err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for WordlistError {
+    fn from(err: io::Error) -> Self {
+        WordlistError::IOError(err)
+    }
+}
+
+/// # Wordlist
+/// The list the words of a passphrase are picked from.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, PartialEq, Eq, Clone)]
+pub struct Wordlist {
+    // name of the list, its file is <name>.txt in the wordlists directory
+    pub name: String,
+    // checksum is the sha3-256 hash of the words in hex
+    pub checksum: String,
+    // size is the number of words of the list, for the strength of the passphrases
+    #[serde(default)]
+    pub size: usize,
+}
+
+impl Wordlist {
+    /// Returns the wordlist of a name as the file is now.
+    pub fn find(root: &Path, name: &str) -> Result<Wordlist, WordlistError> {
+        let words = read(root, name)?;
+        Ok(Wordlist {
+            name: name.to_owned(),
+            checksum: checksum(&words),
+            size: words.len(),
+        })
+    }
+
+    /// Copies a file into the wordlists directory under its name without the extension, so
+    /// passphrases don't depend on where it was. Copying the same words again is fine.
+    pub fn import(root: &Path, file: &Path) -> Result<Wordlist, WordlistError> {
+        let name = file
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .ok_or_else(|| WordlistError::InvalidName(file.display().to_string()))?;
+        let content = fs::read_to_string(file)?;
+        let words = parse(&content);
+        check_length(name, &words)?;
+        if path(root, name)?.exists() {
+            let existing = Wordlist::find(root, name)?;
+            if existing.checksum != checksum(&words) {
+                return Err(WordlistError::AlreadyExists(name.to_owned()));
+            }
+            return Ok(existing);
+        }
+        fs::create_dir_all(root.join(DIRECTORY))?;
+        fs::write(path(root, name)?, content)?;
+        Wordlist::find(root, name)
+    }
+
+    /// Returns the words of the list, if they are still the ones of the checksum.
+    pub fn words(&self, root: &Path) -> Result<Vec<String>, WordlistError> {
+        let words = read(root, &self.name)?;
+        if checksum(&words) != self.checksum {
+            return Err(WordlistError::Changed(self.name.clone()));
+        }
+        Ok(words)
+    }
+}
+
+/// Returns the passphrase the generated characters pick from the words.
+pub fn passphrase(words: &[String], generated: &str) -> String {
+    let digits: Vec<usize> = generated
+        .bytes()
+        .map(|b| b.saturating_sub(CHARSET.start) as usize)
+        .collect();
+    let base = CHARSET.len();
+    digits
+        .chunks(CHARS_PER_WORD)
+        .map(|chunk| {
+            let index = chunk.iter().fold(0, |index, digit| index * base + digit);
+            words[index % words.len()].as_str()
+        })
+        .collect::<Vec<_>>()
+        .join(SEPARATOR)
+}
+
+fn path(root: &Path, name: &str) -> Result<PathBuf, WordlistError> {
+    let valid = !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '-' || c == '_');
+    if !valid {
+        return Err(WordlistError::InvalidName(name.to_owned()));
+    }
+    Ok(root.join(DIRECTORY).join(format!("{}.txt", name)))
+}
+
+fn read(root: &Path, name: &str) -> Result<Vec<String>, WordlistError> {
+    let content = fs::read_to_string(path(root, name)?).map_err(|err| match err.kind() {
+        io::ErrorKind::NotFound => WordlistError::NoSuchList(name.to_owned()),
+        _ => WordlistError::IOError(err),
+    })?;
+    let words = parse(&content);
+    check_length(name, &words)?;
+    Ok(words)
+}
+
+/// Returns the words of the lines, the last field of each, in the order of the file.
+fn parse(content: &str) -> Vec<String> {
+    content
+        .lines()
+        .filter_map(|line| line.split_whitespace().last())
+        .map(|word| word.to_owned())
+        .collect()
+}
+
+fn check_length(name: &str, words: &[String]) -> Result<(), WordlistError> {
+    if words.len() < MIN_WORDS {
+        return Err(WordlistError::TooShort {
+            name: name.to_owned(),
+            words: words.len(),
+        });
+    }
+    Ok(())
+}
+
+fn checksum(words: &[String]) -> String {
+    Sha3_256::digest(words.join("\n").as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn picks_words_and_notices_changed_lists() {
+        let root = std::env::temp_dir().join(format!("zpass-wordlist-{}", std::process::id()));
+        let file = root.join("dice.txt");
+        fs::create_dir_all(&root).unwrap();
+        let lines: Vec<String> = (0..MIN_WORDS)
+            .map(|i| format!("{:05}\tword{}", i, i))
+            .collect();
+        fs::write(&file, lines.join("\n")).unwrap();
+
+        let list = Wordlist::import(&root, &file).unwrap();
+        assert_eq!(list.name, "dice");
+        assert_eq!(list.size, MIN_WORDS);
+        let words = list.words(&root).unwrap();
+        assert_eq!(words[7], "word7");
+        // '!' is the first character of the charset, '"' the second
+        assert_eq!(passphrase(&words, "!!!!!\"!\"!"), "word0-word1-word92");
+
+        fs::write(root.join(DIRECTORY).join("dice.txt"), lines[1..].join("\n")).unwrap();
+        assert!(matches!(
+            list.words(&root),
+            Err(WordlistError::TooShort { .. })
+        ));
+        fs::write(
+            root.join(DIRECTORY).join("dice.txt"),
+            lines.iter().rev().cloned().collect::<Vec<_>>().join("\n"),
+        )
+        .unwrap();
+        assert!(matches!(list.words(&root), Err(WordlistError::Changed(_))));
+        assert!(matches!(
+            Wordlist::import(&root, &file),
+            Err(WordlistError::AlreadyExists(_))
+        ));
+        assert!(matches!(
+            Wordlist::find(&root, "../dice"),
+            Err(WordlistError::InvalidName(_))
+        ));
+        fs::remove_dir_all(&root).unwrap();
+    }
+}